@@ -13,9 +13,14 @@ use std::task::{Context, Poll};
 use futures::Sink;
 use futures::ready;
 use std::ops::Deref;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
 
 use log::info;
 
+type RecvFuture = Pin<Box<dyn Future<Output = io::Result<(BytesMut, SocketAddr)>> + Send>>;
+
 /// A unified `Stream` and `Sink` interface to an underlying `UdpSocket`, using
 /// the `Encoder` and `Decoder` traits to encode and decode frames.
 ///
@@ -34,9 +39,8 @@ use log::info;
 /// them into separate objects, allowing them to interact more easily.
 #[must_use = "sinks do nothing unless polled"]
 #[cfg_attr(docsrs, doc(all(feature = "codec", feature = "udp")))]
-#[derive(Debug)]
 pub struct UdpFramed<C> {
-    socket: UdpSocket,
+    socket: Arc<UdpSocket>,
     codec: C,
     rd: BytesMut,
     wr: BytesMut,
@@ -44,6 +48,19 @@ pub struct UdpFramed<C> {
     flushed: bool,
     is_readable: bool,
     current_addr: Option<SocketAddr>,
+
+    /// The pending receive of the next datagram, into the read buffer.
+    recv: Option<RecvFuture>,
+}
+
+impl<C: fmt::Debug> fmt::Debug for UdpFramed<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpFramed")
+            .field("socket", &self.socket)
+            .field("codec", &self.codec)
+            .field("current_addr", &self.current_addr)
+            .finish()
+    }
 }
 
 impl<C: Decoder + Unpin> Stream for UdpFramed<C> {
@@ -56,7 +73,6 @@ impl<C: Decoder + Unpin> Stream for UdpFramed<C> {
         // pin.rd.reserve(INITIAL_RD_CAPACITY);
 
         loop {
-            pin.rd.reserve(INITIAL_RD_CAPACITY);
             // Are there are still bytes left in the read buffer to decode?
             if pin.is_readable {
                 if let Some(frame) = pin.codec.decode(&mut pin.rd)? {
@@ -81,34 +97,31 @@ impl<C: Decoder + Unpin> Stream for UdpFramed<C> {
             // info!("poll_next loop pin.is_readable2:{}", pin.is_readable);
 
             // We're out of data. Try and fetch more data to decode
-            let addr = unsafe {
-                // Convert `&mut [MaybeUnit<u8>]` to `&mut [u8]` because we will be
-                // writing to it via `poll_recv_from` and therefore initializing the memory.
-                let buf: &mut [u8] =
-                    &mut *(pin.rd.bytes_mut() as *mut [MaybeUninit<u8>] as *mut [u8]);
-                //
-                // let res = ready!(Pin::new(&mut pin.socket).poll(cx, buf));
-                //
-                // let (n, addr) = res?;
-                // pin.rd.advance_mut(n);
-                // addr
-                // info!("futures::executor::block_on");
-
-                let result = pin.socket.recv_from(buf);
-                let result = futures::executor::block_on(result);
-
-                // info!("futures::executor::block_on end");
-
-                match result{
-                    Ok(res)=>{
-                        let (n, addr) = res;
-                        pin.rd.advance_mut(n);
-                        addr
-                    }
-                    _ => {
-                        info!("poll_next loop return Poll::Ready(None)");
-                        return Poll::Ready(None);
-                    }
+            if pin.recv.is_none() {
+                let socket = pin.socket.clone();
+                let mut rd = std::mem::take(&mut pin.rd);
+                rd.clear();
+                rd.resize(INITIAL_RD_CAPACITY, 0);
+
+                pin.recv = Some(Box::pin(async move {
+                    let (n, addr) = socket.recv_from(&mut rd).await?;
+                    rd.truncate(n);
+                    Ok((rd, addr))
+                }));
+            }
+
+            let recv = pin.recv.as_mut().expect("set above when no receive is pending");
+            let result = ready!(recv.as_mut().poll(cx));
+            pin.recv = None;
+
+            let addr = match result {
+                Ok((rd, addr)) => {
+                    pin.rd = rd;
+                    addr
+                }
+                Err(e) => {
+                    info!("poll_next recv error: {}", e);
+                    return Poll::Ready(Some(Err(e.into())));
                 }
             };
 
@@ -220,7 +233,7 @@ impl<C> UdpFramed<C> {
     /// See struct level documentation for more details.
     pub fn new(socket: UdpSocket, codec: C) -> UdpFramed<C> {
         UdpFramed {
-            socket,
+            socket: Arc::new(socket),
             codec,
             out_addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0)),
             rd: BytesMut::with_capacity(INITIAL_RD_CAPACITY),
@@ -228,6 +241,7 @@ impl<C> UdpFramed<C> {
             flushed: true,
             is_readable: false,
             current_addr: None,
+            recv: None,
         }
     }
 
@@ -243,7 +257,7 @@ impl<C> UdpFramed<C> {
     }
 
     /// Returns a mutable reference to the underlying I/O stream wrapped by
-    /// `Framed`.
+    /// `Framed`, cancelling the pending receive.
     ///
     /// # Note
     ///
@@ -251,11 +265,13 @@ impl<C> UdpFramed<C> {
     /// coming in as it may corrupt the stream of frames otherwise being worked
     /// with.
     pub fn get_mut(&mut self) -> &mut UdpSocket {
-        &mut self.socket
+        self.recv = None;
+        Arc::get_mut(&mut self.socket).expect("only the pending receive shares the socket")
     }
 
     /// Consumes the `Framed`, returning its underlying I/O stream.
     pub fn into_inner(self) -> UdpSocket {
-        self.socket
+        drop(self.recv);
+        Arc::try_unwrap(self.socket).expect("only the pending receive shares the socket")
     }
 }
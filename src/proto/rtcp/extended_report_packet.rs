@@ -0,0 +1,735 @@
+//! RTCP extended reports (XR), as defined in RFC 3611.
+
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
+
+use crate::proto::common::sync_io::{ReadExt, WriteExt};
+use crate::proto::error::Error;
+use crate::proto::error::ErrorKind;
+use crate::proto::rtcp::ntp;
+use crate::proto::rtcp::reception::{compact_ntp_duration, compact_ntp_timestamp};
+use crate::proto::rtp::traits::RtcpPacketTrait;
+use crate::proto::traits::{PacketTrait, ReadFrom, Result, WriteTo};
+use crate::proto::types::*;
+
+use super::constants::*;
+use super::rtcp_packet::*;
+
+/// An extended report of the SSRC, with any number of report blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedReportPacket {
+    pub ssrc: u32,
+    pub blocks: Vec<ReportBlock>,
+}
+impl ExtendedReportPacket {
+    pub fn new(ssrc: u32) -> Self {
+        ExtendedReportPacket {
+            ssrc,
+            blocks: Vec::new(),
+        }
+    }
+}
+impl PacketTrait for ExtendedReportPacket {}
+impl RtcpPacketTrait for ExtendedReportPacket {}
+impl ReadFrom for ExtendedReportPacket {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let (_, payload) =
+            track!(read_sctp(reader, RTCP_PACKET_TYPE_XR).map_err(Error::from))?;
+        let reader = &mut &payload[..];
+
+        let ssrc = track!(reader.read_u32be().map_err(Error::from))?;
+        let mut blocks = Vec::new();
+        while !reader.is_empty() {
+            let block = track!(ReportBlock::read_from(reader).map_err(Error::from))?;
+            blocks.push(block);
+        }
+        Ok(ExtendedReportPacket { ssrc, blocks })
+    }
+}
+impl WriteTo for ExtendedReportPacket {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut payload = Vec::new();
+        track!((&mut payload).write_u32be(self.ssrc).map_err(Error::from))?;
+        for block in self.blocks.iter() {
+            track!(block.write_to(&mut payload).map_err(Error::from))?;
+        }
+
+        track!(write_sctp(writer, RTCP_PACKET_TYPE_XR, 0, &payload).map_err(Error::from))?;
+        Ok(())
+    }
+}
+
+/// A report block of an extended report.
+///
+/// Blocks of unknown types are kept as they are, so that they can be forwarded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReportBlock {
+    LossRle(RunLengthBlock),
+    DuplicateRle(RunLengthBlock),
+    ReceiptTimes(ReceiptTimesBlock),
+    ReceiverReferenceTime(ReceiverReferenceTimeBlock),
+    Dlrr(Vec<DlrrSubBlock>),
+    StatisticsSummary(StatisticsSummaryBlock),
+    VoipMetrics(VoipMetricsBlock),
+    Unknown {
+        block_type: u8,
+        type_specific: u8,
+        data: Vec<u8>,
+    },
+}
+impl ReportBlock {
+    fn block_type(&self) -> u8 {
+        match *self {
+            ReportBlock::LossRle(_) => XR_BLOCK_TYPE_LOSS_RLE,
+            ReportBlock::DuplicateRle(_) => XR_BLOCK_TYPE_DUPLICATE_RLE,
+            ReportBlock::ReceiptTimes(_) => XR_BLOCK_TYPE_RECEIPT_TIMES,
+            ReportBlock::ReceiverReferenceTime(_) => XR_BLOCK_TYPE_RRTR,
+            ReportBlock::Dlrr(_) => XR_BLOCK_TYPE_DLRR,
+            ReportBlock::StatisticsSummary(_) => XR_BLOCK_TYPE_STATISTICS_SUMMARY,
+            ReportBlock::VoipMetrics(_) => XR_BLOCK_TYPE_VOIP_METRICS,
+            ReportBlock::Unknown { block_type, .. } => block_type,
+        }
+    }
+}
+impl ReadFrom for ReportBlock {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let block_type = track!(reader.read_u8().map_err(Error::from))?;
+        let type_specific = track!(reader.read_u8().map_err(Error::from))?;
+        let word_count = track!(reader.read_u16be().map_err(Error::from))? as usize;
+        let data = track!(reader.read_bytes(word_count * 4).map_err(Error::from))?;
+        let reader = &mut &data[..];
+
+        let block = match block_type {
+            XR_BLOCK_TYPE_LOSS_RLE => {
+                ReportBlock::LossRle(track!(RunLengthBlock::read_body(type_specific, reader))?)
+            }
+            XR_BLOCK_TYPE_DUPLICATE_RLE => {
+                ReportBlock::DuplicateRle(track!(RunLengthBlock::read_body(type_specific, reader))?)
+            }
+            XR_BLOCK_TYPE_RECEIPT_TIMES => ReportBlock::ReceiptTimes(track!(
+                ReceiptTimesBlock::read_body(type_specific, reader)
+            )?),
+            XR_BLOCK_TYPE_RRTR => {
+                track_assert_eq!(word_count, 2, ErrorKind::Invalid);
+                ReportBlock::ReceiverReferenceTime(ReceiverReferenceTimeBlock {
+                    ntp_sec: track!(reader.read_u32be().map_err(Error::from))?,
+                    ntp_frac: track!(reader.read_u32be().map_err(Error::from))?,
+                })
+            }
+            XR_BLOCK_TYPE_DLRR => {
+                track_assert_eq!(word_count % 3, 0, ErrorKind::Invalid);
+                let mut sub_blocks = Vec::new();
+                while !reader.is_empty() {
+                    sub_blocks.push(DlrrSubBlock {
+                        ssrc: track!(reader.read_u32be().map_err(Error::from))?,
+                        last_rr: track!(reader.read_u32be().map_err(Error::from))?,
+                        delay_since_last_rr: track!(reader.read_u32be().map_err(Error::from))?,
+                    });
+                }
+                ReportBlock::Dlrr(sub_blocks)
+            }
+            XR_BLOCK_TYPE_STATISTICS_SUMMARY => {
+                track_assert_eq!(word_count, 9, ErrorKind::Invalid);
+                ReportBlock::StatisticsSummary(track!(StatisticsSummaryBlock::read_body(
+                    type_specific,
+                    reader
+                ))?)
+            }
+            XR_BLOCK_TYPE_VOIP_METRICS => {
+                track_assert_eq!(word_count, 8, ErrorKind::Invalid);
+                ReportBlock::VoipMetrics(track!(VoipMetricsBlock::read_body(reader))?)
+            }
+            _ => ReportBlock::Unknown {
+                block_type,
+                type_specific,
+                data: data.clone(),
+            },
+        };
+        Ok(block)
+    }
+}
+impl WriteTo for ReportBlock {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut data = Vec::new();
+        let type_specific = match *self {
+            ReportBlock::LossRle(ref block) | ReportBlock::DuplicateRle(ref block) => {
+                track!(block.write_body(&mut data))?
+            }
+            ReportBlock::ReceiptTimes(ref block) => track!(block.write_body(&mut data))?,
+            ReportBlock::ReceiverReferenceTime(ref block) => {
+                track!((&mut data).write_u32be(block.ntp_sec).map_err(Error::from))?;
+                track!((&mut data).write_u32be(block.ntp_frac).map_err(Error::from))?;
+                0
+            }
+            ReportBlock::Dlrr(ref sub_blocks) => {
+                for sub_block in sub_blocks.iter() {
+                    track!((&mut data).write_u32be(sub_block.ssrc).map_err(Error::from))?;
+                    track!((&mut data)
+                        .write_u32be(sub_block.last_rr)
+                        .map_err(Error::from))?;
+                    track!((&mut data)
+                        .write_u32be(sub_block.delay_since_last_rr)
+                        .map_err(Error::from))?;
+                }
+                0
+            }
+            ReportBlock::StatisticsSummary(ref block) => track!(block.write_body(&mut data))?,
+            ReportBlock::VoipMetrics(ref block) => {
+                track!(block.write_body(&mut data))?;
+                0
+            }
+            ReportBlock::Unknown {
+                type_specific,
+                data: ref unknown,
+                ..
+            } => {
+                data.extend(unknown);
+                type_specific
+            }
+        };
+
+        track_assert_eq!(data.len() % 4, 0, ErrorKind::Invalid);
+        let word_count = data.len() / 4;
+        track_assert!(word_count < 0x10000, ErrorKind::Invalid);
+
+        track!(writer.write_u8(self.block_type()).map_err(Error::from))?;
+        track!(writer.write_u8(type_specific).map_err(Error::from))?;
+        track!(writer.write_u16be(word_count as u16).map_err(Error::from))?;
+        track!(writer.write_all(&data).map_err(Error::from))?;
+        Ok(())
+    }
+}
+
+/// A Loss RLE or Duplicate RLE report block, the run length encoded packets of a sequence
+/// number range that were lost or duplicated (RFC 3611 section 4.1 and 4.2).
+///
+/// The chunks are kept encoded, without the null chunk padding the block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunLengthBlock {
+    /// Only packets with sequence numbers that are multiples of 2^thinning are reported.
+    pub thinning: U4,
+    pub ssrc: u32,
+    pub begin_seq: u16,
+
+    /// The sequence number following the last one reported.
+    pub end_seq: u16,
+    pub chunks: Vec<u16>,
+}
+impl RunLengthBlock {
+    fn read_body(type_specific: u8, reader: &mut &[u8]) -> Result<Self> {
+        let ssrc = track!(reader.read_u32be().map_err(Error::from))?;
+        let begin_seq = track!(reader.read_u16be().map_err(Error::from))?;
+        let end_seq = track!(reader.read_u16be().map_err(Error::from))?;
+
+        let mut chunks = Vec::new();
+        while !reader.is_empty() {
+            let chunk = track!(reader.read_u16be().map_err(Error::from))?;
+            if chunk != 0 {
+                chunks.push(chunk);
+            }
+        }
+        Ok(RunLengthBlock {
+            thinning: type_specific & 0b0000_1111,
+            ssrc,
+            begin_seq,
+            end_seq,
+            chunks,
+        })
+    }
+
+    fn write_body(&self, data: &mut Vec<u8>) -> Result<u8> {
+        track_assert!(self.thinning <= 0b0000_1111, ErrorKind::Invalid);
+
+        track!(data.write_u32be(self.ssrc).map_err(Error::from))?;
+        track!(data.write_u16be(self.begin_seq).map_err(Error::from))?;
+        track!(data.write_u16be(self.end_seq).map_err(Error::from))?;
+        for chunk in self.chunks.iter() {
+            track!(data.write_u16be(*chunk).map_err(Error::from))?;
+        }
+        if self.chunks.len() % 2 != 0 {
+            track!(data.write_u16be(0).map_err(Error::from))?;
+        }
+        Ok(self.thinning)
+    }
+}
+
+/// A Packet Receipt Times report block, the arrival times of the packets of a sequence number
+/// range in the RTP timestamp units (RFC 3611 section 4.3).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptTimesBlock {
+    pub thinning: U4,
+    pub ssrc: u32,
+    pub begin_seq: u16,
+    pub end_seq: u16,
+    pub receipt_times: Vec<u32>,
+}
+impl ReceiptTimesBlock {
+    fn read_body(type_specific: u8, reader: &mut &[u8]) -> Result<Self> {
+        let ssrc = track!(reader.read_u32be().map_err(Error::from))?;
+        let begin_seq = track!(reader.read_u16be().map_err(Error::from))?;
+        let end_seq = track!(reader.read_u16be().map_err(Error::from))?;
+
+        let mut receipt_times = Vec::new();
+        while !reader.is_empty() {
+            receipt_times.push(track!(reader.read_u32be().map_err(Error::from))?);
+        }
+        Ok(ReceiptTimesBlock {
+            thinning: type_specific & 0b0000_1111,
+            ssrc,
+            begin_seq,
+            end_seq,
+            receipt_times,
+        })
+    }
+
+    fn write_body(&self, data: &mut Vec<u8>) -> Result<u8> {
+        track_assert!(self.thinning <= 0b0000_1111, ErrorKind::Invalid);
+
+        track!(data.write_u32be(self.ssrc).map_err(Error::from))?;
+        track!(data.write_u16be(self.begin_seq).map_err(Error::from))?;
+        track!(data.write_u16be(self.end_seq).map_err(Error::from))?;
+        for time in self.receipt_times.iter() {
+            track!(data.write_u32be(*time).map_err(Error::from))?;
+        }
+        Ok(self.thinning)
+    }
+}
+
+/// A Receiver Reference Time report block, the NTP timestamp of a report sent by a receiver
+/// (RFC 3611 section 4.4).
+///
+/// It takes the place of the sender report timestamp for the round trip time of receivers,
+/// which answer it with a DLRR block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiverReferenceTimeBlock {
+    pub ntp_sec: u32,
+    pub ntp_frac: u32,
+}
+impl ReceiverReferenceTimeBlock {
+    /// Constructs a block of the wallclock time the report is sent at.
+    pub fn new(wallclock: SystemTime) -> Self {
+        let (ntp_sec, ntp_frac) = ntp::ntp_timestamp(wallclock);
+        ReceiverReferenceTimeBlock { ntp_sec, ntp_frac }
+    }
+}
+
+/// A sub-block of a DLRR report block, answering the last receiver reference time of the SSRC
+/// (RFC 3611 section 4.5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DlrrSubBlock {
+    pub ssrc: u32,
+
+    /// The middle 32 bits of the NTP timestamp of the last receiver reference time.
+    pub last_rr: u32,
+
+    /// The delay since the last receiver reference time was received, in units of 1/65536
+    /// seconds.
+    pub delay_since_last_rr: u32,
+}
+impl DlrrSubBlock {
+    /// Constructs a sub-block answering the receiver reference time of the SSRC, received the
+    /// given delay before.
+    pub fn new(ssrc: u32, reference: &ReceiverReferenceTimeBlock, delay: Duration) -> Self {
+        DlrrSubBlock {
+            ssrc,
+            last_rr: compact_ntp_timestamp(reference.ntp_sec, reference.ntp_frac),
+            delay_since_last_rr: compact_ntp_duration(delay),
+        }
+    }
+
+    /// Returns the round trip time to the participant that sent the sub-block, received at the
+    /// given wallclock time.
+    ///
+    /// Returns `None` if no receiver reference time was received by the participant, or if the
+    /// delays do not fit the arrival time.
+    pub fn round_trip_time(&self, arrival: SystemTime) -> Option<Duration> {
+        if self.last_rr == 0 {
+            return None;
+        }
+
+        let (ntp_sec, ntp_frac) = ntp::ntp_timestamp(arrival);
+        let elapsed = compact_ntp_timestamp(ntp_sec, ntp_frac).wrapping_sub(self.last_rr);
+        if elapsed < self.delay_since_last_rr || elapsed >= 1 << 31 {
+            return None;
+        }
+
+        let units = u64::from(elapsed - self.delay_since_last_rr);
+        Some(Duration::from_nanos((units * 1_000_000_000) >> 16))
+    }
+}
+
+/// A Statistics Summary report block, the loss, duplication, jitter and TTL or hop limit of the
+/// packets of a sequence number range (RFC 3611 section 4.6).
+///
+/// The flags tell which of the fields are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatisticsSummaryBlock {
+    pub loss_reported: bool,
+    pub duplicates_reported: bool,
+    pub jitter_reported: bool,
+
+    /// 0 if the TTL or hop limit fields are not reported, 1 for IPv4 TTLs and 2 for IPv6 hop
+    /// limits.
+    pub ttl_or_hop_limit: U2,
+
+    pub ssrc: u32,
+    pub begin_seq: u16,
+    pub end_seq: u16,
+    pub lost_packets: u32,
+    pub duplicate_packets: u32,
+    pub min_jitter: u32,
+    pub max_jitter: u32,
+    pub mean_jitter: u32,
+    pub dev_jitter: u32,
+    pub min_ttl_or_hl: u8,
+    pub max_ttl_or_hl: u8,
+    pub mean_ttl_or_hl: u8,
+    pub dev_ttl_or_hl: u8,
+}
+impl StatisticsSummaryBlock {
+    fn read_body(type_specific: u8, reader: &mut &[u8]) -> Result<Self> {
+        Ok(StatisticsSummaryBlock {
+            loss_reported: (type_specific & 0b1000_0000) != 0,
+            duplicates_reported: (type_specific & 0b0100_0000) != 0,
+            jitter_reported: (type_specific & 0b0010_0000) != 0,
+            ttl_or_hop_limit: (type_specific >> 3) & 0b0000_0011,
+            ssrc: track!(reader.read_u32be().map_err(Error::from))?,
+            begin_seq: track!(reader.read_u16be().map_err(Error::from))?,
+            end_seq: track!(reader.read_u16be().map_err(Error::from))?,
+            lost_packets: track!(reader.read_u32be().map_err(Error::from))?,
+            duplicate_packets: track!(reader.read_u32be().map_err(Error::from))?,
+            min_jitter: track!(reader.read_u32be().map_err(Error::from))?,
+            max_jitter: track!(reader.read_u32be().map_err(Error::from))?,
+            mean_jitter: track!(reader.read_u32be().map_err(Error::from))?,
+            dev_jitter: track!(reader.read_u32be().map_err(Error::from))?,
+            min_ttl_or_hl: track!(reader.read_u8().map_err(Error::from))?,
+            max_ttl_or_hl: track!(reader.read_u8().map_err(Error::from))?,
+            mean_ttl_or_hl: track!(reader.read_u8().map_err(Error::from))?,
+            dev_ttl_or_hl: track!(reader.read_u8().map_err(Error::from))?,
+        })
+    }
+
+    fn write_body(&self, data: &mut Vec<u8>) -> Result<u8> {
+        track_assert!(self.ttl_or_hop_limit <= 0b0000_0011, ErrorKind::Invalid);
+
+        track!(data.write_u32be(self.ssrc).map_err(Error::from))?;
+        track!(data.write_u16be(self.begin_seq).map_err(Error::from))?;
+        track!(data.write_u16be(self.end_seq).map_err(Error::from))?;
+        track!(data.write_u32be(self.lost_packets).map_err(Error::from))?;
+        track!(data
+            .write_u32be(self.duplicate_packets)
+            .map_err(Error::from))?;
+        track!(data.write_u32be(self.min_jitter).map_err(Error::from))?;
+        track!(data.write_u32be(self.max_jitter).map_err(Error::from))?;
+        track!(data.write_u32be(self.mean_jitter).map_err(Error::from))?;
+        track!(data.write_u32be(self.dev_jitter).map_err(Error::from))?;
+        track!(data.write_u8(self.min_ttl_or_hl).map_err(Error::from))?;
+        track!(data.write_u8(self.max_ttl_or_hl).map_err(Error::from))?;
+        track!(data.write_u8(self.mean_ttl_or_hl).map_err(Error::from))?;
+        track!(data.write_u8(self.dev_ttl_or_hl).map_err(Error::from))?;
+
+        let mut type_specific = self.ttl_or_hop_limit << 3;
+        if self.loss_reported {
+            type_specific |= 0b1000_0000;
+        }
+        if self.duplicates_reported {
+            type_specific |= 0b0100_0000;
+        }
+        if self.jitter_reported {
+            type_specific |= 0b0010_0000;
+        }
+        Ok(type_specific)
+    }
+}
+
+/// A VoIP Metrics report block, the call quality of a voice over IP source (RFC 3611 section
+/// 4.7).
+///
+/// The fields are kept in the units of the RFC, with 127 (or 0 for the MOS and R factors)
+/// meaning unavailable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoipMetricsBlock {
+    pub ssrc: u32,
+    pub loss_rate: u8,
+    pub discard_rate: u8,
+    pub burst_density: u8,
+    pub gap_density: u8,
+    pub burst_duration: u16,
+    pub gap_duration: u16,
+    pub round_trip_delay: u16,
+    pub end_system_delay: u16,
+
+    /// The signal and noise levels in dBm.
+    pub signal_level: i8,
+    pub noise_level: i8,
+    pub residual_echo_return_loss: u8,
+    pub gmin: u8,
+    pub r_factor: u8,
+    pub ext_r_factor: u8,
+    pub mos_lq: u8,
+    pub mos_cq: u8,
+    pub rx_config: u8,
+    pub jb_nominal: u16,
+    pub jb_maximum: u16,
+    pub jb_abs_max: u16,
+}
+impl VoipMetricsBlock {
+    fn read_body(reader: &mut &[u8]) -> Result<Self> {
+        let ssrc = track!(reader.read_u32be().map_err(Error::from))?;
+        let loss_rate = track!(reader.read_u8().map_err(Error::from))?;
+        let discard_rate = track!(reader.read_u8().map_err(Error::from))?;
+        let burst_density = track!(reader.read_u8().map_err(Error::from))?;
+        let gap_density = track!(reader.read_u8().map_err(Error::from))?;
+        let burst_duration = track!(reader.read_u16be().map_err(Error::from))?;
+        let gap_duration = track!(reader.read_u16be().map_err(Error::from))?;
+        let round_trip_delay = track!(reader.read_u16be().map_err(Error::from))?;
+        let end_system_delay = track!(reader.read_u16be().map_err(Error::from))?;
+        let signal_level = track!(reader.read_u8().map_err(Error::from))? as i8;
+        let noise_level = track!(reader.read_u8().map_err(Error::from))? as i8;
+        let residual_echo_return_loss = track!(reader.read_u8().map_err(Error::from))?;
+        let gmin = track!(reader.read_u8().map_err(Error::from))?;
+        let r_factor = track!(reader.read_u8().map_err(Error::from))?;
+        let ext_r_factor = track!(reader.read_u8().map_err(Error::from))?;
+        let mos_lq = track!(reader.read_u8().map_err(Error::from))?;
+        let mos_cq = track!(reader.read_u8().map_err(Error::from))?;
+        let rx_config = track!(reader.read_u8().map_err(Error::from))?;
+        let _reserved = track!(reader.read_u8().map_err(Error::from))?;
+        let jb_nominal = track!(reader.read_u16be().map_err(Error::from))?;
+        let jb_maximum = track!(reader.read_u16be().map_err(Error::from))?;
+        let jb_abs_max = track!(reader.read_u16be().map_err(Error::from))?;
+        Ok(VoipMetricsBlock {
+            ssrc,
+            loss_rate,
+            discard_rate,
+            burst_density,
+            gap_density,
+            burst_duration,
+            gap_duration,
+            round_trip_delay,
+            end_system_delay,
+            signal_level,
+            noise_level,
+            residual_echo_return_loss,
+            gmin,
+            r_factor,
+            ext_r_factor,
+            mos_lq,
+            mos_cq,
+            rx_config,
+            jb_nominal,
+            jb_maximum,
+            jb_abs_max,
+        })
+    }
+
+    fn write_body(&self, data: &mut Vec<u8>) -> Result<()> {
+        track!(data.write_u32be(self.ssrc).map_err(Error::from))?;
+        track!(data.write_u8(self.loss_rate).map_err(Error::from))?;
+        track!(data.write_u8(self.discard_rate).map_err(Error::from))?;
+        track!(data.write_u8(self.burst_density).map_err(Error::from))?;
+        track!(data.write_u8(self.gap_density).map_err(Error::from))?;
+        track!(data.write_u16be(self.burst_duration).map_err(Error::from))?;
+        track!(data.write_u16be(self.gap_duration).map_err(Error::from))?;
+        track!(data.write_u16be(self.round_trip_delay).map_err(Error::from))?;
+        track!(data.write_u16be(self.end_system_delay).map_err(Error::from))?;
+        track!(data.write_u8(self.signal_level as u8).map_err(Error::from))?;
+        track!(data.write_u8(self.noise_level as u8).map_err(Error::from))?;
+        track!(data
+            .write_u8(self.residual_echo_return_loss)
+            .map_err(Error::from))?;
+        track!(data.write_u8(self.gmin).map_err(Error::from))?;
+        track!(data.write_u8(self.r_factor).map_err(Error::from))?;
+        track!(data.write_u8(self.ext_r_factor).map_err(Error::from))?;
+        track!(data.write_u8(self.mos_lq).map_err(Error::from))?;
+        track!(data.write_u8(self.mos_cq).map_err(Error::from))?;
+        track!(data.write_u8(self.rx_config).map_err(Error::from))?;
+        track!(data.write_u8(0).map_err(Error::from))?;
+        track!(data.write_u16be(self.jb_nominal).map_err(Error::from))?;
+        track!(data.write_u16be(self.jb_maximum).map_err(Error::from))?;
+        track!(data.write_u16be(self.jb_abs_max).map_err(Error::from))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::proto::rtcp::rtcp_packet::RtcpPacket;
+    use crate::proto::traits::{PacketData, ReadFrom, WriteTo};
+
+    use super::*;
+
+    #[test]
+    fn test_xr_parse() {
+        let data = vec![
+            0x80, 0xcf, 0x00, 0x0a, // Type: 207 (XR), length: 10
+            0x62, 0x42, 0x76, 0xe0, // SSRC: 0x624276e0
+            0x01, 0x02, 0x00, 0x03, // Loss RLE, thinning: 2, length: 3
+            0x26, 0x24, 0x67, 0x0e, // SSRC: 0x2624670e
+            0x00, 0x0a, 0x00, 0x20, // Begin: 10, end: 32
+            0x40, 0x05, 0x00, 0x00, // Run of 5 lost packets, null chunk
+            0x04, 0x00, 0x00, 0x02, // RRTR, length: 2
+            0xe3, 0x0a, 0x1d, 0x80, // NTP timestamp
+            0x80, 0x00, 0x00, 0x00, 0xff, 0x11, 0x00, 0x01, // Unknown type: 255, length: 1
+            0x01, 0x02, 0x03, 0x04,
+        ];
+
+        let packet = ExtendedReportPacket::read_from(&mut &data[..]).unwrap();
+        assert_eq!(packet.ssrc, 0x6242_76e0);
+        assert_eq!(
+            packet.blocks,
+            vec![
+                ReportBlock::LossRle(RunLengthBlock {
+                    thinning: 2,
+                    ssrc: 0x2624_670e,
+                    begin_seq: 10,
+                    end_seq: 32,
+                    chunks: vec![0x4005],
+                }),
+                ReportBlock::ReceiverReferenceTime(ReceiverReferenceTimeBlock {
+                    ntp_sec: 0xe30a_1d80,
+                    ntp_frac: 0x8000_0000,
+                }),
+                ReportBlock::Unknown {
+                    block_type: 0xff,
+                    type_specific: 0x11,
+                    data: vec![1, 2, 3, 4],
+                },
+            ]
+        );
+        assert_eq!(packet.to_bytes().unwrap(), data);
+
+        match RtcpPacket::read_from(&mut &data[..]).unwrap() {
+            RtcpPacket::Xr(xr) => assert_eq!(xr, packet),
+            other => panic!("unexpected packet: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_xr_create() {
+        let mut packet = ExtendedReportPacket::new(1);
+        packet.blocks = vec![
+            ReportBlock::DuplicateRle(RunLengthBlock {
+                thinning: 0,
+                ssrc: 2,
+                begin_seq: 0,
+                end_seq: 3,
+                chunks: vec![0x8001, 0x4001],
+            }),
+            ReportBlock::ReceiptTimes(ReceiptTimesBlock {
+                thinning: 1,
+                ssrc: 2,
+                begin_seq: 0,
+                end_seq: 4,
+                receipt_times: vec![100, 3100],
+            }),
+            ReportBlock::Dlrr(vec![DlrrSubBlock {
+                ssrc: 2,
+                last_rr: 0x1d80_8000,
+                delay_since_last_rr: 0x0001_0000,
+            }]),
+            ReportBlock::StatisticsSummary(StatisticsSummaryBlock {
+                loss_reported: true,
+                duplicates_reported: false,
+                jitter_reported: true,
+                ttl_or_hop_limit: 1,
+                ssrc: 2,
+                begin_seq: 0,
+                end_seq: 100,
+                lost_packets: 3,
+                duplicate_packets: 0,
+                min_jitter: 10,
+                max_jitter: 40,
+                mean_jitter: 20,
+                dev_jitter: 5,
+                min_ttl_or_hl: 60,
+                max_ttl_or_hl: 64,
+                mean_ttl_or_hl: 62,
+                dev_ttl_or_hl: 1,
+            }),
+            ReportBlock::VoipMetrics(VoipMetricsBlock {
+                ssrc: 2,
+                loss_rate: 12,
+                discard_rate: 3,
+                burst_density: 50,
+                gap_density: 2,
+                burst_duration: 200,
+                gap_duration: 5000,
+                round_trip_delay: 80,
+                end_system_delay: 40,
+                signal_level: -20,
+                noise_level: -70,
+                residual_echo_return_loss: 127,
+                gmin: 16,
+                r_factor: 85,
+                ext_r_factor: 127,
+                mos_lq: 42,
+                mos_cq: 41,
+                rx_config: 0b1000_0000,
+                jb_nominal: 60,
+                jb_maximum: 120,
+                jb_abs_max: 200,
+            }),
+        ];
+
+        let serialized = packet.to_bytes().unwrap();
+        assert_eq!(&serialized[..4], &[0x80, 0xcf, 0x00, 0x21]);
+        assert_eq!(&serialized[8..12], &[0x02, 0x00, 0x00, 0x03]);
+        assert_eq!(
+            ExtendedReportPacket::read_from(&mut &serialized[..]).unwrap(),
+            packet
+        );
+    }
+
+    #[test]
+    fn test_xr_parse_truncated() {
+        // The Loss RLE block body is shorter than its fixed fields.
+        let truncated_body = [
+            0x80, 0xcf, 0x00, 0x03, // Type: 207 (XR), length: 3
+            0x62, 0x42, 0x76, 0xe0, // SSRC: 0x624276e0
+            0x01, 0x02, 0x00, 0x01, // Loss RLE, thinning: 2, length: 1
+            0x26, 0x24, 0x67, 0x0e, // SSRC: 0x2624670e
+        ];
+        assert!(ExtendedReportPacket::read_from(&mut &truncated_body[..]).is_err());
+        assert!(RtcpPacket::read_from(&mut &truncated_body[..]).is_err());
+
+        // A zero-length block has no body at all.
+        let empty_body = [
+            0x80, 0xcf, 0x00, 0x02, // Type: 207 (XR), length: 2
+            0x62, 0x42, 0x76, 0xe0, // SSRC: 0x624276e0
+            0x03, 0x00, 0x00, 0x00, // Receipt times, length: 0
+        ];
+        assert!(ExtendedReportPacket::read_from(&mut &empty_body[..]).is_err());
+
+        // The block length runs past the end of the packet.
+        let overlong_block = [
+            0x80, 0xcf, 0x00, 0x03, // Type: 207 (XR), length: 3
+            0x62, 0x42, 0x76, 0xe0, // SSRC: 0x624276e0
+            0x04, 0x00, 0x00, 0x05, // RRTR, length: 5
+            0xe3, 0x0a, 0x1d, 0x80, // NTP timestamp, cut off
+        ];
+        assert!(ExtendedReportPacket::read_from(&mut &overlong_block[..]).is_err());
+        assert!(RtcpPacket::read_from(&mut &overlong_block[..]).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_time() {
+        let sent = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let reference = ReceiverReferenceTimeBlock::new(sent);
+
+        // The reference time is answered after 250 ms, and the answer arrives after a second.
+        let sub_block = DlrrSubBlock::new(2, &reference, Duration::from_millis(250));
+        let rtt = sub_block
+            .round_trip_time(sent + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(rtt, Duration::from_millis(750));
+
+        assert_eq!(sub_block.round_trip_time(sent), None);
+        let unanswered = DlrrSubBlock {
+            last_rr: 0,
+            ..sub_block
+        };
+        assert_eq!(unanswered.round_trip_time(sent), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! The interval between the RTCP packets of a participant, as computed in section 6.3 and A.7
+//! of RFC 3550.
+
+use std::time::Duration;
+
+use rand::Rng;
+
+/// The interval between RTCP packets at the least.
+pub const MIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The fraction of the session bandwidth used for RTCP.
+pub const RTCP_BANDWIDTH_FRACTION: f64 = 0.05;
+
+/// The fraction of the RTCP bandwidth shared by the senders.
+pub const SENDER_BANDWIDTH_FRACTION: f64 = 0.25;
+
+/// The overhead of the UDP and IPv4 headers, counted in the RTCP packet sizes.
+pub const UDP_IPV4_OVERHEAD: usize = 28;
+
+/// Compensates for the timer reconsideration converging below the intended interval.
+const COMPENSATION: f64 = std::f64::consts::E - 1.5;
+
+/// The state of a session the RTCP interval is computed from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntervalParameters {
+    /// The number of participants, including the local one.
+    pub members: usize,
+    pub senders: usize,
+
+    /// The bandwidth for RTCP in octets per second.
+    pub rtcp_bandwidth: f64,
+
+    /// Whether the local participant sent RTP packets since its last report but one.
+    pub we_sent: bool,
+
+    /// The average size of the RTCP packets sent and received, in octets.
+    pub average_packet_size: f64,
+
+    /// Whether no RTCP packet was sent yet, which halves the minimum interval.
+    pub initial: bool,
+}
+
+impl IntervalParameters {
+    /// Returns the interval before randomization.
+    pub fn deterministic_interval(&self) -> Duration {
+        let min_interval = if self.initial {
+            MIN_INTERVAL / 2
+        } else {
+            MIN_INTERVAL
+        };
+
+        let members = self.members.max(1) as f64;
+        let senders = self.senders as f64;
+        let mut bandwidth = self.rtcp_bandwidth;
+        let mut participants = members;
+
+        // The senders share a quarter of the bandwidth if they are fewer than a quarter.
+        if senders > 0.0 && senders <= members * SENDER_BANDWIDTH_FRACTION {
+            if self.we_sent {
+                bandwidth *= SENDER_BANDWIDTH_FRACTION;
+                participants = senders;
+            } else {
+                bandwidth *= 1.0 - SENDER_BANDWIDTH_FRACTION;
+                participants = members - senders;
+            }
+        }
+
+        if bandwidth <= 0.0 {
+            return min_interval;
+        }
+
+        let interval = Duration::from_secs_f64(participants * self.average_packet_size / bandwidth);
+        interval.max(min_interval)
+    }
+
+    /// Returns the interval randomized between half and one and a half times the deterministic
+    /// one, so that the reports of the participants do not synchronize.
+    pub fn interval<R: Rng>(&self, rng: &mut R) -> Duration {
+        let factor = rng.gen_range(0.5, 1.5) / COMPENSATION;
+        self.deterministic_interval().mul_f64(factor)
+    }
+}
+
+/// Updates the average RTCP packet size with the size of a packet sent or received.
+pub fn update_average_packet_size(average: f64, size: usize) -> f64 {
+    average + (size as f64 - average) / 16.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{IntervalParameters, COMPENSATION, MIN_INTERVAL};
+
+    #[test]
+    fn test_interval() {
+        let mut parameters = IntervalParameters {
+            members: 2,
+            senders: 1,
+            rtcp_bandwidth: 1_000.0,
+            we_sent: false,
+            average_packet_size: 100.0,
+            initial: true,
+        };
+        assert_eq!(parameters.deterministic_interval(), MIN_INTERVAL / 2);
+
+        // Many receivers share three quarters of the bandwidth.
+        parameters.members = 201;
+        parameters.initial = false;
+        assert_eq!(
+            parameters.deterministic_interval(),
+            Duration::from_secs_f64(200.0 * 100.0 / 750.0)
+        );
+
+        parameters.we_sent = true;
+        assert_eq!(parameters.deterministic_interval(), MIN_INTERVAL);
+
+        // Randomized between half and one and a half times the interval, divided by `e - 3/2`.
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let interval = parameters.interval(&mut rng);
+            assert!(interval >= MIN_INTERVAL.mul_f64(0.5 / COMPENSATION));
+            assert!(interval <= MIN_INTERVAL.mul_f64(1.5 / COMPENSATION));
+        }
+    }
+}
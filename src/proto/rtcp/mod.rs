@@ -6,6 +6,10 @@ pub mod bye_packet;
 pub mod app_defined_packet;
 pub mod transport_layer_feedback;
 pub mod payload_specific_feedback;
+pub mod extended_report_packet;
+pub mod reception;
+pub mod interval;
+pub mod ntp;
 
 mod feedback;
 
@@ -73,6 +77,14 @@ mod constants{
     pub const SDES_ITEM_TYPE_NOTE: u8 = 7;
     pub const SDES_ITEM_TYPE_PRIV: u8 = 8;
 
+    pub const XR_BLOCK_TYPE_LOSS_RLE: u8 = 1;
+    pub const XR_BLOCK_TYPE_DUPLICATE_RLE: u8 = 2;
+    pub const XR_BLOCK_TYPE_RECEIPT_TIMES: u8 = 3;
+    pub const XR_BLOCK_TYPE_RRTR: u8 = 4;
+    pub const XR_BLOCK_TYPE_DLRR: u8 = 5;
+    pub const XR_BLOCK_TYPE_STATISTICS_SUMMARY: u8 = 6;
+    pub const XR_BLOCK_TYPE_VOIP_METRICS: u8 = 7;
+
 
     pub const RTPFB_MESSAGE_TYPE_NACK: u8 = 1;
 
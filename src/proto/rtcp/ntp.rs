@@ -0,0 +1,53 @@
+//! Conversions between wallclock times and the NTP timestamps of RTCP sender reports.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The seconds from the NTP epoch, 1900, to the Unix epoch, 1970.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Returns the NTP timestamp of a wallclock time, as its seconds and fraction of a second.
+pub fn ntp_timestamp(time: SystemTime) -> (u32, u32) {
+    let since_epoch = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0));
+
+    // The seconds wrap around in 2036, as they do in the NTP timestamps.
+    let ntp_sec = (since_epoch.as_secs() + NTP_UNIX_OFFSET) as u32;
+    let ntp_frac = ((u64::from(since_epoch.subsec_nanos()) << 32) / 1_000_000_000) as u32;
+
+    (ntp_sec, ntp_frac)
+}
+
+/// Returns the wallclock time of an NTP timestamp, taking timestamps before the Unix epoch as
+/// wrapped around.
+pub fn system_time(ntp_sec: u32, ntp_frac: u32) -> SystemTime {
+    let mut secs = u64::from(ntp_sec);
+    if secs < NTP_UNIX_OFFSET {
+        secs += 1 << 32;
+    }
+
+    let nanos = (u64::from(ntp_frac) * 1_000_000_000) >> 32;
+    UNIX_EPOCH + Duration::new(secs - NTP_UNIX_OFFSET, nanos as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{ntp_timestamp, system_time};
+
+    #[test]
+    fn test_ntp_timestamp() {
+        let time = UNIX_EPOCH + Duration::new(1_600_000_000, 500_000_000);
+        let (ntp_sec, ntp_frac) = ntp_timestamp(time);
+        assert_eq!(ntp_sec, 3_808_988_800);
+        assert_eq!(ntp_frac, 0x8000_0000);
+        assert_eq!(system_time(ntp_sec, ntp_frac), time);
+
+        // Timestamps after 2036 wrap around.
+        let time = UNIX_EPOCH + Duration::from_secs(2_200_000_000);
+        let (ntp_sec, ntp_frac) = ntp_timestamp(time);
+        assert_eq!(ntp_sec, 114_021_504);
+        assert_eq!(system_time(ntp_sec, ntp_frac), time);
+    }
+}
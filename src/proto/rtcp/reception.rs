@@ -0,0 +1,317 @@
+//! Reception statistics of RTP sources, as reported in RTCP reception reports (RFC 3550).
+
+use std::time::{Duration, Instant};
+
+use crate::proto::rtcp::report_packet::{ReceptionReport, SenderReportPacket};
+use crate::proto::rtp::jitter::InterarrivalJitter;
+use crate::proto::rtp::rtp::RtpPacket;
+use crate::proto::rtp::sequence::{MAX_DROPOUT, MAX_MISORDER};
+
+/// The number of packets in sequence after which a source is valid.
+const MIN_SEQUENTIAL: u16 = 2;
+
+const SEQUENCE_MODULO: u32 = 1 << 16;
+
+/// The bounds of the cumulative number of packets lost, a signed 24 bit field.
+const MAX_PACKETS_LOST: i64 = 0x7f_ffff;
+const MIN_PACKETS_LOST: i64 = -0x80_0000;
+
+/// Returns the middle 32 bits of an NTP timestamp, as in the LSR field of reception reports.
+pub fn compact_ntp_timestamp(ntp_sec: u32, ntp_frac: u32) -> u32 {
+    (ntp_sec << 16) | (ntp_frac >> 16)
+}
+
+/// Converts a duration to units of 1/65536 seconds, as in the DLSR field of reception reports.
+pub fn compact_ntp_duration(duration: Duration) -> u32 {
+    let fraction = (u64::from(duration.subsec_nanos()) << 16) / 1_000_000_000;
+    let units = (duration.as_secs() << 16) + fraction;
+
+    if units > u64::from(u32::MAX) {
+        u32::MAX
+    } else {
+        units as u32
+    }
+}
+
+/// The statistics of the RTP packets received from a source, following sections A.1, A.3 and
+/// A.8 of RFC 3550.
+///
+/// A source is only valid once `MIN_SEQUENTIAL` packets arrived in sequence, the packets
+/// before are not counted. A jump in the sequence numbers is taken as a restart of the source
+/// if the next packet follows it.
+#[derive(Debug)]
+pub struct ReceptionStatistics {
+    ssrc: u32,
+
+    /// The highest sequence number received, and the number of sequence number cycles shifted
+    /// left by 16 bits.
+    max_seq: u16,
+    cycles: u64,
+
+    /// The first sequence number counted.
+    base_seq: u64,
+
+    /// The sequence number following a jump, which restarts the source if received next.
+    bad_seq: u32,
+
+    /// The number of packets in sequence still needed for the source to be valid.
+    probation: u16,
+
+    received: u64,
+    expected_prior: u64,
+    received_prior: u64,
+
+    jitter: InterarrivalJitter,
+
+    /// The compact NTP timestamp of the last sender report, and when it arrived.
+    last_sender_report: Option<(u32, Instant)>,
+}
+
+impl ReceptionStatistics {
+    /// Constructs the statistics of a source with RTP timestamps of the clock rate, from the
+    /// sequence number of its first packet, which is then to be passed to `update`.
+    pub fn new(ssrc: u32, clock_rate: u32, seq_num: u16) -> Self {
+        let mut statistics = ReceptionStatistics {
+            ssrc,
+            max_seq: 0,
+            cycles: 0,
+            base_seq: 0,
+            bad_seq: 0,
+            probation: MIN_SEQUENTIAL,
+            received: 0,
+            expected_prior: 0,
+            received_prior: 0,
+            jitter: InterarrivalJitter::new(clock_rate),
+            last_sender_report: None,
+        };
+
+        statistics.init_sequence(seq_num);
+        statistics.max_seq = seq_num.wrapping_sub(1);
+        statistics
+    }
+
+    fn init_sequence(&mut self, seq_num: u16) {
+        self.base_seq = u64::from(seq_num);
+        self.max_seq = seq_num;
+        self.bad_seq = SEQUENCE_MODULO + 1;
+        self.cycles = 0;
+        self.received = 0;
+        self.received_prior = 0;
+        self.expected_prior = 0;
+    }
+
+    /// Counts a packet which arrived at the given time. Returns whether the packet is valid,
+    /// i.e. it is neither part of the probation nor of an unconfirmed jump.
+    pub fn update(&mut self, packet: &RtpPacket, arrival: Instant) -> bool {
+        if !self.update_sequence(packet.header.seq_num) {
+            return false;
+        }
+
+        self.jitter.update(packet.header.timestamp, arrival);
+        true
+    }
+
+    fn update_sequence(&mut self, seq_num: u16) -> bool {
+        let delta = seq_num.wrapping_sub(self.max_seq);
+
+        if self.probation > 0 {
+            if seq_num == self.max_seq.wrapping_add(1) {
+                self.probation -= 1;
+                self.max_seq = seq_num;
+
+                if self.probation == 0 {
+                    self.init_sequence(seq_num);
+                    self.received += 1;
+                    return true;
+                }
+            } else {
+                self.probation = MIN_SEQUENTIAL - 1;
+                self.max_seq = seq_num;
+            }
+
+            return false;
+        }
+
+        if delta < MAX_DROPOUT {
+            if seq_num < self.max_seq {
+                self.cycles += u64::from(SEQUENCE_MODULO);
+            }
+            self.max_seq = seq_num;
+        } else if u32::from(delta) <= SEQUENCE_MODULO - u32::from(MAX_MISORDER) {
+            if u32::from(seq_num) != self.bad_seq {
+                self.bad_seq = u32::from(seq_num.wrapping_add(1));
+                return false;
+            }
+
+            // Two packets in sequence after the jump, the source restarted.
+            self.init_sequence(seq_num);
+        }
+
+        // Duplicate or reordered packets are counted as well.
+        self.received += 1;
+        true
+    }
+
+    /// Records a sender report of the source which arrived at the given time.
+    pub fn on_sender_report(&mut self, report: &SenderReportPacket, arrival: Instant) {
+        let timestamp = compact_ntp_timestamp(report.ntp_sec, report.ntp_frac);
+        self.last_sender_report = Some((timestamp, arrival));
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Returns whether enough packets arrived in sequence for the source to be valid.
+    pub fn is_valid(&self) -> bool {
+        self.probation == 0
+    }
+
+    pub fn extended_highest_sequence_number(&self) -> u64 {
+        self.cycles + u64::from(self.max_seq)
+    }
+
+    /// Returns the number of packets expected from the sequence numbers.
+    pub fn expected(&self) -> u64 {
+        (self.extended_highest_sequence_number() + 1).saturating_sub(self.base_seq)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Returns the cumulative number of packets lost, which is negative if duplicates arrived.
+    pub fn cumulative_lost(&self) -> i64 {
+        self.expected() as i64 - self.received as i64
+    }
+
+    /// Returns the interarrival jitter in units of the clock rate.
+    pub fn jitter(&self) -> u32 {
+        self.jitter.get()
+    }
+
+    /// Returns the reception report of the source at the given time. The fraction lost covers
+    /// the packets since the previous report.
+    pub fn report(&mut self, now: Instant) -> ReceptionReport {
+        let expected = self.expected();
+        let expected_interval = expected.saturating_sub(self.expected_prior);
+        let received_interval = self.received - self.received_prior;
+        self.expected_prior = expected;
+        self.received_prior = self.received;
+
+        let lost_interval = expected_interval as i64 - received_interval as i64;
+        let fraction_lost = if expected_interval == 0 || lost_interval <= 0 {
+            0
+        } else {
+            ((lost_interval << 8) / expected_interval as i64) as u8
+        };
+
+        let packets_lost = self
+            .cumulative_lost()
+            .clamp(MIN_PACKETS_LOST, MAX_PACKETS_LOST);
+
+        let (last_sr_timestamp, delay_since_last_sr) = match self.last_sender_report {
+            Some((timestamp, arrival)) => (
+                timestamp,
+                compact_ntp_duration(now.saturating_duration_since(arrival)),
+            ),
+            None => (0, 0),
+        };
+
+        ReceptionReport {
+            ssrc: self.ssrc,
+            fraction_lost,
+            packets_lost: (packets_lost as u32) & 0xff_ffff,
+            seq_num_ext: self.extended_highest_sequence_number() as u32,
+            jitter: self.jitter(),
+            last_sr_timestamp,
+            delay_since_last_sr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::proto::rtcp::report_packet::SenderReportPacket;
+    use crate::proto::rtp::rtp::{test_support, RtpPacket};
+
+    use super::{compact_ntp_duration, ReceptionStatistics};
+
+    fn packet(seq_num: u16, timestamp: u32) -> RtpPacket {
+        test_support::packet(seq_num, timestamp, false, &[0])
+    }
+
+    fn receive(statistics: &mut ReceptionStatistics, seq_nums: &[u16], now: Instant) {
+        for &seq_num in seq_nums {
+            statistics.update(&packet(seq_num, 0), now);
+        }
+    }
+
+    #[test]
+    fn test_loss() {
+        let now = Instant::now();
+        let mut statistics = ReceptionStatistics::new(0x1234_5678, 90_000, 0xfffd);
+
+        // The first packet is part of the probation.
+        assert!(!statistics.update(&packet(0xfffd, 0), now));
+        assert!(!statistics.is_valid());
+        assert!(statistics.update(&packet(0xfffe, 0), now));
+        assert!(statistics.is_valid());
+
+        // 3 of 8 packets are lost across the wrap around.
+        receive(&mut statistics, &[0xffff, 0x0002, 0x0004, 0x0005], now);
+        let report = statistics.report(now);
+        assert_eq!(report.ssrc, 0x1234_5678);
+        assert_eq!(report.seq_num_ext, 0x1_0005);
+        assert_eq!(statistics.expected(), 8);
+        assert_eq!(report.packets_lost, 3);
+        // 3/8 of 256.
+        assert_eq!(report.fraction_lost, 96);
+
+        // Late packets and duplicates reduce the cumulative loss.
+        receive(
+            &mut statistics,
+            &[0x0001, 0x0003, 0x0006, 0x0006, 0x0006],
+            now,
+        );
+        let report = statistics.report(now);
+        assert_eq!(report.fraction_lost, 0);
+        assert_eq!(report.packets_lost, 0xff_ffff);
+        assert_eq!(statistics.cumulative_lost(), -1);
+    }
+
+    #[test]
+    fn test_restart() {
+        let now = Instant::now();
+        let mut statistics = ReceptionStatistics::new(0x1234_5678, 90_000, 10);
+        receive(&mut statistics, &[10, 11, 12], now);
+
+        // A single packet far off is ignored, two in sequence restart the source.
+        assert!(!statistics.update(&packet(40_000, 0), now));
+        assert_eq!(statistics.extended_highest_sequence_number(), 12);
+        assert!(statistics.update(&packet(40_001, 0), now));
+        assert_eq!(statistics.extended_highest_sequence_number(), 40_001);
+        assert_eq!(statistics.received(), 1);
+        assert_eq!(statistics.cumulative_lost(), 0);
+    }
+
+    #[test]
+    fn test_last_sender_report() {
+        let now = Instant::now();
+        let mut statistics = ReceptionStatistics::new(0x1234_5678, 90_000, 1);
+        receive(&mut statistics, &[1, 2], now);
+        assert_eq!(statistics.report(now).last_sr_timestamp, 0);
+
+        let mut sender_report = SenderReportPacket::new(0x1234_5678);
+        sender_report.ntp_sec = 0x0102_0304;
+        sender_report.ntp_frac = 0x0506_0708;
+        statistics.on_sender_report(&sender_report, now);
+
+        let report = statistics.report(now + Duration::from_millis(1500));
+        assert_eq!(report.last_sr_timestamp, 0x0304_0506);
+        assert_eq!(report.delay_since_last_sr, 0x1_8000);
+        assert_eq!(compact_ntp_duration(Duration::from_secs(1 << 20)), u32::MAX);
+    }
+}
@@ -22,6 +22,7 @@ use crate::proto::types::*;
 
 use super::app_defined_packet::*;
 use super::bye_packet::*;
+use super::extended_report_packet::ExtendedReportPacket;
 use super::payload_specific_feedback;
 use super::report_packet::*;
 use super::source_description_packet::*;
@@ -306,7 +307,8 @@ impl ReadPacket for RtcpPacketReader {
             | RTCP_PACKET_TYPE_BYE
             | RTCP_PACKET_TYPE_APP
             | RTCP_PACKET_TYPE_RTPFB
-            | RTCP_PACKET_TYPE_PSFB => true,
+            | RTCP_PACKET_TYPE_PSFB
+            | RTCP_PACKET_TYPE_XR => true,
             _ => false,
         }
     }
@@ -336,6 +338,7 @@ pub enum RtcpPacket {
     App(ApplicationDefinedPacket),
     Rtpfb(TransportLayerFeedbackPacket),
     Psfb(PayloadSpecificFeedbackPacket),
+    Xr(ExtendedReportPacket),
 }
 impl PacketTrait for RtcpPacket {}
 impl RtcpPacketTrait for RtcpPacket {}
@@ -364,6 +367,9 @@ impl ReadFrom for RtcpPacket {
             RTCP_PACKET_TYPE_PSFB => {
                 track_err!(PayloadSpecificFeedbackPacket::read_from(reader).map(From::from))
             }
+            RTCP_PACKET_TYPE_XR => {
+                track_err!(ExtendedReportPacket::read_from(reader).map(From::from))
+            }
             _ => {
                 track_assert_eq!(buf[0] >> 6, RTP_VERSION, ErrorKind::Invalid);
                 track_panic!(
@@ -384,6 +390,7 @@ impl WriteTo for RtcpPacket {
             RtcpPacket::App(ref p) => track_err!(p.write_to(writer)),
             RtcpPacket::Rtpfb(ref p) => track_err!(p.write_to(writer)),
             RtcpPacket::Psfb(ref p) => track_err!(p.write_to(writer)),
+            RtcpPacket::Xr(ref p) => track_err!(p.write_to(writer)),
         }
     }
 }
@@ -470,6 +477,11 @@ impl From<PayloadSpecificFeedbackPacket> for RtcpPacket {
         RtcpPacket::Psfb(f)
     }
 }
+impl From<ExtendedReportPacket> for RtcpPacket {
+    fn from(f: ExtendedReportPacket) -> Self {
+        RtcpPacket::Xr(f)
+    }
+}
 
 
 pub fn read_sctp<R: Read>(reader: &mut R, expected_type: u8) -> Result<(U5, Vec<u8>)> {
@@ -167,19 +167,18 @@ impl Encoder<MuxedPacket<RtpPacket, RtcpCompoundPacket>> for Codec {
     /// message, a [`CodecEvent::EncodingStarted`] event will be sent. And after encoding has
     /// finished, an [`CodecEvent::EncodingEnded`] event will be sent.
     ///
-    /// Although a [`Result`] is returned, this function will never return an error as the actual
-    /// message encoding cannot fail. As a result, `Ok(())` will always be returned.
+    /// An error is returned if the packet is invalid, e.g. its padding does not match the padding
+    /// flag, in which case nothing is written to the buffer.
     fn encode(&mut self, message: MuxedPacket<RtpPacket, RtcpCompoundPacket>, buffer: &mut BytesMut) -> Result<(), Self::Error> {
         self.send_codec_event(CodecEvent::EncodingStarted);
 
-        let mut data = buffer.as_mut();
-
-        match message {
-            MuxedPacket::Rtp(rtp) => rtp.write_to(&mut data),
-            MuxedPacket::Rtcp(rtcp) => rtcp.write_to(&mut data),
+        let result = match message {
+            MuxedPacket::Rtp(rtp) => rtp.to_bytes(),
+            MuxedPacket::Rtcp(rtcp) => rtcp.to_bytes(),
         };
 
         self.send_codec_event(CodecEvent::EncodingEnded);
+        buffer.extend_from_slice(&track!(result)?);
         Ok(())
     }
 }
@@ -0,0 +1,518 @@
+//! RTP header extension elements (RFC 8285), in their one-byte and two-byte forms.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use fnv::FnvBuildHasher;
+
+use crate::proto::rtp::rtp::{RtpFixedHeader, RtpHeaderExtension};
+use crate::proto::sdp::attribute_type::{SdpAttribute, SdpAttributeExtmap, SdpAttributeType};
+use crate::proto::sdp::media_type::SdpMedia;
+use crate::proto::sdp::SdpSession;
+
+pub mod well_known;
+
+/// The profile of header extensions with one-byte element headers.
+pub const ONE_BYTE_PROFILE: u16 = 0xbede;
+
+/// The profile of header extensions with two-byte element headers, whose lowest 4 bits are
+/// application specific.
+pub const TWO_BYTE_PROFILE: u16 = 0x1000;
+
+const TWO_BYTE_PROFILE_MASK: u16 = 0xfff0;
+const APP_BITS_MASK: u16 = 0x000f;
+
+/// The ID of padding bytes between elements in both forms.
+const PADDING_ID: u8 = 0;
+
+/// The ID which stops the parsing of one-byte elements.
+const ONE_BYTE_RESERVED_ID: u8 = 15;
+
+const ONE_BYTE_MAX_ID: u8 = 14;
+const ONE_BYTE_MAX_LENGTH: usize = 16;
+const TWO_BYTE_MAX_LENGTH: usize = 255;
+
+/// Error that may be returned when parsing or building header extension elements.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ExtensionError {
+    /// The header extension ended before an element it contains.
+    Truncated,
+
+    /// The header extension does not use either form of RFC 8285.
+    UnsupportedProfile,
+
+    /// The element ID is 0, which is reserved for padding.
+    InvalidId,
+
+    /// The element data is longer than 255 bytes.
+    TooLong,
+
+    /// The element data is not valid for its extension.
+    InvalidData,
+
+    /// The extension is not mapped to an ID.
+    Unmapped,
+}
+
+impl Display for ExtensionError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::ExtensionError::*;
+
+        match self {
+            Truncated => write!(formatter, "truncated header extension"),
+            UnsupportedProfile => write!(formatter, "unsupported header extension profile"),
+            InvalidId => write!(formatter, "invalid header extension element ID"),
+            TooLong => write!(formatter, "header extension element too long"),
+            InvalidData => write!(formatter, "invalid header extension element data"),
+            Unmapped => write!(formatter, "header extension not mapped"),
+        }
+    }
+}
+
+impl Error for ExtensionError {}
+
+/// The form of the element headers of a header extension.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ExtensionForm {
+    /// IDs from 1 to 14 and 1 to 16 bytes of data.
+    OneByte,
+
+    /// IDs from 1 to 255 and up to 255 bytes of data.
+    TwoByte,
+}
+
+impl ExtensionForm {
+    /// Returns the form of a header extension profile, if it is one of RFC 8285.
+    pub fn from_profile(profile: u16) -> Option<Self> {
+        if profile == ONE_BYTE_PROFILE {
+            Some(ExtensionForm::OneByte)
+        } else if profile & TWO_BYTE_PROFILE_MASK == TWO_BYTE_PROFILE {
+            Some(ExtensionForm::TwoByte)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether an element fits into this form.
+    fn fits(self, element: &ExtensionElement) -> bool {
+        match self {
+            ExtensionForm::OneByte => {
+                element.id <= ONE_BYTE_MAX_ID
+                    && !element.data.is_empty()
+                    && element.data.len() <= ONE_BYTE_MAX_LENGTH
+            }
+            ExtensionForm::TwoByte => true,
+        }
+    }
+}
+
+/// A header extension whose elements have a typed value.
+pub trait HeaderExtension: Sized {
+    /// The URI identifying the extension in `extmap` attributes.
+    const URI: &'static str;
+
+    /// Parses the data of an element.
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError>;
+
+    /// Returns the data of an element with the value.
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
+/// An element of a header extension.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ExtensionElement {
+    /// The local ID of the element, mapped to the URI of its extension by the session
+    /// description.
+    pub id: u8,
+    pub data: Vec<u8>,
+}
+
+/// The elements of a header extension, for reading them and for adding and removing them when
+/// forwarding packets.
+///
+/// The elements are written in the one-byte form if all of them fit into it, and in the
+/// two-byte form otherwise.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ExtensionElements {
+    elements: Vec<ExtensionElement>,
+
+    /// The application specific bits of the two-byte profile.
+    app_bits: u8,
+}
+
+impl ExtensionElements {
+    /// Constructs an empty set of elements.
+    pub fn new() -> Self {
+        ExtensionElements::default()
+    }
+
+    /// Parses the elements of a header extension.
+    pub fn parse(extension: &RtpHeaderExtension) -> Result<Self, ExtensionError> {
+        let form = ExtensionForm::from_profile(extension.profile_specific)
+            .ok_or(ExtensionError::UnsupportedProfile)?;
+        let mut elements = Vec::new();
+        let mut data = &extension.extension[..];
+
+        while let Some(&first) = data.first() {
+            if first == PADDING_ID {
+                data = &data[1..];
+                continue;
+            }
+
+            let (id, header_size, length) = match form {
+                ExtensionForm::OneByte if first >> 4 == ONE_BYTE_RESERVED_ID => break,
+                ExtensionForm::OneByte => (first >> 4, 1, usize::from(first & 0x0f) + 1),
+                ExtensionForm::TwoByte => {
+                    let length = *data.get(1).ok_or(ExtensionError::Truncated)?;
+                    (first, 2, usize::from(length))
+                }
+            };
+
+            let element = data
+                .get(header_size..header_size + length)
+                .ok_or(ExtensionError::Truncated)?;
+            elements.push(ExtensionElement {
+                id,
+                data: element.to_vec(),
+            });
+            data = &data[header_size + length..];
+        }
+
+        let app_bits = match form {
+            ExtensionForm::OneByte => 0,
+            ExtensionForm::TwoByte => (extension.profile_specific & APP_BITS_MASK) as u8,
+        };
+
+        Ok(ExtensionElements { elements, app_bits })
+    }
+
+    /// Parses the elements of the header extension of a packet, if any.
+    pub fn from_header(header: &RtpFixedHeader) -> Result<Self, ExtensionError> {
+        match &header.extension {
+            Some(extension) => ExtensionElements::parse(extension),
+            None => Ok(ExtensionElements::new()),
+        }
+    }
+
+    /// Returns the data of the element with the ID, if any.
+    pub fn get(&self, id: u8) -> Option<&[u8]> {
+        self.elements
+            .iter()
+            .find(|element| element.id == id)
+            .map(|element| &element.data[..])
+    }
+
+    /// Returns the data of the element of the extension with the URI, if it is mapped and
+    /// present.
+    pub fn get_by_uri(&self, map: &ExtensionMap, uri: &str) -> Option<&[u8]> {
+        self.get(map.id(uri)?)
+    }
+
+    /// Parses the element of a typed extension, if it is mapped and present.
+    pub fn read<T: HeaderExtension>(
+        &self,
+        map: &ExtensionMap,
+    ) -> Result<Option<T>, ExtensionError> {
+        self.get_by_uri(map, T::URI).map(T::parse).transpose()
+    }
+
+    /// Sets the element of a typed extension, which must be mapped.
+    pub fn write<T: HeaderExtension>(
+        &mut self,
+        map: &ExtensionMap,
+        value: &T,
+    ) -> Result<(), ExtensionError> {
+        let id = map.id(T::URI).ok_or(ExtensionError::Unmapped)?;
+        self.insert(id, value.to_bytes()).map(|_| ())
+    }
+
+    /// Returns the elements in the order of the header extension.
+    pub fn iter(&self) -> impl Iterator<Item = &ExtensionElement> {
+        self.elements.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Sets the data of the element with the ID, returning its previous data.
+    pub fn insert(&mut self, id: u8, data: Vec<u8>) -> Result<Option<Vec<u8>>, ExtensionError> {
+        if id == PADDING_ID {
+            return Err(ExtensionError::InvalidId);
+        }
+
+        if data.len() > TWO_BYTE_MAX_LENGTH {
+            return Err(ExtensionError::TooLong);
+        }
+
+        match self.elements.iter_mut().find(|element| element.id == id) {
+            Some(element) => Ok(Some(std::mem::replace(&mut element.data, data))),
+            None => {
+                self.elements.push(ExtensionElement { id, data });
+                Ok(None)
+            }
+        }
+    }
+
+    /// Removes the element with the ID, returning its data.
+    pub fn remove(&mut self, id: u8) -> Option<Vec<u8>> {
+        let index = self.elements.iter().position(|element| element.id == id)?;
+        Some(self.elements.remove(index).data)
+    }
+
+    /// Keeps only the elements for which the predicate returns true, e.g. those whose
+    /// extensions were negotiated with the receiver a packet is forwarded to.
+    pub fn retain<F: FnMut(&ExtensionElement) -> bool>(&mut self, predicate: F) {
+        self.elements.retain(predicate);
+    }
+
+    /// Builds the header extension of the elements, or `None` if there are none.
+    pub fn to_extension(&self) -> Option<RtpHeaderExtension> {
+        if self.elements.is_empty() {
+            return None;
+        }
+
+        let form = if self
+            .elements
+            .iter()
+            .all(|element| ExtensionForm::OneByte.fits(element))
+        {
+            ExtensionForm::OneByte
+        } else {
+            ExtensionForm::TwoByte
+        };
+
+        let mut extension = Vec::new();
+
+        for element in &self.elements {
+            match form {
+                ExtensionForm::OneByte => {
+                    extension.push(element.id << 4 | (element.data.len() - 1) as u8)
+                }
+                ExtensionForm::TwoByte => {
+                    extension.extend_from_slice(&[element.id, element.data.len() as u8])
+                }
+            }
+
+            extension.extend_from_slice(&element.data);
+        }
+
+        // The header extension is padded to a multiple of 4 bytes.
+        while extension.len() % 4 != 0 {
+            extension.push(PADDING_ID);
+        }
+
+        let profile_specific = match form {
+            ExtensionForm::OneByte => ONE_BYTE_PROFILE,
+            ExtensionForm::TwoByte => TWO_BYTE_PROFILE | u16::from(self.app_bits),
+        };
+
+        Some(RtpHeaderExtension {
+            profile_specific,
+            extension,
+        })
+    }
+
+    /// Replaces the header extension of a packet with the elements.
+    pub fn write_to_header(&self, header: &mut RtpFixedHeader) {
+        header.extension = self.to_extension();
+    }
+}
+
+/// Maps the local IDs of header extension elements to the URIs of their extensions, as given
+/// by the `extmap` attributes of the session description.
+#[derive(Clone, Debug, Default)]
+pub struct ExtensionMap {
+    uris: HashMap<u8, String, FnvBuildHasher>,
+}
+
+impl ExtensionMap {
+    /// Constructs an empty map.
+    pub fn new() -> Self {
+        ExtensionMap::default()
+    }
+
+    /// Constructs the map of a media description from its `extmap` attributes, or from those
+    /// of the session if they are given at session level.
+    pub fn from_media(session: &SdpSession, media: &SdpMedia) -> Self {
+        let mut map = ExtensionMap::new();
+        let attributes = session
+            .attribute
+            .iter()
+            .filter(|attribute| SdpAttributeType::from(*attribute) == SdpAttributeType::Extmap)
+            .chain(media.get_attributes_of_type(SdpAttributeType::Extmap));
+
+        for attribute in attributes {
+            if let SdpAttribute::Extmap(extmap) = attribute {
+                map.insert_extmap(extmap);
+            }
+        }
+
+        map
+    }
+
+    /// Maps the ID of an `extmap` attribute to its URI. IDs beyond the range of the two-byte
+    /// form, which are only used for negotiation, are ignored.
+    pub fn insert_extmap(&mut self, extmap: &SdpAttributeExtmap) {
+        if extmap.id > 0 && extmap.id <= 255 {
+            self.insert(extmap.id as u8, extmap.url.clone());
+        }
+    }
+
+    /// Maps an ID to a URI, returning the previous URI.
+    pub fn insert(&mut self, id: u8, uri: String) -> Option<String> {
+        self.uris.insert(id, uri)
+    }
+
+    /// Returns the URI of the extension with the ID, if mapped.
+    pub fn uri(&self, id: u8) -> Option<&str> {
+        self.uris.get(&id).map(String::as_str)
+    }
+
+    /// Returns the ID of the extension with the URI, if mapped.
+    pub fn id(&self, uri: &str) -> Option<u8> {
+        self.uris
+            .iter()
+            .find(|(_, mapped)| mapped.as_str() == uri)
+            .map(|(&id, _)| id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::rtp::RtpHeaderExtension;
+    use crate::proto::sdp::parse_sdp;
+
+    use super::{
+        ExtensionElements, ExtensionError, ExtensionMap, ONE_BYTE_PROFILE, TWO_BYTE_PROFILE,
+    };
+
+    #[test]
+    fn test_parse_one_byte() {
+        let extension = RtpHeaderExtension {
+            profile_specific: ONE_BYTE_PROFILE,
+            extension: vec![
+                0x10, 0xff, 0x00, 0x21, 0x01, 0x02, 0xf0, 0x33, 0x00, 0x00, 0x00, 0x00,
+            ],
+        };
+
+        let elements = ExtensionElements::parse(&extension).unwrap();
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements.get(1), Some(&[0xff][..]));
+        assert_eq!(elements.get(2), Some(&[0x01, 0x02][..]));
+        assert_eq!(elements.get(3), None);
+
+        let extension = RtpHeaderExtension {
+            profile_specific: ONE_BYTE_PROFILE,
+            extension: vec![0x13, 0x01, 0x02, 0x03],
+        };
+        assert_eq!(
+            ExtensionElements::parse(&extension),
+            Err(ExtensionError::Truncated)
+        );
+
+        let extension = RtpHeaderExtension {
+            profile_specific: 0x1234,
+            extension: Vec::new(),
+        };
+        assert_eq!(
+            ExtensionElements::parse(&extension),
+            Err(ExtensionError::UnsupportedProfile)
+        );
+    }
+
+    #[test]
+    fn test_parse_two_byte() {
+        let extension = RtpHeaderExtension {
+            profile_specific: TWO_BYTE_PROFILE | 0x3,
+            extension: vec![0x01, 0x00, 0x00, 0x20, 0x02, 0xaa, 0xbb, 0x00],
+        };
+
+        let elements = ExtensionElements::parse(&extension).unwrap();
+        assert_eq!(elements.get(1), Some(&[][..]));
+        assert_eq!(elements.get(0x20), Some(&[0xaa, 0xbb][..]));
+
+        // The application specific bits are kept when written back, the padding between the
+        // elements is not.
+        assert_eq!(
+            elements.to_extension(),
+            Some(RtpHeaderExtension {
+                profile_specific: TWO_BYTE_PROFILE | 0x3,
+                extension: vec![0x01, 0x00, 0x20, 0x02, 0xaa, 0xbb, 0x00, 0x00],
+            })
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let mut elements = ExtensionElements::new();
+        elements.insert(1, vec![0xff]).unwrap();
+        elements.insert(3, vec![0x01, 0x02, 0x03]).unwrap();
+        assert_eq!(elements.insert(1, vec![0xfe]).unwrap(), Some(vec![0xff]));
+        assert_eq!(
+            elements.insert(0, vec![0xff]),
+            Err(ExtensionError::InvalidId)
+        );
+        assert_eq!(
+            elements.insert(2, vec![0; 256]),
+            Err(ExtensionError::TooLong)
+        );
+
+        assert_eq!(
+            elements.to_extension(),
+            Some(RtpHeaderExtension {
+                profile_specific: ONE_BYTE_PROFILE,
+                extension: vec![0x10, 0xfe, 0x32, 0x01, 0x02, 0x03, 0x00, 0x00],
+            })
+        );
+
+        // Elements which do not fit into the one-byte form switch to the two-byte one.
+        elements.insert(20, vec![0xaa]).unwrap();
+        let extension = elements.to_extension().unwrap();
+        assert_eq!(extension.profile_specific, TWO_BYTE_PROFILE);
+        assert_eq!(ExtensionElements::parse(&extension).unwrap(), elements);
+
+        assert_eq!(elements.remove(20), Some(vec![0xaa]));
+        assert_eq!(elements.remove(20), None);
+        elements.retain(|element| element.id != 1);
+        elements.retain(|element| element.id != 3);
+        assert_eq!(elements.to_extension(), None);
+    }
+
+    #[test]
+    fn test_extension_map() {
+        let sdp = parse_sdp(
+            "v=0\r\n\
+             o=- 0 0 IN IP4 127.0.0.1\r\n\
+             s=-\r\n\
+             c=IN IP4 127.0.0.1\r\n\
+             t=0 0\r\n\
+             m=video 0 RTP/AVP 96\r\n\
+             a=extmap:1 urn:ietf:params:rtp-hdrext:sdes:mid\r\n\
+             a=extmap:3/sendonly http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\n",
+            false,
+        )
+        .unwrap();
+        let map = ExtensionMap::from_media(&sdp, &sdp.media[0]);
+
+        assert_eq!(map.uri(1), Some("urn:ietf:params:rtp-hdrext:sdes:mid"));
+        assert_eq!(
+            map.id("http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time"),
+            Some(3)
+        );
+        assert_eq!(map.uri(2), None);
+
+        let mut elements = ExtensionElements::new();
+        elements.insert(1, b"audio".to_vec()).unwrap();
+        assert_eq!(
+            elements.get_by_uri(&map, "urn:ietf:params:rtp-hdrext:sdes:mid"),
+            Some(&b"audio"[..])
+        );
+    }
+}
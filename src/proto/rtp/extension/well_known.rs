@@ -0,0 +1,389 @@
+//! Typed values of widely used header extensions.
+
+use std::str;
+use std::time::Duration;
+
+use crate::proto::rtp::extension::{ExtensionError, HeaderExtension};
+
+/// Returns the first bytes of the data of an element, which has a fixed length.
+fn fixed(data: &[u8], length: usize) -> Result<&[u8], ExtensionError> {
+    data.get(..length).ok_or(ExtensionError::Truncated)
+}
+
+/// The offset of the transmission time of a packet from its RTP timestamp, in units of the
+/// clock rate (RFC 5450).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TransmissionTimeOffset(pub i32);
+
+impl HeaderExtension for TransmissionTimeOffset {
+    const URI: &'static str = "urn:ietf:params:rtp-hdrext:toffset";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        let data = fixed(data, 3)?;
+
+        // Sign extends the 24 bit offset.
+        Ok(TransmissionTimeOffset(
+            i32::from_be_bytes([data[0], data[1], data[2], 0]) >> 8,
+        ))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_be_bytes()[1..].to_vec()
+    }
+}
+
+/// The absolute send time of a packet, as 24 bit fixed point seconds with 18 fractional bits,
+/// which wrap every 64 seconds.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AbsSendTime(pub u32);
+
+const ABS_SEND_TIME_FRACTION_BITS: u32 = 18;
+const ABS_SEND_TIME_MASK: u32 = 0x00ff_ffff;
+
+impl AbsSendTime {
+    /// Constructs the send time from the time since any epoch, such as the NTP one.
+    pub fn from_duration(time: Duration) -> Self {
+        let seconds = (time.as_secs() as u32) << ABS_SEND_TIME_FRACTION_BITS;
+        let fraction =
+            (u64::from(time.subsec_nanos()) << ABS_SEND_TIME_FRACTION_BITS) / 1_000_000_000;
+
+        AbsSendTime((seconds | fraction as u32) & ABS_SEND_TIME_MASK)
+    }
+
+    /// Returns the send time within the 64 seconds it wraps in.
+    pub fn to_duration(self) -> Duration {
+        Duration::from_nanos(
+            (u64::from(self.0 & ABS_SEND_TIME_MASK) * 1_000_000_000) >> ABS_SEND_TIME_FRACTION_BITS,
+        )
+    }
+}
+
+impl HeaderExtension for AbsSendTime {
+    const URI: &'static str = "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        let data = fixed(data, 3)?;
+        Ok(AbsSendTime(u32::from_be_bytes([
+            0, data[0], data[1], data[2],
+        ])))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_be_bytes()[1..].to_vec()
+    }
+}
+
+/// The sequence number of a packet across all streams of a transport, for transport-wide
+/// congestion control feedback.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TransportSequenceNumber(pub u16);
+
+impl HeaderExtension for TransportSequenceNumber {
+    const URI: &'static str =
+        "http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        let data = fixed(data, 2)?;
+        Ok(TransportSequenceNumber(u16::from_be_bytes([
+            data[0], data[1],
+        ])))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_be_bytes().to_vec()
+    }
+}
+
+/// The level of the audio of a packet (RFC 6464).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AudioLevel {
+    /// Does the audio contain voice, as far as the sender can tell.
+    pub voice_activity: bool,
+
+    /// The level in -dBov, from 0 for the loudest to 127 for silence.
+    pub level: u8,
+}
+
+const VOICE_ACTIVITY_FLAG: u8 = 0b1000_0000;
+const AUDIO_LEVEL_MASK: u8 = 0b0111_1111;
+
+impl HeaderExtension for AudioLevel {
+    const URI: &'static str = "urn:ietf:params:rtp-hdrext:ssrc-audio-level";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        let byte = fixed(data, 1)?[0];
+
+        Ok(AudioLevel {
+            voice_activity: byte & VOICE_ACTIVITY_FLAG != 0,
+            level: byte & AUDIO_LEVEL_MASK,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let flag = if self.voice_activity {
+            VOICE_ACTIVITY_FLAG
+        } else {
+            0
+        };
+
+        vec![flag | self.level.min(AUDIO_LEVEL_MASK)]
+    }
+}
+
+/// The counter-clockwise rotation of a video frame.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Rotation {
+    None,
+    Degrees90,
+    Degrees180,
+    Degrees270,
+}
+
+impl Rotation {
+    /// Returns the rotation in degrees.
+    pub fn degrees(self) -> u16 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Degrees90 => 90,
+            Rotation::Degrees180 => 180,
+            Rotation::Degrees270 => 270,
+        }
+    }
+}
+
+/// The orientation of the camera capturing the video (3GPP TS 26.114 coordination of video
+/// orientation, CVO), which receivers apply when rendering.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct VideoOrientation {
+    /// Was the video captured by a back-facing camera.
+    pub back_facing: bool,
+
+    /// Is the video flipped horizontally.
+    pub flip: bool,
+
+    pub rotation: Rotation,
+}
+
+const CAMERA_FLAG: u8 = 0b0000_1000;
+const FLIP_FLAG: u8 = 0b0000_0100;
+const ROTATION_MASK: u8 = 0b0000_0011;
+
+impl HeaderExtension for VideoOrientation {
+    const URI: &'static str = "urn:3gpp:video-orientation";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        let byte = fixed(data, 1)?[0];
+        let rotation = match byte & ROTATION_MASK {
+            0 => Rotation::None,
+            1 => Rotation::Degrees90,
+            2 => Rotation::Degrees180,
+            _ => Rotation::Degrees270,
+        };
+
+        Ok(VideoOrientation {
+            back_facing: byte & CAMERA_FLAG != 0,
+            flip: byte & FLIP_FLAG != 0,
+            rotation,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut byte = (self.rotation.degrees() / 90) as u8;
+
+        if self.back_facing {
+            byte |= CAMERA_FLAG;
+        }
+
+        if self.flip {
+            byte |= FLIP_FLAG;
+        }
+
+        vec![byte]
+    }
+}
+
+/// The range of the delay from capture to playout the sender asks the receiver for.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PlayoutDelay {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+/// The unit of the playout delays, in milliseconds.
+const PLAYOUT_DELAY_UNIT: u64 = 10;
+const PLAYOUT_DELAY_MAX: u64 = 0x0fff;
+
+impl HeaderExtension for PlayoutDelay {
+    const URI: &'static str = "http://www.webrtc.org/experiments/rtp-hdrext/playout-delay";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        let data = fixed(data, 3)?;
+        let min = u64::from(data[0]) << 4 | u64::from(data[1] >> 4);
+        let max = u64::from(data[1] & 0x0f) << 8 | u64::from(data[2]);
+
+        Ok(PlayoutDelay {
+            min: Duration::from_millis(min * PLAYOUT_DELAY_UNIT),
+            max: Duration::from_millis(max * PLAYOUT_DELAY_UNIT),
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let units = |delay: Duration| {
+            (delay.as_millis() as u64 / PLAYOUT_DELAY_UNIT).min(PLAYOUT_DELAY_MAX) as u16
+        };
+        let (min, max) = (units(self.min), units(self.max));
+
+        vec![
+            (min >> 4) as u8,
+            (min << 4) as u8 | (max >> 8) as u8,
+            max as u8,
+        ]
+    }
+}
+
+/// Parses the identifier of an SDES item extension, which is not empty.
+fn parse_identifier(data: &[u8]) -> Result<String, ExtensionError> {
+    if data.is_empty() {
+        return Err(ExtensionError::Truncated);
+    }
+
+    str::from_utf8(data)
+        .map(str::to_string)
+        .map_err(|_| ExtensionError::InvalidData)
+}
+
+/// The identification tag of the media description of a packet, for bundling (RFC 8843).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Mid(pub String);
+
+impl HeaderExtension for Mid {
+    const URI: &'static str = "urn:ietf:params:rtp-hdrext:sdes:mid";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        parse_identifier(data).map(Mid)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.as_bytes().to_vec()
+    }
+}
+
+/// The RTP stream ID of a packet, identifying its simulcast layer (RFC 8852).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RtpStreamId(pub String);
+
+impl HeaderExtension for RtpStreamId {
+    const URI: &'static str = "urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        parse_identifier(data).map(RtpStreamId)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.as_bytes().to_vec()
+    }
+}
+
+/// The RTP stream ID of the stream a redundancy or retransmission packet repairs (RFC 8852).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RepairedRtpStreamId(pub String);
+
+impl HeaderExtension for RepairedRtpStreamId {
+    const URI: &'static str = "urn:ietf:params:rtp-hdrext:sdes:repaired-rtp-stream-id";
+
+    fn parse(data: &[u8]) -> Result<Self, ExtensionError> {
+        parse_identifier(data).map(RepairedRtpStreamId)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.as_bytes().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::proto::rtp::extension::{
+        ExtensionElements, ExtensionError, ExtensionMap, HeaderExtension,
+    };
+
+    use super::{
+        AbsSendTime, AudioLevel, Mid, PlayoutDelay, Rotation, TransmissionTimeOffset,
+        TransportSequenceNumber, VideoOrientation,
+    };
+
+    #[test]
+    fn test_timing_extensions() {
+        assert_eq!(
+            TransmissionTimeOffset::parse(&[0xff, 0xff, 0xfe]),
+            Ok(TransmissionTimeOffset(-2))
+        );
+        assert_eq!(
+            TransmissionTimeOffset(-2).to_bytes(),
+            vec![0xff, 0xff, 0xfe]
+        );
+        assert_eq!(
+            TransmissionTimeOffset(0x1234).to_bytes(),
+            vec![0x00, 0x12, 0x34]
+        );
+
+        let time = AbsSendTime::from_duration(Duration::from_millis(65_500));
+        assert_eq!(time, AbsSendTime(0x06_0000));
+        assert_eq!(time.to_duration(), Duration::from_millis(1_500));
+        assert_eq!(AbsSendTime::parse(&time.to_bytes()), Ok(time));
+
+        let delay = PlayoutDelay {
+            min: Duration::from_millis(100),
+            max: Duration::from_millis(40_950),
+        };
+        assert_eq!(delay.to_bytes(), vec![0x00, 0xaf, 0xff]);
+        assert_eq!(PlayoutDelay::parse(&delay.to_bytes()), Ok(delay));
+
+        assert_eq!(AbsSendTime::parse(&[0x00]), Err(ExtensionError::Truncated));
+    }
+
+    #[test]
+    fn test_media_extensions() {
+        assert_eq!(
+            AudioLevel::parse(&[0x85]),
+            Ok(AudioLevel {
+                voice_activity: true,
+                level: 5,
+            })
+        );
+
+        let orientation = VideoOrientation::parse(&[0x0b]).unwrap();
+        assert!(orientation.back_facing);
+        assert!(!orientation.flip);
+        assert_eq!(orientation.rotation, Rotation::Degrees270);
+        assert_eq!(orientation.to_bytes(), vec![0x0b]);
+
+        assert_eq!(Mid::parse(&[0xff]), Err(ExtensionError::InvalidData));
+        assert_eq!(Mid::parse(&[]), Err(ExtensionError::Truncated));
+    }
+
+    #[test]
+    fn test_typed_elements() {
+        let mut map = ExtensionMap::new();
+        map.insert(1, Mid::URI.to_string());
+        map.insert(5, TransportSequenceNumber::URI.to_string());
+
+        let mut elements = ExtensionElements::new();
+        elements.write(&map, &Mid("video".to_string())).unwrap();
+        elements
+            .write(&map, &TransportSequenceNumber(0xabcd))
+            .unwrap();
+        assert_eq!(
+            elements.write(&map, &AbsSendTime(0)),
+            Err(ExtensionError::Unmapped)
+        );
+
+        assert_eq!(elements.get(5), Some(&[0xab, 0xcd][..]));
+        assert_eq!(
+            elements.read::<Mid>(&map),
+            Ok(Some(Mid("video".to_string())))
+        );
+        assert_eq!(elements.read::<AbsSendTime>(&map), Ok(None));
+    }
+}
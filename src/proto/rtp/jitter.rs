@@ -0,0 +1,459 @@
+//! Reordering of received RTP packets by sequence number, with the interarrival jitter of
+//! RFC 3550.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::proto::rtp::rtp::RtpPacket;
+use crate::proto::rtp::sequence::{extend_sequence_number, is_sequence_jump};
+
+/// The number of packets held unless configured otherwise.
+pub const DEFAULT_MAX_PACKETS: usize = 512;
+
+/// How many times the interarrival jitter an adaptive latency is.
+const JITTER_MULTIPLIER: f64 = 4.0;
+
+/// The interarrival jitter of section A.8 of RFC 3550.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct InterarrivalJitter {
+    clock_rate: u32,
+
+    /// The jitter in units of the clock rate.
+    jitter: f64,
+
+    /// The base of the arrival times, along with the relative transit time of the last packet.
+    transit: Option<(Instant, i64)>,
+}
+
+impl InterarrivalJitter {
+    pub(crate) fn new(clock_rate: u32) -> Self {
+        InterarrivalJitter {
+            clock_rate,
+            jitter: 0.0,
+            transit: None,
+        }
+    }
+
+    /// Updates the jitter with a packet of the timestamp which arrived at the given time.
+    pub(crate) fn update(&mut self, timestamp: u32, arrival: Instant) {
+        let (base, last_transit) = match self.transit {
+            Some(transit) => transit,
+            None => {
+                self.transit = Some((arrival, -i64::from(timestamp)));
+                return;
+            }
+        };
+
+        let arrival =
+            arrival.saturating_duration_since(base).as_secs_f64() * f64::from(self.clock_rate);
+        let transit = arrival as i64 - i64::from(timestamp);
+
+        // The difference wraps around with the timestamps.
+        let difference = i64::from((transit - last_transit) as i32);
+        self.jitter += ((difference.abs() as f64) - self.jitter) / 16.0;
+        self.transit = Some((base, transit));
+    }
+
+    /// Returns the jitter in units of the clock rate, as reported in RTCP reception reports.
+    pub(crate) fn get(&self) -> u32 {
+        self.jitter as u32
+    }
+
+    pub(crate) fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.jitter / f64::from(self.clock_rate.max(1)))
+    }
+}
+
+/// How long packets after a gap in the sequence numbers are held back for the missing ones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+
+    /// A latency following the interarrival jitter, within the bounds.
+    Adaptive {
+        min: Duration,
+        max: Duration,
+    },
+}
+
+/// What happened to a packet pushed into a jitter buffer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PushOutcome {
+    /// The packet is held until it can be released in order.
+    Buffered,
+
+    /// A packet with the same sequence number is held already, the packet was dropped.
+    Duplicate,
+
+    /// The packet arrived after packets following it were released, it was dropped.
+    Late,
+
+    /// The sequence number jumped far from the previous ones, the packet is held aside until
+    /// the one following it confirms that the stream restarted.
+    Probation,
+}
+
+#[derive(Debug)]
+struct Entry {
+    packet: RtpPacket,
+    arrival: Instant,
+}
+
+/// Reorders the packets of a single stream, i.e. of an SSRC, by their extended sequence
+/// numbers.
+///
+/// Packets following the last released one are released right away. Packets after a gap are
+/// held back until the missing ones arrive, or the latency has passed since they arrived, in
+/// which case the missing ones are declared lost. The released packets then skip sequence
+/// numbers, which makes depacketizers resynchronize.
+///
+/// Two packets in sequence far from the previous ones restart the stream, as after a restart
+/// of the sender. The packets held from before are then dropped and counted as lost.
+#[derive(Debug)]
+pub struct JitterBuffer {
+    latency: Latency,
+    max_packets: usize,
+
+    /// The held packets by extended sequence number.
+    packets: BTreeMap<u64, Entry>,
+
+    /// The extended sequence number of the next packet to release.
+    next: Option<u64>,
+
+    /// The highest extended sequence number received.
+    highest: Option<u64>,
+
+    /// The last packet after a jump in the sequence numbers.
+    jump: Option<Entry>,
+
+    jitter: InterarrivalJitter,
+
+    duplicates: u64,
+    late: u64,
+    lost: u64,
+}
+
+impl JitterBuffer {
+    /// Constructs a new jitter buffer for a stream with RTP timestamps of the clock rate.
+    pub fn new(clock_rate: u32, latency: Latency) -> Self {
+        JitterBuffer {
+            latency,
+            max_packets: DEFAULT_MAX_PACKETS,
+            packets: BTreeMap::new(),
+            next: None,
+            highest: None,
+            jump: None,
+            jitter: InterarrivalJitter::new(clock_rate),
+            duplicates: 0,
+            late: 0,
+            lost: 0,
+        }
+    }
+
+    /// Sets the number of packets held at most. Once exceeded, packets are released despite
+    /// gaps before them.
+    pub fn set_max_packets(&mut self, max_packets: usize) {
+        self.max_packets = max_packets.max(1);
+    }
+
+    /// Adds a packet which arrived at the given time.
+    pub fn push(&mut self, packet: RtpPacket, arrival: Instant) -> PushOutcome {
+        let sequence_number = packet.header.seq_num;
+
+        if let Some(highest) = self.highest {
+            if is_sequence_jump(highest as u16, sequence_number) {
+                let follows_jump = self.jump.as_ref().map_or(false, |jump| {
+                    jump.packet.header.seq_num.wrapping_add(1) == sequence_number
+                });
+
+                if !follows_jump {
+                    self.jump = Some(Entry { packet, arrival });
+                    return PushOutcome::Probation;
+                }
+
+                // Two packets in sequence after the jump, the stream restarted. The restarted
+                // sender picks a new timestamp base, which is no transit time to the old one.
+                self.lost += self.packets.len() as u64;
+                self.packets.clear();
+                self.next = None;
+                self.highest = None;
+                self.jitter = InterarrivalJitter::new(self.jitter.clock_rate);
+
+                if let Some(jump) = self.jump.take() {
+                    self.push(jump.packet, jump.arrival);
+                }
+
+                return self.push(packet, arrival);
+            }
+
+            self.jump = None;
+        }
+
+        let extended = match self.highest {
+            Some(highest) => extend_sequence_number(highest, sequence_number),
+            None => u64::from(sequence_number),
+        };
+
+        if self.next.map_or(false, |next| extended < next) {
+            self.late += 1;
+            return PushOutcome::Late;
+        }
+
+        if self.packets.contains_key(&extended) {
+            self.duplicates += 1;
+            return PushOutcome::Duplicate;
+        }
+
+        self.jitter.update(packet.header.timestamp, arrival);
+        self.highest = Some(
+            self.highest
+                .map_or(extended, |highest| highest.max(extended)),
+        );
+        self.next.get_or_insert(extended);
+        self.packets.insert(extended, Entry { packet, arrival });
+
+        PushOutcome::Buffered
+    }
+
+    /// Returns the next packet that can be released at the given time, if any.
+    pub fn pop(&mut self, now: Instant) -> Option<RtpPacket> {
+        let (&first, entry) = self.packets.iter().next()?;
+        let next = self.next.unwrap_or(first);
+
+        if first != next {
+            let waited = now.saturating_duration_since(entry.arrival);
+
+            if waited < self.latency() && self.packets.len() <= self.max_packets {
+                return None;
+            }
+
+            self.lost += first - next;
+        }
+
+        self.next = Some(first + 1);
+        self.packets.remove(&first).map(|entry| entry.packet)
+    }
+
+    /// Returns when the next packet can be released if none arrive before, if packets are
+    /// held.
+    pub fn deadline(&self) -> Option<Instant> {
+        let (&first, entry) = self.packets.iter().next()?;
+
+        if Some(first) == self.next {
+            Some(entry.arrival)
+        } else {
+            Some(entry.arrival + self.latency())
+        }
+    }
+
+    /// Returns how long packets after a gap are held back.
+    pub fn latency(&self) -> Duration {
+        match self.latency {
+            Latency::Fixed(latency) => latency,
+            Latency::Adaptive { min, max } => {
+                let latency = self.jitter_duration().mul_f64(JITTER_MULTIPLIER);
+                latency.max(min).min(max)
+            }
+        }
+    }
+
+    /// Returns the interarrival jitter in units of the clock rate, as reported in RTCP
+    /// reception reports.
+    pub fn jitter(&self) -> u32 {
+        self.jitter.get()
+    }
+
+    /// Returns the interarrival jitter as a duration.
+    pub fn jitter_duration(&self) -> Duration {
+        self.jitter.duration()
+    }
+
+    /// Returns the highest extended sequence number received, if any.
+    pub fn highest_sequence_number(&self) -> Option<u64> {
+        self.highest
+    }
+
+    /// Returns the number of packets dropped as duplicates.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// Returns the number of packets dropped for arriving too late.
+    pub fn late(&self) -> u64 {
+        self.late
+    }
+
+    /// Returns the number of packets declared lost.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Returns the number of packets held.
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::proto::rtp::rtp::{test_support, RtpPacket};
+
+    use super::{JitterBuffer, Latency, PushOutcome};
+
+    fn packet(seq_num: u16, timestamp: u32) -> RtpPacket {
+        test_support::packet(seq_num, timestamp, false, &[0])
+    }
+
+    fn pop_all(buffer: &mut JitterBuffer, now: Instant) -> Vec<u16> {
+        std::iter::from_fn(|| buffer.pop(now))
+            .map(|packet| packet.header.seq_num)
+            .collect()
+    }
+
+    #[test]
+    fn test_reorder() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Latency::Fixed(Duration::from_millis(50)));
+
+        assert_eq!(buffer.push(packet(0xfffe, 0), start), PushOutcome::Buffered);
+        assert_eq!(pop_all(&mut buffer, start), vec![0xfffe]);
+
+        // Packets after a gap are held until it is filled.
+        buffer.push(packet(0x0000, 0), start);
+        buffer.push(packet(0x0001, 0), start);
+        assert_eq!(pop_all(&mut buffer, start), Vec::<u16>::new());
+        assert_eq!(buffer.deadline(), Some(start + Duration::from_millis(50)));
+
+        assert_eq!(buffer.push(packet(0xffff, 0), start), PushOutcome::Buffered);
+        assert_eq!(
+            buffer.push(packet(0x0000, 0), start),
+            PushOutcome::Duplicate
+        );
+        assert_eq!(pop_all(&mut buffer, start), vec![0xffff, 0x0000, 0x0001]);
+        assert_eq!(buffer.highest_sequence_number(), Some(0x1_0001));
+
+        assert_eq!(buffer.push(packet(0xffff, 0), start), PushOutcome::Late);
+        assert_eq!(buffer.duplicates(), 1);
+        assert_eq!(buffer.late(), 1);
+        assert_eq!(buffer.lost(), 0);
+    }
+
+    #[test]
+    fn test_loss_timeout() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Latency::Fixed(Duration::from_millis(50)));
+
+        buffer.push(packet(1, 0), start);
+        buffer.push(packet(4, 0), start + Duration::from_millis(10));
+        assert_eq!(pop_all(&mut buffer, start), vec![1]);
+        assert_eq!(
+            pop_all(&mut buffer, start + Duration::from_millis(59)),
+            Vec::<u16>::new()
+        );
+
+        // The missing packets are declared lost once the latency has passed.
+        assert_eq!(
+            pop_all(&mut buffer, start + Duration::from_millis(60)),
+            vec![4]
+        );
+        assert_eq!(buffer.lost(), 2);
+        assert_eq!(buffer.push(packet(3, 0), start), PushOutcome::Late);
+
+        // Gaps are skipped as well once too many packets are held.
+        buffer.set_max_packets(2);
+        buffer.push(packet(6, 0), start);
+        buffer.push(packet(7, 0), start);
+        assert_eq!(pop_all(&mut buffer, start), Vec::<u16>::new());
+        buffer.push(packet(8, 0), start);
+        assert_eq!(pop_all(&mut buffer, start), vec![6, 7, 8]);
+        assert_eq!(buffer.lost(), 3);
+    }
+
+    #[test]
+    fn test_sequence_jump() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Latency::Fixed(Duration::from_millis(50)));
+
+        buffer.push(packet(1000, 0), start);
+        buffer.push(packet(1001, 0), start);
+        assert_eq!(pop_all(&mut buffer, start), vec![1000, 1001]);
+
+        // A stray packet far from the others is dropped.
+        assert_eq!(buffer.push(packet(40_000, 0), start), PushOutcome::Probation);
+        assert_eq!(buffer.push(packet(1002, 0), start), PushOutcome::Buffered);
+        assert_eq!(pop_all(&mut buffer, start), vec![1002]);
+
+        // The sender restarted with lower sequence numbers, with a packet of the old stream
+        // still held.
+        buffer.push(packet(1004, 0), start);
+        assert_eq!(buffer.push(packet(10, 0), start), PushOutcome::Probation);
+        assert_eq!(buffer.push(packet(11, 0), start), PushOutcome::Buffered);
+        assert_eq!(pop_all(&mut buffer, start), vec![10, 11]);
+        assert_eq!(buffer.highest_sequence_number(), Some(11));
+        assert_eq!(buffer.lost(), 1);
+
+        assert_eq!(buffer.push(packet(12, 0), start), PushOutcome::Buffered);
+        assert_eq!(pop_all(&mut buffer, start), vec![12]);
+    }
+
+    #[test]
+    fn test_adaptive_latency() {
+        let start = Instant::now();
+        let latency = Latency::Adaptive {
+            min: Duration::from_millis(10),
+            max: Duration::from_millis(500),
+        };
+        let mut buffer = JitterBuffer::new(1_000, latency);
+
+        buffer.push(packet(1, 0), start);
+        assert_eq!(buffer.latency(), Duration::from_millis(10));
+
+        // Packets sent 20 ms apart arrive alternately 10 and 30 ms apart.
+        for index in 1..200u16 {
+            let arrival = u64::from(index) * 20 + u64::from(index % 2) * 10;
+            buffer.push(
+                packet(index + 1, u32::from(index) * 20),
+                start + Duration::from_millis(arrival),
+            );
+        }
+
+        assert!((9..=10).contains(&buffer.jitter()));
+        assert!(buffer.latency() > Duration::from_millis(30));
+        assert!(buffer.latency() < Duration::from_millis(45));
+    }
+
+    #[test]
+    fn test_adaptive_latency_restart() {
+        let start = Instant::now();
+        let latency = Latency::Adaptive {
+            min: Duration::from_millis(10),
+            max: Duration::from_millis(500),
+        };
+        let mut buffer = JitterBuffer::new(1_000, latency);
+
+        for index in 0..10u16 {
+            let arrival = start + Duration::from_millis(u64::from(index) * 20);
+            buffer.push(packet(1000 + index, u32::from(index) * 20), arrival);
+        }
+        assert_eq!(buffer.latency(), Duration::from_millis(10));
+
+        // The sender restarts with new sequence numbers and a new timestamp base.
+        let restart = start + Duration::from_millis(200);
+        assert_eq!(
+            buffer.push(packet(40_000, 0x8000_0000), restart),
+            PushOutcome::Probation
+        );
+        assert_eq!(
+            buffer.push(packet(40_001, 0x8000_0014), restart + Duration::from_millis(20)),
+            PushOutcome::Buffered
+        );
+        assert_eq!(buffer.jitter(), 0);
+        assert_eq!(buffer.latency(), Duration::from_millis(10));
+    }
+}
@@ -2,6 +2,12 @@ pub mod rtp;
 pub mod srtp;
 pub mod mutex;
 pub mod codec;
+pub mod payload;
+pub mod extension;
+pub mod jitter;
+pub mod sequence;
+pub mod sender;
+pub mod wallclock;
 
 
 pub mod constants{
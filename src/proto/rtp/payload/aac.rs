@@ -0,0 +1,964 @@
+//! AAC audio, as MPEG-4 generic (RFC 3640) or MP4A-LATM (RFC 3016) payloads.
+
+use std::collections::VecDeque;
+
+use crate::proto::rtp::payload::bits::BitReader;
+use crate::proto::rtp::payload::{
+    DepacketizeError, Depacketizer, Frame, SequenceCheck, SequenceTracker,
+};
+use crate::proto::rtp::rtp::RtpPacket;
+use crate::proto::sdp::attribute_type::SdpAttributeFmtpParameters;
+
+/// The sampling frequencies of the sampling frequency indices.
+const SAMPLING_FREQUENCIES: [u32; 13] = [
+    96_000, 88_200, 64_000, 48_000, 44_100, 32_000, 24_000, 22_050, 16_000, 12_000, 11_025, 8_000,
+    7_350,
+];
+
+/// The sampling frequency index telling that the frequency follows explicitly.
+const EXPLICIT_SAMPLING_FREQUENCY: u32 = 0xf;
+
+/// The audio object type telling that the type follows in an escape field.
+const OBJECT_TYPE_ESCAPE: u32 = 31;
+
+const OBJECT_TYPE_SBR: u8 = 5;
+const OBJECT_TYPE_PS: u8 = 29;
+const OBJECT_TYPE_ER_AAC_LD: u8 = 23;
+
+/// The size of an ADTS header without CRC.
+const ADTS_HEADER_SIZE: usize = 7;
+
+/// The largest frame an ADTS header can describe, including the header.
+const ADTS_MAX_FRAME_SIZE: usize = 0x1fff;
+
+/// The decoder configuration of an AAC stream (ISO/IEC 14496-3).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AudioSpecificConfig {
+    /// The audio object type of the core coder, e.g. 2 for AAC LC. For HE-AAC signaled
+    /// explicitly, this is the type of the underlying AAC.
+    pub object_type: u8,
+
+    /// The sampling frequency of the core coder in Hz.
+    pub sampling_frequency: u32,
+
+    /// The output sampling frequency of spectral band replication, if signaled explicitly.
+    pub extension_sampling_frequency: Option<u32>,
+
+    /// The channel configuration, e.g. 2 for stereo. 0 means the channels are described by the
+    /// stream itself.
+    pub channel_configuration: u8,
+
+    /// The number of samples per channel of a frame at the core sampling frequency.
+    pub frame_length: u32,
+}
+
+impl AudioSpecificConfig {
+    /// Parses the configuration, as found in the `config` parameter of MPEG-4 generic payloads.
+    ///
+    /// Only the general audio object types, i.e. the AAC variants, are supported.
+    pub fn parse(data: &[u8]) -> Result<Self, DepacketizeError> {
+        AudioSpecificConfig::read(&mut BitReader::new(data)).map_err(config_error)
+    }
+
+    fn read(reader: &mut BitReader<'_>) -> Result<Self, DepacketizeError> {
+        let mut object_type = read_object_type(reader)?;
+        let sampling_frequency = read_sampling_frequency(reader)?;
+        let channel_configuration = reader.read(4)? as u8;
+        let mut extension_sampling_frequency = None;
+
+        if object_type == OBJECT_TYPE_SBR || object_type == OBJECT_TYPE_PS {
+            extension_sampling_frequency = Some(read_sampling_frequency(reader)?);
+            object_type = read_object_type(reader)?;
+        }
+
+        // GASpecificConfig
+        match object_type {
+            1..=4 | 6 | 7 | 17 | 19..=23 => (),
+            _ => return Err(DepacketizeError::Unsupported),
+        }
+
+        let frame_length_flag = reader.read_bool()?;
+        let frame_length = match (object_type, frame_length_flag) {
+            (OBJECT_TYPE_ER_AAC_LD, false) => 512,
+            (OBJECT_TYPE_ER_AAC_LD, true) => 480,
+            (_, false) => 1024,
+            (_, true) => 960,
+        };
+
+        if reader.read_bool()? {
+            // coreCoderDelay
+            reader.skip(14)?;
+        }
+
+        let extension_flag = reader.read_bool()?;
+
+        if channel_configuration == 0 {
+            // A program config element would follow.
+            return Err(DepacketizeError::Unsupported);
+        }
+
+        if object_type == 6 || object_type == 20 {
+            // layerNr
+            reader.skip(3)?;
+        }
+
+        if extension_flag {
+            if object_type == 22 {
+                // numOfSubFrame and layer_length
+                reader.skip(16)?;
+            }
+
+            if object_type == 17 || object_type == 19 || object_type == 20 || object_type == 23 {
+                // The resilience flags.
+                reader.skip(3)?;
+            }
+
+            // extensionFlag3
+            reader.skip(1)?;
+        }
+
+        if object_type == 17 || (19..=23).contains(&object_type) {
+            // epConfig
+            reader.skip(2)?;
+        }
+
+        Ok(AudioSpecificConfig {
+            object_type,
+            sampling_frequency,
+            extension_sampling_frequency,
+            channel_configuration,
+            frame_length,
+        })
+    }
+
+    /// Returns the ADTS header preceding a raw frame of the given size, or `None` if the stream
+    /// or the frame cannot be described by an ADTS header.
+    pub fn adts_header(&self, frame_size: usize) -> Option<[u8; ADTS_HEADER_SIZE]> {
+        if !(1..=4).contains(&self.object_type) || self.channel_configuration > 7 {
+            return None;
+        }
+
+        let index = SAMPLING_FREQUENCIES
+            .iter()
+            .position(|&frequency| frequency == self.sampling_frequency)? as u8;
+        let length = frame_size + ADTS_HEADER_SIZE;
+
+        if length > ADTS_MAX_FRAME_SIZE {
+            return None;
+        }
+
+        let profile = self.object_type - 1;
+        let channels = self.channel_configuration;
+
+        Some([
+            0xff,
+            // MPEG-4, layer 0, no CRC
+            0xf1,
+            profile << 6 | index << 2 | channels >> 2,
+            (channels & 0b11) << 6 | (length >> 11) as u8,
+            (length >> 3) as u8,
+            // The buffer fullness is set to 0x7ff, meaning a variable bitrate.
+            ((length & 0b111) as u8) << 5 | 0b1_1111,
+            // One raw data block.
+            0b1111_1100,
+        ])
+    }
+}
+
+fn read_object_type(reader: &mut BitReader<'_>) -> Result<u8, DepacketizeError> {
+    let object_type = reader.read(5)?;
+
+    if object_type == OBJECT_TYPE_ESCAPE {
+        Ok((32 + reader.read(6)?) as u8)
+    } else {
+        Ok(object_type as u8)
+    }
+}
+
+fn read_sampling_frequency(reader: &mut BitReader<'_>) -> Result<u32, DepacketizeError> {
+    let index = reader.read(4)?;
+
+    if index == EXPLICIT_SAMPLING_FREQUENCY {
+        return reader.read(24);
+    }
+
+    SAMPLING_FREQUENCIES
+        .get(index as usize)
+        .copied()
+        .ok_or(DepacketizeError::InvalidConfig)
+}
+
+/// Reports a configuration that ends early as invalid rather than as a truncated payload.
+fn config_error(error: DepacketizeError) -> DepacketizeError {
+    match error {
+        DepacketizeError::Truncated => DepacketizeError::InvalidConfig,
+        error => error,
+    }
+}
+
+/// The layout of the AU headers of MPEG-4 generic payloads, in bits.
+#[derive(Clone, Copy, Debug, Default)]
+struct AuHeaderLayout {
+    size_length: usize,
+    index_length: usize,
+    index_delta_length: usize,
+    cts_delta_length: usize,
+    dts_delta_length: usize,
+    random_access_indication: bool,
+
+    /// The size of every AU if not signaled by the AU headers, in bytes.
+    constant_size: usize,
+}
+
+impl AuHeaderLayout {
+    fn new(parameters: &SdpAttributeFmtpParameters) -> Result<Self, DepacketizeError> {
+        let mut layout = AuHeaderLayout {
+            size_length: parameters.size_length as usize,
+            index_length: parameters.index_length as usize,
+            index_delta_length: parameters.index_delta_length as usize,
+            cts_delta_length: parameters.cts_delta_length as usize,
+            dts_delta_length: parameters.dts_delta_length as usize,
+            random_access_indication: parameters.random_access_indication,
+            constant_size: parameters.constant_size as usize,
+        };
+
+        // The lengths are mandatory for the AAC modes, but some senders leave them out.
+        if layout.size_length == 0 && layout.constant_size == 0 {
+            if parameters.mode.eq_ignore_ascii_case("AAC-hbr") {
+                layout.size_length = 13;
+                layout.index_length = 3;
+                layout.index_delta_length = 3;
+            } else if parameters.mode.eq_ignore_ascii_case("AAC-lbr") {
+                layout.size_length = 6;
+                layout.index_length = 2;
+                layout.index_delta_length = 2;
+            }
+        }
+
+        let lengths = [
+            layout.size_length,
+            layout.index_length,
+            layout.index_delta_length,
+            layout.cts_delta_length,
+            layout.dts_delta_length,
+        ];
+
+        if lengths.iter().any(|&length| length > 32) {
+            return Err(DepacketizeError::InvalidConfig);
+        }
+
+        // The AU headers following the first would be empty, and never end the section.
+        if layout.has_headers() && layout.following_header_length() == 0 {
+            return Err(DepacketizeError::InvalidConfig);
+        }
+
+        Ok(layout)
+    }
+
+    /// Returns the least length of the AU headers following the first one, in bits.
+    fn following_header_length(&self) -> usize {
+        self.size_length
+            + self.index_delta_length
+            + (self.cts_delta_length != 0) as usize
+            + (self.dts_delta_length != 0) as usize
+            + self.random_access_indication as usize
+    }
+
+    fn has_headers(&self) -> bool {
+        self.size_length != 0
+            || self.index_length != 0
+            || self.index_delta_length != 0
+            || self.cts_delta_length != 0
+            || self.dts_delta_length != 0
+            || self.random_access_indication
+    }
+
+    /// Reads the AU header section at the start of a payload.
+    ///
+    /// Returns the AU headers and the data following the section.
+    fn read<'a>(&self, payload: &'a [u8]) -> Result<(Vec<AuHeader>, &'a [u8]), DepacketizeError> {
+        if payload.len() < 2 {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let length = usize::from(u16::from_be_bytes([payload[0], payload[1]]));
+        let end = 2 + (length + 7) / 8;
+        let section = payload.get(2..end).ok_or(DepacketizeError::Truncated)?;
+        let mut reader = BitReader::new(section);
+        let mut headers: Vec<AuHeader> = Vec::new();
+
+        while reader.position() < length {
+            let position = reader.position();
+
+            let size = if self.size_length != 0 {
+                Some(reader.read(self.size_length)? as usize)
+            } else {
+                None
+            };
+
+            let index = match headers.last() {
+                Some(previous) => {
+                    let delta = reader.read(self.index_delta_length)?;
+                    previous.index.wrapping_add(delta).wrapping_add(1)
+                }
+                None => reader.read(self.index_length)?,
+            };
+
+            let mut cts_delta = None;
+
+            if self.cts_delta_length != 0 && reader.read_bool()? {
+                let delta = reader.read(self.cts_delta_length)?;
+                cts_delta = Some(sign_extend(delta, self.cts_delta_length));
+            }
+
+            if self.dts_delta_length != 0 && reader.read_bool()? {
+                reader.skip(self.dts_delta_length)?;
+            }
+
+            let random_access_point = !self.random_access_indication || reader.read_bool()?;
+
+            if reader.position() == position {
+                return Err(DepacketizeError::Malformed);
+            }
+
+            headers.push(AuHeader {
+                size,
+                index,
+                cts_delta,
+                random_access_point,
+            });
+        }
+
+        Ok((headers, &payload[end..]))
+    }
+}
+
+/// Interprets the lowest bits of a value as a two's complement number.
+fn sign_extend(value: u32, bits: usize) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AuHeader {
+    /// The size of the AU in bytes, if signaled.
+    size: Option<usize>,
+
+    /// The index of the AU in the interleaving sequence.
+    index: u32,
+
+    /// The offset of the composition time of the AU from the RTP timestamp.
+    cts_delta: Option<i32>,
+
+    random_access_point: bool,
+}
+
+/// How the AUs are packetized.
+#[derive(Clone, Copy, Debug)]
+enum Packetization {
+    /// MPEG-4 generic (RFC 3640).
+    Generic(AuHeaderLayout),
+
+    /// MP4A-LATM (RFC 3016) with the number of frames per audio mux element.
+    Latm { sub_frames: usize },
+}
+
+/// An AU fragmented over several packets, or an audio mux element being accumulated.
+#[derive(Clone, Debug)]
+struct Pending {
+    timestamp: u32,
+
+    /// The size of a fragmented AU, if known.
+    size: Option<usize>,
+
+    data: Vec<u8>,
+
+    keyframe: bool,
+
+    corrupted: bool,
+}
+
+/// Reassembles raw AAC frames from MPEG-4 generic or MP4A-LATM RTP packets.
+///
+/// Every frame gets the timestamp of its first sample, so frames sharing a packet get their own
+/// timestamps. The frames may be prefixed with ADTS headers, which turns them into a stream most
+/// players can decode without the `AudioSpecificConfig`.
+///
+/// A frame missing some of its data is emitted as corrupted. LATM payloads are only supported
+/// with the configuration given out of band (`cpresent=0`), a single program and layer, and
+/// frames of variable length.
+#[derive(Debug)]
+pub struct AacDepacketizer {
+    adts_headers: bool,
+
+    clock_rate: u32,
+
+    config: AudioSpecificConfig,
+
+    /// The frames that have not been pulled yet.
+    frames: VecDeque<Frame>,
+
+    packetization: Packetization,
+
+    pending: Option<Pending>,
+
+    sequence: SequenceTracker,
+}
+
+impl AacDepacketizer {
+    /// Constructs a depacketizer of MPEG-4 generic payloads with the format parameters and clock
+    /// rate of the payload type.
+    pub fn mpeg4_generic(
+        parameters: &SdpAttributeFmtpParameters,
+        clock_rate: u32,
+    ) -> Result<Self, DepacketizeError> {
+        let config = AudioSpecificConfig::parse(&parameters.config)?;
+        let layout = AuHeaderLayout::new(parameters)?;
+        AacDepacketizer::new(config, Packetization::Generic(layout), clock_rate)
+    }
+
+    /// Constructs a depacketizer of MP4A-LATM payloads with the format parameters and clock rate
+    /// of the payload type.
+    pub fn latm(
+        parameters: &SdpAttributeFmtpParameters,
+        clock_rate: u32,
+    ) -> Result<Self, DepacketizeError> {
+        if parameters.cpresent {
+            return Err(DepacketizeError::Unsupported);
+        }
+
+        let mut reader = BitReader::new(&parameters.config);
+        let (config, sub_frames) = read_stream_mux_config(&mut reader).map_err(config_error)?;
+        AacDepacketizer::new(config, Packetization::Latm { sub_frames }, clock_rate)
+    }
+
+    fn new(
+        config: AudioSpecificConfig,
+        packetization: Packetization,
+        clock_rate: u32,
+    ) -> Result<Self, DepacketizeError> {
+        if config.sampling_frequency == 0 || clock_rate == 0 {
+            return Err(DepacketizeError::InvalidConfig);
+        }
+
+        Ok(AacDepacketizer {
+            adts_headers: false,
+            clock_rate,
+            config,
+            frames: VecDeque::new(),
+            packetization,
+            pending: None,
+            sequence: SequenceTracker::default(),
+        })
+    }
+
+    /// Returns the decoder configuration of the stream.
+    pub fn config(&self) -> &AudioSpecificConfig {
+        &self.config
+    }
+
+    /// Sets whether the frames are prefixed with ADTS headers. Frames of streams that cannot be
+    /// described by ADTS headers are left raw.
+    pub fn set_adts_headers(&mut self, adts_headers: bool) {
+        self.adts_headers = adts_headers;
+    }
+
+    /// Returns the offset of the frame with the given index from the first frame of a packet, in
+    /// units of the clock rate.
+    fn frame_offset(&self, index: u32) -> u32 {
+        let samples = u64::from(index) * u64::from(self.config.frame_length);
+        (samples * u64::from(self.clock_rate) / u64::from(self.config.sampling_frequency)) as u32
+    }
+
+    fn emit(&mut self, timestamp: u32, data: &[u8], keyframe: bool, corrupted: bool) {
+        let header = if self.adts_headers {
+            self.config.adts_header(data.len())
+        } else {
+            None
+        };
+
+        let mut frame = Frame {
+            timestamp,
+            data: Vec::with_capacity(ADTS_HEADER_SIZE + data.len()),
+            keyframe,
+            corrupted,
+        };
+
+        if let Some(header) = header {
+            frame.data.extend_from_slice(&header);
+        }
+
+        frame.data.extend_from_slice(data);
+        self.frames.push_back(frame);
+    }
+
+    fn depacketize_generic(
+        &mut self,
+        layout: &AuHeaderLayout,
+        packet: &RtpPacket,
+    ) -> Result<(), DepacketizeError> {
+        let timestamp = packet.header.timestamp;
+
+        if let Some(pending) = self.pending.as_mut() {
+            if pending.timestamp == timestamp {
+                // The continuation of a fragmented AU, whose header is repeated in every packet.
+                let data = if layout.has_headers() {
+                    layout.read(&packet.payload)?.1
+                } else {
+                    &packet.payload[..]
+                };
+
+                pending.data.extend_from_slice(data);
+
+                if packet.header.marker
+                    || pending
+                        .size
+                        .map_or(false, |size| pending.data.len() >= size)
+                {
+                    self.finish_pending()?;
+                }
+
+                return Ok(());
+            }
+
+            self.finish_pending()?;
+        }
+
+        let (headers, mut data) = if layout.has_headers() {
+            layout.read(&packet.payload)?
+        } else {
+            (Vec::new(), &packet.payload[..])
+        };
+
+        if headers.is_empty() {
+            let size = if layout.constant_size != 0 {
+                layout.constant_size
+            } else {
+                data.len()
+            };
+
+            let mut index = 0;
+
+            while !data.is_empty() {
+                let au = data.get(..size).ok_or(DepacketizeError::Truncated)?;
+                self.emit(
+                    timestamp.wrapping_add(self.frame_offset(index)),
+                    au,
+                    true,
+                    false,
+                );
+                data = &data[size..];
+                index += 1;
+            }
+
+            return Ok(());
+        }
+
+        let first_index = headers[0].index;
+
+        for header in &headers {
+            let au_timestamp = match header.cts_delta {
+                Some(delta) => timestamp.wrapping_add(delta as u32),
+                None => timestamp
+                    .wrapping_add(self.frame_offset(header.index.wrapping_sub(first_index))),
+            };
+
+            let size = match header.size {
+                Some(size) => size,
+                None if layout.constant_size != 0 => layout.constant_size,
+                None => data.len(),
+            };
+
+            if size > data.len() {
+                if headers.len() != 1 {
+                    return Err(DepacketizeError::Truncated);
+                }
+
+                // The first fragment of an AU.
+                self.pending = Some(Pending {
+                    timestamp: au_timestamp,
+                    size: Some(size),
+                    data: data.to_vec(),
+                    keyframe: header.random_access_point,
+                    corrupted: false,
+                });
+
+                if packet.header.marker {
+                    self.finish_pending()?;
+                }
+
+                return Ok(());
+            }
+
+            self.emit(
+                au_timestamp,
+                &data[..size],
+                header.random_access_point,
+                false,
+            );
+            data = &data[size..];
+        }
+
+        Ok(())
+    }
+
+    fn depacketize_latm(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        let timestamp = packet.header.timestamp;
+
+        if self
+            .pending
+            .as_ref()
+            .map_or(false, |pending| pending.timestamp != timestamp)
+        {
+            // The end of the previous audio mux element was lost.
+            self.corrupt();
+            self.finish_pending()?;
+        }
+
+        self.pending
+            .get_or_insert_with(|| Pending {
+                timestamp,
+                size: None,
+                data: Vec::new(),
+                keyframe: true,
+                corrupted: false,
+            })
+            .data
+            .extend_from_slice(&packet.payload);
+
+        if packet.header.marker {
+            self.finish_pending()?;
+        }
+
+        Ok(())
+    }
+
+    /// Emits the frames of the pending AU or audio mux element.
+    fn finish_pending(&mut self) -> Result<(), DepacketizeError> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        match self.packetization {
+            Packetization::Generic(_) => {
+                let complete = pending.size.map_or(true, |size| pending.data.len() == size);
+                let corrupted = pending.corrupted || !complete;
+                self.emit(
+                    pending.timestamp,
+                    &pending.data,
+                    pending.keyframe,
+                    corrupted,
+                );
+                Ok(())
+            }
+            Packetization::Latm { sub_frames } => {
+                let result = self.emit_audio_mux_element(&pending, sub_frames);
+
+                // The lengths of a corrupted element are unreliable, so failing to follow them is
+                // expected.
+                if pending.corrupted {
+                    Ok(())
+                } else {
+                    result
+                }
+            }
+        }
+    }
+
+    /// Splits an audio mux element into its frames.
+    fn emit_audio_mux_element(
+        &mut self,
+        element: &Pending,
+        sub_frames: usize,
+    ) -> Result<(), DepacketizeError> {
+        let mut data = &element.data[..];
+
+        for index in 0..sub_frames {
+            // PayloadLengthInfo
+            let mut size = 0;
+
+            loop {
+                let (&byte, rest) = data.split_first().ok_or(DepacketizeError::Truncated)?;
+                size += usize::from(byte);
+                data = rest;
+
+                if byte != 0xff {
+                    break;
+                }
+            }
+
+            // PayloadMux
+            let frame = data.get(..size).ok_or(DepacketizeError::Truncated)?;
+            let timestamp = element
+                .timestamp
+                .wrapping_add(self.frame_offset(index as u32));
+            self.emit(timestamp, frame, true, element.corrupted);
+            data = &data[size..];
+        }
+
+        Ok(())
+    }
+
+    fn corrupt(&mut self) {
+        if let Some(pending) = self.pending.as_mut() {
+            pending.corrupted = true;
+        }
+    }
+}
+
+impl Depacketizer for AacDepacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        match self.sequence.check(packet.header.seq_num) {
+            SequenceCheck::Late => return Ok(()),
+            SequenceCheck::Lost => {
+                self.corrupt();
+
+                // Without a pending element the packet may continue an element whose start was
+                // lost.
+                if let Packetization::Latm { .. } = self.packetization {
+                    if self.pending.is_none() {
+                        self.pending = Some(Pending {
+                            timestamp: packet.header.timestamp,
+                            size: None,
+                            data: Vec::new(),
+                            keyframe: true,
+                            corrupted: true,
+                        });
+                    }
+                }
+            }
+            SequenceCheck::InOrder => (),
+        }
+
+        let result = match self.packetization {
+            Packetization::Generic(layout) => self.depacketize_generic(&layout, packet),
+            Packetization::Latm { .. } => self.depacketize_latm(packet),
+        };
+
+        if result.is_err() {
+            self.corrupt();
+        }
+
+        result
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+}
+
+/// Reads the StreamMuxConfig of an LATM stream.
+///
+/// Returns the configuration of its only layer and the number of frames per audio mux element.
+fn read_stream_mux_config(
+    reader: &mut BitReader<'_>,
+) -> Result<(AudioSpecificConfig, usize), DepacketizeError> {
+    let audio_mux_version = reader.read_bool()?;
+    let all_streams_same_time_framing = reader.read_bool()?;
+    let sub_frames = reader.read(6)? as usize + 1;
+    let programs = reader.read(4)? + 1;
+    let layers = reader.read(3)? + 1;
+
+    if audio_mux_version || !all_streams_same_time_framing || programs != 1 || layers != 1 {
+        return Err(DepacketizeError::Unsupported);
+    }
+
+    let config = AudioSpecificConfig::read(reader)?;
+
+    // Only frames of variable length, whose length precedes each of them, are supported.
+    let frame_length_type = reader.read(3)?;
+
+    if frame_length_type != 0 {
+        return Err(DepacketizeError::Unsupported);
+    }
+
+    Ok((config, sub_frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::{DepacketizeError, Depacketizer};
+    use crate::proto::rtp::rtp::test_support::packet;
+    use crate::proto::sdp::attribute_type::{SdpAttribute, SdpAttributeFmtpParameters};
+
+    use super::{AacDepacketizer, AuHeaderLayout, AudioSpecificConfig};
+
+    fn parameters(fmtp: &str) -> SdpAttributeFmtpParameters {
+        match fmtp.parse().unwrap() {
+            SdpAttribute::Fmtp(fmtp) => fmtp.parameters,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_audio_specific_config() {
+        assert_eq!(
+            AudioSpecificConfig::parse(&[0x12, 0x10]),
+            Ok(AudioSpecificConfig {
+                object_type: 2,
+                sampling_frequency: 44_100,
+                extension_sampling_frequency: None,
+                channel_configuration: 2,
+                frame_length: 1024,
+            })
+        );
+
+        // HE-AAC with explicit SBR signaling
+        assert_eq!(
+            AudioSpecificConfig::parse(&[0x2b, 0x11, 0x88, 0x00]),
+            Ok(AudioSpecificConfig {
+                object_type: 2,
+                sampling_frequency: 24_000,
+                extension_sampling_frequency: Some(48_000),
+                channel_configuration: 2,
+                frame_length: 1024,
+            })
+        );
+
+        assert_eq!(
+            AudioSpecificConfig::parse(&[0x12]),
+            Err(DepacketizeError::InvalidConfig)
+        );
+        assert_eq!(
+            AudioSpecificConfig::parse(&[0x17, 0x10]),
+            Err(DepacketizeError::InvalidConfig)
+        );
+    }
+
+    #[test]
+    fn test_adts_header() {
+        let config = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+
+        assert_eq!(
+            config.adts_header(100),
+            Some([0xff, 0xf1, 0x50, 0x80, 0x0d, 0x7f, 0xfc])
+        );
+        assert_eq!(config.adts_header(0x2000), None);
+    }
+
+    #[test]
+    fn test_depacketize_mpeg4_generic() {
+        let parameters = parameters(
+            "fmtp:96 streamtype=5;profile-level-id=15;mode=AAC-hbr;config=1190;\
+             sizelength=13;indexlength=3;indexdeltalength=3",
+        );
+        let mut depacketizer = AacDepacketizer::mpeg4_generic(&parameters, 48_000).unwrap();
+        depacketizer.set_adts_headers(true);
+
+        // Two AUs of 2 and 3 bytes.
+        let payload = [0x00, 0x20, 0x00, 0x10, 0x00, 0x18, 1, 2, 3, 4, 5];
+        depacketizer.push(&packet(1, 1000, true, &payload)).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 1000);
+        assert_eq!(&frame.data[..2], &[0xff, 0xf1]);
+        assert_eq!(&frame.data[7..], &[1, 2]);
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 2024);
+        assert_eq!(&frame.data[7..], &[3, 4, 5]);
+
+        assert_eq!(depacketizer.pull(), None);
+    }
+
+    #[test]
+    fn test_empty_au_headers() {
+        // The AU headers after the first one would be empty.
+        let parameters = parameters(
+            "fmtp:96 mode=generic;config=1190;\
+             indexlength=3;indexdeltalength=0;sizelength=0;constantsize=4",
+        );
+        assert_eq!(
+            AacDepacketizer::mpeg4_generic(&parameters, 48_000).err(),
+            Some(DepacketizeError::InvalidConfig)
+        );
+
+        let layout = AuHeaderLayout::default();
+        assert_eq!(
+            layout.read(&[0x00, 0x08, 0x00]).err(),
+            Some(DepacketizeError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_depacketize_mpeg4_generic_fragmented() {
+        let parameters = parameters("fmtp:96 mode=AAC-lbr;config=1190");
+        let mut depacketizer = AacDepacketizer::mpeg4_generic(&parameters, 48_000).unwrap();
+
+        // An AU of 5 bytes over two packets.
+        depacketizer
+            .push(&packet(1, 1000, false, &[0x00, 0x08, 0x14, 1, 2, 3]))
+            .unwrap();
+        assert_eq!(depacketizer.pull(), None);
+        depacketizer
+            .push(&packet(2, 1000, true, &[0x00, 0x08, 0x14, 4, 5]))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 1000);
+        assert_eq!(frame.data, vec![1, 2, 3, 4, 5]);
+        assert!(!frame.corrupted);
+
+        // The first fragment of the next AU is lost.
+        depacketizer
+            .push(&packet(4, 2024, true, &[0x00, 0x08, 0x14, 4, 5]))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, vec![4, 5]);
+        assert!(frame.corrupted);
+    }
+
+    #[test]
+    fn test_depacketize_latm() {
+        let parameters = parameters("fmtp:96 cpresent=0;config=400024203fc0");
+        let mut depacketizer = AacDepacketizer::latm(&parameters, 44_100).unwrap();
+        assert_eq!(depacketizer.config().sampling_frequency, 44_100);
+
+        let mut element = vec![0xff, 0x01];
+        element.extend((0..=255).map(|byte| byte as u8));
+
+        depacketizer
+            .push(&packet(1, 1000, false, &element[..100]))
+            .unwrap();
+        depacketizer
+            .push(&packet(2, 1000, true, &element[100..]))
+            .unwrap();
+        depacketizer
+            .push(&packet(3, 2024, true, &[0x02, 7, 8]))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 1000);
+        assert_eq!(frame.data, &element[2..]);
+        assert!(!frame.corrupted);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 2024);
+        assert_eq!(frame.data, vec![7, 8]);
+
+        // The first packet of the element is lost.
+        depacketizer
+            .push(&packet(5, 3048, true, &[0x02, 7, 8]))
+            .unwrap();
+        assert!(depacketizer.pull().unwrap().corrupted);
+
+        assert_eq!(
+            depacketizer.push(&packet(6, 4072, true, &[0x03, 7, 8])),
+            Err(DepacketizeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_latm_in_band_config() {
+        let parameters = parameters("fmtp:96 config=400024203fc0");
+
+        assert_eq!(
+            AacDepacketizer::latm(&parameters, 44_100).err(),
+            Some(DepacketizeError::Unsupported)
+        );
+    }
+}
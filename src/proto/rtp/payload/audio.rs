@@ -0,0 +1,133 @@
+//! Audio payloads whose packets carry whole frames, and decoding of their samples.
+
+use std::collections::VecDeque;
+
+use crate::proto::rtp::payload::g711::G711Decoder;
+use crate::proto::rtp::payload::g726::G726Decoder;
+use crate::proto::rtp::payload::registry::{Encoding, PayloadFormat};
+use crate::proto::rtp::payload::{
+    DepacketizeError, Depacketizer, Frame, SequenceCheck, SequenceTracker,
+};
+use crate::proto::rtp::rtp::RtpPacket;
+
+/// Decodes the samples of an audio payload format.
+pub trait AudioDecoder {
+    /// Decodes the payload of a packet, appending the 16 bit linear samples to `samples`.
+    /// Multiple channels are interleaved.
+    fn decode(&mut self, data: &[u8], samples: &mut Vec<i16>);
+}
+
+/// Returns a decoder of the payload format, if the decoding of its samples is supported.
+pub fn audio_decoder(format: &PayloadFormat) -> Option<Box<dyn AudioDecoder + Send>> {
+    match format.encoding {
+        Encoding::Pcmu => Some(Box::new(G711Decoder::ulaw())),
+        Encoding::Pcma => Some(Box::new(G711Decoder::alaw())),
+        Encoding::G726 { rate, packing } => Some(Box::new(G726Decoder::new(rate, packing))),
+        Encoding::L16 => Some(Box::new(L16Decoder)),
+        _ => None,
+    }
+}
+
+/// Decodes uncompressed audio, with samples in network byte order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct L16Decoder;
+
+impl AudioDecoder for L16Decoder {
+    fn decode(&mut self, data: &[u8], samples: &mut Vec<i16>) {
+        samples.extend(
+            data.chunks_exact(2)
+                .map(|sample| i16::from_be_bytes([sample[0], sample[1]])),
+        );
+    }
+}
+
+/// Turns the packets of audio payloads where every packet holds whole frames, such as G.711,
+/// G.722 and G.726, into frames of the payload of every packet.
+///
+/// Every frame is a keyframe. Lost packets do not corrupt the frames of the following ones.
+#[derive(Debug, Default)]
+pub struct AudioDepacketizer {
+    /// The frames that have not been pulled yet.
+    frames: VecDeque<Frame>,
+
+    sequence: SequenceTracker,
+}
+
+impl AudioDepacketizer {
+    /// Constructs a new depacketizer.
+    pub fn new() -> Self {
+        AudioDepacketizer::default()
+    }
+}
+
+impl Depacketizer for AudioDepacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        if self.sequence.check(packet.header.seq_num) == SequenceCheck::Late
+            || packet.payload.is_empty()
+        {
+            return Ok(());
+        }
+
+        self.frames.push_back(Frame {
+            timestamp: packet.header.timestamp,
+            data: packet.payload.clone(),
+            keyframe: true,
+            corrupted: false,
+        });
+
+        Ok(())
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::registry::{static_payload_format, Encoding, PayloadFormat};
+    use crate::proto::rtp::payload::Depacketizer;
+    use crate::proto::rtp::rtp::test_support::packet;
+
+    use super::{audio_decoder, AudioDepacketizer};
+
+    #[test]
+    fn test_depacketize() {
+        let mut depacketizer = AudioDepacketizer::new();
+
+        depacketizer.push(&packet(1, 160, false, &[0xff, 0x80])).unwrap();
+        depacketizer.push(&packet(3, 480, false, &[0x7f])).unwrap();
+        depacketizer.push(&packet(2, 320, false, &[0x00])).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 160);
+        assert_eq!(frame.data, vec![0xff, 0x80]);
+        assert!(frame.keyframe);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 480);
+        assert!(!frame.corrupted);
+
+        assert_eq!(depacketizer.pull(), None);
+    }
+
+    #[test]
+    fn test_audio_decoder() {
+        let mut samples = Vec::new();
+        let mut decoder = audio_decoder(&static_payload_format(0).unwrap()).unwrap();
+        decoder.decode(&[0xff, 0x80], &mut samples);
+        assert_eq!(samples, vec![0, 32124]);
+
+        let mut samples = Vec::new();
+        let mut decoder = audio_decoder(&static_payload_format(11).unwrap()).unwrap();
+        decoder.decode(&[0x12, 0x34, 0xff, 0xfe, 0x00], &mut samples);
+        assert_eq!(samples, vec![0x1234, -2]);
+
+        let mut decoder = audio_decoder(&static_payload_format(2).unwrap()).unwrap();
+        let mut samples = Vec::new();
+        decoder.decode(&[0x00, 0x00], &mut samples);
+        assert_eq!(samples.len(), 4);
+
+        assert!(audio_decoder(&PayloadFormat::new(Encoding::Opus, 48_000, 2)).is_none());
+    }
+}
@@ -0,0 +1,83 @@
+//! Reading of bit fields that are not aligned to bytes.
+
+use crate::proto::rtp::payload::DepacketizeError;
+
+/// Reads bit fields from a byte slice, most significant bit first.
+#[derive(Clone, Debug)]
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+
+    /// The position of the next bit to read.
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        BitReader { data, position: 0 }
+    }
+
+    /// Returns the number of bits left to read.
+    pub(crate) fn remaining(&self) -> usize {
+        (self.data.len() * 8).saturating_sub(self.position)
+    }
+
+    /// Returns the number of bits read so far.
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Reads a field of at most 32 bits.
+    pub(crate) fn read(&mut self, bits: usize) -> Result<u32, DepacketizeError> {
+        debug_assert!(bits <= 32);
+
+        if bits > self.remaining() {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let mut value = 0u32;
+
+        for _ in 0..bits {
+            let byte = self.data[self.position / 8];
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = value << 1 | u32::from(bit);
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+
+    pub(crate) fn read_bool(&mut self) -> Result<bool, DepacketizeError> {
+        Ok(self.read(1)? == 1)
+    }
+
+    pub(crate) fn skip(&mut self, bits: usize) -> Result<(), DepacketizeError> {
+        if bits > self.remaining() {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        self.position += bits;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::DepacketizeError;
+
+    use super::BitReader;
+
+    #[test]
+    fn test_read() {
+        let mut reader = BitReader::new(&[0b1011_0011, 0b1100_0001]);
+
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read(3), Ok(0b011));
+        assert_eq!(reader.read(6), Ok(0b00_1111));
+        reader.skip(2).unwrap();
+        assert_eq!(reader.position(), 12);
+        assert_eq!(reader.remaining(), 4);
+        assert_eq!(reader.read(5), Err(DepacketizeError::Truncated));
+        assert_eq!(reader.read(4), Ok(0b0001));
+        assert_eq!(reader.read(0), Ok(0));
+    }
+}
@@ -0,0 +1,98 @@
+//! G.711 μ-law and A-law audio (RFC 3551).
+
+use crate::proto::rtp::payload::audio::AudioDecoder;
+
+const ULAW_BIAS: i16 = 0x84;
+
+/// Converts a μ-law sample into a 16 bit linear sample.
+pub fn ulaw_to_linear(sample: u8) -> i16 {
+    let sample = !sample;
+    let magnitude = ((i16::from(sample & 0x0f) << 3) + ULAW_BIAS) << ((sample & 0x70) >> 4);
+
+    if sample & 0x80 != 0 {
+        ULAW_BIAS - magnitude
+    } else {
+        magnitude - ULAW_BIAS
+    }
+}
+
+/// Converts an A-law sample into a 16 bit linear sample.
+pub fn alaw_to_linear(sample: u8) -> i16 {
+    let sample = sample ^ 0x55;
+    let mut magnitude = i16::from(sample & 0x0f) << 4;
+
+    match (sample & 0x70) >> 4 {
+        0 => magnitude += 0x8,
+        1 => magnitude += 0x108,
+        segment => magnitude = (magnitude + 0x108) << (segment - 1),
+    }
+
+    if sample & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+/// Decodes G.711 audio, one sample per octet.
+#[derive(Clone, Copy, Debug)]
+pub struct G711Decoder {
+    convert: fn(u8) -> i16,
+}
+
+impl G711Decoder {
+    /// Constructs a μ-law (PCMU) decoder.
+    pub fn ulaw() -> Self {
+        G711Decoder {
+            convert: ulaw_to_linear,
+        }
+    }
+
+    /// Constructs an A-law (PCMA) decoder.
+    pub fn alaw() -> Self {
+        G711Decoder {
+            convert: alaw_to_linear,
+        }
+    }
+}
+
+impl AudioDecoder for G711Decoder {
+    fn decode(&mut self, data: &[u8], samples: &mut Vec<i16>) {
+        samples.extend(data.iter().map(|&sample| (self.convert)(sample)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::audio::AudioDecoder;
+
+    use super::{alaw_to_linear, ulaw_to_linear, G711Decoder};
+
+    #[test]
+    fn test_ulaw_to_linear() {
+        assert_eq!(ulaw_to_linear(0xff), 0);
+        assert_eq!(ulaw_to_linear(0x7f), 0);
+        assert_eq!(ulaw_to_linear(0x80), 32124);
+        assert_eq!(ulaw_to_linear(0x00), -32124);
+        assert_eq!(ulaw_to_linear(0xf0), 120);
+        assert_eq!(ulaw_to_linear(0x70), -120);
+    }
+
+    #[test]
+    fn test_alaw_to_linear() {
+        assert_eq!(alaw_to_linear(0xd5), 8);
+        assert_eq!(alaw_to_linear(0x55), -8);
+        assert_eq!(alaw_to_linear(0xaa), 32256);
+        assert_eq!(alaw_to_linear(0x2a), -32256);
+        assert_eq!(alaw_to_linear(0xc5), 264);
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut samples = Vec::new();
+        G711Decoder::ulaw().decode(&[0xff, 0x80], &mut samples);
+        G711Decoder::alaw().decode(&[0x55], &mut samples);
+
+        assert_eq!(samples, vec![0, 32124, -8]);
+    }
+}
@@ -0,0 +1,493 @@
+//! G.726 ADPCM audio (RFC 3551).
+//!
+//! The decoder follows the ITU-T G.726 reference algorithm, with all arithmetic in integers so
+//! that its output is bit exact.
+
+use crate::proto::rtp::payload::audio::AudioDecoder;
+use crate::proto::rtp::payload::registry::{G726Packing, G726Rate};
+
+/// The quantizer tables of a bit rate, indexed by code word.
+struct Tables {
+    /// The log of the quantized difference signal.
+    dqln: &'static [i32],
+
+    /// The scale factor multipliers.
+    wi: &'static [i32],
+
+    /// The transition detection functions of the adaptation speed control.
+    fi: &'static [i32],
+}
+
+const TABLES_16: Tables = Tables {
+    dqln: &[116, 365, 365, 116],
+    wi: &[-704, 14048, 14048, -704],
+    fi: &[0x000, 0xe00, 0xe00, 0x000],
+};
+
+const TABLES_24: Tables = Tables {
+    dqln: &[-2048, 135, 273, 373, 373, 273, 135, -2048],
+    wi: &[-128, 960, 4384, 18624, 18624, 4384, 960, -128],
+    fi: &[0x000, 0x200, 0x400, 0xe00, 0xe00, 0x400, 0x200, 0x000],
+};
+
+const TABLES_32: Tables = Tables {
+    dqln: &[
+        -2048, 4, 135, 213, 273, 323, 373, 425, 425, 373, 323, 273, 213, 135, 4, -2048,
+    ],
+    wi: &[
+        -384, 576, 1312, 2048, 3584, 6336, 11360, 35904, 35904, 11360, 6336, 3584, 2048, 1312, 576,
+        -384,
+    ],
+    fi: &[
+        0x000, 0x000, 0x000, 0x200, 0x200, 0x200, 0x600, 0xe00, 0xe00, 0x600, 0x200, 0x200, 0x200,
+        0x000, 0x000, 0x000,
+    ],
+};
+
+const TABLES_40: Tables = Tables {
+    dqln: &[
+        -2048, -66, 28, 104, 169, 224, 274, 318, 358, 395, 429, 459, 488, 514, 539, 566, 566, 539,
+        514, 488, 459, 429, 395, 358, 318, 274, 224, 169, 104, 28, -66, -2048,
+    ],
+    wi: &[
+        448, 448, 768, 1248, 1280, 1312, 1856, 3200, 4512, 5728, 7008, 8960, 11456, 14080, 16928,
+        22272, 22272, 16928, 14080, 11456, 8960, 7008, 5728, 4512, 3200, 1856, 1312, 1280, 1248,
+        768, 448, 448,
+    ],
+    fi: &[
+        0x000, 0x000, 0x000, 0x000, 0x000, 0x200, 0x200, 0x200, 0x200, 0x200, 0x400, 0x600, 0x800,
+        0xa00, 0xc00, 0xc00, 0xc00, 0xc00, 0xa00, 0x800, 0x600, 0x400, 0x200, 0x200, 0x200, 0x200,
+        0x200, 0x000, 0x000, 0x000, 0x000, 0x000,
+    ],
+};
+
+/// The floating point representation of zero used for the delayed signals.
+const FLOAT_ZERO: i32 = 0x20;
+
+/// The floating point representation of the most negative delayed signal, 0xfc20 as a 16 bit
+/// signed integer.
+const FLOAT_NEGATIVE_MAX: i32 = -0x3e0;
+
+/// Returns the index of the first power of 2 larger than the value, up to 15.
+fn quantize_power_2(value: i32) -> i32 {
+    (0..15)
+        .find(|&exponent| value < 1 << exponent)
+        .unwrap_or(15)
+}
+
+/// Multiplies a predictor coefficient by a signal in the floating point representation.
+fn float_multiply(an: i32, srn: i32) -> i32 {
+    let anmag = if an > 0 { an } else { (-an) & 0x1fff };
+    let anexp = quantize_power_2(anmag) - 6;
+    let anmant = if anmag == 0 {
+        32
+    } else if anexp >= 0 {
+        anmag >> anexp
+    } else {
+        anmag << -anexp
+    };
+
+    let wanexp = anexp + ((srn >> 6) & 0xf) - 13;
+    let wanmant = (anmant * (srn & 0o77) + 0x30) >> 4;
+    let product = if wanexp >= 0 {
+        (wanmant << wanexp) & 0x7fff
+    } else {
+        wanmant >> -wanexp
+    };
+
+    if (an ^ srn) < 0 {
+        -product
+    } else {
+        product
+    }
+}
+
+/// Converts a magnitude into the floating point representation with a 4 bit exponent and a
+/// 6 bit mantissa.
+fn to_float(magnitude: i32) -> i32 {
+    let exponent = quantize_power_2(magnitude);
+    (exponent << 6) + ((magnitude << 6) >> exponent)
+}
+
+/// Decodes G.726 audio at one of its bit rates.
+#[derive(Clone, Debug)]
+pub struct G726Decoder {
+    rate: G726Rate,
+    packing: G726Packing,
+
+    /// The code word bits left over from the previous packet, for bit rates whose code words
+    /// do not align with octets.
+    bits: u32,
+    bit_count: usize,
+
+    /// The locked quantizer scale factor.
+    yl: i32,
+
+    /// The unlocked quantizer scale factor.
+    yu: i32,
+
+    /// The short term energy estimate.
+    dms: i32,
+
+    /// The long term energy estimate.
+    dml: i32,
+
+    /// The linear weighting coefficient of `yl` and `yu`.
+    ap: i32,
+
+    /// The coefficients of the pole part of the predictor.
+    a: [i32; 2],
+
+    /// The coefficients of the zero part of the predictor.
+    b: [i32; 6],
+
+    /// The signs of the previous partially reconstructed signals.
+    pk: [i32; 2],
+
+    /// The previous quantized difference signals in the floating point representation.
+    dq: [i32; 6],
+
+    /// The previous reconstructed signals in the floating point representation.
+    sr: [i32; 2],
+
+    /// Was a tone detected.
+    td: bool,
+}
+
+impl G726Decoder {
+    pub fn new(rate: G726Rate, packing: G726Packing) -> Self {
+        G726Decoder {
+            rate,
+            packing,
+            bits: 0,
+            bit_count: 0,
+            yl: 34816,
+            yu: 544,
+            dms: 0,
+            dml: 0,
+            ap: 0,
+            a: [0; 2],
+            b: [0; 6],
+            pk: [0; 2],
+            dq: [FLOAT_ZERO; 6],
+            sr: [FLOAT_ZERO; 2],
+            td: false,
+        }
+    }
+
+    fn tables(&self) -> &'static Tables {
+        match self.rate {
+            G726Rate::Kbps16 => &TABLES_16,
+            G726Rate::Kbps24 => &TABLES_24,
+            G726Rate::Kbps32 => &TABLES_32,
+            G726Rate::Kbps40 => &TABLES_40,
+        }
+    }
+
+    /// Decodes a code word into a 16 bit linear sample.
+    pub fn decode_sample(&mut self, code: u8) -> i16 {
+        let bits_per_sample = self.rate.bits_per_sample();
+        let code = usize::from(code) & ((1 << bits_per_sample) - 1);
+        let negative = code >> (bits_per_sample - 1) != 0;
+        let tables = self.tables();
+
+        let sezi = self.predict_zero();
+        let sez = sezi >> 1;
+        let se = (sezi + self.predict_pole()) >> 1;
+
+        let y = self.step_size();
+        let dq = reconstruct(negative, tables.dqln[code], y);
+
+        let sr = if dq < 0 { se - (dq & 0x3fff) } else { se + dq };
+
+        let dqsez = sr - se + sez;
+        self.update(y, tables.wi[code], tables.fi[code], dq, sr, dqsez);
+
+        // The reconstructed signal has a 14 bit range.
+        (sr << 2).max(i32::from(i16::MIN)).min(i32::from(i16::MAX)) as i16
+    }
+
+    fn predict_zero(&self) -> i32 {
+        self.b
+            .iter()
+            .zip(self.dq.iter())
+            .map(|(&b, &dq)| float_multiply(b >> 2, dq))
+            .sum()
+    }
+
+    fn predict_pole(&self) -> i32 {
+        float_multiply(self.a[1] >> 2, self.sr[1]) + float_multiply(self.a[0] >> 2, self.sr[0])
+    }
+
+    /// Computes the quantizer scale factor, mixing the locked and unlocked ones.
+    fn step_size(&self) -> i32 {
+        if self.ap >= 256 {
+            return self.yu;
+        }
+
+        let mut y = self.yl >> 6;
+        let difference = self.yu - y;
+        let al = self.ap >> 2;
+
+        if difference > 0 {
+            y += (difference * al) >> 6;
+        } else if difference < 0 {
+            y += (difference * al + 0x3f) >> 6;
+        }
+
+        y
+    }
+
+    /// Updates the state after decoding a sample.
+    fn update(&mut self, y: i32, wi: i32, fi: i32, dq: i32, sr: i32, dqsez: i32) {
+        let pk0 = if dqsez < 0 { 1 } else { 0 };
+        let magnitude = dq & 0x7fff;
+
+        // Transition detection
+        let ylint = self.yl >> 15;
+        let ylfrac = (self.yl >> 10) & 0x1f;
+        let thr1 = (32 + ylfrac) << ylint;
+        let thr2 = if ylint > 9 { 31 << 10 } else { thr1 };
+        let dqthr = (thr2 + (thr2 >> 1)) >> 1;
+        let transition = self.td && magnitude > dqthr;
+
+        // Quantizer scale factor adaptation
+        self.yu = (y + ((wi - y) >> 5)).max(544).min(5120);
+        self.yl += self.yu + ((-self.yl) >> 6);
+
+        // Adaptive predictor coefficients
+        let mut a2p = 0;
+
+        if transition {
+            self.a = [0; 2];
+            self.b = [0; 6];
+        } else {
+            let pks1 = pk0 ^ self.pk[0];
+
+            a2p = self.a[1] - (self.a[1] >> 7);
+
+            if dqsez != 0 {
+                let fa1 = if pks1 != 0 { self.a[0] } else { -self.a[0] };
+
+                if fa1 < -8191 {
+                    a2p -= 0x100;
+                } else if fa1 > 8191 {
+                    a2p += 0xff;
+                } else {
+                    a2p += fa1 >> 5;
+                }
+
+                if pk0 ^ self.pk[1] != 0 {
+                    if a2p <= -12160 {
+                        a2p = -12288;
+                    } else if a2p >= 12416 {
+                        a2p = 12288;
+                    } else {
+                        a2p -= 0x80;
+                    }
+                } else if a2p <= -12416 {
+                    a2p = -12288;
+                } else if a2p >= 12160 {
+                    a2p = 12288;
+                } else {
+                    a2p += 0x80;
+                }
+            }
+
+            self.a[1] = a2p;
+
+            self.a[0] -= self.a[0] >> 8;
+
+            if dqsez != 0 {
+                if pks1 == 0 {
+                    self.a[0] += 192;
+                } else {
+                    self.a[0] -= 192;
+                }
+            }
+
+            let a1ul = 15360 - a2p;
+            self.a[0] = self.a[0].max(-a1ul).min(a1ul);
+
+            let leak = if self.rate == G726Rate::Kbps40 { 9 } else { 8 };
+
+            for (b, &previous_dq) in self.b.iter_mut().zip(self.dq.iter()) {
+                *b -= *b >> leak;
+
+                if magnitude != 0 {
+                    if (dq ^ previous_dq) >= 0 {
+                        *b += 128;
+                    } else {
+                        *b -= 128;
+                    }
+                }
+            }
+        }
+
+        self.dq.rotate_right(1);
+
+        self.dq[0] = if magnitude == 0 {
+            if dq >= 0 {
+                FLOAT_ZERO
+            } else {
+                FLOAT_NEGATIVE_MAX
+            }
+        } else if dq >= 0 {
+            to_float(magnitude)
+        } else {
+            to_float(magnitude) - 0x400
+        };
+
+        self.sr[1] = self.sr[0];
+
+        self.sr[0] = if sr == 0 {
+            FLOAT_ZERO
+        } else if sr > 0 {
+            to_float(sr)
+        } else if sr > -32768 {
+            to_float(-sr) - 0x400
+        } else {
+            FLOAT_NEGATIVE_MAX
+        };
+
+        self.pk[1] = self.pk[0];
+        self.pk[0] = pk0;
+
+        // Tone detection
+        self.td = !transition && a2p < -11776;
+
+        // Adaptation speed control
+        self.dms += (fi - self.dms) >> 5;
+        self.dml += ((fi << 2) - self.dml) >> 7;
+
+        if transition {
+            self.ap = 256;
+        } else if y < 1536 || self.td || ((self.dms << 2) - self.dml).abs() >= (self.dml >> 3) {
+            self.ap += (0x200 - self.ap) >> 4;
+        } else {
+            self.ap += (-self.ap) >> 4;
+        }
+    }
+}
+
+/// Reconstructs the quantized difference signal from its log.
+fn reconstruct(negative: bool, dqln: i32, y: i32) -> i32 {
+    let dql = dqln + (y >> 2);
+
+    if dql < 0 {
+        return if negative { -0x8000 } else { 0 };
+    }
+
+    let dex = (dql >> 7) & 15;
+    let dqt = 128 + (dql & 127);
+    let dq = (dqt << 7) >> (14 - dex);
+
+    if negative {
+        dq - 0x8000
+    } else {
+        dq
+    }
+}
+
+impl AudioDecoder for G726Decoder {
+    fn decode(&mut self, data: &[u8], samples: &mut Vec<i16>) {
+        let bits_per_sample = self.rate.bits_per_sample();
+        let mask = (1 << bits_per_sample) - 1;
+
+        for &byte in data {
+            match self.packing {
+                G726Packing::LittleEndian => self.bits |= u32::from(byte) << self.bit_count,
+                G726Packing::BigEndian => self.bits = self.bits << 8 | u32::from(byte),
+            }
+
+            self.bit_count += 8;
+
+            while self.bit_count >= bits_per_sample {
+                let code = match self.packing {
+                    G726Packing::LittleEndian => {
+                        let code = self.bits & mask;
+                        self.bits >>= bits_per_sample;
+                        code
+                    }
+                    G726Packing::BigEndian => {
+                        (self.bits >> (self.bit_count - bits_per_sample)) & mask
+                    }
+                };
+
+                self.bit_count -= bits_per_sample;
+                samples.push(self.decode_sample(code as u8));
+            }
+
+            if self.packing == G726Packing::BigEndian {
+                self.bits &= (1 << self.bit_count) - 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::audio::AudioDecoder;
+    use crate::proto::rtp::payload::registry::{G726Packing, G726Rate};
+
+    use super::G726Decoder;
+
+    #[test]
+    fn test_decode_silence() {
+        for &rate in [G726Rate::Kbps24, G726Rate::Kbps32, G726Rate::Kbps40].iter() {
+            let mut decoder = G726Decoder::new(rate, G726Packing::LittleEndian);
+
+            // The code words of a positive and a negative zero difference.
+            let negative_zero = (1 << rate.bits_per_sample()) - 1;
+
+            for index in 0..800 {
+                let code = if index % 2 == 0 { 0 } else { negative_zero };
+                assert_eq!(decoder.decode_sample(code), 0, "{:?}", rate);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_steps() {
+        let mut decoder = G726Decoder::new(G726Rate::Kbps32, G726Packing::LittleEndian);
+        assert_eq!(decoder.decode_sample(0x7), 88);
+
+        let mut decoder = G726Decoder::new(G726Rate::Kbps32, G726Packing::LittleEndian);
+        assert_eq!(decoder.decode_sample(0x8), -88);
+
+        // The largest positive difference, repeatedly, ramps the signal up.
+        let samples: Vec<i16> = (0..8).map(|_| decoder.decode_sample(0x7)).collect();
+        assert!(samples.windows(2).all(|pair| pair[1] > pair[0]));
+    }
+
+    #[test]
+    fn test_packing() {
+        let codes = [0x1u8, 0x5, 0x7, 0x2, 0x6, 0x0, 0x3, 0x4];
+
+        let mut expected = Vec::new();
+        let mut decoder = G726Decoder::new(G726Rate::Kbps24, G726Packing::LittleEndian);
+        for &code in codes.iter() {
+            expected.push(decoder.decode_sample(code));
+        }
+
+        // The code words packed from the least significant bits, split over two packets.
+        let little_endian =
+            0x1u32 | 0x5 << 3 | 0x7 << 6 | 0x2 << 9 | 0x6 << 12 | 0x0 << 15 | 0x3 << 18 | 0x4 << 21;
+        let bytes = little_endian.to_le_bytes();
+        let mut decoder = G726Decoder::new(G726Rate::Kbps24, G726Packing::LittleEndian);
+        let mut samples = Vec::new();
+        decoder.decode(&bytes[..1], &mut samples);
+        decoder.decode(&bytes[1..3], &mut samples);
+        assert_eq!(samples, expected);
+
+        // The code words packed from the most significant bits.
+        let big_endian =
+            0x1u32 << 21 | 0x5 << 18 | 0x7 << 15 | 0x2 << 12 | 0x6 << 9 | 0x0 << 6 | 0x3 << 3 | 0x4;
+        let bytes = big_endian.to_be_bytes();
+        let mut decoder = G726Decoder::new(G726Rate::Kbps24, G726Packing::BigEndian);
+        let mut samples = Vec::new();
+        decoder.decode(&bytes[1..2], &mut samples);
+        decoder.decode(&bytes[2..], &mut samples);
+        assert_eq!(samples, expected);
+    }
+}
@@ -0,0 +1,538 @@
+//! H.264 video (RFC 6184).
+
+use crate::proto::rtp::payload::{
+    DepacketizeError, Depacketizer, Frame, PacketSequencer, PacketizeError, Packetizer,
+};
+use crate::proto::rtp::payload::nal::{
+    annex_b_nal_units, length_prefixed_nal_units, AccessUnitAssembler,
+};
+use crate::proto::rtp::rtp::RtpPacket;
+use crate::proto::types::U7;
+
+/// The clock rate of H.264 RTP timestamps.
+pub const CLOCK_RATE: u32 = 90_000;
+
+pub const NAL_UNIT_TYPE_IDR: u8 = 5;
+pub const NAL_UNIT_TYPE_SEI: u8 = 6;
+pub const NAL_UNIT_TYPE_SPS: u8 = 7;
+pub const NAL_UNIT_TYPE_PPS: u8 = 8;
+pub const NAL_UNIT_TYPE_AUD: u8 = 9;
+pub const NAL_UNIT_TYPE_STAP_A: u8 = 24;
+pub const NAL_UNIT_TYPE_STAP_B: u8 = 25;
+pub const NAL_UNIT_TYPE_MTAP16: u8 = 26;
+pub const NAL_UNIT_TYPE_MTAP24: u8 = 27;
+pub const NAL_UNIT_TYPE_FU_A: u8 = 28;
+pub const NAL_UNIT_TYPE_FU_B: u8 = 29;
+
+const NAL_UNIT_TYPE_MASK: u8 = 0b0001_1111;
+const NAL_UNIT_HEADER_F_NRI_MASK: u8 = 0b1110_0000;
+const FU_HEADER_START: u8 = 0b1000_0000;
+const FU_HEADER_END: u8 = 0b0100_0000;
+
+/// Returns the type of the NAL unit with the given header byte.
+pub fn nal_unit_type(header: u8) -> u8 {
+    header & NAL_UNIT_TYPE_MASK
+}
+
+/// Reassembles H.264 access units from RTP packets.
+///
+/// Single NAL unit packets, STAP-A, STAP-B, MTAP16, MTAP24, FU-A and FU-B are supported. Packets
+/// of the interleaved mode are not reordered by their decoding order number, their NAL units are
+/// emitted in transmission order, and the NAL units of a multi-time aggregation packet all belong
+/// to the access unit of the packet.
+///
+/// Every frame is an Annex B byte stream, and is a keyframe if it contains an IDR picture. If any
+/// packet of an access unit was lost, the frame is emitted as corrupted, and an incomplete
+/// fragmented NAL unit is left out.
+#[derive(Debug, Default)]
+pub struct H264Depacketizer {
+    assembler: AccessUnitAssembler,
+}
+
+impl H264Depacketizer {
+    /// Constructs a new depacketizer.
+    pub fn new() -> Self {
+        H264Depacketizer::default()
+    }
+
+    fn push_nal_unit(&mut self, nal_unit: &[u8]) {
+        let keyframe = nal_unit_type(nal_unit[0]) == NAL_UNIT_TYPE_IDR;
+        self.assembler.push_nal_unit(nal_unit, keyframe);
+    }
+
+    fn depacketize(&mut self, payload: &[u8]) -> Result<(), DepacketizeError> {
+        let header = *payload.first().ok_or(DepacketizeError::Truncated)?;
+
+        match nal_unit_type(header) {
+            1..=23 => {
+                self.push_nal_unit(payload);
+                Ok(())
+            }
+            NAL_UNIT_TYPE_STAP_A => self.push_aggregated(&payload[1..], 0),
+            NAL_UNIT_TYPE_STAP_B => self.push_aggregated(skip(payload, 3)?, 0),
+            NAL_UNIT_TYPE_MTAP16 => self.push_aggregated(skip(payload, 3)?, 3),
+            NAL_UNIT_TYPE_MTAP24 => self.push_aggregated(skip(payload, 3)?, 4),
+            NAL_UNIT_TYPE_FU_A | NAL_UNIT_TYPE_FU_B => self.push_fragment(payload),
+            _ => Err(DepacketizeError::Unsupported),
+        }
+    }
+
+    /// Pushes the NAL units of an aggregation packet, each preceded by its size and
+    /// `unit_header_length` bytes of decoding order number and timestamp offset.
+    fn push_aggregated(
+        &mut self,
+        mut units: &[u8],
+        unit_header_length: usize,
+    ) -> Result<(), DepacketizeError> {
+        let mut nal_units = Vec::new();
+
+        while !units.is_empty() {
+            if units.len() < 2 {
+                return Err(DepacketizeError::Truncated);
+            }
+
+            let size = u16::from_be_bytes([units[0], units[1]]) as usize;
+            let unit = units.get(2..2 + size).ok_or(DepacketizeError::Truncated)?;
+            let nal_unit = unit
+                .get(unit_header_length..)
+                .filter(|nal_unit| !nal_unit.is_empty())
+                .ok_or(DepacketizeError::Truncated)?;
+            nal_units.push(nal_unit);
+            units = &units[2 + size..];
+        }
+
+        if nal_units.is_empty() {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        for nal_unit in nal_units {
+            self.push_nal_unit(nal_unit);
+        }
+
+        Ok(())
+    }
+
+    fn push_fragment(&mut self, payload: &[u8]) -> Result<(), DepacketizeError> {
+        if payload.len() < 2 {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let indicator = payload[0];
+        let fu_header = payload[1];
+        let start = fu_header & FU_HEADER_START != 0;
+        let end = fu_header & FU_HEADER_END != 0;
+        let mut data = &payload[2..];
+        let nal_unit_header = [(indicator & NAL_UNIT_HEADER_F_NRI_MASK) | nal_unit_type(fu_header)];
+
+        // Only the first fragment of an FU-B carries the decoding order number.
+        if start && nal_unit_type(indicator) == NAL_UNIT_TYPE_FU_B {
+            data = skip(data, 2)?;
+        }
+
+        let nal_unit_header = if start { Some(&nal_unit_header[..]) } else { None };
+
+        if let Some(nal_unit) = self.assembler.push_fragment(nal_unit_header, data, end) {
+            self.push_nal_unit(&nal_unit);
+        }
+
+        Ok(())
+    }
+}
+
+impl Depacketizer for H264Depacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        if !self.assembler.start_packet(&packet.header) {
+            return Ok(());
+        }
+
+        let result = self.depacketize(&packet.payload);
+        self.assembler.end_packet(&packet.header, result)
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.assembler.pull()
+    }
+}
+
+/// Splits H.264 access units into RTP packets in non-interleaved mode.
+///
+/// NAL units that fit into a packet are sent as single NAL unit packets, larger ones are
+/// fragmented into FU-A packets. Consecutive parameter sets and other non-VCL NAL units, e.g. the
+/// SPS and PPS preceding an IDR picture, are aggregated into STAP-A packets when they fit.
+#[derive(Clone, Debug)]
+pub struct H264Packetizer {
+    sequencer: PacketSequencer,
+}
+
+impl H264Packetizer {
+    /// Constructs a new packetizer producing RTP packets of at most `max_packet_size` bytes, e.g.
+    /// [`DEFAULT_MAX_PACKET_SIZE`](crate::proto::rtp::payload::DEFAULT_MAX_PACKET_SIZE).
+    pub fn new(payload_type: U7, ssrc: u32, max_packet_size: usize) -> Self {
+        H264Packetizer {
+            sequencer: PacketSequencer::new(payload_type, ssrc, max_packet_size),
+        }
+    }
+
+    /// Splits an AVCC access unit into packets. See [`length_prefixed_nal_units`].
+    pub fn packetize_avcc(
+        &mut self,
+        access_unit: &[u8],
+        length_size: usize,
+        timestamp: u32,
+    ) -> Result<Vec<RtpPacket>, PacketizeError> {
+        self.packetize_nal_units(&length_prefixed_nal_units(access_unit, length_size)?, timestamp)
+    }
+
+    /// Splits the NAL units of an access unit into packets.
+    pub fn packetize_nal_units(
+        &mut self,
+        nal_units: &[&[u8]],
+        timestamp: u32,
+    ) -> Result<Vec<RtpPacket>, PacketizeError> {
+        if nal_units.iter().any(|nal_unit| nal_unit.is_empty()) {
+            return Err(PacketizeError::Truncated);
+        }
+
+        if nal_units.is_empty() {
+            return Err(PacketizeError::Empty);
+        }
+
+        let max_payload_size = self.sequencer.max_payload_size();
+        let mut payloads = Vec::new();
+        let mut index = 0;
+
+        while index < nal_units.len() {
+            let aggregated = self.aggregate(&nal_units[index..]);
+
+            if aggregated > 1 {
+                payloads.push(stap_a(&nal_units[index..index + aggregated]));
+                index += aggregated;
+                continue;
+            }
+
+            let nal_unit = nal_units[index];
+
+            if nal_unit.len() <= max_payload_size {
+                payloads.push(nal_unit.to_vec());
+            } else {
+                payloads.extend(fu_a(nal_unit, max_payload_size));
+            }
+
+            index += 1;
+        }
+
+        let last = payloads.len() - 1;
+
+        Ok(payloads
+            .into_iter()
+            .enumerate()
+            .map(|(index, payload)| self.sequencer.packet(timestamp, index == last, payload))
+            .collect())
+    }
+
+    /// Returns how many of the leading NAL units fit into a single STAP-A.
+    fn aggregate(&self, nal_units: &[&[u8]]) -> usize {
+        let mut size = 1;
+
+        nal_units
+            .iter()
+            .take_while(|nal_unit| {
+                size += 2 + nal_unit.len();
+                !is_vcl(nal_unit[0]) && size <= self.sequencer.max_payload_size()
+            })
+            .count()
+    }
+}
+
+impl Packetizer for H264Packetizer {
+    /// Splits an access unit given as an Annex B byte stream into packets.
+    fn packetize(&mut self, frame: &Frame) -> Result<Vec<RtpPacket>, PacketizeError> {
+        self.packetize_nal_units(&annex_b_nal_units(&frame.data), frame.timestamp)
+    }
+}
+
+/// Returns whether the NAL unit with the given header byte contains a coded slice.
+fn is_vcl(header: u8) -> bool {
+    (1..=NAL_UNIT_TYPE_IDR).contains(&nal_unit_type(header))
+}
+
+fn stap_a(nal_units: &[&[u8]]) -> Vec<u8> {
+    let forbidden_bit = nal_units.iter().fold(0, |bit, nal_unit| bit | (nal_unit[0] & 0b1000_0000));
+    let nri = nal_units.iter().map(|nal_unit| nal_unit[0] & 0b0110_0000).max().unwrap_or(0);
+    let mut payload = vec![forbidden_bit | nri | NAL_UNIT_TYPE_STAP_A];
+
+    for nal_unit in nal_units {
+        payload.extend_from_slice(&(nal_unit.len() as u16).to_be_bytes());
+        payload.extend_from_slice(nal_unit);
+    }
+
+    payload
+}
+
+fn fu_a(nal_unit: &[u8], max_payload_size: usize) -> Vec<Vec<u8>> {
+    let header = nal_unit[0];
+    let indicator = (header & NAL_UNIT_HEADER_F_NRI_MASK) | NAL_UNIT_TYPE_FU_A;
+    let chunks = nal_unit[1..].chunks(max_payload_size - 2);
+    let last = chunks.len() - 1;
+
+    chunks
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut fu_header = nal_unit_type(header);
+
+            if index == 0 {
+                fu_header |= FU_HEADER_START;
+            }
+
+            if index == last {
+                fu_header |= FU_HEADER_END;
+            }
+
+            let mut payload = Vec::with_capacity(2 + chunk.len());
+            payload.push(indicator);
+            payload.push(fu_header);
+            payload.extend_from_slice(chunk);
+            payload
+        })
+        .collect()
+}
+
+/// Returns the data following the first `length` bytes.
+fn skip(data: &[u8], length: usize) -> Result<&[u8], DepacketizeError> {
+    data.get(length..).ok_or(DepacketizeError::Truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::proto::rtp::codec::Codec;
+    use crate::proto::rtp::mutex::MuxedPacket;
+    use crate::proto::rtp::payload::{DepacketizeError, Depacketizer, Frame, Packetizer};
+    use crate::proto::rtp::rtp::{test_support::packet, RtpPacket};
+
+    use super::{H264Depacketizer, H264Packetizer};
+
+    const SPS: &[u8] = &[0x67, 0x42, 0x00, 0x1f];
+    const PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+    const IDR: &[u8] = &[0x65, 0x88, 0x84, 0x00, 0x33, 0xff];
+    const SLICE: &[u8] = &[0x41, 0x9a, 0x02];
+
+    fn annex_b(nal_units: &[&[u8]]) -> Vec<u8> {
+        nal_units
+            .iter()
+            .flat_map(|nal_unit| [0, 0, 0, 1].iter().chain(nal_unit.iter()).cloned())
+            .collect()
+    }
+
+    fn stap_a(nal_units: &[&[u8]]) -> Vec<u8> {
+        let mut payload = vec![0x78];
+
+        for nal_unit in nal_units {
+            payload.extend_from_slice(&(nal_unit.len() as u16).to_be_bytes());
+            payload.extend_from_slice(nal_unit);
+        }
+
+        payload
+    }
+
+    #[test]
+    fn test_single_nal_units_and_stap_a() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        depacketizer.push(&packet(1, 3000, false, &stap_a(&[SPS, PPS]))).unwrap();
+        depacketizer.push(&packet(2, 3000, true, IDR)).unwrap();
+        depacketizer.push(&packet(3, 6000, true, SLICE)).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 3000);
+        assert_eq!(frame.data, annex_b(&[SPS, PPS, IDR]));
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 6000);
+        assert_eq!(frame.data, annex_b(&[SLICE]));
+        assert!(!frame.keyframe);
+        assert!(!frame.corrupted);
+
+        assert_eq!(depacketizer.pull(), None);
+    }
+
+    #[test]
+    fn test_fu_a() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        depacketizer.push(&packet(65535, 3000, false, &[0x7c, 0x85, 0x88, 0x84])).unwrap();
+        depacketizer.push(&packet(0, 3000, false, &[0x7c, 0x05, 0x00])).unwrap();
+        depacketizer.push(&packet(1, 3000, true, &[0x7c, 0x45, 0x33, 0xff])).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, annex_b(&[IDR]));
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+    }
+
+    #[test]
+    fn test_access_unit_without_marker() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        depacketizer.push(&packet(1, 3000, false, SLICE)).unwrap();
+        assert_eq!(depacketizer.pull(), None);
+
+        depacketizer.push(&packet(2, 6000, false, SLICE)).unwrap();
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 3000);
+        assert!(!frame.corrupted);
+    }
+
+    #[test]
+    fn test_lost_fragment() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        depacketizer.push(&packet(1, 3000, false, SLICE)).unwrap();
+        depacketizer.push(&packet(2, 3000, false, &[0x7c, 0x85, 0x88, 0x84])).unwrap();
+        depacketizer.push(&packet(4, 3000, true, &[0x7c, 0x45, 0x33, 0xff])).unwrap();
+        depacketizer.push(&packet(5, 6000, true, SLICE)).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, annex_b(&[SLICE]));
+        assert!(!frame.keyframe);
+        assert!(frame.corrupted);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 6000);
+        assert!(!frame.corrupted);
+    }
+
+    #[test]
+    fn test_late_packet() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        depacketizer.push(&packet(10, 3000, true, SLICE)).unwrap();
+        depacketizer.push(&packet(9, 0, true, IDR)).unwrap();
+        depacketizer.push(&packet(11, 6000, true, SLICE)).unwrap();
+
+        assert_eq!(depacketizer.pull().unwrap().timestamp, 3000);
+        assert_eq!(depacketizer.pull().unwrap().timestamp, 6000);
+        assert_eq!(depacketizer.pull(), None);
+    }
+
+    #[test]
+    fn test_stap_b_and_mtap16() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        let mut stap_b = vec![0x79, 0x00, 0x01];
+        stap_b.extend_from_slice(&stap_a(&[SPS, PPS])[1..]);
+        depacketizer.push(&packet(1, 3000, false, &stap_b)).unwrap();
+
+        let mut mtap16 = vec![0x7a, 0x00, 0x02];
+        mtap16.extend_from_slice(&(IDR.len() as u16 + 3).to_be_bytes());
+        mtap16.extend_from_slice(&[0x00, 0x00, 0x00]);
+        mtap16.extend_from_slice(IDR);
+        depacketizer.push(&packet(2, 3000, true, &mtap16)).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, annex_b(&[SPS, PPS, IDR]));
+        assert!(frame.keyframe);
+    }
+
+    #[test]
+    fn test_malformed_payload() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        assert_eq!(
+            depacketizer.push(&packet(1, 3000, false, &[0x78, 0x00, 0x10, 0x67])),
+            Err(DepacketizeError::Truncated)
+        );
+        assert_eq!(
+            depacketizer.push(&packet(2, 3000, true, &[0x7e])),
+            Err(DepacketizeError::Unsupported)
+        );
+
+        let frame = depacketizer.pull().unwrap();
+        assert!(frame.data.is_empty());
+        assert!(frame.corrupted);
+    }
+
+    #[test]
+    fn test_packetize() {
+        let mut packetizer = H264Packetizer::new(96, 0x1234_5678, 100);
+        let mut idr = vec![0x65];
+        idr.extend((0..250).map(|byte| byte as u8));
+        let frame = Frame {
+            timestamp: 3000,
+            data: annex_b(&[SPS, PPS, &idr]),
+            keyframe: true,
+            corrupted: false,
+        };
+
+        let packets = packetizer.packetize(&frame).unwrap();
+
+        // The parameter sets are aggregated, the IDR picture is fragmented.
+        assert_eq!(packets.len(), 4);
+        assert_eq!(packets[0].payload[0], 0x78);
+        assert_eq!(packets[1].payload[..2], [0x7c, 0x85]);
+        assert_eq!(packets[2].payload[..2], [0x7c, 0x05]);
+        assert_eq!(packets[3].payload[..2], [0x7c, 0x45]);
+
+        for (index, packet) in packets.iter().enumerate() {
+            assert!(packet.payload.len() <= 100 - 12);
+            assert_eq!(packet.header.marker, index == 3);
+            assert_eq!(packet.header.timestamp, packets[0].header.timestamp);
+            assert_eq!(
+                packet.header.seq_num,
+                packets[0].header.seq_num.wrapping_add(index as u16)
+            );
+        }
+
+        let timestamp = packets[0].header.timestamp;
+        let packets = packetizer.packetize(&Frame { timestamp: 6000, ..frame }).unwrap();
+        assert_eq!(packets[0].header.timestamp.wrapping_sub(timestamp), 3000);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut packetizer = H264Packetizer::new(96, 0x1234_5678, 100);
+        let mut depacketizer = H264Depacketizer::new();
+        let mut codec = Codec::new();
+        let mut idr = vec![0x65];
+        idr.extend((0..1000).map(|byte| byte as u8));
+        let frames = vec![
+            Frame {
+                timestamp: 0,
+                data: annex_b(&[SPS, PPS, &idr]),
+                keyframe: true,
+                corrupted: false,
+            },
+            Frame {
+                timestamp: 3000,
+                data: annex_b(&[SLICE]),
+                keyframe: false,
+                corrupted: false,
+            },
+        ];
+
+        for frame in frames.iter() {
+            for packet in packetizer.packetize(frame).unwrap() {
+                let mut buffer = BytesMut::new();
+                codec.encode(MuxedPacket::Rtp(packet), &mut buffer).unwrap();
+
+                match codec.decode(&mut buffer).unwrap() {
+                    Some(MuxedPacket::Rtp(packet)) => depacketizer.push(&packet).unwrap(),
+                    _ => panic!("expected RTP packet"),
+                }
+            }
+        }
+
+        let first = depacketizer.pull().unwrap();
+        let second = depacketizer.pull().unwrap();
+        assert_eq!(first.data, frames[0].data);
+        assert!(first.keyframe);
+        assert!(!first.corrupted);
+        assert_eq!(second.data, frames[1].data);
+        assert_eq!(second.timestamp.wrapping_sub(first.timestamp), 3000);
+        assert_eq!(depacketizer.pull(), None);
+    }
+}
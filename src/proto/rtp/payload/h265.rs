@@ -0,0 +1,494 @@
+//! H.265/HEVC video (RFC 7798).
+
+use crate::proto::rtp::payload::{
+    DepacketizeError, Depacketizer, Frame, PacketSequencer, PacketizeError, Packetizer,
+};
+use crate::proto::rtp::payload::nal::{
+    annex_b_nal_units, length_prefixed_nal_units, AccessUnitAssembler,
+};
+use crate::proto::rtp::rtp::RtpPacket;
+use crate::proto::types::U7;
+
+/// The clock rate of H.265 RTP timestamps.
+pub const CLOCK_RATE: u32 = 90_000;
+
+pub const NAL_UNIT_TYPE_BLA_W_LP: u8 = 16;
+pub const NAL_UNIT_TYPE_IDR_W_RADL: u8 = 19;
+pub const NAL_UNIT_TYPE_IDR_N_LP: u8 = 20;
+pub const NAL_UNIT_TYPE_CRA: u8 = 21;
+pub const NAL_UNIT_TYPE_VPS: u8 = 32;
+pub const NAL_UNIT_TYPE_SPS: u8 = 33;
+pub const NAL_UNIT_TYPE_PPS: u8 = 34;
+pub const NAL_UNIT_TYPE_AUD: u8 = 35;
+pub const NAL_UNIT_TYPE_AP: u8 = 48;
+pub const NAL_UNIT_TYPE_FU: u8 = 49;
+pub const NAL_UNIT_TYPE_PACI: u8 = 50;
+
+/// The size of the NAL unit header, which is also the size of the payload header.
+const NAL_UNIT_HEADER_SIZE: usize = 2;
+
+/// The size of the DONL field, and of a NAL unit size field of an aggregation packet.
+const FIELD_SIZE: usize = 2;
+
+/// The size of the FU header.
+const FU_HEADER_SIZE: usize = 1;
+
+/// The last of the reserved IRAP NAL unit types.
+const NAL_UNIT_TYPE_RSV_IRAP_VCL23: u8 = 23;
+
+/// The last of the VCL NAL unit types.
+const NAL_UNIT_TYPE_RSV_VCL31: u8 = 31;
+
+const NAL_UNIT_TYPE_SHIFT: u8 = 1;
+const NAL_UNIT_TYPE_MASK: u8 = 0b0011_1111;
+const NAL_UNIT_HEADER_F: u8 = 0b1000_0000;
+const NAL_UNIT_HEADER_LAYER_ID_HIGH: u8 = 0b0000_0001;
+const FU_HEADER_START: u8 = 0b1000_0000;
+const FU_HEADER_END: u8 = 0b0100_0000;
+const FU_HEADER_TYPE_MASK: u8 = 0b0011_1111;
+
+/// Returns the type of the NAL unit with the given first header byte.
+pub fn nal_unit_type(header: u8) -> u8 {
+    (header >> NAL_UNIT_TYPE_SHIFT) & NAL_UNIT_TYPE_MASK
+}
+
+/// Returns whether the NAL unit with the given first header byte belongs to an intra random
+/// access point (IRAP) picture, i.e. a BLA, IDR or CRA picture.
+pub fn is_irap(header: u8) -> bool {
+    (NAL_UNIT_TYPE_BLA_W_LP..=NAL_UNIT_TYPE_RSV_IRAP_VCL23).contains(&nal_unit_type(header))
+}
+
+/// Returns whether the NAL unit with the given first header byte contains a coded slice.
+fn is_vcl(header: u8) -> bool {
+    nal_unit_type(header) <= NAL_UNIT_TYPE_RSV_VCL31
+}
+
+/// Reassembles H.265 access units from RTP packets.
+///
+/// Single NAL unit packets, aggregation packets and fragmentation units are supported, PACI
+/// packets are not. If the stream carries decoding order numbers, which is the case when
+/// `sprop-max-don-diff` is greater than 0 in the SDP, the depacketizer has to be constructed with
+/// [`H265Depacketizer::with_donl`]. The NAL units are not reordered by their decoding order number
+/// though, they are emitted in transmission order.
+///
+/// Every frame is an Annex B byte stream, and is a keyframe if it contains an IRAP picture. If
+/// any packet of an access unit was lost, the frame is emitted as corrupted, and an incomplete
+/// fragmented NAL unit is left out.
+#[derive(Debug, Default)]
+pub struct H265Depacketizer {
+    assembler: AccessUnitAssembler,
+
+    /// Do the payloads carry the DONL and DOND fields.
+    donl: bool,
+}
+
+impl H265Depacketizer {
+    /// Constructs a new depacketizer for a stream without decoding order numbers.
+    pub fn new() -> Self {
+        H265Depacketizer::default()
+    }
+
+    /// Constructs a new depacketizer for a stream with decoding order numbers.
+    pub fn with_donl() -> Self {
+        H265Depacketizer {
+            assembler: AccessUnitAssembler::default(),
+            donl: true,
+        }
+    }
+
+    fn push_nal_unit(&mut self, nal_unit: &[u8]) {
+        let keyframe = is_irap(nal_unit[0]);
+        self.assembler.push_nal_unit(nal_unit, keyframe);
+    }
+
+    fn depacketize(&mut self, payload: &[u8]) -> Result<(), DepacketizeError> {
+        if payload.len() < NAL_UNIT_HEADER_SIZE {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        match nal_unit_type(payload[0]) {
+            NAL_UNIT_TYPE_AP => self.push_aggregated(&payload[NAL_UNIT_HEADER_SIZE..]),
+            NAL_UNIT_TYPE_FU => self.push_fragment(payload),
+            NAL_UNIT_TYPE_PACI => Err(DepacketizeError::Unsupported),
+            _ if self.donl => {
+                let data = skip(payload, NAL_UNIT_HEADER_SIZE + FIELD_SIZE)?;
+                let mut nal_unit = Vec::with_capacity(NAL_UNIT_HEADER_SIZE + data.len());
+                nal_unit.extend_from_slice(&payload[..NAL_UNIT_HEADER_SIZE]);
+                nal_unit.extend_from_slice(data);
+                self.push_nal_unit(&nal_unit);
+                Ok(())
+            }
+            _ => {
+                self.push_nal_unit(payload);
+                Ok(())
+            }
+        }
+    }
+
+    /// Pushes the NAL units of an aggregation packet. With decoding order numbers, the first NAL
+    /// unit is preceded by a DONL field and the following ones by a DOND field.
+    fn push_aggregated(&mut self, mut units: &[u8]) -> Result<(), DepacketizeError> {
+        let mut nal_units = Vec::new();
+
+        while !units.is_empty() {
+            if self.donl {
+                let don_size = if nal_units.is_empty() { FIELD_SIZE } else { 1 };
+                units = skip(units, don_size)?;
+            }
+
+            if units.len() < FIELD_SIZE {
+                return Err(DepacketizeError::Truncated);
+            }
+
+            let size = u16::from_be_bytes([units[0], units[1]]) as usize;
+            let nal_unit = units
+                .get(FIELD_SIZE..FIELD_SIZE + size)
+                .filter(|nal_unit| nal_unit.len() >= NAL_UNIT_HEADER_SIZE)
+                .ok_or(DepacketizeError::Truncated)?;
+            nal_units.push(nal_unit);
+            units = &units[FIELD_SIZE + size..];
+        }
+
+        if nal_units.is_empty() {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        for nal_unit in nal_units {
+            self.push_nal_unit(nal_unit);
+        }
+
+        Ok(())
+    }
+
+    fn push_fragment(&mut self, payload: &[u8]) -> Result<(), DepacketizeError> {
+        let fu_header = *payload
+            .get(NAL_UNIT_HEADER_SIZE)
+            .ok_or(DepacketizeError::Truncated)?;
+        let start = fu_header & FU_HEADER_START != 0;
+        let end = fu_header & FU_HEADER_END != 0;
+        let mut data = &payload[NAL_UNIT_HEADER_SIZE + FU_HEADER_SIZE..];
+        let nal_unit_header = [
+            (payload[0] & (NAL_UNIT_HEADER_F | NAL_UNIT_HEADER_LAYER_ID_HIGH))
+                | ((fu_header & FU_HEADER_TYPE_MASK) << NAL_UNIT_TYPE_SHIFT),
+            payload[1],
+        ];
+
+        // Only the first fragment carries the decoding order number.
+        if start && self.donl {
+            data = skip(data, FIELD_SIZE)?;
+        }
+
+        let nal_unit_header = if start { Some(&nal_unit_header[..]) } else { None };
+
+        if let Some(nal_unit) = self.assembler.push_fragment(nal_unit_header, data, end) {
+            self.push_nal_unit(&nal_unit);
+        }
+
+        Ok(())
+    }
+}
+
+impl Depacketizer for H265Depacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        if !self.assembler.start_packet(&packet.header) {
+            return Ok(());
+        }
+
+        let result = self.depacketize(&packet.payload);
+        self.assembler.end_packet(&packet.header, result)
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.assembler.pull()
+    }
+}
+
+/// Splits H.265 access units into RTP packets, without decoding order numbers.
+///
+/// NAL units that fit into a packet are sent as single NAL unit packets, larger ones are
+/// fragmented into fragmentation units. Consecutive parameter sets and other non-VCL NAL units,
+/// e.g. the VPS, SPS and PPS preceding an IRAP picture, are aggregated into aggregation packets
+/// when they fit.
+#[derive(Clone, Debug)]
+pub struct H265Packetizer {
+    sequencer: PacketSequencer,
+}
+
+impl H265Packetizer {
+    /// Constructs a new packetizer producing RTP packets of at most `max_packet_size` bytes, e.g.
+    /// [`DEFAULT_MAX_PACKET_SIZE`](crate::proto::rtp::payload::DEFAULT_MAX_PACKET_SIZE).
+    pub fn new(payload_type: U7, ssrc: u32, max_packet_size: usize) -> Self {
+        H265Packetizer {
+            sequencer: PacketSequencer::new(payload_type, ssrc, max_packet_size),
+        }
+    }
+
+    /// Splits a length prefixed access unit, as stored in MP4 files, into packets. See
+    /// [`length_prefixed_nal_units`].
+    pub fn packetize_length_prefixed(
+        &mut self,
+        access_unit: &[u8],
+        length_size: usize,
+        timestamp: u32,
+    ) -> Result<Vec<RtpPacket>, PacketizeError> {
+        self.packetize_nal_units(&length_prefixed_nal_units(access_unit, length_size)?, timestamp)
+    }
+
+    /// Splits the NAL units of an access unit into packets.
+    pub fn packetize_nal_units(
+        &mut self,
+        nal_units: &[&[u8]],
+        timestamp: u32,
+    ) -> Result<Vec<RtpPacket>, PacketizeError> {
+        if nal_units.iter().any(|nal_unit| nal_unit.len() < NAL_UNIT_HEADER_SIZE) {
+            return Err(PacketizeError::Truncated);
+        }
+
+        if nal_units.is_empty() {
+            return Err(PacketizeError::Empty);
+        }
+
+        let max_payload_size = self.sequencer.max_payload_size();
+        let mut payloads = Vec::new();
+        let mut index = 0;
+
+        while index < nal_units.len() {
+            let aggregated = self.aggregate(&nal_units[index..]);
+
+            if aggregated > 1 {
+                payloads.push(aggregation_packet(&nal_units[index..index + aggregated]));
+                index += aggregated;
+                continue;
+            }
+
+            let nal_unit = nal_units[index];
+
+            if nal_unit.len() <= max_payload_size {
+                payloads.push(nal_unit.to_vec());
+            } else {
+                payloads.extend(fragmentation_units(nal_unit, max_payload_size));
+            }
+
+            index += 1;
+        }
+
+        let last = payloads.len() - 1;
+
+        Ok(payloads
+            .into_iter()
+            .enumerate()
+            .map(|(index, payload)| self.sequencer.packet(timestamp, index == last, payload))
+            .collect())
+    }
+
+    /// Returns how many of the leading NAL units fit into a single aggregation packet.
+    fn aggregate(&self, nal_units: &[&[u8]]) -> usize {
+        let mut size = NAL_UNIT_HEADER_SIZE;
+
+        nal_units
+            .iter()
+            .take_while(|nal_unit| {
+                size += FIELD_SIZE + nal_unit.len();
+                !is_vcl(nal_unit[0]) && size <= self.sequencer.max_payload_size()
+            })
+            .count()
+    }
+}
+
+impl Packetizer for H265Packetizer {
+    /// Splits an access unit given as an Annex B byte stream into packets.
+    fn packetize(&mut self, frame: &Frame) -> Result<Vec<RtpPacket>, PacketizeError> {
+        self.packetize_nal_units(&annex_b_nal_units(&frame.data), frame.timestamp)
+    }
+}
+
+/// Returns the layer ID and temporal ID plus 1 of the NAL unit.
+fn layer(nal_unit: &[u8]) -> (u8, u8) {
+    let layer_id = (nal_unit[0] & NAL_UNIT_HEADER_LAYER_ID_HIGH) << 5 | nal_unit[1] >> 3;
+    (layer_id, nal_unit[1] & 0b0000_0111)
+}
+
+fn aggregation_packet(nal_units: &[&[u8]]) -> Vec<u8> {
+    // The payload header uses the lowest layer and temporal ID of the aggregated NAL units.
+    let forbidden_bit = nal_units.iter().fold(0, |bit, nal_unit| bit | (nal_unit[0] & NAL_UNIT_HEADER_F));
+    let (layer_id, temporal_id) = nal_units
+        .iter()
+        .map(|nal_unit| layer(nal_unit))
+        .fold((u8::max_value(), u8::max_value()), |(layer_id, temporal_id), layer| {
+            (layer_id.min(layer.0), temporal_id.min(layer.1))
+        });
+    let mut payload = vec![
+        forbidden_bit | NAL_UNIT_TYPE_AP << NAL_UNIT_TYPE_SHIFT | layer_id >> 5,
+        (layer_id & 0b0001_1111) << 3 | temporal_id,
+    ];
+
+    for nal_unit in nal_units {
+        payload.extend_from_slice(&(nal_unit.len() as u16).to_be_bytes());
+        payload.extend_from_slice(nal_unit);
+    }
+
+    payload
+}
+
+fn fragmentation_units(nal_unit: &[u8], max_payload_size: usize) -> Vec<Vec<u8>> {
+    let payload_header = [
+        (nal_unit[0] & !(NAL_UNIT_TYPE_MASK << NAL_UNIT_TYPE_SHIFT))
+            | NAL_UNIT_TYPE_FU << NAL_UNIT_TYPE_SHIFT,
+        nal_unit[1],
+    ];
+    let chunks = nal_unit[NAL_UNIT_HEADER_SIZE..]
+        .chunks(max_payload_size - NAL_UNIT_HEADER_SIZE - FU_HEADER_SIZE);
+    let last = chunks.len() - 1;
+
+    chunks
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut fu_header = nal_unit_type(nal_unit[0]);
+
+            if index == 0 {
+                fu_header |= FU_HEADER_START;
+            }
+
+            if index == last {
+                fu_header |= FU_HEADER_END;
+            }
+
+            let mut payload = Vec::with_capacity(NAL_UNIT_HEADER_SIZE + FU_HEADER_SIZE + chunk.len());
+            payload.extend_from_slice(&payload_header);
+            payload.push(fu_header);
+            payload.extend_from_slice(chunk);
+            payload
+        })
+        .collect()
+}
+
+/// Returns the data following the first `length` bytes.
+fn skip(data: &[u8], length: usize) -> Result<&[u8], DepacketizeError> {
+    data.get(length..).ok_or(DepacketizeError::Truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::{DepacketizeError, Depacketizer, Frame, Packetizer};
+    use crate::proto::rtp::rtp::{test_support::packet, RtpPacket};
+
+    use super::{is_irap, H265Depacketizer, H265Packetizer};
+
+    const VPS: &[u8] = &[0x40, 0x01, 0x0c, 0x01];
+    const SPS: &[u8] = &[0x42, 0x01, 0x01, 0x01];
+    const PPS: &[u8] = &[0x44, 0x01, 0xc1, 0x72];
+    const IDR: &[u8] = &[0x26, 0x01, 0xaf, 0x06, 0xb8, 0x63];
+    const TRAIL: &[u8] = &[0x02, 0x01, 0xd0, 0x09];
+
+    fn annex_b(nal_units: &[&[u8]]) -> Vec<u8> {
+        nal_units
+            .iter()
+            .flat_map(|nal_unit| [0, 0, 0, 1].iter().chain(nal_unit.iter()).cloned())
+            .collect()
+    }
+
+    #[test]
+    fn test_is_irap() {
+        assert!(is_irap(IDR[0]));
+        assert!(!is_irap(TRAIL[0]));
+        assert!(!is_irap(VPS[0]));
+    }
+
+    #[test]
+    fn test_depacketize() {
+        let mut depacketizer = H265Depacketizer::new();
+        let mut aggregation_packet = vec![0x60, 0x01];
+
+        for nal_unit in [VPS, SPS, PPS].iter() {
+            aggregation_packet.extend_from_slice(&(nal_unit.len() as u16).to_be_bytes());
+            aggregation_packet.extend_from_slice(nal_unit);
+        }
+
+        depacketizer.push(&packet(1, 3000, false, &aggregation_packet)).unwrap();
+        depacketizer.push(&packet(2, 3000, false, &[0x62, 0x01, 0x93, 0xaf, 0x06])).unwrap();
+        depacketizer.push(&packet(3, 3000, true, &[0x62, 0x01, 0x53, 0xb8, 0x63])).unwrap();
+        depacketizer.push(&packet(4, 6000, true, TRAIL)).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 3000);
+        assert_eq!(frame.data, annex_b(&[VPS, SPS, PPS, IDR]));
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, annex_b(&[TRAIL]));
+        assert!(!frame.keyframe);
+
+        assert_eq!(depacketizer.pull(), None);
+    }
+
+    #[test]
+    fn test_depacketize_donl() {
+        let mut depacketizer = H265Depacketizer::with_donl();
+        let mut aggregation_packet = vec![0x60, 0x01, 0x00, 0x07];
+        aggregation_packet.extend_from_slice(&(VPS.len() as u16).to_be_bytes());
+        aggregation_packet.extend_from_slice(VPS);
+        aggregation_packet.push(0x00);
+        aggregation_packet.extend_from_slice(&(SPS.len() as u16).to_be_bytes());
+        aggregation_packet.extend_from_slice(SPS);
+
+        depacketizer.push(&packet(1, 3000, false, &aggregation_packet)).unwrap();
+        depacketizer.push(&packet(2, 3000, false, &[0x44, 0x01, 0x00, 0x09, 0xc1, 0x72])).unwrap();
+        depacketizer.push(&packet(3, 3000, false, &[0x62, 0x01, 0x93, 0x00, 0x0a, 0xaf, 0x06])).unwrap();
+        depacketizer.push(&packet(4, 3000, true, &[0x62, 0x01, 0x53, 0xb8, 0x63])).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, annex_b(&[VPS, SPS, PPS, IDR]));
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+    }
+
+    #[test]
+    fn test_depacketize_lost_fragment() {
+        let mut depacketizer = H265Depacketizer::new();
+
+        depacketizer.push(&packet(1, 3000, false, &[0x62, 0x01, 0x93, 0xaf, 0x06])).unwrap();
+        depacketizer.push(&packet(3, 3000, true, &[0x62, 0x01, 0x53, 0xb8, 0x63])).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert!(frame.data.is_empty());
+        assert!(frame.corrupted);
+
+        assert_eq!(
+            depacketizer.push(&packet(4, 6000, true, &[0x64, 0x01])),
+            Err(DepacketizeError::Unsupported)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut packetizer = H265Packetizer::new(96, 0x1234_5678, 100);
+        let mut depacketizer = H265Depacketizer::new();
+        let mut idr = IDR.to_vec();
+        idr.extend((0..1000).map(|byte| byte as u8));
+        let frame = Frame {
+            timestamp: 0,
+            data: annex_b(&[VPS, SPS, PPS, &idr]),
+            keyframe: true,
+            corrupted: false,
+        };
+
+        let packets = packetizer.packetize(&frame).unwrap();
+
+        // The parameter sets are aggregated, the IDR picture is fragmented.
+        assert_eq!(packets[0].payload[..2], [0x60, 0x01]);
+        assert_eq!(packets[1].payload[..3], [0x62, 0x01, 0x93]);
+        assert_eq!(packets.last().unwrap().payload[..3], [0x62, 0x01, 0x53]);
+        assert!(packets.iter().all(|packet| packet.payload.len() <= 100 - 12));
+        assert!(packets.last().unwrap().header.marker);
+
+        for packet in packets.iter() {
+            depacketizer.push(packet).unwrap();
+        }
+
+        let depacketized = depacketizer.pull().unwrap();
+        assert_eq!(depacketized.data, frame.data);
+        assert!(depacketized.keyframe);
+        assert!(!depacketized.corrupted);
+    }
+}
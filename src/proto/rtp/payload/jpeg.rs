@@ -0,0 +1,628 @@
+//! JPEG video (RFC 2435), as sent by MJPEG cameras.
+
+use std::collections::HashMap;
+
+use fnv::FnvBuildHasher;
+
+use crate::proto::rtp::payload::{DepacketizeError, Depacketizer, Frame, FrameAssembler};
+use crate::proto::rtp::rtp::RtpPacket;
+
+/// The clock rate of JPEG RTP timestamps.
+pub const CLOCK_RATE: u32 = 90_000;
+
+/// The static payload type of JPEG.
+pub const PAYLOAD_TYPE: u8 = 26;
+
+const MAIN_HEADER_SIZE: usize = 8;
+const RESTART_MARKER_HEADER_SIZE: usize = 4;
+const QUANTIZATION_TABLE_HEADER_SIZE: usize = 4;
+
+/// The types with the restart marker header, which are otherwise the same as the types 64
+/// below them.
+const RESTART_MARKER_TYPES: std::ops::Range<u8> = 64..128;
+
+/// The Q values from which on the quantization tables are sent in band.
+const IN_BAND_TABLES_Q: u8 = 128;
+
+/// The Q value of tables which may change with every frame, so that they are never cached.
+const DYNAMIC_TABLES_Q: u8 = 255;
+
+const SOI: [u8; 2] = [0xff, 0xd8];
+const EOI: [u8; 2] = [0xff, 0xd9];
+
+/// The base luminance quantization table of ITU-T T.81 Annex K, in natural order.
+const LUMINANCE_QUANTIZER: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// The base chrominance quantization table of ITU-T T.81 Annex K, in natural order.
+const CHROMINANCE_QUANTIZER: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// The natural order index of every zigzag order index.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// The Huffman tables of ITU-T T.81 Annex K, as code counts per length and symbols.
+
+const LUMINANCE_DC_CODE_LENGTHS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const LUMINANCE_DC_SYMBOLS: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const LUMINANCE_AC_CODE_LENGTHS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const LUMINANCE_AC_SYMBOLS: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+const CHROMINANCE_DC_CODE_LENGTHS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const CHROMINANCE_DC_SYMBOLS: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const CHROMINANCE_AC_CODE_LENGTHS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const CHROMINANCE_AC_SYMBOLS: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+/// The RTP JPEG header preceding the payload of every packet, along with its restart marker
+/// header.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct JpegHeader {
+    /// Tells whether the image is progressive (0) or a field of an interlaced frame.
+    pub type_specific: u8,
+
+    /// The offset of the packet data in the entropy-coded data of the frame.
+    pub fragment_offset: u32,
+
+    /// The type of the image, telling its chroma subsampling and whether restart markers are
+    /// used.
+    pub kind: u8,
+
+    /// The quality factor the quantization tables are derived from, or from 128 on tells that
+    /// the tables are sent in band.
+    pub q: u8,
+
+    /// The width of the image in pixels.
+    pub width: u16,
+
+    /// The height of the image in pixels.
+    pub height: u16,
+
+    /// The number of MCUs between restart markers, if restart markers are used.
+    pub restart_interval: Option<u16>,
+}
+
+impl JpegHeader {
+    /// Parses the header at the start of a payload.
+    ///
+    /// Returns the header and the data following it, which starts with the quantization table
+    /// header in the first packet of a frame with in band tables.
+    pub fn parse(payload: &[u8]) -> Result<(Self, &[u8]), DepacketizeError> {
+        if payload.len() < MAIN_HEADER_SIZE {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let mut header = JpegHeader {
+            type_specific: payload[0],
+            fragment_offset: u32::from_be_bytes([0, payload[1], payload[2], payload[3]]),
+            kind: payload[4],
+            q: payload[5],
+            width: u16::from(payload[6]) * 8,
+            height: u16::from(payload[7]) * 8,
+            restart_interval: None,
+        };
+        let mut data = &payload[MAIN_HEADER_SIZE..];
+
+        if RESTART_MARKER_TYPES.contains(&header.kind) {
+            if data.len() < RESTART_MARKER_HEADER_SIZE {
+                return Err(DepacketizeError::Truncated);
+            }
+
+            header.restart_interval = Some(u16::from_be_bytes([data[0], data[1]]));
+            data = &data[RESTART_MARKER_HEADER_SIZE..];
+        }
+
+        Ok((header, data))
+    }
+
+    /// Returns the sampling factors of the luminance component, or `None` for unknown types.
+    fn luminance_sampling(&self) -> Option<u8> {
+        match self.kind {
+            // 4:2:2
+            0 | 64 => Some(0x21),
+
+            // 4:2:0
+            1 | 65 => Some(0x22),
+
+            _ => None,
+        }
+    }
+}
+
+/// Returns the luminance and chrominance quantization tables of a quality factor from 1 to 99,
+/// in zigzag order.
+fn quantization_tables(q: u8) -> Vec<u8> {
+    let q = u32::from(q.max(1).min(99));
+    let factor = if q < 50 { 5000 / q } else { 200 - q * 2 };
+    let scale = |base: u8| (u32::from(base) * factor + 50) / 100;
+
+    let luminance = ZIGZAG
+        .iter()
+        .map(|&index| scale(LUMINANCE_QUANTIZER[index]));
+    let chrominance = ZIGZAG
+        .iter()
+        .map(|&index| scale(CHROMINANCE_QUANTIZER[index]));
+
+    luminance
+        .chain(chrominance)
+        .map(|value| value.max(1).min(255) as u8)
+        .collect()
+}
+
+/// The quantization tables of a frame as sent in band.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct QuantizationTables {
+    /// The bits set for the tables with 16 bit values.
+    precision: u8,
+
+    data: Vec<u8>,
+}
+
+impl QuantizationTables {
+    /// Splits the tables into the precision and data of each table.
+    fn tables(&self) -> Vec<(u8, &[u8])> {
+        let mut tables = Vec::new();
+        let mut data = &self.data[..];
+
+        while !data.is_empty() && tables.len() < 4 {
+            let precision = (self.precision >> tables.len()) & 1;
+            let size = if precision == 0 { 64 } else { 128 };
+
+            if data.len() < size {
+                break;
+            }
+
+            tables.push((precision, &data[..size]));
+            data = &data[size..];
+        }
+
+        tables
+    }
+}
+
+/// Writes a marker segment with its length.
+fn write_segment(output: &mut Vec<u8>, marker: u8, contents: &[u8]) {
+    output.extend_from_slice(&[0xff, marker]);
+    output.extend_from_slice(&(contents.len() as u16 + 2).to_be_bytes());
+    output.extend_from_slice(contents);
+}
+
+/// Writes the headers of a baseline JFIF image, up to the start of its entropy-coded data.
+fn write_headers(
+    output: &mut Vec<u8>,
+    header: &JpegHeader,
+    luminance_sampling: u8,
+    tables: &[(u8, &[u8])],
+) {
+    output.extend_from_slice(&SOI);
+
+    // APP0: JFIF 1.01 without thumbnail and with a 1:1 pixel aspect ratio.
+    write_segment(
+        output,
+        0xe0,
+        &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0],
+    );
+
+    for (index, (precision, table)) in tables.iter().enumerate() {
+        let mut contents = vec![precision << 4 | index as u8];
+        contents.extend_from_slice(table);
+        write_segment(output, 0xdb, &contents);
+    }
+
+    if let Some(interval) = header.restart_interval {
+        write_segment(output, 0xdd, &interval.to_be_bytes());
+    }
+
+    // The chrominance components use the second table, if any.
+    let chrominance_table = if tables.len() > 1 { 1 } else { 0 };
+    let [height_high, height_low] = header.height.to_be_bytes();
+    let [width_high, width_low] = header.width.to_be_bytes();
+
+    // SOF0: 8 bit precision and 3 components.
+    write_segment(
+        output,
+        0xc0,
+        &[
+            8,
+            height_high,
+            height_low,
+            width_high,
+            width_low,
+            3,
+            1,
+            luminance_sampling,
+            0,
+            2,
+            0x11,
+            chrominance_table,
+            3,
+            0x11,
+            chrominance_table,
+        ],
+    );
+
+    let huffman_tables: [(u8, &[u8], &[u8]); 4] = [
+        (0x00, &LUMINANCE_DC_CODE_LENGTHS, &LUMINANCE_DC_SYMBOLS),
+        (0x10, &LUMINANCE_AC_CODE_LENGTHS, &LUMINANCE_AC_SYMBOLS),
+        (0x01, &CHROMINANCE_DC_CODE_LENGTHS, &CHROMINANCE_DC_SYMBOLS),
+        (0x11, &CHROMINANCE_AC_CODE_LENGTHS, &CHROMINANCE_AC_SYMBOLS),
+    ];
+
+    for (class_and_id, code_lengths, symbols) in huffman_tables.iter() {
+        let mut contents = vec![*class_and_id];
+        contents.extend_from_slice(code_lengths);
+        contents.extend_from_slice(symbols);
+        write_segment(output, 0xc4, &contents);
+    }
+
+    // SOS: the components with their Huffman tables, and the full spectral selection.
+    write_segment(output, 0xda, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+}
+
+/// Reassembles JPEG images from RTP packets.
+///
+/// The JFIF headers stripped by the sender are rebuilt from the RTP JPEG header in front of the
+/// entropy-coded data, so that every frame is a complete `.jpg` file. The frames are emitted
+/// along with the RTP JPEG header of their first packet.
+///
+/// If any packet of a frame was lost, the frame is emitted as corrupted. A frame missing its
+/// first packet has no headers and is not a keyframe.
+#[derive(Debug)]
+pub struct JpegDepacketizer {
+    assembler: FrameAssembler<JpegHeader>,
+
+    /// The offset the next fragment of the frame being assembled is expected at.
+    next_offset: u32,
+
+    /// The in band quantization tables of the Q values below 255, which may be sent with the
+    /// first frame only.
+    tables: HashMap<u8, QuantizationTables, FnvBuildHasher>,
+}
+
+impl JpegDepacketizer {
+    /// Constructs a new depacketizer.
+    pub fn new() -> Self {
+        JpegDepacketizer {
+            assembler: FrameAssembler::new(),
+            next_offset: 0,
+            tables: HashMap::default(),
+        }
+    }
+
+    /// Returns the next complete frame along with the RTP JPEG header of its first packet, if
+    /// any.
+    pub fn pull_with_header(&mut self) -> Option<(Frame, JpegHeader)> {
+        self.assembler.pull()
+    }
+
+    fn depacketize(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        let (header, mut data) = JpegHeader::parse(&packet.payload)?;
+
+        if header.fragment_offset == 0 {
+            let luminance_sampling = header
+                .luminance_sampling()
+                .ok_or(DepacketizeError::Unsupported)?;
+
+            let tables = if header.q >= IN_BAND_TABLES_Q {
+                let (tables, rest) = self.read_tables(header.q, data)?;
+                data = rest;
+                tables
+            } else if header.q > 0 && header.q < 100 {
+                QuantizationTables {
+                    precision: 0,
+                    data: quantization_tables(header.q),
+                }
+            } else {
+                return Err(DepacketizeError::Unsupported);
+            };
+
+            let mut headers = Vec::new();
+            write_headers(&mut headers, &header, luminance_sampling, &tables.tables());
+
+            self.assembler
+                .start_frame(packet.header.timestamp, header, true);
+            self.assembler.append(&headers);
+        } else if !self.assembler.in_frame() {
+            // The start of the frame was lost.
+            self.assembler
+                .start_frame(packet.header.timestamp, header, false);
+            self.assembler.corrupt();
+        } else if header.fragment_offset != self.next_offset {
+            self.assembler.corrupt();
+        }
+
+        self.next_offset = header.fragment_offset + data.len() as u32;
+        self.assembler.append(data);
+
+        if packet.header.marker && !data.ends_with(&EOI) {
+            self.assembler.append(&EOI);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the quantization table header and tables of the first packet of a frame, falling
+    /// back to the tables cached for the Q value if they are left out.
+    ///
+    /// Returns the tables and the data following them.
+    fn read_tables<'a>(
+        &mut self,
+        q: u8,
+        data: &'a [u8],
+    ) -> Result<(QuantizationTables, &'a [u8]), DepacketizeError> {
+        if data.len() < QUANTIZATION_TABLE_HEADER_SIZE {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let precision = data[1];
+        let length = usize::from(u16::from_be_bytes([data[2], data[3]]));
+        let rest = &data[QUANTIZATION_TABLE_HEADER_SIZE..];
+
+        if length == 0 {
+            return match self.tables.get(&q) {
+                Some(tables) if q != DYNAMIC_TABLES_Q => Ok((tables.clone(), rest)),
+                _ => Err(DepacketizeError::Truncated),
+            };
+        }
+
+        let table_data = rest.get(..length).ok_or(DepacketizeError::Truncated)?;
+        let tables = QuantizationTables {
+            precision,
+            data: table_data.to_vec(),
+        };
+
+        if q != DYNAMIC_TABLES_Q {
+            self.tables.insert(q, tables.clone());
+        }
+
+        Ok((tables, &rest[length..]))
+    }
+}
+
+impl Default for JpegDepacketizer {
+    fn default() -> Self {
+        JpegDepacketizer::new()
+    }
+}
+
+impl Depacketizer for JpegDepacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        if !self.assembler.start_packet(&packet.header) {
+            return Ok(());
+        }
+
+        let result = self.depacketize(packet);
+
+        if result.is_err() {
+            self.assembler.corrupt();
+        }
+
+        if packet.header.marker {
+            self.assembler.finish_frame();
+        }
+
+        result
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.pull_with_header().map(|(frame, _)| frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::{DepacketizeError, Depacketizer};
+    use crate::proto::rtp::rtp::test_support::packet;
+
+    use super::{quantization_tables, JpegDepacketizer, JpegHeader};
+
+    /// Returns the markers of the segments of a JPEG image up to its entropy-coded data, along
+    /// with the data following them.
+    fn segments(image: &[u8]) -> (Vec<u8>, &[u8]) {
+        assert_eq!(&image[..2], &[0xff, 0xd8]);
+
+        let mut markers = Vec::new();
+        let mut data = &image[2..];
+
+        loop {
+            assert_eq!(data[0], 0xff);
+            let marker = data[1];
+            let length = usize::from(u16::from_be_bytes([data[2], data[3]]));
+            markers.push(marker);
+            data = &data[2 + length..];
+
+            if marker == 0xda {
+                return (markers, data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let payload = [
+            0, 0, 0x01, 0x02, 65, 80, 40, 30, 0x00, 0x10, 0xff, 0xff, 0xaa,
+        ];
+        let (header, data) = JpegHeader::parse(&payload).unwrap();
+
+        assert_eq!(
+            header,
+            JpegHeader {
+                type_specific: 0,
+                fragment_offset: 0x0102,
+                kind: 65,
+                q: 80,
+                width: 320,
+                height: 240,
+                restart_interval: Some(16),
+            }
+        );
+        assert_eq!(data, &[0xaa]);
+
+        assert_eq!(
+            JpegHeader::parse(&payload[..10]),
+            Err(DepacketizeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_quantization_tables() {
+        let tables = quantization_tables(50);
+
+        assert_eq!(tables.len(), 128);
+        assert_eq!(&tables[..6], &[16, 11, 12, 14, 12, 10]);
+        assert_eq!(&tables[64..68], &[17, 18, 18, 24]);
+
+        let tables = quantization_tables(99);
+        assert!(tables.iter().all(|&value| value <= 2));
+
+        let tables = quantization_tables(1);
+        assert!(tables.iter().all(|&value| value == 255));
+    }
+
+    #[test]
+    fn test_depacketize() {
+        let mut depacketizer = JpegDepacketizer::new();
+
+        depacketizer
+            .push(&packet(
+                1,
+                3000,
+                false,
+                &[0, 0, 0, 0, 1, 50, 2, 2, 0x12, 0x34],
+            ))
+            .unwrap();
+        depacketizer
+            .push(&packet(2, 3000, true, &[0, 0, 0, 2, 1, 50, 2, 2, 0x56]))
+            .unwrap();
+
+        let (frame, header) = depacketizer.pull_with_header().unwrap();
+        assert_eq!(frame.timestamp, 3000);
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+        assert_eq!((header.width, header.height), (16, 16));
+
+        let (markers, data) = segments(&frame.data);
+        assert_eq!(
+            markers,
+            vec![0xe0, 0xdb, 0xdb, 0xc0, 0xc4, 0xc4, 0xc4, 0xc4, 0xda]
+        );
+        assert_eq!(data, &[0x12, 0x34, 0x56, 0xff, 0xd9]);
+
+        // The frame size and sampling factors.
+        let sof = frame
+            .data
+            .windows(2)
+            .position(|marker| marker == [0xff, 0xc0])
+            .unwrap();
+        assert_eq!(&frame.data[sof + 5..sof + 11], &[0, 16, 0, 16, 3, 1]);
+        assert_eq!(frame.data[sof + 11], 0x22);
+    }
+
+    #[test]
+    fn test_depacketize_in_band_tables() {
+        let mut depacketizer = JpegDepacketizer::new();
+
+        let mut payload = vec![0, 0, 0, 0, 64, 128, 1, 1, 0x00, 0x04, 0xff, 0xff];
+        payload.extend_from_slice(&[0, 0, 0, 128]);
+        payload.extend((0..128).map(|value| value as u8));
+        payload.extend_from_slice(&[0x12, 0xff, 0xd9]);
+        depacketizer.push(&packet(1, 3000, true, &payload)).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        let (markers, data) = segments(&frame.data);
+        assert_eq!(
+            markers,
+            vec![0xe0, 0xdb, 0xdb, 0xdd, 0xc0, 0xc4, 0xc4, 0xc4, 0xc4, 0xda]
+        );
+        assert_eq!(data, &[0x12, 0xff, 0xd9]);
+
+        let dqt = frame
+            .data
+            .windows(2)
+            .position(|marker| marker == [0xff, 0xdb])
+            .unwrap();
+        assert_eq!(&frame.data[dqt + 4..dqt + 7], &[0x00, 0, 1]);
+
+        // The tables of the Q value are cached.
+        let payload = [
+            0, 0, 0, 0, 64, 128, 1, 1, 0x00, 0x04, 0xff, 0xff, 0, 0, 0, 0, 0x34,
+        ];
+        depacketizer.push(&packet(2, 6000, true, &payload)).unwrap();
+
+        let cached = depacketizer.pull().unwrap();
+        assert_eq!(&cached.data[..dqt + 200], &frame.data[..dqt + 200]);
+
+        // Dynamic tables are not.
+        let payload = [
+            0, 0, 0, 0, 64, 255, 1, 1, 0x00, 0x04, 0xff, 0xff, 0, 0, 0, 0, 0x34,
+        ];
+        assert_eq!(
+            depacketizer.push(&packet(3, 9000, true, &payload)),
+            Err(DepacketizeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_depacketize_lost_packets() {
+        let mut depacketizer = JpegDepacketizer::new();
+
+        depacketizer
+            .push(&packet(
+                1,
+                3000,
+                false,
+                &[0, 0, 0, 0, 1, 50, 2, 2, 0x12, 0x34],
+            ))
+            .unwrap();
+        depacketizer
+            .push(&packet(3, 3000, true, &[0, 0, 0, 4, 1, 50, 2, 2, 0x56]))
+            .unwrap();
+        depacketizer
+            .push(&packet(5, 6000, true, &[0, 0, 0, 2, 1, 50, 2, 2, 0x56]))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert!(frame.keyframe);
+        assert!(frame.corrupted);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 6000);
+        assert!(!frame.keyframe);
+        assert!(frame.corrupted);
+    }
+}
@@ -0,0 +1,378 @@
+//! Payload formats, turning RTP packets into media frames and back.
+
+pub mod aac;
+pub mod audio;
+mod bits;
+pub mod g711;
+pub mod g726;
+pub mod h264;
+pub mod h265;
+pub mod jpeg;
+pub mod nal;
+pub mod pes;
+pub mod ps;
+pub mod registry;
+pub mod ts;
+pub mod vp8;
+pub mod vp9;
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+use crate::proto::rtp::rtp::{RtpFixedHeader, RtpPacket};
+use crate::proto::rtp::sequence::is_sequence_jump;
+use crate::proto::types::U7;
+
+/// The size of an RTP packet produced by the packetizers unless configured otherwise, leaving
+/// room for the IP and UDP headers and tunneling overhead within the common Ethernet MTU.
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 1200;
+
+/// The size of the fixed RTP header without any CSRCs or header extension.
+const RTP_FIXED_HEADER_SIZE: usize = 12;
+
+/// The smallest payload size the packetizers are limited to, however small the maximum packet
+/// size is, so that fragmentation always makes progress.
+const MIN_MAX_PAYLOAD_SIZE: usize = 16;
+
+/// A complete media frame reassembled from RTP packets, e.g. a video access unit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    /// The timestamp of the frame in units of the clock rate of the payload.
+    pub timestamp: u32,
+
+    /// The media data in the canonical format of the payload, e.g. an Annex B byte stream for
+    /// H.264.
+    pub data: Vec<u8>,
+
+    /// Can decoding start with this frame.
+    pub keyframe: bool,
+
+    /// Was any part of the frame lost or malformed. A corrupted frame is missing some of its
+    /// data, so decoding it may produce artifacts.
+    pub corrupted: bool,
+}
+
+/// Reassembles the frames of a payload format from its RTP packets.
+pub trait Depacketizer {
+    /// Processes the next packet of the stream.
+    ///
+    /// Packets are expected in sequence number order, packets arriving late are dropped. An
+    /// error is returned if the payload is malformed, in which case the frame it belongs to is
+    /// marked as corrupted.
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError>;
+
+    /// Returns the next complete frame, if any.
+    fn pull(&mut self) -> Option<Frame>;
+}
+
+/// Splits the frames of a payload format into RTP packets.
+pub trait Packetizer {
+    /// Splits the frame into packets.
+    ///
+    /// The frame data has to be in the canonical format of the payload, and its timestamp is
+    /// in units of the clock rate of the payload, relative to the start of the stream. The
+    /// packets get consecutive sequence numbers, and the marker bit is set on the last packet of
+    /// the frame.
+    fn packetize(&mut self, frame: &Frame) -> Result<Vec<RtpPacket>, PacketizeError>;
+}
+
+/// Error that may be returned when depacketizing a payload.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum DepacketizeError {
+    /// The payload ended before a structure it contains.
+    Truncated,
+
+    /// The payload uses a packetization that is not supported.
+    Unsupported,
+
+    /// The configuration of the payload, e.g. from its format parameters, is malformed.
+    InvalidConfig,
+
+    /// The payload violates its format, e.g. a transport stream packet without a sync byte.
+    Malformed,
+}
+
+impl Display for DepacketizeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::DepacketizeError::*;
+
+        match self {
+            Truncated => write!(formatter, "truncated payload"),
+            Unsupported => write!(formatter, "unsupported payload"),
+            InvalidConfig => write!(formatter, "invalid payload configuration"),
+            Malformed => write!(formatter, "malformed payload"),
+        }
+    }
+}
+
+impl Error for DepacketizeError {}
+
+/// Error that may be returned when packetizing a frame.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum PacketizeError {
+    /// The frame does not contain any data to packetize.
+    Empty,
+
+    /// The frame data ended before a structure it contains.
+    Truncated,
+
+    /// The size of the NAL unit length prefixes is not between 1 and 4 bytes.
+    InvalidLengthSize,
+}
+
+impl Display for PacketizeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::PacketizeError::*;
+
+        match self {
+            Empty => write!(formatter, "empty frame"),
+            Truncated => write!(formatter, "truncated frame"),
+            InvalidLengthSize => write!(formatter, "invalid NAL unit length size"),
+        }
+    }
+}
+
+impl Error for PacketizeError {}
+
+/// Converts a time relative to the start of a stream into an RTP timestamp with the given clock
+/// rate, e.g. 90 kHz for video.
+pub fn rtp_timestamp(time: Duration, clock_rate: u32) -> u32 {
+    (time.as_nanos() * u128::from(clock_rate) / 1_000_000_000) as u32
+}
+
+/// Numbers the packets of a stream, starting at a random sequence number and timestamp.
+#[derive(Clone, Debug)]
+pub(crate) struct PacketSequencer {
+    /// The largest payload that fits into a packet.
+    max_payload_size: usize,
+
+    payload_type: U7,
+
+    sequence_number: u16,
+
+    ssrc: u32,
+
+    timestamp_offset: u32,
+}
+
+impl PacketSequencer {
+    pub(crate) fn new(payload_type: U7, ssrc: u32, max_packet_size: usize) -> Self {
+        PacketSequencer {
+            max_payload_size: max_packet_size
+                .saturating_sub(RTP_FIXED_HEADER_SIZE)
+                .max(MIN_MAX_PAYLOAD_SIZE),
+            payload_type,
+            sequence_number: rand::random(),
+            ssrc,
+            timestamp_offset: rand::random(),
+        }
+    }
+
+    pub(crate) fn max_payload_size(&self) -> usize {
+        self.max_payload_size
+    }
+
+    /// Returns the next packet of the stream, with the timestamp relative to the start of the
+    /// stream.
+    pub(crate) fn packet(&mut self, timestamp: u32, marker: bool, payload: Vec<u8>) -> RtpPacket {
+        let seq_num = self.sequence_number;
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+
+        RtpPacket {
+            header: RtpFixedHeader {
+                padding: false,
+                marker,
+                payload_type: self.payload_type,
+                seq_num,
+                timestamp: timestamp.wrapping_add(self.timestamp_offset),
+                ssrc: self.ssrc,
+                csrc_list: Vec::new(),
+                extension: None,
+            },
+            payload,
+            padding: Vec::new(),
+        }
+    }
+}
+
+/// Concatenates the payloads of the packets of a frame, for payloads whose packets tell where a
+/// frame starts. Along with every frame, the information of its first packet is kept.
+#[derive(Debug)]
+pub(crate) struct FrameAssembler<TInfo> {
+    /// The frame being assembled.
+    frame: Option<(Frame, TInfo)>,
+
+    /// The complete frames that have not been pulled yet.
+    frames: VecDeque<(Frame, TInfo)>,
+
+    sequence: SequenceTracker,
+}
+
+impl<TInfo> FrameAssembler<TInfo> {
+    pub(crate) fn new() -> Self {
+        FrameAssembler {
+            frame: None,
+            frames: VecDeque::new(),
+            sequence: SequenceTracker::default(),
+        }
+    }
+
+    /// Starts processing a packet, finishing the frame being assembled if the packet has another
+    /// timestamp. If packets were lost, the frame being assembled is marked as corrupted.
+    ///
+    /// Returns `false` if the packet arrived late and has to be dropped.
+    pub(crate) fn start_packet(&mut self, header: &RtpFixedHeader) -> bool {
+        match self.sequence.check(header.seq_num) {
+            SequenceCheck::Late => return false,
+            SequenceCheck::Lost => self.corrupt(),
+            SequenceCheck::InOrder => (),
+        }
+
+        if self
+            .frame
+            .as_ref()
+            .map_or(false, |(frame, _)| frame.timestamp != header.timestamp)
+        {
+            self.finish_frame();
+        }
+
+        true
+    }
+
+    /// Starts a new frame, finishing the frame being assembled.
+    pub(crate) fn start_frame(&mut self, timestamp: u32, info: TInfo, keyframe: bool) {
+        self.finish_frame();
+
+        let frame = Frame {
+            timestamp,
+            data: Vec::new(),
+            keyframe,
+            corrupted: false,
+        };
+        self.frame = Some((frame, info));
+    }
+
+    /// Returns whether a frame is being assembled.
+    pub(crate) fn in_frame(&self) -> bool {
+        self.frame.is_some()
+    }
+
+    /// Appends data to the frame being assembled.
+    pub(crate) fn append(&mut self, data: &[u8]) {
+        if let Some((frame, _)) = self.frame.as_mut() {
+            frame.data.extend_from_slice(data);
+        }
+    }
+
+    /// Marks the frame being assembled as corrupted.
+    pub(crate) fn corrupt(&mut self) {
+        if let Some((frame, _)) = self.frame.as_mut() {
+            frame.corrupted = true;
+        }
+    }
+
+    /// Moves the frame being assembled into the complete ones.
+    pub(crate) fn finish_frame(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.frames.push_back(frame);
+        }
+    }
+
+    /// Returns the next complete frame along with its information, if any.
+    pub(crate) fn pull(&mut self) -> Option<(Frame, TInfo)> {
+        self.frames.pop_front()
+    }
+}
+
+/// Reads a VP8 or VP9 picture ID, which is 7 bits long, or 15 bits long if the first bit is set.
+///
+/// Returns the picture ID and the data following it.
+pub(crate) fn read_picture_id(data: &[u8]) -> Result<(u16, &[u8]), DepacketizeError> {
+    let first = *data.first().ok_or(DepacketizeError::Truncated)?;
+
+    if first & 0b1000_0000 == 0 {
+        return Ok((u16::from(first), &data[1..]));
+    }
+
+    let second = *data.get(1).ok_or(DepacketizeError::Truncated)?;
+    Ok((u16::from(first & 0b0111_1111) << 8 | u16::from(second), &data[2..]))
+}
+
+/// How a packet relates to the packets received before it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SequenceCheck {
+    /// The packet directly follows the previous one.
+    InOrder,
+
+    /// Packets were lost before this one.
+    Lost,
+
+    /// The packet is a duplicate or arrived after a later one.
+    Late,
+}
+
+/// Detects lost and late packets by their sequence numbers.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SequenceTracker {
+    next_sequence_number: Option<u16>,
+
+    /// The sequence number following a packet far behind the expected one, which restarts the
+    /// sequence if received next.
+    restart_sequence_number: Option<u16>,
+}
+
+impl SequenceTracker {
+    /// Checks the sequence number of the next packet. Late packets do not move the expected
+    /// sequence number back, unless two packets in sequence are further behind than reordering
+    /// allows, as after a restart of the sender.
+    pub(crate) fn check(&mut self, sequence_number: u16) -> SequenceCheck {
+        let check = match self.next_sequence_number {
+            Some(next) if next == sequence_number => SequenceCheck::InOrder,
+            Some(next) if sequence_number.wrapping_sub(next) >= 0x8000 => {
+                let jump = is_sequence_jump(next.wrapping_sub(1), sequence_number);
+
+                if !jump || self.restart_sequence_number != Some(sequence_number) {
+                    if jump {
+                        self.restart_sequence_number = Some(sequence_number.wrapping_add(1));
+                    }
+
+                    return SequenceCheck::Late;
+                }
+
+                SequenceCheck::Lost
+            }
+            Some(_) => SequenceCheck::Lost,
+            None => SequenceCheck::InOrder,
+        };
+
+        self.next_sequence_number = Some(sequence_number.wrapping_add(1));
+        self.restart_sequence_number = None;
+        check
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SequenceCheck, SequenceTracker};
+
+    #[test]
+    fn test_sequence_tracker() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.check(1000), SequenceCheck::InOrder);
+        assert_eq!(tracker.check(1001), SequenceCheck::InOrder);
+        assert_eq!(tracker.check(1003), SequenceCheck::Lost);
+        assert_eq!(tracker.check(1002), SequenceCheck::Late);
+        assert_eq!(tracker.check(1003), SequenceCheck::Late);
+
+        // The sender restarted with lower sequence numbers.
+        assert_eq!(tracker.check(10), SequenceCheck::Late);
+        assert_eq!(tracker.check(11), SequenceCheck::Lost);
+        assert_eq!(tracker.check(12), SequenceCheck::InOrder);
+        assert_eq!(tracker.check(1004), SequenceCheck::Lost);
+        assert_eq!(tracker.check(12), SequenceCheck::Late);
+    }
+}
@@ -0,0 +1,270 @@
+//! NAL unit handling shared by the H.264 and H.265 payloads.
+
+use std::collections::VecDeque;
+
+use crate::proto::rtp::payload::{
+    DepacketizeError, Frame, PacketizeError, SequenceCheck, SequenceTracker,
+};
+use crate::proto::rtp::rtp::RtpFixedHeader;
+
+/// The start code preceding every NAL unit of an Annex B byte stream.
+pub const ANNEX_B_START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Groups NAL units into access units, as Annex B byte streams.
+///
+/// The NAL units are grouped by their timestamp, an access unit is complete once a packet with the
+/// marker bit is received, or a packet with another timestamp. If any packet of an access unit was
+/// lost, the frame is emitted as corrupted, and an incomplete fragmented NAL unit is left out.
+#[derive(Debug, Default)]
+pub(crate) struct AccessUnitAssembler {
+    /// The access unit being assembled.
+    access_unit: Option<Frame>,
+
+    /// The NAL unit being reassembled from fragmentation units.
+    fragment: Option<Vec<u8>>,
+
+    /// The complete access units that have not been pulled yet.
+    frames: VecDeque<Frame>,
+
+    sequence: SequenceTracker,
+}
+
+impl AccessUnitAssembler {
+    /// Starts processing a packet, finishing the previous access unit if the packet has another
+    /// timestamp.
+    ///
+    /// Returns `false` if the packet arrived late and has to be dropped.
+    pub(crate) fn start_packet(&mut self, header: &RtpFixedHeader) -> bool {
+        let check = self.sequence.check(header.seq_num);
+
+        if check == SequenceCheck::Late {
+            return false;
+        }
+
+        let lost = check == SequenceCheck::Lost;
+
+        if lost {
+            self.corrupt();
+        }
+
+        if self
+            .access_unit
+            .as_ref()
+            .map_or(false, |access_unit| access_unit.timestamp != header.timestamp)
+        {
+            self.finish_access_unit();
+        }
+
+        let access_unit = self.access_unit.get_or_insert_with(|| Frame {
+            timestamp: header.timestamp,
+            data: Vec::new(),
+            keyframe: false,
+            corrupted: false,
+        });
+
+        // The lost packets may have started this access unit as well.
+        if lost {
+            access_unit.corrupted = true;
+        }
+
+        true
+    }
+
+    /// Finishes processing a packet with the result of depacketizing its payload.
+    pub(crate) fn end_packet(
+        &mut self,
+        header: &RtpFixedHeader,
+        result: Result<(), DepacketizeError>,
+    ) -> Result<(), DepacketizeError> {
+        if result.is_err() {
+            self.corrupt();
+        }
+
+        if header.marker {
+            self.finish_access_unit();
+        }
+
+        result
+    }
+
+    /// Marks the access unit being assembled as corrupted, dropping any incomplete fragmented
+    /// NAL unit.
+    pub(crate) fn corrupt(&mut self) {
+        self.fragment = None;
+
+        if let Some(access_unit) = self.access_unit.as_mut() {
+            access_unit.corrupted = true;
+        }
+    }
+
+    /// Appends a complete NAL unit to the access unit being assembled.
+    pub(crate) fn push_nal_unit(&mut self, nal_unit: &[u8], keyframe: bool) {
+        // A NAL unit interrupting a fragmented one means the rest of it was lost.
+        if self.fragment.is_some() {
+            self.corrupt();
+        }
+
+        let access_unit = self
+            .access_unit
+            .as_mut()
+            .expect("access unit should be started before pushing NAL units");
+        access_unit.keyframe |= keyframe;
+        access_unit.data.extend_from_slice(&ANNEX_B_START_CODE);
+        access_unit.data.extend_from_slice(nal_unit);
+    }
+
+    /// Appends the data of a fragmentation unit to the fragmented NAL unit. The reconstructed NAL
+    /// unit header is given for the first fragment.
+    ///
+    /// Returns the NAL unit once its last fragment is pushed.
+    pub(crate) fn push_fragment(
+        &mut self,
+        nal_unit_header: Option<&[u8]>,
+        data: &[u8],
+        end: bool,
+    ) -> Option<Vec<u8>> {
+        match nal_unit_header {
+            Some(nal_unit_header) => {
+                if self.fragment.is_some() {
+                    self.corrupt();
+                }
+
+                let mut nal_unit = Vec::with_capacity(nal_unit_header.len() + data.len());
+                nal_unit.extend_from_slice(nal_unit_header);
+                nal_unit.extend_from_slice(data);
+                self.fragment = Some(nal_unit);
+            }
+            None => match self.fragment.as_mut() {
+                Some(fragment) => fragment.extend_from_slice(data),
+                None => {
+                    // The start of the NAL unit was lost, so the rest of it is useless.
+                    self.corrupt();
+                    return None;
+                }
+            },
+        }
+
+        if end {
+            self.fragment.take()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the next complete access unit, if any.
+    pub(crate) fn pull(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+
+    /// Moves the access unit being assembled into the complete ones.
+    fn finish_access_unit(&mut self) {
+        if self.fragment.is_some() {
+            self.corrupt();
+        }
+
+        if let Some(access_unit) = self.access_unit.take() {
+            if !access_unit.data.is_empty() || access_unit.corrupted {
+                self.frames.push_back(access_unit);
+            }
+        }
+    }
+}
+
+/// Splits an Annex B byte stream into its NAL units, without their start codes.
+pub fn annex_b_nal_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut nal_units = Vec::new();
+    let mut start = None;
+    let mut index = 0;
+
+    while index + 3 <= data.len() {
+        if data[index] == 0 && data[index + 1] == 0 && data[index + 2] == 1 {
+            if let Some(start) = start {
+                nal_units.push(&data[start..index]);
+            }
+
+            index += 3;
+            start = Some(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    if let Some(start) = start {
+        nal_units.push(&data[start..]);
+    }
+
+    // The zero bytes preceding a start code, e.g. of a 4-byte start code, are not part of the
+    // previous NAL unit.
+    nal_units
+        .into_iter()
+        .map(|nal_unit| {
+            let length = nal_unit.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+            &nal_unit[..length]
+        })
+        .filter(|nal_unit| !nal_unit.is_empty())
+        .collect()
+}
+
+/// Splits length prefixed NAL units, as stored in MP4 files, e.g. AVCC. Every NAL unit is
+/// preceded by its length, which is `length_size` bytes long, as given by the decoder
+/// configuration record. Sizes outside of 1 to 4 bytes are invalid.
+pub fn length_prefixed_nal_units(
+    mut data: &[u8],
+    length_size: usize,
+) -> Result<Vec<&[u8]>, PacketizeError> {
+    if !(1..=4).contains(&length_size) {
+        return Err(PacketizeError::InvalidLengthSize);
+    }
+
+    let mut nal_units = Vec::new();
+
+    while !data.is_empty() {
+        let length = data
+            .get(..length_size)
+            .ok_or(PacketizeError::Truncated)?
+            .iter()
+            .fold(0, |length, &byte| length << 8 | byte as usize);
+        let nal_unit = data
+            .get(length_size..length_size + length)
+            .ok_or(PacketizeError::Truncated)?;
+
+        if !nal_unit.is_empty() {
+            nal_units.push(nal_unit);
+        }
+
+        data = &data[length_size + length..];
+    }
+
+    Ok(nal_units)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::PacketizeError;
+
+    use super::{annex_b_nal_units, length_prefixed_nal_units};
+
+    #[test]
+    fn test_annex_b_nal_units() {
+        let data = [0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 0, 1, 0x65, 0x88];
+        assert_eq!(
+            annex_b_nal_units(&data),
+            vec![&[0x67, 0x42][..], &[0x68, 0xce][..], &[0x65, 0x88][..]]
+        );
+    }
+
+    #[test]
+    fn test_length_prefixed_nal_units() {
+        let data = [0, 0, 0, 2, 0x67, 0x42, 0, 0, 0, 1, 0x68];
+        assert_eq!(
+            length_prefixed_nal_units(&data, 4),
+            Ok(vec![&[0x67, 0x42][..], &[0x68][..]])
+        );
+        assert_eq!(
+            length_prefixed_nal_units(&data[..10], 4),
+            Err(PacketizeError::Truncated)
+        );
+        assert_eq!(length_prefixed_nal_units(&data, 0), Err(PacketizeError::InvalidLengthSize));
+        assert_eq!(length_prefixed_nal_units(&data, 9), Err(PacketizeError::InvalidLengthSize));
+    }
+}
@@ -0,0 +1,367 @@
+//! Packetized elementary streams (ISO/IEC 13818-1), as multiplexed into MPEG-2 program and
+//! transport streams.
+
+use std::collections::VecDeque;
+
+use crate::proto::rtp::payload::nal::annex_b_nal_units;
+use crate::proto::rtp::payload::{h264, h265, DepacketizeError, Frame};
+
+/// The size of the start code prefix and stream ID preceding every PES packet.
+pub(crate) const START_CODE_SIZE: usize = 4;
+
+/// The size of the start code prefix, stream ID and packet length of a PES packet.
+pub(crate) const PES_PACKET_HEADER_SIZE: usize = 6;
+
+/// The size of the optional PES header up to its header data.
+const OPTIONAL_HEADER_SIZE: usize = 3;
+
+const TIMESTAMP_SIZE: usize = 5;
+
+const STREAM_ID_PROGRAM_STREAM_MAP: u8 = 0xbc;
+const STREAM_ID_PADDING: u8 = 0xbe;
+const STREAM_ID_PRIVATE_STREAM_2: u8 = 0xbf;
+const STREAM_ID_ECM: u8 = 0xf0;
+const STREAM_ID_EMM: u8 = 0xf1;
+const STREAM_ID_DSMCC: u8 = 0xf2;
+const STREAM_ID_H222_1_TYPE_E: u8 = 0xf8;
+const STREAM_ID_DIRECTORY: u8 = 0xff;
+
+const PTS_DTS_FLAGS_MASK: u8 = 0b1100_0000;
+const PTS_FLAG: u8 = 0b1000_0000;
+const PTS_DTS_FLAGS: u8 = 0b1100_0000;
+
+/// The type of an elementary stream, as signaled by the program stream map or the program map
+/// table.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StreamType {
+    Mpeg1Video,
+    Mpeg2Video,
+    Mpeg1Audio,
+    Mpeg2Audio,
+
+    /// AAC with ADTS headers.
+    Aac,
+
+    Mpeg4Video,
+
+    /// AAC with LATM headers.
+    AacLatm,
+
+    H264,
+    H265,
+
+    /// G.711 A-law, as assigned by GB/T 28181.
+    G711A,
+
+    /// G.711 μ-law, as assigned by GB/T 28181.
+    G711U,
+
+    /// G.722.1, as assigned by GB/T 28181.
+    G7221,
+
+    /// G.723.1, as assigned by GB/T 28181.
+    G7231,
+
+    /// G.729, as assigned by GB/T 28181.
+    G729,
+
+    Other(u8),
+}
+
+impl From<u8> for StreamType {
+    fn from(stream_type: u8) -> Self {
+        match stream_type {
+            0x01 => StreamType::Mpeg1Video,
+            0x02 => StreamType::Mpeg2Video,
+            0x03 => StreamType::Mpeg1Audio,
+            0x04 => StreamType::Mpeg2Audio,
+            0x0f => StreamType::Aac,
+            0x10 => StreamType::Mpeg4Video,
+            0x11 => StreamType::AacLatm,
+            0x1b => StreamType::H264,
+            0x24 => StreamType::H265,
+            0x90 => StreamType::G711A,
+            0x91 => StreamType::G711U,
+            0x92 => StreamType::G7221,
+            0x93 => StreamType::G7231,
+            0x99 => StreamType::G729,
+            stream_type => StreamType::Other(stream_type),
+        }
+    }
+}
+
+impl StreamType {
+    /// Returns whether the stream carries audio.
+    pub fn is_audio(self) -> bool {
+        matches!(
+            self,
+            StreamType::Mpeg1Audio
+                | StreamType::Mpeg2Audio
+                | StreamType::Aac
+                | StreamType::AacLatm
+                | StreamType::G711A
+                | StreamType::G711U
+                | StreamType::G7221
+                | StreamType::G7231
+                | StreamType::G729
+        )
+    }
+
+    /// Returns whether decoding can start with the access unit. Audio access units always are,
+    /// video ones are recognized for H.264 and H.265 only.
+    pub fn is_keyframe(self, access_unit: &[u8]) -> bool {
+        match self {
+            StreamType::H264 => annex_b_nal_units(access_unit)
+                .iter()
+                .any(|nal_unit| h264::nal_unit_type(nal_unit[0]) == h264::NAL_UNIT_TYPE_IDR),
+            StreamType::H265 => annex_b_nal_units(access_unit)
+                .iter()
+                .any(|nal_unit| h265::is_irap(nal_unit[0])),
+            stream_type => stream_type.is_audio(),
+        }
+    }
+}
+
+/// The information of an access unit demultiplexed from an MPEG-2 stream.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PesInfo {
+    /// The stream ID of its PES packets, e.g. from 0xe0 on for video.
+    pub stream_id: u8,
+
+    /// The type of its elementary stream, if signaled already.
+    pub stream_type: Option<StreamType>,
+
+    /// The presentation timestamp, in units of 90 kHz.
+    pub pts: Option<u64>,
+
+    /// The decoding timestamp, in units of 90 kHz, if it differs from the presentation
+    /// timestamp.
+    pub dts: Option<u64>,
+}
+
+/// Returns whether the PES packets of the stream ID have the optional PES header.
+pub(crate) fn has_optional_header(stream_id: u8) -> bool {
+    !matches!(
+        stream_id,
+        STREAM_ID_PROGRAM_STREAM_MAP
+            | STREAM_ID_PADDING
+            | STREAM_ID_PRIVATE_STREAM_2
+            | STREAM_ID_ECM
+            | STREAM_ID_EMM
+            | STREAM_ID_DSMCC
+            | STREAM_ID_H222_1_TYPE_E
+            | STREAM_ID_DIRECTORY
+    )
+}
+
+/// Parses the header of a PES packet, starting with its start code prefix.
+///
+/// Returns the stream ID, the timestamps, and the data following the header up to the end of
+/// `packet`.
+pub(crate) fn parse_pes_header(packet: &[u8]) -> Result<(PesInfo, &[u8]), DepacketizeError> {
+    if packet.len() < PES_PACKET_HEADER_SIZE {
+        return Err(DepacketizeError::Truncated);
+    }
+
+    let mut info = PesInfo {
+        stream_id: packet[3],
+        stream_type: None,
+        pts: None,
+        dts: None,
+    };
+
+    let data = &packet[PES_PACKET_HEADER_SIZE..];
+
+    if !has_optional_header(info.stream_id) {
+        return Ok((info, data));
+    }
+
+    if data.len() < OPTIONAL_HEADER_SIZE {
+        return Err(DepacketizeError::Truncated);
+    }
+
+    // MPEG-1 packet headers are not supported.
+    if data[0] >> 6 != 0b10 {
+        return Err(DepacketizeError::Unsupported);
+    }
+
+    let flags = data[1] & PTS_DTS_FLAGS_MASK;
+    let header_data_length = usize::from(data[2]);
+    let header_data = data
+        .get(OPTIONAL_HEADER_SIZE..OPTIONAL_HEADER_SIZE + header_data_length)
+        .ok_or(DepacketizeError::Truncated)?;
+
+    if flags & PTS_FLAG != 0 {
+        let pts = header_data
+            .get(..TIMESTAMP_SIZE)
+            .ok_or(DepacketizeError::Truncated)?;
+        info.pts = Some(read_timestamp(pts));
+    }
+
+    if flags == PTS_DTS_FLAGS {
+        let dts = header_data
+            .get(TIMESTAMP_SIZE..2 * TIMESTAMP_SIZE)
+            .ok_or(DepacketizeError::Truncated)?;
+        info.dts = Some(read_timestamp(dts));
+    }
+
+    Ok((info, &data[OPTIONAL_HEADER_SIZE + header_data_length..]))
+}
+
+/// Reads a 33 bit timestamp, which is split into fields separated by marker bits.
+fn read_timestamp(data: &[u8]) -> u64 {
+    u64::from(data[0] >> 1 & 0b111) << 30
+        | u64::from(data[1]) << 22
+        | u64::from(data[2] >> 1) << 15
+        | u64::from(data[3]) << 7
+        | u64::from(data[4] >> 1)
+}
+
+/// Assembles the access units of the elementary streams of an MPEG-2 stream from the payloads
+/// of their PES packets.
+///
+/// An access unit may be split over several PES packets. It ends before a PES packet of the
+/// same stream with another presentation timestamp, or when the stream tells so, e.g. at the
+/// end of an RTP frame.
+#[derive(Debug, Default)]
+pub(crate) struct PesAssembler {
+    /// The access units being assembled, one per stream at most.
+    pending: Vec<(Frame, PesInfo)>,
+
+    /// The complete access units that have not been pulled yet.
+    frames: VecDeque<(Frame, PesInfo)>,
+}
+
+impl PesAssembler {
+    /// Appends the payload of a PES packet to the access unit of its stream.
+    ///
+    /// Access units without a presentation timestamp get the fallback timestamp.
+    pub(crate) fn push(&mut self, info: PesInfo, data: &[u8], fallback_timestamp: u32) {
+        let index = self
+            .pending
+            .iter()
+            .position(|(_, pending)| pending.stream_id == info.stream_id);
+
+        if let Some(index) = index {
+            let pending = &self.pending[index].1;
+
+            if info.pts.is_none() || info.pts == pending.pts {
+                self.pending[index].0.data.extend_from_slice(data);
+                return;
+            }
+
+            let access_unit = self.pending.remove(index);
+            self.finish(access_unit);
+        }
+
+        let frame = Frame {
+            timestamp: info.pts.map_or(fallback_timestamp, |pts| pts as u32),
+            data: data.to_vec(),
+            keyframe: false,
+            corrupted: false,
+        };
+
+        self.pending.push((frame, info));
+    }
+
+    /// Finishes the access units of all streams.
+    pub(crate) fn finish_all(&mut self) {
+        for access_unit in std::mem::take(&mut self.pending) {
+            self.finish(access_unit);
+        }
+    }
+
+    /// Marks the access units being assembled as corrupted.
+    pub(crate) fn corrupt_all(&mut self) {
+        for (frame, _) in &mut self.pending {
+            frame.corrupted = true;
+        }
+    }
+
+    fn finish(&mut self, (mut frame, info): (Frame, PesInfo)) {
+        if frame.data.is_empty() && !frame.corrupted {
+            return;
+        }
+
+        frame.keyframe = info
+            .stream_type
+            .map_or(false, |stream_type| stream_type.is_keyframe(&frame.data));
+        self.frames.push_back((frame, info));
+    }
+
+    /// Returns the next complete access unit along with its information, if any.
+    pub(crate) fn pull(&mut self) -> Option<(Frame, PesInfo)> {
+        self.frames.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::DepacketizeError;
+
+    use super::{parse_pes_header, PesAssembler, PesInfo, StreamType};
+
+    #[test]
+    fn test_parse_pes_header() {
+        let packet = [
+            0x00, 0x00, 0x01, 0xe0, 0x00, 0x0e, 0x80, 0xc0, 0x0a, 0x31, 0x00, 0x05, 0xbf, 0x41,
+            0x11, 0x00, 0x05, 0xbe, 0xe1, 0xaa,
+        ];
+        let (info, data) = parse_pes_header(&packet).unwrap();
+
+        assert_eq!(info.stream_id, 0xe0);
+        assert_eq!(info.pts, Some(90_000 + 0x10));
+        assert_eq!(info.dts, Some(90_000 - 0x20));
+        assert_eq!(data, &[0xaa]);
+
+        let (info, data) = parse_pes_header(&[0x00, 0x00, 0x01, 0xbe, 0x00, 0x01, 0xff]).unwrap();
+        assert_eq!(info.pts, None);
+        assert_eq!(data, &[0xff]);
+
+        assert_eq!(
+            parse_pes_header(&packet[..12]),
+            Err(DepacketizeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_pes_assembler() {
+        let mut assembler = PesAssembler::default();
+        let video = |pts| PesInfo {
+            stream_id: 0xe0,
+            stream_type: Some(StreamType::H264),
+            pts,
+            dts: None,
+        };
+        let audio = PesInfo {
+            stream_id: 0xc0,
+            stream_type: Some(StreamType::G711A),
+            pts: Some(3600),
+            dts: None,
+        };
+
+        assembler.push(video(Some(3000)), &[0, 0, 0, 1, 0x65, 1], 0);
+        assembler.push(audio, &[0xd5], 0);
+        assembler.push(video(None), &[2], 0);
+        assembler.push(video(Some(3000)), &[3], 0);
+        assembler.push(video(Some(6000)), &[0, 0, 0, 1, 0x41, 4], 0);
+        assembler.finish_all();
+
+        let (frame, info) = assembler.pull().unwrap();
+        assert_eq!(info.stream_id, 0xe0);
+        assert_eq!(frame.timestamp, 3000);
+        assert_eq!(frame.data, vec![0, 0, 0, 1, 0x65, 1, 2, 3]);
+        assert!(frame.keyframe);
+
+        let (frame, info) = assembler.pull().unwrap();
+        assert_eq!(info.stream_id, 0xc0);
+        assert_eq!(frame.timestamp, 3600);
+        assert!(frame.keyframe);
+
+        let (frame, _) = assembler.pull().unwrap();
+        assert_eq!(frame.timestamp, 6000);
+        assert!(!frame.keyframe);
+
+        assert_eq!(assembler.pull(), None);
+    }
+}
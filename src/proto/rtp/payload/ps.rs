@@ -0,0 +1,398 @@
+//! MPEG-2 program streams (ISO/IEC 13818-1) over RTP, as sent by GB/T 28181 devices.
+
+use std::collections::HashMap;
+use std::mem;
+
+use fnv::FnvBuildHasher;
+
+use crate::proto::rtp::payload::pes::{
+    parse_pes_header, PesAssembler, PesInfo, StreamType, PES_PACKET_HEADER_SIZE, START_CODE_SIZE,
+};
+use crate::proto::rtp::payload::{
+    DepacketizeError, Depacketizer, Frame, SequenceCheck, SequenceTracker,
+};
+use crate::proto::rtp::rtp::RtpPacket;
+
+/// The clock rate of program stream RTP timestamps.
+pub const CLOCK_RATE: u32 = 90_000;
+
+const START_CODE_PREFIX: [u8; 3] = [0x00, 0x00, 0x01];
+
+const PROGRAM_END_CODE: u8 = 0xb9;
+const PACK_START_CODE: u8 = 0xba;
+const SYSTEM_HEADER_START_CODE: u8 = 0xbb;
+const STREAM_ID_PROGRAM_STREAM_MAP: u8 = 0xbc;
+const STREAM_ID_PRIVATE_STREAM_1: u8 = 0xbd;
+const STREAM_ID_AUDIO_FIRST: u8 = 0xc0;
+const STREAM_ID_VIDEO_LAST: u8 = 0xef;
+
+/// The size of an MPEG-2 pack header without stuffing.
+const PACK_HEADER_SIZE: usize = 14;
+
+/// The size of an MPEG-1 pack header.
+const MPEG1_PACK_HEADER_SIZE: usize = 12;
+
+const PACK_STUFFING_LENGTH_MASK: u8 = 0b0000_0111;
+
+/// The size of the CRC at the end of the program stream map.
+const CRC_SIZE: usize = 4;
+
+/// Returns the size of the unit starting with a start code, or `None` if more data is needed to
+/// tell.
+fn unit_size(data: &[u8]) -> Option<usize> {
+    match data[3] {
+        PROGRAM_END_CODE => Some(START_CODE_SIZE),
+        PACK_START_CODE => {
+            let marker = *data.get(START_CODE_SIZE)?;
+
+            if marker >> 6 == 0b01 {
+                let stuffing = *data.get(PACK_HEADER_SIZE - 1)? & PACK_STUFFING_LENGTH_MASK;
+                Some(PACK_HEADER_SIZE + usize::from(stuffing))
+            } else if marker >> 4 == 0b0010 {
+                Some(MPEG1_PACK_HEADER_SIZE)
+            } else {
+                // Not a pack header after all, skip the start code.
+                Some(START_CODE_SIZE)
+            }
+        }
+        SYSTEM_HEADER_START_CODE..=0xff => {
+            let length = data.get(START_CODE_SIZE..PES_PACKET_HEADER_SIZE)?;
+            Some(PES_PACKET_HEADER_SIZE + usize::from(u16::from_be_bytes([length[0], length[1]])))
+        }
+
+        // Start codes of the elementary streams, which may follow the start code prefix within
+        // the payload of a PES packet.
+        _ => Some(START_CODE_SIZE),
+    }
+}
+
+/// Demultiplexes the elementary streams of a program stream from RTP packets.
+///
+/// The program stream is a byte stream split over the RTP packets regardless of its structure,
+/// in which the pack headers, system headers, program stream maps and PES packets are parsed
+/// across packet boundaries. The stream types are taken from the program stream map.
+///
+/// The access units of every elementary stream are emitted along with their stream and
+/// timestamps, and end with the RTP frame they were sent in, i.e. the packet with the marker
+/// bit. The frames get their presentation timestamp, or the RTP timestamp if they have none, so
+/// that H.264 and H.265 access units are Annex B byte streams just like those of the RTP
+/// depacketizers.
+///
+/// If packets were lost, the access units being assembled are emitted as corrupted, and the
+/// stream is skipped up to the next pack header.
+#[derive(Debug)]
+pub struct PsDepacketizer {
+    assembler: PesAssembler,
+
+    /// The data of the program stream that has not been parsed yet.
+    buffer: Vec<u8>,
+
+    sequence: SequenceTracker,
+
+    /// The stream types of the stream IDs, from the last program stream map.
+    stream_types: HashMap<u8, StreamType, FnvBuildHasher>,
+
+    /// Is the position in the stream known, i.e. was a pack header found since the start or the
+    /// last loss.
+    synchronized: bool,
+
+    /// The RTP timestamp of the last packet.
+    timestamp: u32,
+}
+
+impl PsDepacketizer {
+    /// Constructs a new depacketizer.
+    pub fn new() -> Self {
+        PsDepacketizer {
+            assembler: PesAssembler::default(),
+            buffer: Vec::new(),
+            sequence: SequenceTracker::default(),
+            stream_types: HashMap::default(),
+            synchronized: false,
+            timestamp: 0,
+        }
+    }
+
+    /// Returns the next complete access unit along with its stream and timestamps, if any.
+    pub fn pull_with_info(&mut self) -> Option<(Frame, PesInfo)> {
+        self.assembler.pull()
+    }
+
+    /// Parses the complete units of the buffered stream, leaving an incomplete one in the buffer.
+    fn parse(&mut self) -> Result<(), DepacketizeError> {
+        // Taken out of the depacketizer while the units are parsed into it.
+        let mut buffer = mem::take(&mut self.buffer);
+        let mut position = 0;
+        let mut result = Ok(());
+
+        loop {
+            let data = &buffer[position..];
+
+            let start = match data
+                .windows(START_CODE_PREFIX.len())
+                .position(|prefix| prefix == START_CODE_PREFIX)
+            {
+                Some(start) => start,
+                None => {
+                    // Keep the bytes which may be the start of a start code prefix.
+                    position += data.len().saturating_sub(START_CODE_PREFIX.len() - 1);
+                    break;
+                }
+            };
+
+            position += start;
+            let data = &buffer[position..];
+
+            if data.len() < START_CODE_SIZE {
+                break;
+            }
+
+            let size = match unit_size(data) {
+                Some(size) => size,
+                None => break,
+            };
+
+            if data[3] == PACK_START_CODE && size != START_CODE_SIZE {
+                self.synchronized = true;
+            }
+
+            if !self.synchronized {
+                position += START_CODE_SIZE;
+                continue;
+            }
+
+            let unit = match data.get(..size) {
+                Some(unit) => unit,
+                None => break,
+            };
+
+            if let Err(error) = self.parse_unit(unit) {
+                result = result.and(Err(error));
+            }
+
+            position += size;
+        }
+
+        buffer.drain(..position);
+        self.buffer = buffer;
+        result
+    }
+
+    fn parse_unit(&mut self, unit: &[u8]) -> Result<(), DepacketizeError> {
+        match unit[3] {
+            STREAM_ID_PROGRAM_STREAM_MAP => self.parse_program_stream_map(unit),
+            STREAM_ID_PRIVATE_STREAM_1 | STREAM_ID_AUDIO_FIRST..=STREAM_ID_VIDEO_LAST => {
+                let (mut info, data) = parse_pes_header(unit)?;
+                info.stream_type = self.stream_types.get(&info.stream_id).copied();
+                self.assembler.push(info, data, self.timestamp);
+                Ok(())
+            }
+
+            // Pack headers, system headers, padding and the other streams.
+            _ => Ok(()),
+        }
+    }
+
+    fn parse_program_stream_map(&mut self, unit: &[u8]) -> Result<(), DepacketizeError> {
+        // Up to the program stream info length.
+        let info_length_start = PES_PACKET_HEADER_SIZE + 2;
+        let data = unit
+            .get(..unit.len().saturating_sub(CRC_SIZE))
+            .ok_or(DepacketizeError::Truncated)?;
+
+        let info_length = read_length(data, info_length_start)?;
+        let map_length_start = info_length_start + 2 + info_length;
+        let map_length = read_length(data, map_length_start)?;
+        let mut map = data
+            .get(map_length_start + 2..map_length_start + 2 + map_length)
+            .ok_or(DepacketizeError::Truncated)?;
+
+        self.stream_types.clear();
+
+        while !map.is_empty() {
+            let stream_type = *map.first().ok_or(DepacketizeError::Truncated)?;
+            let stream_id = *map.get(1).ok_or(DepacketizeError::Truncated)?;
+            let info_length = read_length(map, 2)?;
+
+            self.stream_types
+                .insert(stream_id, StreamType::from(stream_type));
+            map = map
+                .get(4 + info_length..)
+                .ok_or(DepacketizeError::Truncated)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a 16 bit length at the given position.
+fn read_length(data: &[u8], position: usize) -> Result<usize, DepacketizeError> {
+    let length = data
+        .get(position..position + 2)
+        .ok_or(DepacketizeError::Truncated)?;
+    Ok(usize::from(u16::from_be_bytes([length[0], length[1]])))
+}
+
+impl Default for PsDepacketizer {
+    fn default() -> Self {
+        PsDepacketizer::new()
+    }
+}
+
+impl Depacketizer for PsDepacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        match self.sequence.check(packet.header.seq_num) {
+            SequenceCheck::Late => return Ok(()),
+            SequenceCheck::Lost => {
+                self.assembler.corrupt_all();
+                self.buffer.clear();
+                self.synchronized = false;
+            }
+            SequenceCheck::InOrder => (),
+        }
+
+        self.timestamp = packet.header.timestamp;
+        self.buffer.extend_from_slice(&packet.payload);
+
+        let result = self.parse();
+
+        if packet.header.marker {
+            self.assembler.finish_all();
+        }
+
+        result
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.pull_with_info().map(|(frame, _)| frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::pes::StreamType;
+    use crate::proto::rtp::payload::Depacketizer;
+    use crate::proto::rtp::rtp::test_support::packet;
+
+    use super::PsDepacketizer;
+
+    fn pack_header() -> Vec<u8> {
+        vec![
+            0x00, 0x00, 0x01, 0xba, 0x44, 0x00, 0x04, 0x00, 0x04, 0x01, 0x01, 0x89, 0xc3, 0xf8,
+        ]
+    }
+
+    fn system_header() -> Vec<u8> {
+        vec![
+            0x00, 0x00, 0x01, 0xbb, 0x00, 0x0c, 0x80, 0xcc, 0xf5, 0x04, 0xe1, 0x7f, 0xe0, 0xe0,
+            0xe8, 0xc0, 0xc0, 0x20,
+        ]
+    }
+
+    fn program_stream_map() -> Vec<u8> {
+        vec![
+            0x00, 0x00, 0x01, 0xbc, 0x00, 0x12, 0xe0, 0xff, 0x00, 0x00, 0x00, 0x08, 0x1b, 0xe0,
+            0x00, 0x00, 0x90, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]
+    }
+
+    fn pes(stream_id: u8, pts: u64, data: &[u8]) -> Vec<u8> {
+        let mut pes = vec![0x00, 0x00, 0x01, stream_id];
+        pes.extend_from_slice(&(8 + data.len() as u16).to_be_bytes());
+        pes.extend_from_slice(&[0x80, 0x80, 0x05]);
+        pes.extend_from_slice(&[
+            0x21 | ((pts >> 29) & 0x0e) as u8,
+            (pts >> 22) as u8,
+            0x01 | ((pts >> 14) & 0xfe) as u8,
+            (pts >> 7) as u8,
+            0x01 | ((pts << 1) & 0xfe) as u8,
+        ]);
+        pes.extend_from_slice(data);
+        pes
+    }
+
+    #[test]
+    fn test_demux() {
+        let mut stream = pack_header();
+        stream.extend(system_header());
+        stream.extend(program_stream_map());
+        stream.extend(pes(0xe0, 3600, &[0, 0, 0, 1, 0x67, 1, 0, 0, 0, 1, 0x65, 2]));
+        stream.extend(pes(0xe0, 3600, &[3, 4]));
+        stream.extend(pes(0xc0, 3600, &[0xd5, 0xd5]));
+
+        let mut depacketizer = PsDepacketizer::new();
+
+        // The stream is split regardless of its structure.
+        depacketizer
+            .push(&packet(1, 3600, false, &stream[..7]))
+            .unwrap();
+        depacketizer
+            .push(&packet(2, 3600, false, &stream[7..50]))
+            .unwrap();
+        depacketizer
+            .push(&packet(3, 3600, true, &stream[50..]))
+            .unwrap();
+
+        let (frame, info) = depacketizer.pull_with_info().unwrap();
+        assert_eq!(info.stream_id, 0xe0);
+        assert_eq!(info.stream_type, Some(StreamType::H264));
+        assert_eq!(info.pts, Some(3600));
+        assert_eq!(frame.timestamp, 3600);
+        assert_eq!(
+            frame.data,
+            vec![0, 0, 0, 1, 0x67, 1, 0, 0, 0, 1, 0x65, 2, 3, 4]
+        );
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+
+        let (frame, info) = depacketizer.pull_with_info().unwrap();
+        assert_eq!(info.stream_type, Some(StreamType::G711A));
+        assert_eq!(frame.data, vec![0xd5, 0xd5]);
+
+        assert_eq!(depacketizer.pull(), None);
+
+        let mut stream = pack_header();
+        stream.extend(pes(0xe0, 7200, &[0, 0, 0, 1, 0x41, 5]));
+        depacketizer.push(&packet(4, 7200, true, &stream)).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 7200);
+        assert!(!frame.keyframe);
+    }
+
+    #[test]
+    fn test_demux_lost_packets() {
+        let mut stream = pack_header();
+        stream.extend(program_stream_map());
+        stream.extend(pes(0xe0, 3600, &[0, 0, 0, 1, 0x65, 1]));
+        stream.extend(pes(0xe0, 3600, &[2; 40]));
+        let split = stream.len() - 20;
+
+        let mut depacketizer = PsDepacketizer::new();
+
+        // The stream is only parsed from the first pack header on.
+        depacketizer
+            .push(&packet(1, 0, true, &stream[4..]))
+            .unwrap();
+        assert_eq!(depacketizer.pull(), None);
+
+        depacketizer
+            .push(&packet(2, 3600, false, &stream[..split]))
+            .unwrap();
+        depacketizer
+            .push(&packet(4, 3600, true, &stream[split + 10..]))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, vec![0, 0, 0, 1, 0x65, 1]);
+        assert!(frame.corrupted);
+
+        let mut stream = pack_header();
+        stream.extend(pes(0xe0, 7200, &[0, 0, 0, 1, 0x41, 3]));
+        depacketizer.push(&packet(5, 7200, true, &stream)).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, vec![0, 0, 0, 1, 0x41, 3]);
+        assert!(!frame.corrupted);
+    }
+}
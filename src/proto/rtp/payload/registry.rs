@@ -0,0 +1,468 @@
+//! Mapping of RTP payload types to payload formats.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use fnv::FnvBuildHasher;
+
+use crate::proto::rtp::payload::audio::AudioDepacketizer;
+use crate::proto::rtp::payload::h264::H264Depacketizer;
+use crate::proto::rtp::payload::h265::H265Depacketizer;
+use crate::proto::rtp::payload::jpeg::JpegDepacketizer;
+use crate::proto::rtp::payload::ps::PsDepacketizer;
+use crate::proto::rtp::payload::ts::TsDepacketizer;
+use crate::proto::rtp::payload::vp8::Vp8Depacketizer;
+use crate::proto::rtp::payload::vp9::Vp9Depacketizer;
+use crate::proto::rtp::payload::Depacketizer;
+use crate::proto::sdp::attribute_type::{SdpAttribute, SdpAttributeRtpmap, SdpAttributeType};
+use crate::proto::sdp::media_type::SdpMedia;
+use crate::proto::types::U7;
+
+/// The bit rate of a G.726 stream, which determines the size of its code words.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum G726Rate {
+    /// 2 bits per sample.
+    Kbps16,
+
+    /// 3 bits per sample.
+    Kbps24,
+
+    /// 4 bits per sample.
+    Kbps32,
+
+    /// 5 bits per sample.
+    Kbps40,
+}
+
+impl G726Rate {
+    /// Returns the size of a code word in bits.
+    pub fn bits_per_sample(self) -> usize {
+        match self {
+            G726Rate::Kbps16 => 2,
+            G726Rate::Kbps24 => 3,
+            G726Rate::Kbps32 => 4,
+            G726Rate::Kbps40 => 5,
+        }
+    }
+
+    fn kbps(self) -> u32 {
+        match self {
+            G726Rate::Kbps16 => 16,
+            G726Rate::Kbps24 => 24,
+            G726Rate::Kbps32 => 32,
+            G726Rate::Kbps40 => 40,
+        }
+    }
+}
+
+/// How the G.726 code words are packed into octets.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum G726Packing {
+    /// The first code word in the least significant bits, as in RFC 3551.
+    LittleEndian,
+
+    /// The first code word in the most significant bits, as in ITU-T I.366.2 (the `AAL2-G726`
+    /// encoding names).
+    BigEndian,
+}
+
+/// The encoding of a payload format, as named by its RTP payload format specification.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Encoding {
+    Pcmu,
+    Pcma,
+    G722,
+    G723,
+    G726 {
+        rate: G726Rate,
+        packing: G726Packing,
+    },
+    G728,
+    G729,
+    Gsm,
+    Dvi4,
+    Lpc,
+    Qcelp,
+    Cn,
+    L16,
+    Mpa,
+    Mpeg4Generic,
+    Mp4aLatm,
+    Opus,
+    TelephoneEvent,
+    CelB,
+    Jpeg,
+    Nv,
+    H261,
+    H263,
+    H264,
+    H265,
+    Mpv,
+    Mp2t,
+    /// MPEG-2 program streams, as used by GB/T 28181.
+    Ps,
+    Vp8,
+    Vp9,
+
+    /// An encoding without built-in support, with its name as given.
+    Other(String),
+}
+
+impl Encoding {
+    /// Returns the encoding with the given name, which is compared case-insensitively.
+    pub fn from_name(name: &str) -> Encoding {
+        let upper = name.to_ascii_uppercase();
+
+        match upper.as_str() {
+            "PCMU" => Encoding::Pcmu,
+            "PCMA" => Encoding::Pcma,
+            "G722" => Encoding::G722,
+            "G723" => Encoding::G723,
+            "G728" => Encoding::G728,
+            "G729" => Encoding::G729,
+            "GSM" => Encoding::Gsm,
+            "DVI4" => Encoding::Dvi4,
+            "LPC" => Encoding::Lpc,
+            "QCELP" => Encoding::Qcelp,
+            "CN" => Encoding::Cn,
+            "L16" => Encoding::L16,
+            "MPA" => Encoding::Mpa,
+            "MPEG4-GENERIC" => Encoding::Mpeg4Generic,
+            "MP4A-LATM" => Encoding::Mp4aLatm,
+            "OPUS" => Encoding::Opus,
+            "TELEPHONE-EVENT" => Encoding::TelephoneEvent,
+            "CELB" => Encoding::CelB,
+            "JPEG" => Encoding::Jpeg,
+            "NV" => Encoding::Nv,
+            "H261" => Encoding::H261,
+            "H263" => Encoding::H263,
+            "H264" => Encoding::H264,
+            "H265" => Encoding::H265,
+            "MPV" => Encoding::Mpv,
+            "MP2T" => Encoding::Mp2t,
+            "PS" | "MP2P" => Encoding::Ps,
+            "VP8" => Encoding::Vp8,
+            "VP9" => Encoding::Vp9,
+            _ => g726_encoding(&upper).unwrap_or_else(|| Encoding::Other(name.to_string())),
+        }
+    }
+}
+
+/// Parses the `G726-<kbps>` and `AAL2-G726-<kbps>` encoding names.
+fn g726_encoding(upper_name: &str) -> Option<Encoding> {
+    let (packing, name) = match upper_name.strip_prefix("AAL2-") {
+        Some(name) => (G726Packing::BigEndian, name),
+        None => (G726Packing::LittleEndian, upper_name),
+    };
+
+    let rate = match name.strip_prefix("G726-")? {
+        "16" => G726Rate::Kbps16,
+        "24" => G726Rate::Kbps24,
+        "32" => G726Rate::Kbps32,
+        "40" => G726Rate::Kbps40,
+        _ => return None,
+    };
+
+    Some(Encoding::G726 { rate, packing })
+}
+
+impl Display for Encoding {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::Encoding::*;
+
+        let name = match self {
+            Pcmu => "PCMU",
+            Pcma => "PCMA",
+            G722 => "G722",
+            G723 => "G723",
+            G726 { rate, packing } => {
+                let prefix = match packing {
+                    G726Packing::LittleEndian => "",
+                    G726Packing::BigEndian => "AAL2-",
+                };
+
+                return write!(formatter, "{}G726-{}", prefix, rate.kbps());
+            }
+            G728 => "G728",
+            G729 => "G729",
+            Gsm => "GSM",
+            Dvi4 => "DVI4",
+            Lpc => "LPC",
+            Qcelp => "QCELP",
+            Cn => "CN",
+            L16 => "L16",
+            Mpa => "MPA",
+            Mpeg4Generic => "mpeg4-generic",
+            Mp4aLatm => "MP4A-LATM",
+            Opus => "opus",
+            TelephoneEvent => "telephone-event",
+            CelB => "CelB",
+            Jpeg => "JPEG",
+            Nv => "nv",
+            H261 => "H261",
+            H263 => "H263",
+            H264 => "H264",
+            H265 => "H265",
+            Mpv => "MPV",
+            Mp2t => "MP2T",
+            Ps => "PS",
+            Vp8 => "VP8",
+            Vp9 => "VP9",
+            Other(name) => name,
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+/// The format of the payload of a payload type.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PayloadFormat {
+    pub encoding: Encoding,
+
+    /// The clock rate of the RTP timestamps in Hz.
+    pub clock_rate: u32,
+
+    /// The number of audio channels, 1 for video.
+    pub channels: u32,
+}
+
+impl PayloadFormat {
+    pub fn new(encoding: Encoding, clock_rate: u32, channels: u32) -> Self {
+        PayloadFormat {
+            encoding,
+            clock_rate,
+            channels,
+        }
+    }
+
+    /// Returns the sampling rate of the audio in Hz, which is the clock rate except for G.722,
+    /// whose clock rate stays at 8 kHz for historical reasons although it is sampled at 16 kHz.
+    pub fn sample_rate(&self) -> u32 {
+        match self.encoding {
+            Encoding::G722 => 16_000,
+            _ => self.clock_rate,
+        }
+    }
+
+    /// Returns a depacketizer of the payload format, if it is supported without format
+    /// parameters. AAC needs those, see `AacDepacketizer`.
+    pub fn depacketizer(&self) -> Option<Box<dyn Depacketizer + Send>> {
+        match self.encoding {
+            Encoding::Pcmu
+            | Encoding::Pcma
+            | Encoding::G722
+            | Encoding::G726 { .. }
+            | Encoding::L16 => Some(Box::new(AudioDepacketizer::new())),
+            Encoding::Jpeg => Some(Box::new(JpegDepacketizer::new())),
+            Encoding::H264 => Some(Box::new(H264Depacketizer::new())),
+            Encoding::H265 => Some(Box::new(H265Depacketizer::new())),
+            Encoding::Mp2t => Some(Box::new(TsDepacketizer::new())),
+            Encoding::Ps => Some(Box::new(PsDepacketizer::new())),
+            Encoding::Vp8 => Some(Box::new(Vp8Depacketizer::new())),
+            Encoding::Vp9 => Some(Box::new(Vp9Depacketizer::new())),
+            _ => None,
+        }
+    }
+}
+
+impl From<&SdpAttributeRtpmap> for PayloadFormat {
+    fn from(rtpmap: &SdpAttributeRtpmap) -> Self {
+        PayloadFormat {
+            encoding: Encoding::from_name(&rtpmap.codec_name),
+            clock_rate: rtpmap.frequency,
+            channels: rtpmap.channels.unwrap_or(1),
+        }
+    }
+}
+
+/// Returns the format of a static payload type of RFC 3551.
+///
+/// Payload type 2 is mapped to G.726-32, which RFC 1890 assigned to it as G.721, since many
+/// devices still use it.
+pub fn static_payload_format(payload_type: U7) -> Option<PayloadFormat> {
+    let (encoding, clock_rate, channels) = match payload_type {
+        0 => (Encoding::Pcmu, 8_000, 1),
+        2 => (
+            Encoding::G726 {
+                rate: G726Rate::Kbps32,
+                packing: G726Packing::LittleEndian,
+            },
+            8_000,
+            1,
+        ),
+        3 => (Encoding::Gsm, 8_000, 1),
+        4 => (Encoding::G723, 8_000, 1),
+        5 => (Encoding::Dvi4, 8_000, 1),
+        6 => (Encoding::Dvi4, 16_000, 1),
+        7 => (Encoding::Lpc, 8_000, 1),
+        8 => (Encoding::Pcma, 8_000, 1),
+        9 => (Encoding::G722, 8_000, 1),
+        10 => (Encoding::L16, 44_100, 2),
+        11 => (Encoding::L16, 44_100, 1),
+        12 => (Encoding::Qcelp, 8_000, 1),
+        13 => (Encoding::Cn, 8_000, 1),
+        14 => (Encoding::Mpa, 90_000, 1),
+        15 => (Encoding::G728, 8_000, 1),
+        16 => (Encoding::Dvi4, 11_025, 1),
+        17 => (Encoding::Dvi4, 22_050, 1),
+        18 => (Encoding::G729, 8_000, 1),
+        25 => (Encoding::CelB, 90_000, 1),
+        26 => (Encoding::Jpeg, 90_000, 1),
+        28 => (Encoding::Nv, 90_000, 1),
+        31 => (Encoding::H261, 90_000, 1),
+        32 => (Encoding::Mpv, 90_000, 1),
+        33 => (Encoding::Mp2t, 90_000, 1),
+        34 => (Encoding::H263, 90_000, 1),
+        _ => return None,
+    };
+
+    Some(PayloadFormat::new(encoding, clock_rate, channels))
+}
+
+/// Maps the payload types of a stream to their formats.
+///
+/// A new registry knows the static payload types. Dynamic payload types are added from the
+/// `rtpmap` attributes of the session description, which may also redefine static ones.
+#[derive(Clone, Debug)]
+pub struct PayloadRegistry {
+    formats: HashMap<U7, PayloadFormat, FnvBuildHasher>,
+}
+
+impl PayloadRegistry {
+    /// Constructs a registry of the static payload types.
+    pub fn new() -> Self {
+        let formats = (0..=127)
+            .filter_map(|payload_type| {
+                static_payload_format(payload_type).map(|format| (payload_type, format))
+            })
+            .collect();
+
+        PayloadRegistry { formats }
+    }
+
+    /// Constructs a registry of the static payload types and the payload types mapped by the
+    /// `rtpmap` attributes of a media description.
+    pub fn from_media(media: &SdpMedia) -> Self {
+        let mut registry = PayloadRegistry::new();
+
+        for attribute in media.get_attributes_of_type(SdpAttributeType::Rtpmap) {
+            if let SdpAttribute::Rtpmap(rtpmap) = attribute {
+                registry.insert_rtpmap(rtpmap);
+            }
+        }
+
+        registry
+    }
+
+    /// Returns the format of a payload type, if known.
+    pub fn get(&self, payload_type: U7) -> Option<&PayloadFormat> {
+        self.formats.get(&payload_type)
+    }
+
+    /// Sets the format of a payload type, returning the previous one.
+    pub fn insert(&mut self, payload_type: U7, format: PayloadFormat) -> Option<PayloadFormat> {
+        self.formats.insert(payload_type, format)
+    }
+
+    /// Sets the format of the payload type of an `rtpmap` attribute. Attributes with payload types
+    /// out of the RTP range are ignored.
+    pub fn insert_rtpmap(&mut self, rtpmap: &SdpAttributeRtpmap) {
+        if rtpmap.payload_type <= 127 {
+            self.insert(rtpmap.payload_type, PayloadFormat::from(rtpmap));
+        }
+    }
+}
+
+impl Default for PayloadRegistry {
+    fn default() -> Self {
+        PayloadRegistry::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::sdp::parse_sdp;
+
+    use super::{Encoding, G726Packing, G726Rate, PayloadFormat, PayloadRegistry};
+
+    #[test]
+    fn test_encoding_names() {
+        let names = [
+            ("PCMU", Encoding::Pcmu),
+            ("pcma", Encoding::Pcma),
+            ("H264", Encoding::H264),
+            ("mpeg4-generic", Encoding::Mpeg4Generic),
+            ("PS", Encoding::Ps),
+            (
+                "G726-24",
+                Encoding::G726 {
+                    rate: G726Rate::Kbps24,
+                    packing: G726Packing::LittleEndian,
+                },
+            ),
+            (
+                "AAL2-G726-40",
+                Encoding::G726 {
+                    rate: G726Rate::Kbps40,
+                    packing: G726Packing::BigEndian,
+                },
+            ),
+        ];
+
+        for (name, encoding) in names.iter() {
+            assert_eq!(&Encoding::from_name(name), encoding);
+        }
+
+        assert_eq!(Encoding::Pcma.to_string(), "PCMA");
+        assert_eq!(
+            Encoding::from_name("AAL2-G726-40").to_string(),
+            "AAL2-G726-40"
+        );
+        assert_eq!(
+            Encoding::from_name("G726-48"),
+            Encoding::Other("G726-48".to_string())
+        );
+    }
+
+    #[test]
+    fn test_registry() {
+        let sdp = parse_sdp(
+            "v=0\r\n\
+             o=- 0 0 IN IP4 127.0.0.1\r\n\
+             s=-\r\n\
+             c=IN IP4 127.0.0.1\r\n\
+             t=0 0\r\n\
+             m=audio 0 RTP/AVP 0 8 9 97\r\n\
+             a=rtpmap:8 PCMA/16000\r\n\
+             a=rtpmap:97 G726-32/8000\r\n",
+            false,
+        )
+        .unwrap();
+        let registry = PayloadRegistry::from_media(&sdp.media[0]);
+
+        assert_eq!(
+            registry.get(0),
+            Some(&PayloadFormat::new(Encoding::Pcmu, 8_000, 1))
+        );
+        assert_eq!(
+            registry.get(8),
+            Some(&PayloadFormat::new(Encoding::Pcma, 16_000, 1))
+        );
+        assert_eq!(registry.get(9).unwrap().sample_rate(), 16_000);
+        assert_eq!(
+            registry.get(97).map(|format| &format.encoding),
+            Some(&Encoding::G726 {
+                rate: G726Rate::Kbps32,
+                packing: G726Packing::LittleEndian,
+            })
+        );
+        assert_eq!(registry.get(96), None);
+        assert_eq!(registry.get(33).unwrap().clock_rate, 90_000);
+
+        assert!(registry.get(33).unwrap().depacketizer().is_some());
+        assert!(registry.get(97).unwrap().depacketizer().is_some());
+        assert!(registry.get(4).unwrap().depacketizer().is_none());
+    }
+}
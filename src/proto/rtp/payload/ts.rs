@@ -0,0 +1,755 @@
+//! MPEG-2 transport streams (ISO/IEC 13818-1) over RTP, as specified by RFC 2250.
+
+use std::collections::{HashMap, VecDeque};
+
+use fnv::FnvBuildHasher;
+
+use crate::proto::rtp::payload::pes::{
+    parse_pes_header, PesInfo, StreamType, PES_PACKET_HEADER_SIZE,
+};
+use crate::proto::rtp::payload::{
+    DepacketizeError, Depacketizer, Frame, SequenceCheck, SequenceTracker,
+};
+use crate::proto::rtp::rtp::RtpPacket;
+
+/// The clock rate of transport stream RTP timestamps.
+pub const CLOCK_RATE: u32 = 90_000;
+
+/// The static payload type of transport streams.
+pub const PAYLOAD_TYPE: u8 = 33;
+
+/// The size of every transport stream packet.
+pub const PACKET_SIZE: usize = 188;
+
+const HEADER_SIZE: usize = 4;
+const SYNC_BYTE: u8 = 0x47;
+
+const TRANSPORT_ERROR_INDICATOR: u8 = 0b1000_0000;
+const PAYLOAD_UNIT_START_INDICATOR: u8 = 0b0100_0000;
+const ADAPTATION_FIELD_PRESENT: u8 = 0b10;
+const PAYLOAD_PRESENT: u8 = 0b01;
+const CONTINUITY_COUNTER_MASK: u8 = 0x0f;
+
+const DISCONTINUITY_INDICATOR: u8 = 0b1000_0000;
+const RANDOM_ACCESS_INDICATOR: u8 = 0b0100_0000;
+const PCR_FLAG: u8 = 0b0001_0000;
+const PCR_SIZE: usize = 6;
+
+const PID_PROGRAM_ASSOCIATION_TABLE: u16 = 0x0000;
+const PID_MASK: u16 = 0x1fff;
+
+const TABLE_ID_PROGRAM_ASSOCIATION: u8 = 0x00;
+const TABLE_ID_PROGRAM_MAP: u8 = 0x02;
+
+/// The size of a section header up to the end of the section length.
+const SECTION_HEADER_SIZE: usize = 3;
+
+/// The size of a long section header up to its table data.
+const LONG_SECTION_HEADER_SIZE: usize = 8;
+
+const SECTION_SYNTAX_INDICATOR: u8 = 0b1000_0000;
+const CURRENT_NEXT_INDICATOR: u8 = 0b0000_0001;
+const SECTION_LENGTH_MASK: u16 = 0x0fff;
+const STUFFING_BYTE: u8 = 0xff;
+const CRC_SIZE: usize = 4;
+
+/// The information of an access unit demultiplexed from a transport stream.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TsInfo {
+    /// The PID of the transport stream packets of the elementary stream.
+    pub pid: u16,
+
+    /// The stream and timestamps of the access unit.
+    pub pes: PesInfo,
+
+    /// The last program clock reference of the program received before the access unit, in
+    /// units of 27 MHz.
+    pub pcr: Option<u64>,
+}
+
+/// What the packets of a PID carry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PidKind {
+    ProgramAssociationTable,
+    ProgramMapTable,
+    ElementaryStream {
+        stream_type: StreamType,
+        pcr_pid: u16,
+    },
+}
+
+/// A section or PES packet being assembled from the payloads of transport stream packets.
+#[derive(Debug, Default)]
+struct Unit {
+    data: Vec<u8>,
+
+    /// The RTP timestamp of the packet the unit started in.
+    timestamp: u32,
+
+    pcr: Option<u64>,
+
+    /// Was the random access indicator set in the packet the unit started in.
+    random_access: bool,
+
+    corrupted: bool,
+}
+
+#[derive(Debug)]
+struct PidState {
+    kind: PidKind,
+    continuity_counter: Option<u8>,
+
+    /// The unit being assembled, if its start was received.
+    unit: Option<Unit>,
+}
+
+impl PidState {
+    fn new(kind: PidKind) -> Self {
+        PidState {
+            kind,
+            continuity_counter: None,
+            unit: None,
+        }
+    }
+
+    /// Handles lost packets. Incomplete sections are dropped, PES packets are marked as
+    /// corrupted.
+    fn lose(&mut self) {
+        match self.kind {
+            PidKind::ElementaryStream { .. } => {
+                if let Some(unit) = &mut self.unit {
+                    unit.corrupted = true;
+                }
+            }
+            _ => self.unit = None,
+        }
+    }
+
+    /// Appends the payload of a packet to the sections, returning the complete ones.
+    fn push_sections(
+        &mut self,
+        unit_start: bool,
+        mut payload: &[u8],
+    ) -> Result<Vec<Vec<u8>>, DepacketizeError> {
+        let mut sections = Vec::new();
+
+        if unit_start {
+            // The pointer field tells where the new section starts, after the end of the
+            // previous one.
+            let pointer = usize::from(*payload.first().ok_or(DepacketizeError::Truncated)?);
+            let end = payload
+                .get(1..1 + pointer)
+                .ok_or(DepacketizeError::Truncated)?;
+
+            if let Some(unit) = &mut self.unit {
+                unit.data.extend_from_slice(end);
+            }
+
+            sections.extend(self.complete_section());
+            self.unit = Some(Unit::default());
+            payload = &payload[1 + pointer..];
+        }
+
+        if let Some(unit) = &mut self.unit {
+            unit.data.extend_from_slice(payload);
+        }
+
+        sections.extend(self.complete_section());
+        Ok(sections)
+    }
+
+    fn complete_section(&mut self) -> Option<Vec<u8>> {
+        let data = &self.unit.as_ref()?.data;
+
+        if data.first() == Some(&STUFFING_BYTE) {
+            self.unit = None;
+            return None;
+        }
+
+        let length = SECTION_HEADER_SIZE + read_12_bits(data.get(1..SECTION_HEADER_SIZE)?);
+
+        if data.len() < length {
+            return None;
+        }
+
+        let mut data = self.unit.take()?.data;
+        data.truncate(length);
+        Some(data)
+    }
+}
+
+/// The fields of an adaptation field used by the demuxer.
+#[derive(Clone, Copy, Debug, Default)]
+struct AdaptationField {
+    discontinuity: bool,
+    random_access: bool,
+    pcr: Option<u64>,
+}
+
+impl AdaptationField {
+    fn parse(field: &[u8]) -> Self {
+        let flags = match field.first() {
+            Some(&flags) => flags,
+            None => return AdaptationField::default(),
+        };
+
+        let pcr = if flags & PCR_FLAG != 0 {
+            field.get(1..1 + PCR_SIZE).map(read_pcr)
+        } else {
+            None
+        };
+
+        AdaptationField {
+            discontinuity: flags & DISCONTINUITY_INDICATOR != 0,
+            random_access: flags & RANDOM_ACCESS_INDICATOR != 0,
+            pcr,
+        }
+    }
+}
+
+/// Reads a program clock reference, a 33 bit base in units of 90 kHz and a 9 bit extension in
+/// units of 27 MHz.
+fn read_pcr(data: &[u8]) -> u64 {
+    let base = u64::from(data[0]) << 25
+        | u64::from(data[1]) << 17
+        | u64::from(data[2]) << 9
+        | u64::from(data[3]) << 1
+        | u64::from(data[4] >> 7);
+    let extension = u64::from(data[4] & 1) << 8 | u64::from(data[5]);
+
+    base * 300 + extension
+}
+
+fn read_pid(data: &[u8]) -> u16 {
+    u16::from_be_bytes([data[0], data[1]]) & PID_MASK
+}
+
+fn read_12_bits(data: &[u8]) -> usize {
+    usize::from(u16::from_be_bytes([data[0], data[1]]) & SECTION_LENGTH_MASK)
+}
+
+/// Returns the table ID and the table data of a long section, or `None` if the section is not
+/// applicable yet.
+fn parse_section(section: &[u8]) -> Result<Option<(u8, &[u8])>, DepacketizeError> {
+    if section.len() < LONG_SECTION_HEADER_SIZE + CRC_SIZE {
+        return Err(DepacketizeError::Truncated);
+    }
+
+    if section[1] & SECTION_SYNTAX_INDICATOR == 0 {
+        return Err(DepacketizeError::Unsupported);
+    }
+
+    if section[5] & CURRENT_NEXT_INDICATOR == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        section[0],
+        &section[LONG_SECTION_HEADER_SIZE..section.len() - CRC_SIZE],
+    )))
+}
+
+/// Returns the length of a PES packet, if it is bounded.
+fn pes_packet_length(data: &[u8]) -> Option<usize> {
+    let length = data.get(PES_PACKET_HEADER_SIZE - 2..PES_PACKET_HEADER_SIZE)?;
+
+    match usize::from(u16::from_be_bytes([length[0], length[1]])) {
+        0 => None,
+        length => Some(PES_PACKET_HEADER_SIZE + length),
+    }
+}
+
+/// Demultiplexes the elementary streams of a transport stream from RTP packets.
+///
+/// Every RTP packet holds whole transport stream packets. The programs are found from the
+/// program association table, and the elementary streams and their types from the program map
+/// tables, which are expected to be repeated in the stream.
+///
+/// Every PES packet is emitted as an access unit along with its PID, stream, timestamps and
+/// the last program clock reference of its program. A PES packet of unbounded length, as used
+/// for video, ends with the start of the next one of its PID, so it is emitted only then. The
+/// frames get their presentation timestamp, or the RTP timestamp if they have none, so that
+/// H.264 and H.265 access units are Annex B byte streams just like those of the RTP
+/// depacketizers.
+///
+/// Lost packets are detected by the RTP sequence numbers and the continuity counters of the
+/// PIDs, in which case the PES packets being assembled are emitted as corrupted. Duplicate
+/// transport stream packets are dropped.
+#[derive(Debug)]
+pub struct TsDepacketizer {
+    /// The access units that have not been pulled yet.
+    frames: VecDeque<(Frame, TsInfo)>,
+
+    /// The last program clock references by PID.
+    pcrs: HashMap<u16, u64, FnvBuildHasher>,
+
+    /// The PIDs carrying tables or elementary streams.
+    pids: HashMap<u16, PidState, FnvBuildHasher>,
+
+    sequence: SequenceTracker,
+
+    /// The RTP timestamp of the last packet.
+    timestamp: u32,
+}
+
+impl TsDepacketizer {
+    /// Constructs a new depacketizer.
+    pub fn new() -> Self {
+        let mut pids = HashMap::default();
+        pids.insert(
+            PID_PROGRAM_ASSOCIATION_TABLE,
+            PidState::new(PidKind::ProgramAssociationTable),
+        );
+
+        TsDepacketizer {
+            frames: VecDeque::new(),
+            pcrs: HashMap::default(),
+            pids,
+            sequence: SequenceTracker::default(),
+            timestamp: 0,
+        }
+    }
+
+    /// Returns the next complete access unit along with its stream and timestamps, if any.
+    pub fn pull_with_info(&mut self) -> Option<(Frame, TsInfo)> {
+        self.frames.pop_front()
+    }
+
+    fn push_ts_packet(&mut self, packet: &[u8]) -> Result<(), DepacketizeError> {
+        if packet[0] != SYNC_BYTE {
+            return Err(DepacketizeError::Malformed);
+        }
+
+        let transport_error = packet[1] & TRANSPORT_ERROR_INDICATOR != 0;
+        let unit_start = packet[1] & PAYLOAD_UNIT_START_INDICATOR != 0;
+        let pid = read_pid(&packet[1..3]);
+        let adaptation_field_control = packet[3] >> 4 & 0b11;
+        let continuity_counter = packet[3] & CONTINUITY_COUNTER_MASK;
+
+        let mut payload = &packet[HEADER_SIZE..];
+        let mut adaptation_field = AdaptationField::default();
+
+        if adaptation_field_control & ADAPTATION_FIELD_PRESENT != 0 {
+            let length = usize::from(payload[0]);
+            let field = payload
+                .get(1..1 + length)
+                .ok_or(DepacketizeError::Malformed)?;
+
+            adaptation_field = AdaptationField::parse(field);
+            payload = &payload[1 + length..];
+        }
+
+        if adaptation_field_control & PAYLOAD_PRESENT == 0 {
+            payload = &[];
+        }
+
+        if let Some(pcr) = adaptation_field.pcr {
+            self.pcrs.insert(pid, pcr);
+        }
+
+        let state = match self.pids.get_mut(&pid) {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+
+        if transport_error {
+            state.lose();
+            return Ok(());
+        }
+
+        // The continuity counter is only incremented by packets with payload.
+        if adaptation_field_control & PAYLOAD_PRESENT != 0 {
+            match state.continuity_counter {
+                _ if adaptation_field.discontinuity => (),
+                Some(last) if last == continuity_counter => return Ok(()),
+                Some(last) if (last + 1) & CONTINUITY_COUNTER_MASK != continuity_counter => {
+                    state.lose()
+                }
+                _ => (),
+            }
+
+            state.continuity_counter = Some(continuity_counter);
+        }
+
+        let (stream_type, pcr_pid) = match state.kind {
+            PidKind::ElementaryStream {
+                stream_type,
+                pcr_pid,
+            } => (stream_type, pcr_pid),
+            kind => {
+                for section in state.push_sections(unit_start, payload)? {
+                    self.parse_table(kind, &section)?;
+                }
+
+                return Ok(());
+            }
+        };
+
+        let mut result = Ok(());
+
+        if unit_start {
+            if let Some(unit) = state.unit.take() {
+                result = push_pes_packet(&mut self.frames, pid, stream_type, unit);
+            }
+
+            state.unit = Some(Unit {
+                data: payload.to_vec(),
+                timestamp: self.timestamp,
+                pcr: self.pcrs.get(&pcr_pid).copied(),
+                random_access: adaptation_field.random_access,
+                corrupted: false,
+            });
+        } else if let Some(unit) = &mut state.unit {
+            unit.data.extend_from_slice(payload);
+        }
+
+        let complete = state.unit.as_ref().map_or(false, |unit| {
+            pes_packet_length(&unit.data).map_or(false, |length| unit.data.len() >= length)
+        });
+
+        if complete {
+            if let Some(unit) = state.unit.take() {
+                result = result.and(push_pes_packet(&mut self.frames, pid, stream_type, unit));
+            }
+        }
+
+        result
+    }
+
+    fn parse_table(&mut self, kind: PidKind, section: &[u8]) -> Result<(), DepacketizeError> {
+        let (table_id, data) = match parse_section(section)? {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+
+        match (kind, table_id) {
+            (PidKind::ProgramAssociationTable, TABLE_ID_PROGRAM_ASSOCIATION) => {
+                for program in data.chunks_exact(4) {
+                    // Program number 0 refers to the network information table.
+                    if program[..2] != [0, 0] {
+                        self.pids
+                            .entry(read_pid(&program[2..]))
+                            .or_insert_with(|| PidState::new(PidKind::ProgramMapTable));
+                    }
+                }
+            }
+            (PidKind::ProgramMapTable, TABLE_ID_PROGRAM_MAP) => {
+                let pcr_pid = read_pid(data.get(..2).ok_or(DepacketizeError::Truncated)?);
+                let info_length = read_12_bits(data.get(2..4).ok_or(DepacketizeError::Truncated)?);
+                let mut streams = data
+                    .get(4 + info_length..)
+                    .ok_or(DepacketizeError::Truncated)?;
+
+                while !streams.is_empty() {
+                    let stream = streams.get(..5).ok_or(DepacketizeError::Truncated)?;
+                    let kind = PidKind::ElementaryStream {
+                        stream_type: StreamType::from(stream[0]),
+                        pcr_pid,
+                    };
+
+                    // The tables are repeated, so only new or changed streams are reset.
+                    let state = self
+                        .pids
+                        .entry(read_pid(&stream[1..3]))
+                        .or_insert_with(|| PidState::new(kind));
+
+                    if state.kind != kind {
+                        *state = PidState::new(kind);
+                    }
+
+                    streams = streams
+                        .get(5 + read_12_bits(&stream[3..5])..)
+                        .ok_or(DepacketizeError::Truncated)?;
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Emits a complete PES packet as an access unit.
+fn push_pes_packet(
+    frames: &mut VecDeque<(Frame, TsInfo)>,
+    pid: u16,
+    stream_type: StreamType,
+    unit: Unit,
+) -> Result<(), DepacketizeError> {
+    let mut data = unit.data;
+
+    if let Some(length) = pes_packet_length(&data) {
+        data.truncate(length);
+    }
+
+    let (mut pes, data) = parse_pes_header(&data)?;
+    pes.stream_type = Some(stream_type);
+
+    if data.is_empty() && !unit.corrupted {
+        return Ok(());
+    }
+
+    let frame = Frame {
+        timestamp: pes.pts.map_or(unit.timestamp, |pts| pts as u32),
+        data: data.to_vec(),
+        keyframe: unit.random_access || stream_type.is_keyframe(data),
+        corrupted: unit.corrupted,
+    };
+
+    frames.push_back((
+        frame,
+        TsInfo {
+            pid,
+            pes,
+            pcr: unit.pcr,
+        },
+    ));
+
+    Ok(())
+}
+
+impl Default for TsDepacketizer {
+    fn default() -> Self {
+        TsDepacketizer::new()
+    }
+}
+
+impl Depacketizer for TsDepacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        match self.sequence.check(packet.header.seq_num) {
+            SequenceCheck::Late => return Ok(()),
+            SequenceCheck::Lost => {
+                for state in self.pids.values_mut() {
+                    state.lose();
+                }
+            }
+            SequenceCheck::InOrder => (),
+        }
+
+        self.timestamp = packet.header.timestamp;
+
+        let mut result = Ok(());
+        let mut ts_packets = packet.payload.chunks_exact(PACKET_SIZE);
+
+        for ts_packet in &mut ts_packets {
+            if let Err(error) = self.push_ts_packet(ts_packet) {
+                result = result.and(Err(error));
+            }
+        }
+
+        if !ts_packets.remainder().is_empty() {
+            result = result.and(Err(DepacketizeError::Truncated));
+        }
+
+        result
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.pull_with_info().map(|(frame, _)| frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::pes::StreamType;
+    use crate::proto::rtp::payload::Depacketizer;
+    use crate::proto::rtp::rtp::{test_support, RtpPacket};
+
+    use super::{TsDepacketizer, PACKET_SIZE, PAYLOAD_TYPE};
+
+    fn packet(seq_num: u16, timestamp: u32, ts_packets: &[Vec<u8>]) -> RtpPacket {
+        let mut packet = test_support::packet(seq_num, timestamp, false, &ts_packets.concat());
+        packet.header.payload_type = PAYLOAD_TYPE;
+        packet
+    }
+
+    /// Builds a transport stream packet, stuffing its adaptation field to fill it.
+    fn ts_packet(pid: u16, start: bool, counter: u8, pcr: Option<u64>, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![
+            0x47,
+            (start as u8) << 6 | (pid >> 8) as u8,
+            pid as u8,
+            0x10 | counter,
+        ];
+        let mut field = Vec::new();
+
+        if let Some(pcr) = pcr {
+            let (base, extension) = (pcr / 300, pcr % 300);
+            field = vec![
+                0x10,
+                (base >> 25) as u8,
+                (base >> 17) as u8,
+                (base >> 9) as u8,
+                (base >> 1) as u8,
+                (base << 7) as u8 | 0x7e | (extension >> 8) as u8,
+                extension as u8,
+            ];
+        }
+
+        if payload.len() < PACKET_SIZE - 4 || !field.is_empty() {
+            let length = PACKET_SIZE - 5 - payload.len();
+
+            if field.is_empty() && length > 0 {
+                field.push(0x00);
+            }
+
+            field.resize(length, 0xff);
+            packet[3] |= 0x20;
+            packet.push(length as u8);
+            packet.extend(field);
+        }
+
+        packet.extend_from_slice(payload);
+        assert_eq!(packet.len(), PACKET_SIZE);
+        packet
+    }
+
+    fn program_association_table() -> Vec<u8> {
+        vec![
+            0x00, 0x00, 0xb0, 0x0d, 0x00, 0x01, 0xc1, 0x00, 0x00, 0x00, 0x01, 0xe1, 0x00, 0x00,
+            0x00, 0x00, 0x00,
+        ]
+    }
+
+    fn program_map_table() -> Vec<u8> {
+        vec![
+            0x00, 0x02, 0xb0, 0x17, 0x00, 0x01, 0xc1, 0x00, 0x00, 0xe1, 0x01, 0xf0, 0x00, 0x1b,
+            0xe1, 0x01, 0xf0, 0x00, 0x0f, 0xe1, 0x02, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]
+    }
+
+    fn pes(stream_id: u8, pts: u64, data: &[u8], bounded: bool) -> Vec<u8> {
+        let length = if bounded { 8 + data.len() as u16 } else { 0 };
+        let mut pes = vec![0x00, 0x00, 0x01, stream_id];
+        pes.extend_from_slice(&length.to_be_bytes());
+        pes.extend_from_slice(&[0x80, 0x80, 0x05]);
+        pes.extend_from_slice(&[
+            0x21 | ((pts >> 29) & 0x0e) as u8,
+            (pts >> 22) as u8,
+            0x01 | ((pts >> 14) & 0xfe) as u8,
+            (pts >> 7) as u8,
+            0x01 | ((pts << 1) & 0xfe) as u8,
+        ]);
+        pes.extend_from_slice(data);
+        pes
+    }
+
+    #[test]
+    fn test_demux() {
+        let mut video = vec![0, 0, 0, 1, 0x65];
+        video.resize(300, 7);
+        let video_pes = pes(0xe0, 3600, &video, false);
+
+        let mut depacketizer = TsDepacketizer::new();
+
+        depacketizer
+            .push(&packet(
+                1,
+                0,
+                &[
+                    ts_packet(0x0000, true, 0, None, &program_association_table()),
+                    ts_packet(0x0100, true, 0, None, &program_map_table()),
+                    ts_packet(0x0101, true, 0, Some(27_000_123), &video_pes[..176]),
+                ],
+            ))
+            .unwrap();
+        depacketizer
+            .push(&packet(
+                2,
+                0,
+                &[
+                    ts_packet(0x0101, false, 1, None, &video_pes[176..]),
+                    ts_packet(0x0102, true, 0, None, &pes(0xc0, 3000, &[0xff, 0xf1], true)),
+                ],
+            ))
+            .unwrap();
+
+        // The bounded audio PES packet is complete, the video one is not yet.
+        let (frame, info) = depacketizer.pull_with_info().unwrap();
+        assert_eq!(info.pid, 0x0102);
+        assert_eq!(info.pes.stream_type, Some(StreamType::Aac));
+        assert_eq!(info.pes.pts, Some(3000));
+        assert_eq!(frame.timestamp, 3000);
+        assert_eq!(frame.data, vec![0xff, 0xf1]);
+        assert_eq!(depacketizer.pull(), None);
+
+        depacketizer
+            .push(&packet(
+                3,
+                0,
+                &[ts_packet(
+                    0x0101,
+                    true,
+                    2,
+                    None,
+                    &pes(0xe0, 7200, &[0, 0, 1, 0x41], false),
+                )],
+            ))
+            .unwrap();
+
+        let (frame, info) = depacketizer.pull_with_info().unwrap();
+        assert_eq!(info.pid, 0x0101);
+        assert_eq!(info.pes.stream_type, Some(StreamType::H264));
+        assert_eq!(info.pes.pts, Some(3600));
+        assert_eq!(info.pcr, Some(27_000_123));
+        assert_eq!(frame.timestamp, 3600);
+        assert_eq!(frame.data, video);
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+        assert_eq!(depacketizer.pull(), None);
+    }
+
+    #[test]
+    fn test_demux_continuity() {
+        let mut video = vec![0, 0, 0, 1, 0x41];
+        video.resize(500, 7);
+        let video_pes = pes(0xe0, 3600, &video, false);
+        let next_pes = pes(0xe0, 7200, &[0, 0, 1, 0x41], false);
+
+        let mut depacketizer = TsDepacketizer::new();
+
+        let tables = [
+            ts_packet(0x0000, true, 0, None, &program_association_table()),
+            ts_packet(0x0100, true, 0, None, &program_map_table()),
+        ];
+        depacketizer.push(&packet(1, 0, &tables)).unwrap();
+
+        // Duplicate packets are dropped, and the gap in the continuity counters is a loss.
+        let first = ts_packet(0x0101, true, 3, None, &video_pes[..184]);
+        depacketizer
+            .push(&packet(
+                2,
+                0,
+                &[
+                    first.clone(),
+                    first,
+                    ts_packet(0x0101, false, 5, None, &video_pes[368..]),
+                    ts_packet(0x0101, true, 6, None, &next_pes),
+                ],
+            ))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data.len(), 500 - 184);
+        assert!(frame.corrupted);
+        assert!(!frame.keyframe);
+        assert_eq!(depacketizer.pull(), None);
+
+        // So are lost RTP packets.
+        depacketizer
+            .push(&packet(
+                5,
+                0,
+                &[ts_packet(0x0101, true, 7, None, &next_pes)],
+            ))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, vec![0, 0, 1, 0x41]);
+        assert!(frame.corrupted);
+
+        assert!(depacketizer
+            .push(&packet(6, 0, &[vec![0x47; 100]]))
+            .is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//! VP8 video (RFC 7741).
+
+use crate::proto::rtp::payload::{
+    read_picture_id, DepacketizeError, Depacketizer, Frame, FrameAssembler,
+};
+use crate::proto::rtp::rtp::RtpPacket;
+
+/// The clock rate of VP8 RTP timestamps.
+pub const CLOCK_RATE: u32 = 90_000;
+
+const DESCRIPTOR_EXTENDED: u8 = 0b1000_0000;
+const DESCRIPTOR_NON_REFERENCE: u8 = 0b0010_0000;
+const DESCRIPTOR_START_OF_PARTITION: u8 = 0b0001_0000;
+const DESCRIPTOR_PARTITION_INDEX_MASK: u8 = 0b0000_0111;
+const EXTENSION_PICTURE_ID: u8 = 0b1000_0000;
+const EXTENSION_TL0_PIC_IDX: u8 = 0b0100_0000;
+const EXTENSION_TEMPORAL_ID: u8 = 0b0010_0000;
+const EXTENSION_KEY_INDEX: u8 = 0b0001_0000;
+const LAYER_SYNC: u8 = 0b0010_0000;
+const KEY_INDEX_MASK: u8 = 0b0001_1111;
+
+/// The bit of the VP8 payload header (the first byte of the frame) that is set for interframes.
+const PAYLOAD_HEADER_INTERFRAME: u8 = 0b0000_0001;
+
+/// The VP8 payload descriptor preceding the payload of every packet.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Vp8PayloadDescriptor {
+    /// Can the frame be discarded without affecting any other frame.
+    pub non_reference: bool,
+
+    /// Does the packet start a VP8 partition.
+    pub start_of_partition: bool,
+
+    /// The index of the partition the packet belongs to.
+    pub partition_index: u8,
+
+    /// The 7 or 15 bit picture ID, incremented with every frame.
+    pub picture_id: Option<u16>,
+
+    /// The running index of the frames of temporal layer 0.
+    pub tl0_pic_idx: Option<u8>,
+
+    /// The temporal layer of the frame.
+    pub temporal_id: Option<u8>,
+
+    /// Does the frame only depend on frames of temporal layer 0, so that forwarding can switch up
+    /// to its temporal layer.
+    pub layer_sync: bool,
+
+    /// The running index of the keyframes.
+    pub key_index: Option<u8>,
+}
+
+impl Vp8PayloadDescriptor {
+    /// Parses the descriptor at the start of a payload.
+    ///
+    /// Returns the descriptor and the VP8 data following it.
+    pub fn parse(payload: &[u8]) -> Result<(Self, &[u8]), DepacketizeError> {
+        let first = *payload.first().ok_or(DepacketizeError::Truncated)?;
+        let mut descriptor = Vp8PayloadDescriptor {
+            non_reference: first & DESCRIPTOR_NON_REFERENCE != 0,
+            start_of_partition: first & DESCRIPTOR_START_OF_PARTITION != 0,
+            partition_index: first & DESCRIPTOR_PARTITION_INDEX_MASK,
+            ..Vp8PayloadDescriptor::default()
+        };
+        let mut data = &payload[1..];
+
+        if first & DESCRIPTOR_EXTENDED == 0 {
+            return Ok((descriptor, data));
+        }
+
+        let extension = *data.first().ok_or(DepacketizeError::Truncated)?;
+        data = &data[1..];
+
+        if extension & EXTENSION_PICTURE_ID != 0 {
+            let (picture_id, rest) = read_picture_id(data)?;
+            descriptor.picture_id = Some(picture_id);
+            data = rest;
+        }
+
+        if extension & EXTENSION_TL0_PIC_IDX != 0 {
+            descriptor.tl0_pic_idx = Some(*data.first().ok_or(DepacketizeError::Truncated)?);
+            data = &data[1..];
+        }
+
+        if extension & (EXTENSION_TEMPORAL_ID | EXTENSION_KEY_INDEX) != 0 {
+            let byte = *data.first().ok_or(DepacketizeError::Truncated)?;
+            data = &data[1..];
+
+            if extension & EXTENSION_TEMPORAL_ID != 0 {
+                descriptor.temporal_id = Some(byte >> 6);
+                descriptor.layer_sync = byte & LAYER_SYNC != 0;
+            }
+
+            if extension & EXTENSION_KEY_INDEX != 0 {
+                descriptor.key_index = Some(byte & KEY_INDEX_MASK);
+            }
+        }
+
+        Ok((descriptor, data))
+    }
+
+    /// Returns whether the packet starts a frame.
+    pub fn is_frame_start(&self) -> bool {
+        self.start_of_partition && self.partition_index == 0
+    }
+}
+
+/// Reassembles VP8 frames from RTP packets.
+///
+/// A frame starts with the packet starting its first partition, and ends with the packet with
+/// the marker bit, or before a packet with another timestamp. The frames are emitted along with
+/// the payload descriptor of their first packet, which tells forwarding code their temporal layer.
+///
+/// If any packet of a frame was lost, the frame is emitted as corrupted.
+#[derive(Debug)]
+pub struct Vp8Depacketizer {
+    assembler: FrameAssembler<Vp8PayloadDescriptor>,
+}
+
+impl Vp8Depacketizer {
+    /// Constructs a new depacketizer.
+    pub fn new() -> Self {
+        Vp8Depacketizer {
+            assembler: FrameAssembler::new(),
+        }
+    }
+
+    /// Returns the next complete frame along with the payload descriptor of its first packet, if
+    /// any.
+    pub fn pull_with_descriptor(&mut self) -> Option<(Frame, Vp8PayloadDescriptor)> {
+        self.assembler.pull()
+    }
+
+    fn depacketize(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        let (descriptor, data) = Vp8PayloadDescriptor::parse(&packet.payload)?;
+
+        if descriptor.is_frame_start() {
+            let first = *data.first().ok_or(DepacketizeError::Truncated)?;
+            let keyframe = first & PAYLOAD_HEADER_INTERFRAME == 0;
+            self.assembler.start_frame(packet.header.timestamp, descriptor, keyframe);
+        } else if !self.assembler.in_frame() {
+            // The start of the frame was lost.
+            self.assembler.start_frame(packet.header.timestamp, descriptor, false);
+            self.assembler.corrupt();
+        }
+
+        self.assembler.append(data);
+        Ok(())
+    }
+}
+
+impl Default for Vp8Depacketizer {
+    fn default() -> Self {
+        Vp8Depacketizer::new()
+    }
+}
+
+impl Depacketizer for Vp8Depacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        if !self.assembler.start_packet(&packet.header) {
+            return Ok(());
+        }
+
+        let result = self.depacketize(packet);
+
+        if result.is_err() {
+            self.assembler.corrupt();
+        }
+
+        if packet.header.marker {
+            self.assembler.finish_frame();
+        }
+
+        result
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.pull_with_descriptor().map(|(frame, _)| frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::{DepacketizeError, Depacketizer};
+    use crate::proto::rtp::rtp::{test_support::packet, RtpPacket};
+
+    use super::{Vp8Depacketizer, Vp8PayloadDescriptor};
+
+    #[test]
+    fn test_parse_descriptor() {
+        let payload = [0x90, 0xf0, 0x81, 0x23, 0x05, 0x67, 0x10];
+        let (descriptor, data) = Vp8PayloadDescriptor::parse(&payload).unwrap();
+
+        assert_eq!(
+            descriptor,
+            Vp8PayloadDescriptor {
+                non_reference: false,
+                start_of_partition: true,
+                partition_index: 0,
+                picture_id: Some(0x0123),
+                tl0_pic_idx: Some(5),
+                temporal_id: Some(1),
+                layer_sync: true,
+                key_index: Some(7),
+            }
+        );
+        assert!(descriptor.is_frame_start());
+        assert_eq!(data, &[0x10]);
+
+        let (descriptor, data) = Vp8PayloadDescriptor::parse(&[0x21, 0x10]).unwrap();
+        assert!(descriptor.non_reference);
+        assert!(!descriptor.is_frame_start());
+        assert_eq!(descriptor.picture_id, None);
+        assert_eq!(data, &[0x10]);
+
+        assert_eq!(
+            Vp8PayloadDescriptor::parse(&[0x90, 0x80, 0x81]),
+            Err(DepacketizeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_depacketize() {
+        let mut depacketizer = Vp8Depacketizer::new();
+
+        depacketizer.push(&packet(1, 3000, false, &[0x90, 0x80, 0x01, 0x10, 0x02])).unwrap();
+        depacketizer.push(&packet(2, 3000, true, &[0x80, 0x80, 0x01, 0x03, 0x04])).unwrap();
+        depacketizer.push(&packet(3, 6000, true, &[0x90, 0x80, 0x02, 0x11, 0x05])).unwrap();
+
+        let (frame, descriptor) = depacketizer.pull_with_descriptor().unwrap();
+        assert_eq!(frame.timestamp, 3000);
+        assert_eq!(frame.data, vec![0x10, 0x02, 0x03, 0x04]);
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+        assert_eq!(descriptor.picture_id, Some(1));
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.data, vec![0x11, 0x05]);
+        assert!(!frame.keyframe);
+
+        assert_eq!(depacketizer.pull(), None);
+    }
+
+    #[test]
+    fn test_depacketize_lost_packets() {
+        let mut depacketizer = Vp8Depacketizer::new();
+
+        depacketizer.push(&packet(1, 3000, false, &[0x90, 0x80, 0x01, 0x10, 0x02])).unwrap();
+        depacketizer.push(&packet(3, 3000, true, &[0x80, 0x80, 0x01, 0x03, 0x04])).unwrap();
+        depacketizer.push(&packet(5, 6000, true, &[0x80, 0x80, 0x02, 0x05])).unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert!(frame.keyframe);
+        assert!(frame.corrupted);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 6000);
+        assert!(frame.corrupted);
+    }
+}
@@ -0,0 +1,417 @@
+//! VP9 video (RFC 9628).
+
+use crate::proto::rtp::payload::{
+    read_picture_id, DepacketizeError, Depacketizer, Frame, FrameAssembler,
+};
+use crate::proto::rtp::rtp::RtpPacket;
+
+/// The clock rate of VP9 RTP timestamps.
+pub const CLOCK_RATE: u32 = 90_000;
+
+/// The largest number of reference indices of a picture in flexible mode.
+const MAX_REFERENCE_INDICES: usize = 3;
+
+const DESCRIPTOR_PICTURE_ID: u8 = 0b1000_0000;
+const DESCRIPTOR_INTER_PICTURE_PREDICTED: u8 = 0b0100_0000;
+const DESCRIPTOR_LAYER_INDICES: u8 = 0b0010_0000;
+const DESCRIPTOR_FLEXIBLE_MODE: u8 = 0b0001_0000;
+const DESCRIPTOR_START_OF_FRAME: u8 = 0b0000_1000;
+const DESCRIPTOR_END_OF_FRAME: u8 = 0b0000_0100;
+const DESCRIPTOR_SCALABILITY_STRUCTURE: u8 = 0b0000_0010;
+const DESCRIPTOR_NOT_REFERENCE: u8 = 0b0000_0001;
+const LAYER_SWITCHING_UP_POINT: u8 = 0b0001_0000;
+const LAYER_INTER_LAYER_DEPENDENCY: u8 = 0b0000_0001;
+const REFERENCE_INDEX_MORE: u8 = 0b0000_0001;
+const SCALABILITY_STRUCTURE_RESOLUTIONS: u8 = 0b0001_0000;
+const SCALABILITY_STRUCTURE_PICTURE_GROUP: u8 = 0b0000_1000;
+const PICTURE_GROUP_SWITCHING_UP_POINT: u8 = 0b0001_0000;
+
+/// The layer indices of a picture.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Vp9LayerIndices {
+    pub temporal_id: u8,
+
+    /// Does the picture only depend on pictures of lower temporal layers, so that forwarding can
+    /// switch up to its temporal layer.
+    pub switching_up_point: bool,
+
+    pub spatial_id: u8,
+
+    /// Does the picture depend on the picture of the next lower spatial layer.
+    pub inter_layer_dependency: bool,
+}
+
+/// A picture of the group of pictures described by a scalability structure.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Vp9PictureGroupEntry {
+    pub temporal_id: u8,
+
+    pub switching_up_point: bool,
+
+    /// How many pictures before this one each picture it references is.
+    pub reference_indices: Vec<u8>,
+}
+
+/// The scalability structure describing the layers of the stream, sent with keyframes.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Vp9ScalabilityStructure {
+    /// The number of spatial layers.
+    pub spatial_layers: u8,
+
+    /// The width and height of every spatial layer, if present.
+    pub resolutions: Vec<(u16, u16)>,
+
+    /// The structure of the group of pictures, if present.
+    pub picture_group: Vec<Vp9PictureGroupEntry>,
+}
+
+/// The VP9 payload descriptor preceding the payload of every packet.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Vp9PayloadDescriptor {
+    /// The 7 or 15 bit picture ID, incremented with every picture.
+    pub picture_id: Option<u16>,
+
+    /// Does the frame reference other pictures of the same spatial layer.
+    pub inter_picture_predicted: bool,
+
+    /// Does every picture describe its references, instead of following a scalability structure.
+    pub flexible_mode: bool,
+
+    /// Does the packet start a frame.
+    pub start_of_frame: bool,
+
+    /// Does the packet end a frame.
+    pub end_of_frame: bool,
+
+    /// Is the frame not referenced by the frames of higher spatial layers of the same picture.
+    pub not_reference: bool,
+
+    pub layer_indices: Option<Vp9LayerIndices>,
+
+    /// The running index of the pictures of temporal layer 0, in non-flexible mode.
+    pub tl0_pic_idx: Option<u8>,
+
+    /// How many pictures before this one each picture it references is, in flexible mode.
+    pub reference_indices: Vec<u8>,
+
+    pub scalability_structure: Option<Vp9ScalabilityStructure>,
+}
+
+impl Vp9PayloadDescriptor {
+    /// Parses the descriptor at the start of a payload.
+    ///
+    /// Returns the descriptor and the VP9 data following it.
+    pub fn parse(payload: &[u8]) -> Result<(Self, &[u8]), DepacketizeError> {
+        let first = *payload.first().ok_or(DepacketizeError::Truncated)?;
+        let mut descriptor = Vp9PayloadDescriptor {
+            inter_picture_predicted: first & DESCRIPTOR_INTER_PICTURE_PREDICTED != 0,
+            flexible_mode: first & DESCRIPTOR_FLEXIBLE_MODE != 0,
+            start_of_frame: first & DESCRIPTOR_START_OF_FRAME != 0,
+            end_of_frame: first & DESCRIPTOR_END_OF_FRAME != 0,
+            not_reference: first & DESCRIPTOR_NOT_REFERENCE != 0,
+            ..Vp9PayloadDescriptor::default()
+        };
+        let mut data = &payload[1..];
+
+        if first & DESCRIPTOR_PICTURE_ID != 0 {
+            let (picture_id, rest) = read_picture_id(data)?;
+            descriptor.picture_id = Some(picture_id);
+            data = rest;
+        }
+
+        if first & DESCRIPTOR_LAYER_INDICES != 0 {
+            let (byte, rest) = read_u8(data)?;
+            data = rest;
+            descriptor.layer_indices = Some(Vp9LayerIndices {
+                temporal_id: byte >> 5,
+                switching_up_point: byte & LAYER_SWITCHING_UP_POINT != 0,
+                spatial_id: (byte >> 1) & 0b0000_0111,
+                inter_layer_dependency: byte & LAYER_INTER_LAYER_DEPENDENCY != 0,
+            });
+
+            if !descriptor.flexible_mode {
+                let (tl0_pic_idx, rest) = read_u8(data)?;
+                descriptor.tl0_pic_idx = Some(tl0_pic_idx);
+                data = rest;
+            }
+        }
+
+        if descriptor.flexible_mode && descriptor.inter_picture_predicted {
+            loop {
+                let (byte, rest) = read_u8(data)?;
+                data = rest;
+                descriptor.reference_indices.push(byte >> 1);
+
+                if byte & REFERENCE_INDEX_MORE == 0 {
+                    break;
+                }
+
+                if descriptor.reference_indices.len() == MAX_REFERENCE_INDICES {
+                    return Err(DepacketizeError::Unsupported);
+                }
+            }
+        }
+
+        if first & DESCRIPTOR_SCALABILITY_STRUCTURE != 0 {
+            let (scalability_structure, rest) = parse_scalability_structure(data)?;
+            descriptor.scalability_structure = Some(scalability_structure);
+            data = rest;
+        }
+
+        Ok((descriptor, data))
+    }
+
+    /// Returns the spatial layer of the frame, which is 0 without layer indices.
+    pub fn spatial_id(&self) -> u8 {
+        self.layer_indices.map_or(0, |layer_indices| layer_indices.spatial_id)
+    }
+
+    /// Returns whether decoding can start with the frame, i.e. whether it is a frame of the lowest
+    /// spatial layer that does not reference any other picture.
+    pub fn is_keyframe(&self) -> bool {
+        !self.inter_picture_predicted && self.spatial_id() == 0
+    }
+}
+
+fn parse_scalability_structure(
+    data: &[u8],
+) -> Result<(Vp9ScalabilityStructure, &[u8]), DepacketizeError> {
+    let (first, mut data) = read_u8(data)?;
+    let mut scalability_structure = Vp9ScalabilityStructure {
+        spatial_layers: (first >> 5) + 1,
+        ..Vp9ScalabilityStructure::default()
+    };
+
+    if first & SCALABILITY_STRUCTURE_RESOLUTIONS != 0 {
+        for _ in 0..scalability_structure.spatial_layers {
+            let resolution = data.get(..4).ok_or(DepacketizeError::Truncated)?;
+            scalability_structure.resolutions.push((
+                u16::from_be_bytes([resolution[0], resolution[1]]),
+                u16::from_be_bytes([resolution[2], resolution[3]]),
+            ));
+            data = &data[4..];
+        }
+    }
+
+    if first & SCALABILITY_STRUCTURE_PICTURE_GROUP != 0 {
+        let (pictures, rest) = read_u8(data)?;
+        data = rest;
+
+        for _ in 0..pictures {
+            let (byte, rest) = read_u8(data)?;
+            let reference_count = ((byte >> 2) & 0b0000_0011) as usize;
+            let reference_indices = rest.get(..reference_count).ok_or(DepacketizeError::Truncated)?;
+            scalability_structure.picture_group.push(Vp9PictureGroupEntry {
+                temporal_id: byte >> 5,
+                switching_up_point: byte & PICTURE_GROUP_SWITCHING_UP_POINT != 0,
+                reference_indices: reference_indices.to_vec(),
+            });
+            data = &rest[reference_count..];
+        }
+    }
+
+    Ok((scalability_structure, data))
+}
+
+fn read_u8(data: &[u8]) -> Result<(u8, &[u8]), DepacketizeError> {
+    let byte = *data.first().ok_or(DepacketizeError::Truncated)?;
+    Ok((byte, &data[1..]))
+}
+
+/// Reassembles VP9 frames from RTP packets.
+///
+/// A frame starts with the packet with the start of frame bit, and ends with the packet with the
+/// end of frame bit. Every spatial layer of a picture is a separate frame, and they all share the
+/// timestamp of the picture. The frames are emitted along with the payload descriptor of their
+/// first packet, which tells forwarding code their layers and holds the scalability structure.
+///
+/// If any packet of a frame was lost, the frame is emitted as corrupted.
+#[derive(Debug)]
+pub struct Vp9Depacketizer {
+    assembler: FrameAssembler<Vp9PayloadDescriptor>,
+}
+
+impl Vp9Depacketizer {
+    /// Constructs a new depacketizer.
+    pub fn new() -> Self {
+        Vp9Depacketizer {
+            assembler: FrameAssembler::new(),
+        }
+    }
+
+    /// Returns the next complete frame along with the payload descriptor of its first packet, if
+    /// any.
+    pub fn pull_with_descriptor(&mut self) -> Option<(Frame, Vp9PayloadDescriptor)> {
+        self.assembler.pull()
+    }
+
+    /// Depacketizes the payload, returning whether it ends a frame.
+    fn depacketize(&mut self, packet: &RtpPacket) -> Result<bool, DepacketizeError> {
+        let (descriptor, data) = Vp9PayloadDescriptor::parse(&packet.payload)?;
+        let end_of_frame = descriptor.end_of_frame;
+
+        if descriptor.start_of_frame {
+            let keyframe = descriptor.is_keyframe();
+            self.assembler.start_frame(packet.header.timestamp, descriptor, keyframe);
+        } else if !self.assembler.in_frame() {
+            // The start of the frame was lost.
+            self.assembler.start_frame(packet.header.timestamp, descriptor, false);
+            self.assembler.corrupt();
+        }
+
+        self.assembler.append(data);
+        Ok(end_of_frame)
+    }
+}
+
+impl Default for Vp9Depacketizer {
+    fn default() -> Self {
+        Vp9Depacketizer::new()
+    }
+}
+
+impl Depacketizer for Vp9Depacketizer {
+    fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+        if !self.assembler.start_packet(&packet.header) {
+            return Ok(());
+        }
+
+        let result = self.depacketize(packet);
+
+        if result.is_err() {
+            self.assembler.corrupt();
+        }
+
+        if packet.header.marker || result == Ok(true) {
+            self.assembler.finish_frame();
+        }
+
+        result.map(|_| ())
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.pull_with_descriptor().map(|(frame, _)| frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::rtp::payload::{DepacketizeError, Depacketizer};
+    use crate::proto::rtp::rtp::{test_support::packet, RtpPacket};
+
+    use super::{
+        Vp9Depacketizer, Vp9LayerIndices, Vp9PayloadDescriptor, Vp9PictureGroupEntry,
+        Vp9ScalabilityStructure,
+    };
+
+    #[test]
+    fn test_parse_non_flexible_descriptor() {
+        let payload = [
+            0xaa, // I, L, B, V
+            0x80, 0x05, // picture ID
+            0x20, // TID 1, SID 0
+            0x07, // TL0PICIDX
+            0x38, // 2 spatial layers, resolutions, picture group
+            0x01, 0x40, 0x00, 0xb4, 0x02, 0x80, 0x01, 0x68, // resolutions
+            0x02, // 2 pictures
+            0x04, 0x01, // TID 0, 1 reference
+            0x34, 0x01, // TID 1, switching up point, 1 reference
+            0xff,
+        ];
+        let (descriptor, data) = Vp9PayloadDescriptor::parse(&payload).unwrap();
+
+        assert_eq!(
+            descriptor,
+            Vp9PayloadDescriptor {
+                picture_id: Some(5),
+                inter_picture_predicted: false,
+                flexible_mode: false,
+                start_of_frame: true,
+                end_of_frame: false,
+                not_reference: false,
+                layer_indices: Some(Vp9LayerIndices {
+                    temporal_id: 1,
+                    switching_up_point: false,
+                    spatial_id: 0,
+                    inter_layer_dependency: false,
+                }),
+                tl0_pic_idx: Some(7),
+                reference_indices: Vec::new(),
+                scalability_structure: Some(Vp9ScalabilityStructure {
+                    spatial_layers: 2,
+                    resolutions: vec![(320, 180), (640, 360)],
+                    picture_group: vec![
+                        Vp9PictureGroupEntry {
+                            temporal_id: 0,
+                            switching_up_point: false,
+                            reference_indices: vec![1],
+                        },
+                        Vp9PictureGroupEntry {
+                            temporal_id: 1,
+                            switching_up_point: true,
+                            reference_indices: vec![1],
+                        },
+                    ],
+                }),
+            }
+        );
+        assert!(descriptor.is_keyframe());
+        assert_eq!(data, &[0xff]);
+    }
+
+    #[test]
+    fn test_parse_flexible_descriptor() {
+        let payload = [0xfc, 0x05, 0x23, 0x03, 0x04, 0xff];
+        let (descriptor, data) = Vp9PayloadDescriptor::parse(&payload).unwrap();
+
+        assert!(descriptor.flexible_mode);
+        assert!(descriptor.inter_picture_predicted);
+        assert!(!descriptor.is_keyframe());
+        assert_eq!(descriptor.spatial_id(), 1);
+        assert_eq!(descriptor.tl0_pic_idx, None);
+        assert_eq!(descriptor.reference_indices, vec![1, 2]);
+        assert_eq!(data, &[0xff]);
+
+        assert_eq!(
+            Vp9PayloadDescriptor::parse(&[0x50, 0x03, 0x03, 0x03, 0x03]),
+            Err(DepacketizeError::Unsupported)
+        );
+    }
+
+    #[test]
+    fn test_depacketize_spatial_layers() {
+        let mut depacketizer = Vp9Depacketizer::new();
+
+        depacketizer.push(&packet(1, 3000, false, &[0xa8, 0x01, 0x00, 0x00, 0x10])).unwrap();
+        depacketizer.push(&packet(2, 3000, false, &[0xa4, 0x01, 0x00, 0x00, 0x11])).unwrap();
+        depacketizer.push(&packet(3, 3000, true, &[0xad, 0x01, 0x03, 0x00, 0x12])).unwrap();
+
+        let (frame, descriptor) = depacketizer.pull_with_descriptor().unwrap();
+        assert_eq!(frame.data, vec![0x10, 0x11]);
+        assert!(frame.keyframe);
+        assert!(!frame.corrupted);
+        assert_eq!(descriptor.spatial_id(), 0);
+
+        let (frame, descriptor) = depacketizer.pull_with_descriptor().unwrap();
+        assert_eq!(frame.timestamp, 3000);
+        assert_eq!(frame.data, vec![0x12]);
+        assert!(!frame.keyframe);
+        assert_eq!(descriptor.spatial_id(), 1);
+
+        assert_eq!(depacketizer.pull(), None);
+    }
+
+    #[test]
+    fn test_depacketize_lost_start() {
+        let mut depacketizer = Vp9Depacketizer::new();
+
+        depacketizer.push(&packet(1, 3000, true, &[0x88, 0x01, 0x10])).unwrap();
+        depacketizer.push(&packet(3, 6000, true, &[0xc4, 0x02, 0x11])).unwrap();
+
+        assert!(!depacketizer.pull().unwrap().corrupted);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(frame.timestamp, 6000);
+        assert!(frame.corrupted);
+    }
+}
@@ -194,4 +194,28 @@ impl WriteTo for RtpHeaderExtension {
         track!(writer.write_all(&self.extension).map_err(Error::from));
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Packets shared by the tests of the modules that depacketize or receive RTP.
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::{RtpFixedHeader, RtpPacket};
+
+    /// Constructs a packet of the stream the tests depacketize or receive.
+    pub(crate) fn packet(seq_num: u16, timestamp: u32, marker: bool, payload: &[u8]) -> RtpPacket {
+        RtpPacket {
+            header: RtpFixedHeader {
+                padding: false,
+                marker,
+                payload_type: 96,
+                seq_num,
+                timestamp,
+                ssrc: 0x1234_5678,
+                csrc_list: Vec::new(),
+                extension: None,
+            },
+            payload: payload.to_vec(),
+            padding: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Packetization of outgoing media into RTP packets, with the sender information of RTCP
+//! sender reports.
+
+use std::time::{Instant, SystemTime};
+
+use crate::proto::rtcp::ntp;
+use crate::proto::rtcp::report_packet::SenderReportPacket;
+use crate::proto::rtp::rtp::{RtpFixedHeader, RtpPacket};
+
+/// Assigns the sequence numbers and timestamps of the RTP packets of a source, and counts them.
+///
+/// The initial sequence number and the offset of the timestamps from the media timestamps are
+/// random, as recommended by RFC 3550.
+#[derive(Clone, Debug)]
+pub struct RtpSender {
+    ssrc: u32,
+    payload_type: u8,
+    clock_rate: u32,
+
+    /// The sequence number of the next packet.
+    sequence_number: u16,
+    timestamp_offset: u32,
+
+    packet_count: u32,
+    octet_count: u32,
+
+    /// The RTP timestamp of the last packet and when it was sent, which the RTP timestamps of
+    /// sender reports are extrapolated from.
+    last_packet: Option<(u32, Instant)>,
+}
+
+impl RtpSender {
+    /// Constructs a sender of packets of the payload type, with media timestamps of the clock
+    /// rate.
+    pub fn new(ssrc: u32, payload_type: u8, clock_rate: u32) -> Self {
+        RtpSender {
+            ssrc,
+            payload_type,
+            clock_rate,
+            sequence_number: rand::random(),
+            timestamp_offset: rand::random(),
+            packet_count: 0,
+            octet_count: 0,
+            last_packet: None,
+        }
+    }
+
+    /// Changes the SSRC, e.g. after a collision, which restarts the sequence numbers, the
+    /// timestamps and the counters as for a new source.
+    pub fn set_ssrc(&mut self, ssrc: u32) {
+        *self = RtpSender::new(ssrc, self.payload_type, self.clock_rate);
+    }
+
+    /// Returns the RTP packet of a payload with the media timestamp, which is offset into the
+    /// RTP timestamp, sent at the given time.
+    pub fn packetize(
+        &mut self,
+        payload: Vec<u8>,
+        timestamp: u32,
+        marker: bool,
+        now: Instant,
+    ) -> RtpPacket {
+        let timestamp = timestamp.wrapping_add(self.timestamp_offset);
+        let packet = RtpPacket {
+            header: RtpFixedHeader {
+                padding: false,
+                marker,
+                payload_type: self.payload_type,
+                seq_num: self.sequence_number,
+                timestamp,
+                ssrc: self.ssrc,
+                csrc_list: Vec::new(),
+                extension: None,
+            },
+            payload,
+            padding: Vec::new(),
+        };
+
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+        self.packet_count = self.packet_count.wrapping_add(1);
+        self.octet_count = self.octet_count.wrapping_add(packet.payload.len() as u32);
+        self.last_packet = Some((timestamp, now));
+
+        packet
+    }
+
+    /// Returns a sender report at the given time, which is the wallclock time, correlating the
+    /// NTP timestamp of the wallclock time with the RTP timestamp.
+    pub fn sender_report(&self, now: Instant, wallclock: SystemTime) -> SenderReportPacket {
+        let (ntp_sec, ntp_frac) = ntp::ntp_timestamp(wallclock);
+
+        let rtp_timestamp = match self.last_packet {
+            Some((timestamp, sent)) => {
+                let elapsed = now.saturating_duration_since(sent).as_secs_f64();
+                timestamp.wrapping_add((elapsed * f64::from(self.clock_rate)) as u32)
+            }
+            None => self.timestamp_offset,
+        };
+
+        let mut report = SenderReportPacket::new(self.ssrc);
+        report.ntp_sec = ntp_sec;
+        report.ntp_frac = ntp_frac;
+        report.rtp_timestamp = rtp_timestamp;
+        report.sent_packets = self.packet_count;
+        report.sent_octets = self.octet_count;
+        report
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    pub fn payload_type(&self) -> u8 {
+        self.payload_type
+    }
+
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// Returns the sequence number of the next packet.
+    pub fn sequence_number(&self) -> u16 {
+        self.sequence_number
+    }
+
+    /// Returns the offset of the RTP timestamps from the media timestamps.
+    pub fn timestamp_offset(&self) -> u32 {
+        self.timestamp_offset
+    }
+
+    /// Returns the number of packets sent, wrapping around as in sender reports.
+    pub fn packet_count(&self) -> u32 {
+        self.packet_count
+    }
+
+    /// Returns the number of payload octets sent, wrapping around as in sender reports.
+    pub fn octet_count(&self) -> u32 {
+        self.octet_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    use super::RtpSender;
+
+    #[test]
+    fn test_sender() {
+        let start = Instant::now();
+        let mut sender = RtpSender::new(0x1234_5678, 96, 90_000);
+        let sequence_number = sender.sequence_number();
+        let offset = sender.timestamp_offset();
+
+        let first = sender.packetize(vec![0; 100], 0, false, start);
+        let second = sender.packetize(vec![0; 50], 3000, true, start);
+        assert_eq!(first.header.ssrc, 0x1234_5678);
+        assert_eq!(first.header.payload_type, 96);
+        assert_eq!(first.header.seq_num, sequence_number);
+        assert_eq!(second.header.seq_num, sequence_number.wrapping_add(1));
+        assert_eq!(first.header.timestamp, offset);
+        assert_eq!(second.header.timestamp, offset.wrapping_add(3000));
+        assert!(second.header.marker);
+
+        // The RTP timestamp of the report is extrapolated from the last packet.
+        let wallclock = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let report = sender.sender_report(start + Duration::from_millis(500), wallclock);
+        assert_eq!(report.ssrc, 0x1234_5678);
+        assert_eq!(report.ntp_sec, 3_808_988_800);
+        assert_eq!(report.rtp_timestamp, offset.wrapping_add(3000 + 45_000));
+        assert_eq!(report.sent_packets, 2);
+        assert_eq!(report.sent_octets, 150);
+
+        sender.set_ssrc(1);
+        assert_eq!(sender.ssrc(), 1);
+        assert_eq!(sender.packet_count(), 0);
+        assert_eq!(sender.octet_count(), 0);
+    }
+}
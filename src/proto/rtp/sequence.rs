@@ -0,0 +1,44 @@
+//! Sequence number arithmetic shared by the receivers of RTP packets, as in section A.1 of
+//! RFC 3550.
+
+/// The largest gap in the sequence numbers taken as packet loss.
+pub(crate) const MAX_DROPOUT: u16 = 3000;
+
+/// The largest step back in the sequence numbers taken as reordering.
+pub(crate) const MAX_MISORDER: u16 = 100;
+
+/// Returns whether a sequence number is too far from the highest one received to be taken as
+/// loss or reordering. Unless the packet is a stray one, the source restarted.
+pub(crate) fn is_sequence_jump(highest: u16, sequence_number: u16) -> bool {
+    let delta = sequence_number.wrapping_sub(highest);
+    delta >= MAX_DROPOUT && delta <= 0u16.wrapping_sub(MAX_MISORDER)
+}
+
+/// Extends a sequence number to 64 bits from the highest extended sequence number received so
+/// far, taking it as the closest one, before or after.
+pub(crate) fn extend_sequence_number(highest: u64, sequence_number: u16) -> u64 {
+    let delta = sequence_number.wrapping_sub(highest as u16) as i16;
+    (highest as i64 + i64::from(delta)).max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extend_sequence_number, is_sequence_jump};
+
+    #[test]
+    fn test_extend_sequence_number() {
+        assert_eq!(extend_sequence_number(0xfffe, 0x0001), 0x1_0001);
+        assert_eq!(extend_sequence_number(0x1_0001, 0xffff), 0xffff);
+        assert_eq!(extend_sequence_number(5, 0xfffe), 0);
+    }
+
+    #[test]
+    fn test_is_sequence_jump() {
+        assert!(!is_sequence_jump(1000, 1001));
+        assert!(!is_sequence_jump(1000, 3999));
+        assert!(is_sequence_jump(1000, 4000));
+        assert!(!is_sequence_jump(1000, 901));
+        assert!(is_sequence_jump(1000, 900));
+        assert!(!is_sequence_jump(0xffff, 0x0010));
+    }
+}
@@ -0,0 +1,443 @@
+//! Mapping of RTP timestamps to wallclock times from the NTP and RTP timestamp pairs of RTCP
+//! sender reports, for the synchronization of the tracks of a session (RFC 3550, section 6.4.1).
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use fnv::FnvBuildHasher;
+
+use crate::proto::rtcp::ntp;
+use crate::proto::rtcp::report_packet::SenderReportPacket;
+use crate::proto::rtcp::rtcp_packet::{RtcpCompoundPacket, RtcpPacket};
+use crate::proto::rtp::rtp::RtpPacket;
+
+/// The time between sender reports needed at the least to estimate the drift of the sender
+/// clock.
+const MIN_DRIFT_SPAN: Duration = Duration::from_secs(5);
+
+/// The largest drift of a sender clock taken as such, larger ones are taken as a restart of the
+/// sender.
+const MAX_DRIFT: f64 = 0.01;
+
+/// Extends an RTP timestamp to 64 bits from the highest extended timestamp received so far,
+/// taking it as the closest one, before or after.
+pub(crate) fn extend_timestamp(highest: u64, timestamp: u32) -> u64 {
+    let delta = timestamp.wrapping_sub(highest as u32) as i32;
+    (highest as i64 + i64::from(delta)).max(0) as u64
+}
+
+/// Returns the wallclock time the given seconds after or, if negative, before a time.
+fn offset_time(time: SystemTime, seconds: f64) -> SystemTime {
+    if seconds >= 0.0 {
+        time + Duration::from_secs_f64(seconds)
+    } else {
+        time - Duration::from_secs_f64(-seconds)
+    }
+}
+
+/// Returns the seconds from a time to another, negative if the other is earlier.
+fn seconds_between(from: SystemTime, to: SystemTime) -> f64 {
+    match to.duration_since(from) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+/// An extended RTP timestamp along with its wallclock time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Reference {
+    timestamp: u64,
+    wallclock: SystemTime,
+}
+
+/// Maps the RTP timestamps of a source to wallclock times.
+///
+/// The timestamps are mapped from the latest sender report, at the rate of the sender clock
+/// estimated from the first sender report since the sender started. Until a sender report
+/// arrives, they are mapped from the arrival time of the first packet instead, which does not
+/// synchronize the sources.
+#[derive(Debug)]
+pub struct ClockMapping {
+    clock_rate: u32,
+
+    /// The highest extended timestamp, which the timestamps are unwrapped from.
+    highest: Option<u64>,
+
+    reference: Option<Reference>,
+    synchronized: bool,
+
+    /// The first sender report, and the rate of the sender clock relative to the clock rate.
+    first_report: Option<Reference>,
+    drift: f64,
+}
+
+impl ClockMapping {
+    /// Constructs the mapping of a source with RTP timestamps of the clock rate.
+    pub fn new(clock_rate: u32) -> Self {
+        ClockMapping {
+            clock_rate,
+            highest: None,
+            reference: None,
+            synchronized: false,
+            first_report: None,
+            drift: 1.0,
+        }
+    }
+
+    /// Unwraps a timestamp from the highest one.
+    fn extended(&self, timestamp: u32) -> u64 {
+        // The first timestamp is a cycle ahead, so that earlier ones do not go below zero.
+        match self.highest {
+            Some(highest) => extend_timestamp(highest, timestamp),
+            None => (1 << 32) + u64::from(timestamp),
+        }
+    }
+
+    /// Unwraps a received timestamp, keeping the highest one.
+    fn extend(&mut self, timestamp: u32) -> u64 {
+        let extended = self.extended(timestamp);
+
+        self.highest = Some(
+            self.highest
+                .map_or(extended, |highest| highest.max(extended)),
+        );
+        extended
+    }
+
+    /// Maps the timestamps from the NTP and RTP timestamps of a sender report of the source.
+    pub fn on_sender_report(&mut self, report: &SenderReportPacket) {
+        let reference = Reference {
+            timestamp: self.extend(report.rtp_timestamp),
+            wallclock: ntp::system_time(report.ntp_sec, report.ntp_frac),
+        };
+
+        match self.first_report {
+            Some(first) => {
+                let seconds = seconds_between(first.wallclock, reference.wallclock);
+                let ticks = reference.timestamp as f64 - first.timestamp as f64;
+
+                if seconds >= MIN_DRIFT_SPAN.as_secs_f64() {
+                    let drift = ticks / seconds / f64::from(self.clock_rate);
+
+                    if (drift - 1.0).abs() <= MAX_DRIFT {
+                        self.drift = drift;
+                    } else {
+                        self.first_report = Some(reference);
+                        self.drift = 1.0;
+                    }
+                } else if seconds < 0.0 {
+                    self.first_report = Some(reference);
+                    self.drift = 1.0;
+                }
+            }
+            None => self.first_report = Some(reference),
+        }
+
+        self.reference = Some(reference);
+        self.synchronized = true;
+    }
+
+    /// Records the timestamp of a packet which arrived at the given wallclock time, which maps
+    /// the timestamps until a sender report arrives.
+    pub fn on_packet(&mut self, timestamp: u32, arrival: SystemTime) {
+        let timestamp = self.extend(timestamp);
+
+        if self.reference.is_none() {
+            self.reference = Some(Reference {
+                timestamp,
+                wallclock: arrival,
+            });
+        }
+    }
+
+    /// Returns the wallclock time of a timestamp, once a sender report or a packet arrived.
+    pub fn wallclock(&self, timestamp: u32) -> Option<SystemTime> {
+        let reference = self.reference?;
+        let timestamp = self.extended(timestamp);
+
+        let ticks = timestamp as f64 - reference.timestamp as f64;
+        let seconds = ticks / (f64::from(self.clock_rate.max(1)) * self.drift);
+        Some(offset_time(reference.wallclock, seconds))
+    }
+
+    /// Returns whether the timestamps are mapped from a sender report rather than from the
+    /// arrival of a packet.
+    pub fn is_synchronized(&self) -> bool {
+        self.synchronized
+    }
+
+    /// Returns the estimated rate of the sender clock relative to its clock rate.
+    pub fn drift(&self) -> f64 {
+        self.drift
+    }
+
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+}
+
+/// Synchronizes the tracks of a session, e.g. the audio and the video of an RTSP session, by
+/// mapping their RTP timestamps to presentation times on a common timeline.
+///
+/// The timeline starts at the wallclock time of the first presentation time asked for. Tracks
+/// without a sender report are mapped from arrival times, which may be far from the sender
+/// clock. Once the offset between the two is known, the times of either are moved onto the
+/// clock of the origin, and the origin is re-based onto the sender clock, so that the
+/// presentation times stay continuous when the tracks become synchronized.
+#[derive(Debug, Default)]
+pub struct LipSync {
+    tracks: HashMap<u32, ClockMapping, FnvBuildHasher>,
+
+    /// The start of the timeline, along with whether it is a time of the sender clock.
+    origin: Option<(SystemTime, bool)>,
+
+    /// The seconds from the arrival times to the sender clock.
+    sender_offset: Option<f64>,
+}
+
+impl LipSync {
+    pub fn new() -> Self {
+        LipSync::default()
+    }
+
+    /// Adds the track of the SSRC, with RTP timestamps of the clock rate.
+    pub fn add_track(&mut self, ssrc: u32, clock_rate: u32) {
+        self.tracks.insert(ssrc, ClockMapping::new(clock_rate));
+    }
+
+    pub fn track(&self, ssrc: u32) -> Option<&ClockMapping> {
+        self.tracks.get(&ssrc)
+    }
+
+    /// Handles the sender reports of the tracks in a compound RTCP packet.
+    pub fn on_rtcp(&mut self, packet: &RtcpCompoundPacket) {
+        for packet in packet.packets.iter() {
+            if let RtcpPacket::Sr(report) = packet {
+                let track = match self.tracks.get_mut(&report.ssrc) {
+                    Some(track) => track,
+                    None => continue,
+                };
+
+                // The latest timestamp of a track mapped from arrival times so far relates the
+                // arrival times to the sender clock.
+                let arrival = match track.highest {
+                    Some(highest) if !track.is_synchronized() => track
+                        .wallclock(highest as u32)
+                        .map(|wallclock| (highest as u32, wallclock)),
+                    _ => None,
+                };
+
+                track.on_sender_report(report);
+
+                if let Some((timestamp, arrival)) = arrival {
+                    if let Some(sender) = track.wallclock(timestamp) {
+                        self.set_sender_offset(seconds_between(arrival, sender));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handles an RTP packet of a track which arrived at the given wallclock time.
+    pub fn on_packet(&mut self, packet: &RtpPacket, arrival: SystemTime) {
+        let track = match self.tracks.get_mut(&packet.header.ssrc) {
+            Some(track) => track,
+            None => return,
+        };
+
+        track.on_packet(packet.header.timestamp, arrival);
+
+        if track.is_synchronized() && self.sender_offset.is_none() {
+            if let Some(sender) = track.wallclock(packet.header.timestamp) {
+                self.set_sender_offset(seconds_between(arrival, sender));
+            }
+        }
+    }
+
+    /// Sets the offset from the arrival times to the sender clock, unless known already, and
+    /// re-bases the origin onto the sender clock.
+    fn set_sender_offset(&mut self, offset: f64) {
+        if self.sender_offset.is_some() {
+            return;
+        }
+
+        self.sender_offset = Some(offset);
+        if let Some((origin, false)) = self.origin {
+            self.origin = Some((offset_time(origin, offset), true));
+        }
+    }
+
+    /// Returns the wallclock time of a timestamp of a track.
+    pub fn wallclock(&self, ssrc: u32, timestamp: u32) -> Option<SystemTime> {
+        self.tracks.get(&ssrc)?.wallclock(timestamp)
+    }
+
+    /// Returns the presentation time of a timestamp of a track, which is zero for timestamps
+    /// before the start of the timeline.
+    pub fn presentation_time(&mut self, ssrc: u32, timestamp: u32) -> Option<Duration> {
+        let track = self.tracks.get(&ssrc)?;
+        let synchronized = track.is_synchronized();
+        let wallclock = track.wallclock(timestamp)?;
+
+        let (origin, on_sender_clock) = *self.origin.get_or_insert((wallclock, synchronized));
+        let wallclock = match self.sender_offset {
+            Some(offset) if synchronized && !on_sender_clock => offset_time(wallclock, -offset),
+            Some(offset) if !synchronized && on_sender_clock => offset_time(wallclock, offset),
+            _ => wallclock,
+        };
+
+        Some(wallclock.duration_since(origin).unwrap_or_default())
+    }
+
+    /// Returns whether every track is mapped from sender reports, i.e. the tracks are
+    /// synchronized.
+    pub fn is_synchronized(&self) -> bool {
+        self.tracks.values().all(|track| track.is_synchronized())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::proto::rtcp::ntp;
+    use crate::proto::rtcp::report_packet::SenderReportPacket;
+    use crate::proto::rtcp::rtcp_packet::RtcpCompoundPacket;
+    use crate::proto::rtp::rtp::{test_support, RtpPacket};
+
+    use super::{ClockMapping, LipSync};
+
+    fn sender_report(ssrc: u32, seconds: f64, rtp_timestamp: u32) -> SenderReportPacket {
+        let wallclock = UNIX_EPOCH + Duration::from_secs_f64(1_600_000_000.0 + seconds);
+        let (ntp_sec, ntp_frac) = ntp::ntp_timestamp(wallclock);
+
+        let mut report = SenderReportPacket::new(ssrc);
+        report.ntp_sec = ntp_sec;
+        report.ntp_frac = ntp_frac;
+        report.rtp_timestamp = rtp_timestamp;
+        report
+    }
+
+    fn packet(ssrc: u32, timestamp: u32) -> RtpPacket {
+        let mut packet = test_support::packet(0, timestamp, false, &[0]);
+        packet.header.ssrc = ssrc;
+        packet
+    }
+
+    fn seconds(mapping: &ClockMapping, timestamp: u32) -> f64 {
+        let wallclock = mapping.wallclock(timestamp).unwrap();
+        wallclock.duration_since(UNIX_EPOCH).unwrap().as_secs_f64() - 1_600_000_000.0
+    }
+
+    #[test]
+    fn test_sender_reports() {
+        let mut mapping = ClockMapping::new(90_000);
+        assert_eq!(mapping.wallclock(0), None);
+
+        // The timestamps are unwrapped around the sender report.
+        mapping.on_sender_report(&sender_report(1, 10.0, 0xffff_0000));
+        assert!(mapping.is_synchronized());
+        assert!((seconds(&mapping, 0xffff_0000u32.wrapping_add(90_000)) - 11.0).abs() < 1e-6);
+        assert!((seconds(&mapping, 0xffff_0000 - 45_000) - 9.5).abs() < 1e-6);
+
+        // A sender clock 100 ppm fast is compensated.
+        let timestamp = 0xffff_0000u32.wrapping_add(9_000_900);
+        mapping.on_sender_report(&sender_report(1, 110.0, timestamp));
+        assert!((mapping.drift() - 1.0001).abs() < 1e-9);
+        assert!((seconds(&mapping, timestamp.wrapping_add(90_009)) - 111.0).abs() < 1e-6);
+
+        // A restarted sender is not taken as drift.
+        mapping.on_sender_report(&sender_report(1, 120.0, 0));
+        assert_eq!(mapping.drift(), 1.0);
+    }
+
+    #[test]
+    fn test_wallclock_query() {
+        let mut mapping = ClockMapping::new(90_000);
+        mapping.on_sender_report(&sender_report(1, 100_000.0, 0));
+
+        // Asking about a timestamp far ahead does not change how later ones are unwrapped.
+        assert!((seconds(&mapping, 0x6000_0000) - (100_000.0 + 17_895.697)).abs() < 1e-3);
+        assert!((seconds(&mapping, 0xc000_0000) - (100_000.0 - 11_930.465)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_arrival_fallback() {
+        let mut mapping = ClockMapping::new(8_000);
+        let arrival = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+        mapping.on_packet(1_000, arrival);
+        mapping.on_packet(9_000, arrival + Duration::from_secs(5));
+        assert!(!mapping.is_synchronized());
+        assert!((seconds(&mapping, 5_000) - 0.5).abs() < 1e-6);
+
+        mapping.on_sender_report(&sender_report(1, 30.0, 5_000));
+        assert!(mapping.is_synchronized());
+        assert!((seconds(&mapping, 5_000) - 30.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_lip_sync() {
+        let mut lip_sync = LipSync::new();
+        lip_sync.add_track(1, 8_000);
+        lip_sync.add_track(2, 90_000);
+        assert!(!lip_sync.is_synchronized());
+
+        // The tracks have unrelated timestamps, the sender reports relate them.
+        let audio = sender_report(1, 10.0, 500);
+        let video = sender_report(2, 10.2, 3_000_000);
+        lip_sync.on_rtcp(&RtcpCompoundPacket::new(vec![audio.into(), video.into()]));
+        assert!(lip_sync.is_synchronized());
+
+        assert_eq!(
+            lip_sync.presentation_time(1, 8_500),
+            Some(Duration::from_secs(0))
+        );
+        let video_time = lip_sync.presentation_time(2, 3_000_000 + 72_000).unwrap();
+        assert!((video_time.as_secs_f64() - 0.0).abs() < 1e-6);
+
+        let audio_time = lip_sync.presentation_time(1, 8_500 + 4_000).unwrap();
+        let video_time = lip_sync
+            .presentation_time(2, 3_000_000 + 72_000 + 45_000)
+            .unwrap();
+        assert!((audio_time.as_secs_f64() - 0.5).abs() < 1e-6);
+        assert!((video_time.as_secs_f64() - 0.5).abs() < 1e-6);
+
+        assert_eq!(lip_sync.presentation_time(3, 0), None);
+    }
+
+    #[test]
+    fn test_lip_sync_after_arrival_times() {
+        let mut lip_sync = LipSync::new();
+        lip_sync.add_track(1, 8_000);
+        lip_sync.add_track(2, 90_000);
+
+        // The tracks are mapped from arrival times, hours before the sender clock.
+        let arrival = UNIX_EPOCH + Duration::from_secs(1_599_990_000);
+        lip_sync.on_packet(&packet(1, 1_000), arrival);
+        lip_sync.on_packet(&packet(2, 5_000), arrival);
+        assert_eq!(
+            lip_sync.presentation_time(1, 1_000),
+            Some(Duration::from_secs(0))
+        );
+        let audio_time = lip_sync.presentation_time(1, 5_000).unwrap();
+        assert!((audio_time.as_secs_f64() - 0.5).abs() < 1e-6);
+
+        // The first sender report of the audio re-bases the timeline onto the sender clock,
+        // while the video is still mapped from arrival times.
+        lip_sync.on_packet(&packet(1, 9_000), arrival + Duration::from_secs(1));
+        let audio = sender_report(1, 1.0, 9_000);
+        lip_sync.on_rtcp(&RtcpCompoundPacket::new(vec![audio.into()]));
+        let audio_time = lip_sync.presentation_time(1, 13_000).unwrap();
+        assert!((audio_time.as_secs_f64() - 1.5).abs() < 1e-6);
+        let video_time = lip_sync.presentation_time(2, 5_000 + 135_000).unwrap();
+        assert!((video_time.as_secs_f64() - 1.5).abs() < 1e-6);
+
+        // The sender report of the video moves it 100 ms onto the audio.
+        let video = sender_report(2, 1.6, 5_000 + 135_000);
+        lip_sync.on_rtcp(&RtcpCompoundPacket::new(vec![video.into()]));
+        assert!(lip_sync.is_synchronized());
+        let video_time = lip_sync.presentation_time(2, 5_000 + 135_000).unwrap();
+        assert!((video_time.as_secs_f64() - 1.6).abs() < 1e-6);
+    }
+}
@@ -4,7 +4,7 @@ use std::fmt::{self, Display, Formatter};
 use std::io;
 use std::sync::Arc;
 
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 // use bytes::BytesMut;
 use futures::channel::mpsc::UnboundedSender;
 use tokio_util::codec::{Decoder, Encoder};
@@ -32,6 +32,12 @@ pub mod encoder;
 /// requests and responses.
 const MINIMUM_INFO_LINE_SIZE: usize = 5;
 
+/// The size of the header of an interleaved frame: the `'$'`, the channel and the 16-bit length.
+const INTERLEAVED_HEADER_SIZE: usize = 4;
+
+/// The maximum size of the payload of an interleaved frame, limited by its 16-bit length field.
+pub const MAX_INTERLEAVED_PAYLOAD_SIZE: usize = u16::MAX as usize;
+
 /// The codec that handles encoding requests/responses and decoding requests/responses. Because
 /// servers and clients can both send requests and receive responses, this codec is shared by the
 /// two (as well as proxies).
@@ -117,6 +123,36 @@ impl Codec {
         }
     }
 
+    /// Decodes an interleaved binary frame as described by
+    /// [[RFC7826, Section 14](https://tools.ietf.org/html/rfc7826#section-14)].
+    ///
+    /// The frame starts with a `'$'`, followed by the one byte channel identifier and the length of
+    /// the payload as a 16-bit big-endian integer. The buffer must start with the `'$'`.
+    ///
+    /// If there is not enough data for the whole frame yet, `Ok(None)` is returned and nothing is
+    /// consumed from the buffer.
+    fn decode_interleaved(
+        &mut self,
+        buffer: &mut BytesMut,
+    ) -> Result<Option<<Self as Decoder>::Item>, <Self as Decoder>::Error> {
+        if buffer.len() < INTERLEAVED_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let channel = buffer[1];
+        let length = u16::from_be_bytes([buffer[2], buffer[3]]) as usize;
+
+        if buffer.len() < INTERLEAVED_HEADER_SIZE + length {
+            buffer.reserve(INTERLEAVED_HEADER_SIZE + length - buffer.len());
+            return Ok(None);
+        }
+
+        buffer.split_to(INTERLEAVED_HEADER_SIZE);
+        let payload = buffer.split_to(length);
+        self.send_codec_event(CodecEvent::DecodingEnded);
+        Ok(Some(Message::Interleaved(channel, payload)))
+    }
+
     /// Constructs a new codec without an event sink.
     pub fn new() -> Self {
         Codec {
@@ -157,7 +193,8 @@ impl Decoder for Codec {
     /// Using the internal decoders, this function will attempt to make progress on decoding either
     /// a request or response using the buffer. If neither of the decoders are active, this
     /// function will send a [`CodecEvent::DecodingStarted`] event if the buffer is non-empty after
-    /// removing all preceding newlines.
+    /// removing all preceding newlines. Between messages, a `'$'` starts an interleaved binary
+    /// frame instead.
     ///
     /// The return value of this function can be divided into four parts:
     ///
@@ -186,7 +223,9 @@ impl Decoder for Codec {
                 self.send_codec_event(CodecEvent::DecodingStarted);
             }
 
-            if buffer.len() < MINIMUM_INFO_LINE_SIZE {
+            if buffer.starts_with(b"$") {
+                self.decode_interleaved(buffer)
+            } else if buffer.len() < MINIMUM_INFO_LINE_SIZE {
                 Ok(None)
             } else if buffer.starts_with(b"RTSP/") {
                 self.decode_response(buffer)
@@ -233,13 +272,24 @@ impl Encoder<Message> for Codec {
     ///
     /// Although a [`Result`] is returned, this function will never return an error as the actual
     /// message encoding cannot fail. As a result, `Ok(())` will always be returned.
+    ///
+    /// The payload of an interleaved frame must not be larger than
+    /// [`MAX_INTERLEAVED_PAYLOAD_SIZE`], which the connection checks before queueing the frame.
     fn encode(&mut self, message: Message, buffer: &mut BytesMut) -> Result<(), Self::Error> {
         self.send_codec_event(CodecEvent::EncodingStarted);
 
         match message {
             Message::Request(request) => request::encode(&request, buffer),
             Message::Response(response) => response::encode(&response, buffer),
-            _ => {}
+            Message::Interleaved(channel, payload) => {
+                debug_assert!(payload.len() <= MAX_INTERLEAVED_PAYLOAD_SIZE);
+
+                buffer.reserve(INTERLEAVED_HEADER_SIZE + payload.len());
+                buffer.put_u8(b'$');
+                buffer.put_u8(channel);
+                buffer.put_u16(payload.len() as u16);
+                buffer.extend_from_slice(&payload);
+            }
         }
 
         self.send_codec_event(CodecEvent::EncodingEnded);
@@ -266,10 +316,14 @@ pub enum CodecEvent {
     EncodingStarted,
 }
 
-/// An abstract message type that is either a request or response.
+/// An abstract message type that is either a request, a response or an interleaved binary frame.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[allow(clippy::large_enum_variant)]
 pub enum Message {
+    /// This message is an interleaved binary frame, usually carrying RTP or RTCP, with the channel
+    /// identifier and the payload.
+    Interleaved(u8, BytesMut),
+
     /// This message is a request.
     Request(Request<BytesMut>),
 
@@ -427,6 +481,44 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_codec_decoding_interleaved() {
+        let mut codec = Codec::new();
+        let mut buffer = BytesMut::from(&b"$\x01\x00\x03ab"[..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(buffer.len(), 6);
+
+        buffer.extend_from_slice(b"c$\x00\x00\x00RTSP/2.0 200 OK\r\n\r\n");
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap().unwrap(),
+            Message::Interleaved(1, BytesMut::from("abc"))
+        );
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap().unwrap(),
+            Message::Interleaved(0, BytesMut::new())
+        );
+
+        let mut builder = Response::builder();
+        builder.body(BytesMut::new());
+        let response = builder.build().unwrap();
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap().unwrap(),
+            Message::Response(response)
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_codec_encoding_interleaved() {
+        let mut codec = Codec::new();
+        let mut buffer = BytesMut::new();
+
+        codec
+            .encode(Message::Interleaved(3, BytesMut::from("abc")), &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, BytesMut::from(&b"$\x03\x00\x03abc"[..]));
+    }
+
     #[test]
     fn test_codec_encoding() {
         let mut codec = Codec::new();
@@ -2,7 +2,7 @@ use std::time::Duration;
 use std::str;
 
 use bytes::{BytesMut, Buf};
-use futures::channel::mpsc::{Receiver, UnboundedSender, UnboundedReceiver};
+use futures::channel::mpsc::{Sender, UnboundedSender, UnboundedReceiver};
 use futures::{Future, StreamExt};
 // use futures::future::Fuse;
 use futures::future::FutureExt;
@@ -34,9 +34,12 @@ use crate::proto::rtsp::message::uri::Scheme;
 use crate::proto::rtsp::message::response::{NOT_IMPLEMENTED_RESPONSE, BAD_REQUEST_RESPONSE};
 use crate::proto::rtsp::connection::sender::SenderHandle;
 use crate::proto::rtsp::message::header::types::authenticate::WWWAuthenticate;
+use crate::proto::rtsp::connection::{IncomingRequest, InterleavedChannels};
 
 pub struct MessageHandler{
-    rx_incoming_request: Receiver<(CSeq, Request<BytesMut>)>,
+    /// The consumer of incoming requests, which has to answer them in order. Without one, all
+    /// incoming requests are answered with Not Implemented (501).
+    tx_incoming_request: Option<Sender<(CSeq, IncomingRequest)>>,
     rx_pending_request: UnboundedReceiver<PendingRequestUpdate>,
     continue_wait_duration: Option<Duration>,
 
@@ -60,6 +63,8 @@ pub struct MessageHandler{
 
     www_authenticate: Option<WWWAuthenticate>,
 
+    /// The consumers of interleaved binary frames by channel identifier.
+    interleaved_channels: InterleavedChannels,
 
 }
 
@@ -67,14 +72,15 @@ pub struct MessageHandler{
 impl MessageHandler{
 
     pub fn new(
-        rx_incoming_request: Receiver<(CSeq, Request<BytesMut>)>,
+        tx_incoming_request: Option<Sender<(CSeq, IncomingRequest)>>,
         rx_pending_request: UnboundedReceiver<PendingRequestUpdate>,
         continue_wait_duration: Option<Duration>,
         request_buffer_size: usize,
         sender_handle: SenderHandle,
+        interleaved_channels: InterleavedChannels,
     ) -> Self {
         MessageHandler {
-            rx_incoming_request,
+            tx_incoming_request,
             rx_pending_request,
             continue_wait_duration,
             buffered_requests: HashMap::with_capacity_and_hasher(
@@ -89,6 +95,7 @@ impl MessageHandler{
             requests_allowed: true,
             sender_handle: Some(sender_handle),
             www_authenticate:None,
+            interleaved_channels,
         }
     }
 
@@ -100,8 +107,14 @@ impl MessageHandler{
      ///
      /// If it is a response, it will be matched against a pending request with the same `"CSeq"` (if
      /// it exists).
+     ///
+     /// If it is an interleaved frame, it will be forwarded to the consumer of its channel (if it
+     /// exists).
     pub(crate) fn handle_message(&mut self, message: Message) -> Result<(), RequestReceiverError> {
         match message {
+            Message::Interleaved(channel, payload) => {
+                self.process_interleaved(channel, payload);
+            }
             Message::Request(request) => {
                 if self.requests_allowed {
                     self.process_request(request)?;
@@ -163,6 +176,23 @@ impl MessageHandler{
     }
 
 
+    /// Forwards the payload of an interleaved frame to the consumer registered for its channel.
+    ///
+    /// Frames for channels without a consumer are dropped. If the consumer has gone away, it is
+    /// removed.
+    fn process_interleaved(&mut self, channel: u8, payload: BytesMut) {
+        let mut channels = self
+            .interleaved_channels
+            .lock()
+            .expect("`MessageHandler.interleaved_channels` should not be poisoned");
+
+        if let Entry::Occupied(entry) = channels.entry(channel) {
+            if entry.get().unbounded_send(payload).is_err() {
+                entry.remove();
+            }
+        }
+    }
+
     pub fn process_response(&mut self, response: Response<BytesMut>) {
 
         let mut codec = Codec::new();
@@ -310,114 +340,91 @@ impl MessageHandler{
     }
 
 
-    //
-    // /// Tries to forward any ready requests to the request handler.
-    // ///
-    // /// If `Poll::Ready(Ok(()))` is returned, then all requests that could have been forwarded have
-    // /// been forwarded.
-    // ///
-    // /// If `Poll::Pending` is returned, then channel between the forwarding receiver and the
-    // /// request handler is full, and forwarding will have to be tried again later.
-    // ///
-    // /// If `Err(())` is returned, then the request handler's receiver has been dropped meaning the
-    // /// forwarding receiver can be shutdown.
-    //
-    // fn poll_incoming_request(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ProtocolError>>{
-    //     if let Some(mut incoming_sequence_number) = self.incoming_sequence_number {
-    //         // let test = self.tx_incoming_request;
-    //         // test.
-    //
-    //         while let Some(request) = self.buffered_requests.remove(&incoming_sequence_number) {
-    //             match self
-    //                 .tx_incoming_request
-    //                 .try_send((incoming_sequence_number, request.clone()))
-    //             // .map_err(|_| ())?
-    //             {
-    //                 Ok(_) => {
-    //                     incoming_sequence_number = incoming_sequence_number.wrapping_increment()
-    //                 }
-    //                 Err(_) => {
-    //                     self.buffered_requests
-    //                         .insert(incoming_sequence_number, request);
-    //                     self.incoming_sequence_number = Some(incoming_sequence_number);
-    //                     return Poll::Pending;
-    //                 }
-    //                 _ => {}
-    //             }
-    //         }
-    //
-    //         self.incoming_sequence_number = Some(incoming_sequence_number);
-    //     }
-    //
-    //     Poll::Ready(Ok(()))
-    // }
-}
-
-
-impl Future for MessageHandler
-    // where
-    //     TStream: Stream<Item = Result<Message, ProtocolError>> + Send + 'static,
-{
-    type Output = ();
-
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        // info!("message handler poll");
-
-        self.as_mut().poll_pending_request(cx);
-
-
-
-        if let Some(mut incoming_sequence_number) = self.incoming_sequence_number {
-            // let test = self.tx_incoming_request;
-            // test.
-
-            while let Some(request) = self.buffered_requests.remove(&incoming_sequence_number) {
-
-                if request.uri().scheme() == Some(Scheme::RTSPU) {
-                    self.send_response(incoming_sequence_number, NOT_IMPLEMENTED_RESPONSE.clone());
-                    // return;
+    /// Tries to forward any ready requests to the request handler in `"CSeq"` order.
+    ///
+    /// Requests that cannot be processed at all are answered directly: requests using the
+    /// `"rtspu"` scheme and all requests without a request handler with Not Implemented (501), and
+    /// requests with a body but without a `"Content-Type"` with Bad Request (400).
+    ///
+    /// If `Poll::Ready(Ok(()))` is returned, then all requests that could have been forwarded have
+    /// been forwarded.
+    ///
+    /// If `Poll::Pending` is returned, then channel between the message handler and the request
+    /// handler is full, and forwarding will have to be tried again later.
+    fn poll_incoming_request(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ProtocolError>> {
+        let mut incoming_sequence_number = match self.incoming_sequence_number {
+            Some(incoming_sequence_number) => incoming_sequence_number,
+            None => return Poll::Ready(Ok(())),
+        };
+
+        while self.buffered_requests.contains_key(&incoming_sequence_number) {
+            if let Some(tx_incoming_request) = self.tx_incoming_request.as_mut() {
+                match tx_incoming_request.poll_ready(cx) {
+                    Poll::Ready(Ok(())) => (),
+                    Poll::Ready(Err(_)) => self.tx_incoming_request = None,
+                    Poll::Pending => {
+                        self.incoming_sequence_number = Some(incoming_sequence_number);
+                        return Poll::Pending;
+                    }
                 }
+            }
+
+            let cseq = incoming_sequence_number;
+            let request = self.buffered_requests.remove(&cseq).unwrap();
+            incoming_sequence_number = incoming_sequence_number.wrapping_increment();
 
+            let rejection = if request.uri().scheme() == Some(Scheme::RTSPU) {
+                Some(NOT_IMPLEMENTED_RESPONSE.clone())
+            } else {
                 match request.headers().typed_get::<ContentLength>() {
                     Some(content_length)
                     if *content_length > 0
                         && !request.headers().contains_key(&HeaderName::ContentType) =>
                         {
-                            self.send_response(incoming_sequence_number, BAD_REQUEST_RESPONSE.clone());
+                            Some(BAD_REQUEST_RESPONSE.clone())
                         }
-                    _ => {
-                        // self.reset_continue_timer();
-                        // self.serviced_request = Some((incoming_sequence_number, self.service.call(request)));
-                    }
+                    _ => None,
                 }
+            };
+
+            // Rejections are passed on as well, so that they do not overtake the responses to
+            // earlier requests.
+            let incoming_request = match rejection.clone() {
+                Some(response) => IncomingRequest::Rejected(response),
+                None => IncomingRequest::Request(request),
+            };
+            let forwarded = match self.tx_incoming_request.as_mut() {
+                Some(tx_incoming_request) => {
+                    tx_incoming_request.start_send((cseq, incoming_request)).is_ok()
+                }
+                None => false,
+            };
 
-
-                // match self
-                //     .tx_incoming_request
-                //     .try_send((incoming_sequence_number, request.clone()))
-                // // .map_err(|_| ())?
-                // {
-                //     Ok(_) => {
-                //         incoming_sequence_number = incoming_sequence_number.wrapping_increment()
-                //     }
-                //     Err(_) => {
-                //         self.buffered_requests
-                //             .insert(incoming_sequence_number, request);
-                //         self.incoming_sequence_number = Some(incoming_sequence_number);
-                //         return Poll::Pending;
-                //     }
-                //     _ => {}
-                // }
+            if !forwarded {
+                self.tx_incoming_request = None;
+                let response = rejection.unwrap_or_else(|| NOT_IMPLEMENTED_RESPONSE.clone());
+                self.send_response(cseq, response);
             }
-
-            self.incoming_sequence_number = Some(incoming_sequence_number);
         }
 
-        // Poll::Ready(Ok(()))
+        self.incoming_sequence_number = Some(incoming_sequence_number);
+        Poll::Ready(Ok(()))
+    }
+}
 
 
-        Poll::Pending
-    }
+impl Future for MessageHandler
+    // where
+    //     TStream: Stream<Item = Result<Message, ProtocolError>> + Send + 'static,
+{
+    type Output = ();
 
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // info!("message handler poll");
+
+        let _ = self.as_mut().poll_pending_request(cx);
+        let _ = self.as_mut().poll_incoming_request(cx);
 
-}
\ No newline at end of file
+        Poll::Pending
+    }
+}
@@ -31,9 +31,6 @@ pub struct MessageReceiver<TStream>
     /// A stream of codec events used to reset the decoding timer.
     rx_codec_event: UnboundedReceiver<CodecEvent>,
 
-
-    tx_incoming_request: Sender<(CSeq, Request<BytesMut>)>,
-
     message_handler: Option<MessageHandler>,
     /// How long should we wait before decoding is timed out and the connection is dropped.
     decode_timeout_duration: Duration,
@@ -53,14 +50,12 @@ impl <TStream> MessageReceiver<TStream>
     pub fn new(
         stream: TStream,
         rx_codec_event: UnboundedReceiver<CodecEvent>,
-        tx_incoming_request: Sender<(CSeq, Request<BytesMut>)>,
         message_handler: Option<MessageHandler>,
         decode_timeout_duration: Duration,
     ) -> Self {
         MessageReceiver {
             stream,
             rx_codec_event,
-            tx_incoming_request,
             message_handler,
             decode_timeout_duration,
             // requests_allowed: true,
@@ -111,116 +106,59 @@ impl <TStream> MessageReceiver<TStream>
     //     }
     // }
 
-    pub fn poll_handler(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ProtocolError>> {
-        // if let Some(message_handler) = self.message_handler.as_ref() {
-        //     // If the forwarding receiver is full, then any incoming requests cannot be
-        //     // handled. This also blocks any incoming responses, since we have to process
-        //     // messages as they come.
-        //     if message_handler.is_full() {
-        //         // self.stream = Some(stream);
-        //         return Poll::Pending;
-        //     }
-        //
-        //     // Box::pin(message_handler).poll(cx);
-        // }
-
+    /// Polls the message handler, so that buffered requests are forwarded and pending request
+    /// updates are processed.
+    pub fn poll_handler(&mut self, cx: &mut Context<'_>) {
         if let Some(message_handler) = self.message_handler.as_mut() {
-
-            message_handler.poll_unpin(cx);
-
+            let _ = message_handler.poll_unpin(cx);
         }
-
-        // self.as_mut().message_handler(cx);
-
-
-
-        Poll::Pending
     }
 
+    /// Returns whether the message handler cannot buffer any more requests.
+    fn is_handler_full(&self) -> bool {
+        self.message_handler
+            .as_ref()
+            .map_or(false, MessageHandler::is_full)
+    }
 
-
-        /// Checks if there are any messages to be processed from the internal connection stream.
+    /// Checks if there are any messages to be processed from the internal connection stream.
     ///
-    /// If `Ok(Async::Ready(()))` is returned, then the stream has been closed and no more messages
+    /// If `Poll::Ready(Ok(()))` is returned, then the stream has been closed and no more messages
     /// will be received.
     ///
-    /// If `Ok(Async::NotReady)` is returned, then either there are no more messages to be processed
+    /// If `Poll::Pending` is returned, then either there are no more messages to be processed
     /// from the stream currently, or no messages can currently be accepted.
     ///
-    /// If `Err(`[`ProtocolError`]`)` is returned, then there was a protocol error while trying to
-    /// poll the stream.
-    pub fn poll_stream(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ProtocolError>> {
-
-
-
-
-        let stream_result = self.stream.poll_next_unpin(cx);
-        match stream_result {
-            Poll::Ready(Some(result)) => {
-                match result{
-                    Ok(message) => {
-
-                        info!("Message recieved");
+    /// If `Poll::Ready(Err(`[`ProtocolError`]`))` is returned, then there was a protocol error
+    /// while trying to poll the stream.
+    pub fn poll_stream(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), ProtocolError>> {
+        loop {
+            // If the request buffer is full, then any incoming requests cannot be handled. This
+            // also blocks any incoming responses, since we have to process messages as they come.
+            if self.is_handler_full() {
+                self.poll_handler(cx);
+
+                if self.is_handler_full() {
+                    return Poll::Pending;
+                }
+            }
 
-                        if let Some(message_handler) = self.message_handler.as_mut() {
-                            message_handler.handle_message(message);
+            match self.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(message))) => {
+                    if let Some(message_handler) = self.message_handler.as_mut() {
+                        if let Err(error) = message_handler.handle_message(message) {
+                            error!("dropped incoming request: {}", error);
                         }
-
-                        // if let Message::Request(request) = message.clone(){
-                        //     info!("Message recieved:{}", "request");
-                        // }
-                        // if let Message::Response(response) = message.clone(){
-                        //     info!("Message recieved:{}", "response");
-                        // }
-
-
-
-                        // if let Err(error) = self.as_mut().handle_message(message) {
-                        //     // self.as_mut().handle_request_receiver_error(error);
-                        // }
-                    },
-
-                    Err(p) =>{
-                        error!("poll_stream error:{}", p);
-                        return Poll::Ready(Err(p))
                     }
-
                 }
+                Poll::Ready(Some(Err(error))) => {
+                    error!("poll_stream error:{}", error);
+                    return Poll::Ready(Err(error));
+                }
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
             }
-            Poll::Pending => {
-                // self.stream = Some(stream);
-                return Poll::Pending;
-            }
-            Poll::Ready(None) => return Poll::Ready(Ok(())),
-            // Poll::Ready(Err(error)) => {
-            //     self.handle_protocol_error(&error);
-            //     return Poll::Ready(Err(error));
-            // }
         }
-
-
-
-        Poll::Pending
-        // let s = self.stream.take(1);
-        // match s
-        // {
-        //
-        //     // Ok(Async::Ready(Some(message))) => {
-        //     //     if let Err(error) = self.handle_message(message) {
-        //     //         self.handle_request_receiver_error(error);
-        //     //     }
-        //     // }
-        //     // Ok(Async::NotReady) => {
-        //     //     self.stream = Some(stream);
-        //     //     return Ok(Async::NotReady);
-        //     // }
-        //     // Ok(Async::Ready(None)) => return Ok(Async::Ready(())),
-        //     // Err(error) => {
-        //     //     self.handle_protocol_error(&error);
-        //     //     return Err(error);
-        //     // }
-        // }
-
     }
 }
 
@@ -231,71 +169,19 @@ impl <TStream> Future for MessageReceiver<TStream>
     type Output = ();
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        // info!("message receiver poll");
-
-        match self.as_mut().poll_handler(cx) {
-            Poll::Ready(Ok(message))=> {
-                // match message{
-                //     Message::Request(request) =>{
-                //         info!("request received");
-                //     },
-                //     Message::Response(request) =>{
-                //         info!("response received");
-                //     },
-                //     _ => {}
-                // }
-                info!("poll_handler ok");
-            },
-
-            Poll::Ready(Err(err))=> {
-                info!("poll_handler error");
-            },
-
-            // Ok(Async::Ready(_)) | Err(_) => {
-            //     self.shutdown_receiving();
-            // }
-            _ => (),
+        while let Poll::Ready(Some(event)) = self.rx_codec_event.poll_next_unpin(cx) {
+            self.handle_codec_event(event);
         }
 
-        match self.as_mut().poll_stream(cx) {
-            Poll::Ready(Ok(message))=> {
-                // match message{
-                //     Message::Request(request) =>{
-                //         info!("request received");
-                //     },
-                //     Message::Response(request) =>{
-                //         info!("response received");
-                //     },
-                //     _ => {}
-                // }
-                info!("message received ok");
-            },
+        let result = self.poll_stream(cx);
 
-            Poll::Ready(Err(err))=> {
-                info!("message received error");
-            },
+        // Requests buffered while reading the stream are forwarded right away.
+        self.poll_handler(cx);
 
-            // Ok(Async::Ready(_)) | Err(_) => {
-            //     self.shutdown_receiving();
-            // }
-            _ => (),
+        match result {
+            Poll::Ready(_) => Poll::Ready(()),
+            Poll::Pending => Poll::Pending,
         }
-
-
-        // let is_shutdown = match self.message_handler.as_mut() {
-        //     Some(message_handler) => match message_handler.poll_unpin(cx) {
-        //         Poll::Ready(Err(_)) => self.as_mut().shutdown_forwarding_receiver(),
-        //         Poll::Ready(Ok(_)) if self.is_request_receiver_shutdown() => {
-        //             self.as_mut().shutdown_forwarding_receiver()
-        //         }
-        //         _ => false,
-        //     },
-        //     None => self.is_shutdown(),
-        // };
-
-        // self.poll_codec_events(cx);
-
-        Poll::Pending
     }
 }
 
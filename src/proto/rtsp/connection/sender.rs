@@ -46,31 +46,37 @@ impl<TSink> MessageSender<TSink>
     }
 
 
-    /// Tries to send the given message through the internal sink.
+    /// Stops accepting new messages. The messages already queued are still sent, after which the
+    /// sink is closed and the sender finishes.
+    pub fn close(&mut self) {
+        self.rx_outgoing_message.close();
+    }
+
+    /// Tries to send the buffered message through the internal sink.
     ///
     /// If `Poll::Ready(Ok(()))` is returned, then the message was successfully sent through the
-    /// sink. It may not have been flushed yet though, this will happen at a later point.
+    /// sink (or there was none). It may not have been flushed yet though, this will happen at a
+    /// later point.
     ///
     /// If `Poll::Pending` is returned, then the sink is unable to accept the message at this
-    /// time, probably because it is full. The message will be buffered temporarily until we can try
-    /// to send it through the sink again.
+    /// time, probably because it is full. The message stays buffered until we can try to send it
+    /// through the sink again.
     ///
     /// If `Err(`[`ProtocolError`]`)` is returned, there was an error trying to send the message
     /// through the sink.
-    fn try_send_message(mut self: Pin<&mut Self>, cx: &mut Context<'_>, message: Message) -> Poll<Result<(), ProtocolError>> {
-        // debug_assert!(self.buffered_message.is_none());
-
-        if let Poll::Pending = self.sink.poll_ready_unpin(cx){
-            return Poll::Pending;
+    fn try_send_buffered_message(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ProtocolError>> {
+        if self.buffered_message.is_none() {
+            return Poll::Ready(Ok(()));
         }
 
-        //TODO duplicate send
-        if let Err(rr) = self.sink.start_send_unpin(message.clone()) {
-            self.buffered_message = Some(message);
-            return Poll::Pending;
+        match self.sink.poll_ready_unpin(cx) {
+            Poll::Ready(Ok(())) => {
+                let message = self.buffered_message.take().unwrap();
+                Poll::Ready(self.sink.start_send_unpin(message))
+            }
+            Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+            Poll::Pending => Poll::Pending,
         }
-
-        Poll::Ready(Ok(()))
     }
 }
 
@@ -84,15 +90,16 @@ impl <TSink> Future for MessageSender<TSink>
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // info!("message sender poll");
 
-        if let Some(buffered_message) = self.buffered_message.take() {
-            return self.as_mut().try_send_message(cx, buffered_message);
-        }
-
         loop {
+            match self.as_mut().try_send_buffered_message(cx) {
+                Poll::Ready(Ok(())) => (),
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => return Poll::Pending,
+            }
+
             match self
                 .rx_outgoing_message
                 .poll_next_unpin(cx)
-            // .expect("`Sender.rx_outgoing_message` should not error")
             {
                 Poll::Ready(Some(mut message)) => {
                     match message {
@@ -102,27 +109,21 @@ impl <TSink> Future for MessageSender<TSink>
                         Message::Response(ref mut response) => {
                             response.headers_mut().typed_insert(Date::new());
                         }
+                        Message::Interleaved(..) => (),
                     }
 
-                    self.as_mut().try_send_message(cx, message);
+                    self.buffered_message = Some(message);
                 }
                 Poll::Pending => {
-                    // ready!(self.sink.poll_flush_unpin(cx));
-
-                    let num = match self.sink.poll_flush_unpin(cx) {
-                        Poll::Ready(t) => t,
-                        Poll::Pending => return Poll::Pending,
+                    return match self.sink.poll_flush_unpin(cx) {
+                        Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+                        _ => Poll::Pending,
                     };
-
-
-                    return Poll::Pending;
                 }
-                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                // All messages have been sent, so the sink can be closed.
+                Poll::Ready(None) => return self.sink.poll_close_unpin(cx),
             }
         }
-
-
-        Poll::Pending
     }
 }
 
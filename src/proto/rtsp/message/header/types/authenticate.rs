@@ -16,6 +16,8 @@ use itertools::Itertools;
 use std::iter::{once, FromIterator};
 use log4rs::append::Append;
 use std::ops::{Deref, DerefMut};
+use crypto::digest::Digest;
+use crypto::sha2::{Sha256, Sha512Trunc256};
 // use md5::{Md5, Digest};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -24,6 +26,106 @@ pub enum AuthenticateMethod{
     Basic,
 }
 
+/// The hash algorithms of [RFC7616](https://tools.ietf.org/html/rfc7616#section-6.1) used to
+/// compute a `Digest` response.
+///
+/// The `-sess` variants additionally mix the nonce and the client nonce into `H(A1)`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DigestAlgorithm {
+    MD5,
+    MD5Sess,
+    SHA256,
+    SHA256Sess,
+    SHA512_256,
+    SHA512_256Sess,
+}
+
+impl DigestAlgorithm {
+    /// Returns a `&str` representation of the algorithm as used in the `"algorithm"` parameter.
+    pub fn as_str(&self) -> &str {
+        use self::DigestAlgorithm::*;
+
+        match self {
+            MD5 => "MD5",
+            MD5Sess => "MD5-sess",
+            SHA256 => "SHA-256",
+            SHA256Sess => "SHA-256-sess",
+            SHA512_256 => "SHA-512-256",
+            SHA512_256Sess => "SHA-512-256-sess",
+        }
+    }
+
+    /// Returns whether this is one of the `-sess` algorithms.
+    pub fn is_session(&self) -> bool {
+        use self::DigestAlgorithm::*;
+
+        match self {
+            MD5Sess | SHA256Sess | SHA512_256Sess => true,
+            MD5 | SHA256 | SHA512_256 => false,
+        }
+    }
+
+    /// Returns the lowercase hex encoded hash of the given value.
+    pub fn hash(&self, value: &[u8]) -> String {
+        use self::DigestAlgorithm::*;
+
+        match self {
+            MD5 | MD5Sess => format!("{:x}", md5::compute(value)),
+            SHA256 | SHA256Sess => {
+                let mut hasher = Sha256::new();
+                hasher.input(value);
+                hasher.result_str()
+            }
+            SHA512_256 | SHA512_256Sess => {
+                let mut hasher = Sha512Trunc256::new();
+                hasher.input(value);
+                hasher.result_str()
+            }
+        }
+    }
+
+    /// Returns how strongly the algorithm is preferred when a server offers several challenges.
+    fn strength(&self) -> u8 {
+        use self::DigestAlgorithm::*;
+
+        match self {
+            MD5 | MD5Sess => 0,
+            SHA256 | SHA256Sess => 1,
+            SHA512_256 | SHA512_256Sess => 2,
+        }
+    }
+}
+
+impl Default for DigestAlgorithm {
+    fn default() -> Self {
+        DigestAlgorithm::MD5
+    }
+}
+
+impl Display for DigestAlgorithm {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.as_str())
+    }
+}
+
+impl<'algorithm> TryFrom<&'algorithm str> for DigestAlgorithm {
+    type Error = WWWAuthenticateError;
+
+    fn try_from(value: &'algorithm str) -> Result<Self, Self::Error> {
+        use self::DigestAlgorithm::*;
+
+        match value.to_uppercase().as_str() {
+            "MD5" => Ok(MD5),
+            "MD5-SESS" => Ok(MD5Sess),
+            "SHA-256" => Ok(SHA256),
+            "SHA-256-SESS" => Ok(SHA256Sess),
+            "SHA-512-256" => Ok(SHA512_256),
+            "SHA-512-256-SESS" => Ok(SHA512_256Sess),
+            _ => Err(WWWAuthenticateError::InvalidAuthenticateItem),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct WWWAuthenticateSingle{
     pub(crate) method: AuthenticateMethod,
@@ -80,16 +182,185 @@ impl Authorization{
     //     // H(A2) = MD5(method:digestURI)
     //     // response = MD5(H(A1):nonce:H(A2)
     pub fn gen_digest_response(username:&str, password :&str, realm :&str, nonce :&str, method:&str, uri:&str,) -> String{
-        let ha1 = format!("{:x}", md5::compute(format!("{}:{}:{}", username, realm, password)));
-        let ha2 = format!("{:x}", md5::compute(format!("{}:{}", method, uri)));
+        let ha1 = format!("{:x}", md5::compute(format!("{}:{}:{}", username, realm, password).as_bytes()));
+        let ha2 = format!("{:x}", md5::compute(format!("{}:{}", method, uri).as_bytes()));
 
-        let result = format!("{:x}",  md5::compute(format!("{}:{}:{}", ha1, nonce, ha2)));
+        let result = format!("{:x}",  md5::compute(format!("{}:{}:{}", ha1, nonce, ha2).as_bytes()));
 
         result
     }
+
+    /// Answers a `Digest` challenge as described by
+    /// [RFC7616](https://tools.ietf.org/html/rfc7616#section-3.4).
+    ///
+    /// If the challenge offers a quality of protection, `qop=auth` is preferred over `qop=auth-int`
+    /// and `nonce_count` and `cnonce` are included in the response. The nonce count has to be
+    /// incremented by the caller for every request sent with the same nonce. Without a quality of
+    /// protection, the response is computed as described by RFC2069 which older cameras expect.
+    ///
+    /// Returns an error if the challenge uses an unknown algorithm or only offers unknown qualities
+    /// of protection.
+    #[allow(clippy::too_many_arguments)]
+    pub fn gen_digest_authorization(
+        challenge: &WWWAuthenticateSingle,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        body: &[u8],
+        nonce_count: u32,
+        cnonce: &str,
+    ) -> Result<Authorization, WWWAuthenticateError> {
+        let algorithm = challenge
+            .algorithm()
+            .ok_or(WWWAuthenticateError::InvalidAuthenticateItem)?;
+        let realm = challenge.realm();
+        let nonce = challenge.nonce();
+        let qop = match challenge.qop_options() {
+            options if options.is_empty() => None,
+            options if options.contains(&"auth") => Some("auth"),
+            options if options.contains(&"auth-int") => Some("auth-int"),
+            _ => return Err(WWWAuthenticateError::InvalidAuthenticateItem),
+        };
+
+        let mut ha1 = algorithm.hash(format!("{}:{}:{}", username, realm, password).as_bytes());
+
+        if algorithm.is_session() {
+            ha1 = algorithm.hash(format!("{}:{}:{}", ha1, nonce, cnonce).as_bytes());
+        }
+
+        let ha2 = match qop {
+            Some("auth-int") => {
+                let body = algorithm.hash(body);
+                algorithm.hash(format!("{}:{}:{}", method, uri, body).as_bytes())
+            }
+            _ => algorithm.hash(format!("{}:{}", method, uri).as_bytes()),
+        };
+
+        let response = match qop {
+            Some(qop) => algorithm.hash(format!(
+                "{}:{}:{:08x}:{}:{}:{}",
+                ha1, nonce, nonce_count, cnonce, qop, ha2
+            ).as_bytes()),
+            None => algorithm.hash(format!("{}:{}:{}", ha1, nonce, ha2).as_bytes()),
+        };
+
+        let mut parts = LinkedHashSet::new();
+        parts.insert(AuthenticatePart::Username(username.to_string()));
+        parts.insert(AuthenticatePart::Realm(realm));
+        parts.insert(AuthenticatePart::Nonce(nonce));
+        parts.insert(AuthenticatePart::Uri(uri.to_string()));
+
+        if challenge.has_algorithm() {
+            parts.insert(AuthenticatePart::Algorithm(algorithm));
+        }
+
+        parts.insert(AuthenticatePart::Response(response));
+
+        if let Some(opaque) = challenge.opaque() {
+            parts.insert(AuthenticatePart::Opaque(opaque));
+        }
+
+        if let Some(qop) = qop {
+            parts.insert(AuthenticatePart::Qop(qop.to_string()));
+            parts.insert(AuthenticatePart::NonceCount(nonce_count));
+            parts.insert(AuthenticatePart::Cnonce(cnonce.to_string()));
+        }
+
+        Ok(Authorization {
+            method: AuthenticateMethod::Digest,
+            parts,
+        })
+    }
+}
+
+impl WWWAuthenticateSingle {
+    /// Returns the digest algorithm of the challenge.
+    ///
+    /// The algorithm defaults to `MD5` if the challenge does not name one. If the named algorithm
+    /// is not supported, `None` is returned.
+    pub fn algorithm(&self) -> Option<DigestAlgorithm> {
+        let mut algorithm = Some(DigestAlgorithm::default());
+
+        for part in self.parts.iter() {
+            match part {
+                AuthenticatePart::Algorithm(value) => algorithm = Some(*value),
+                AuthenticatePart::Extension(name, _) if name.eq_ignore_ascii_case("algorithm") => {
+                    algorithm = None
+                }
+                _ => (),
+            }
+        }
+
+        algorithm
+    }
+
+    /// Returns whether the challenge explicitly names its algorithm, in which case the
+    /// `"Authorization"` header has to repeat it.
+    pub fn has_algorithm(&self) -> bool {
+        self.parts.iter().any(|part| match part {
+            AuthenticatePart::Algorithm(_) => true,
+            AuthenticatePart::Extension(name, _) => name.eq_ignore_ascii_case("algorithm"),
+            _ => false,
+        })
+    }
+
+    /// Returns the opaque value of the challenge which has to be returned unchanged.
+    pub fn opaque(&self) -> Option<String> {
+        self.parts.iter().find_map(|part| match part {
+            AuthenticatePart::Opaque(value) => Some(value.clone()),
+            _ => None,
+        })
+    }
+
+    /// Returns the qualities of protection offered by the challenge, e.g. `["auth", "auth-int"]`.
+    pub fn qop_options(&self) -> Vec<&str> {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                AuthenticatePart::Qop(value) => Some(value.as_str()),
+                _ => None,
+            })
+            .flat_map(|value| value.split(','))
+            .map(syntax::trim_whitespace)
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+}
+
+impl WWWAuthenticate {
+    /// Returns the challenge a client should answer, preferring `Digest` over `Basic` since the
+    /// latter sends the password in the clear. If there are several `Digest` challenges, the one
+    /// with the strongest supported algorithm is chosen.
+    pub fn preferred(&self) -> Option<&WWWAuthenticateSingle> {
+        let digest = self
+            .iter()
+            .filter(|challenge| challenge.method == AuthenticateMethod::Digest)
+            .filter_map(|challenge| challenge.algorithm().map(|algorithm| (algorithm, challenge)))
+            .fold(None, |best: Option<(DigestAlgorithm, &WWWAuthenticateSingle)>, current| {
+                match best {
+                    Some(best) if best.0.strength() >= current.0.strength() => Some(best),
+                    _ => Some(current),
+                }
+            })
+            .map(|(_, challenge)| challenge);
+
+        digest.or_else(|| {
+            self.iter()
+                .find(|challenge| challenge.method == AuthenticateMethod::Basic)
+        })
+    }
 }
 
 impl WWWAuthenticateSingle{
+    /// Returns whether the challenge has `stale=true`, meaning the previous request was rejected
+    /// only because its nonce expired and the credentials themselves were correct.
+    pub fn stale(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| *part == AuthenticatePart::Stale(true))
+    }
+
     pub fn realm(&self) -> String{
         for part in self.parts.iter(){
             if let AuthenticatePart::Realm(value) = part{
@@ -186,21 +457,13 @@ pub fn decoce_www_authenticate(value:&str) -> Result<WWWAuthenticateSingle, WWWA
     {
         "DIGEST" =>{
 
-            let authenticates = v[1].split(',');
-            for auth_part in authenticates {
-                let part = AuthenticatePart::try_from(syntax::trim_whitespace(auth_part))?;
-                auth_parts.insert(part);
-            }
+            decode_parts(v[1], &mut auth_parts)?;
             return Ok(WWWAuthenticateSingle{ method:AuthenticateMethod::Digest, parts:auth_parts})
         },
 
         "BASIC" =>{
 
-            let authenticates = v[1].split(',');
-            for auth_part in authenticates {
-                let part = AuthenticatePart::try_from(syntax::trim_whitespace(auth_part))?;
-                auth_parts.insert(part);
-            }
+            decode_parts(v[1], &mut auth_parts)?;
             return Ok(WWWAuthenticateSingle{ method:AuthenticateMethod::Basic, parts:auth_parts})
         },
 
@@ -232,22 +495,14 @@ pub fn decode_autherization(value:&str) -> Result<Authorization, WWWAuthenticate
     {
         "DIGEST" =>{
 
-            let authenticates = v[1].split(',');
-            for auth_part in authenticates {
-                let part = AuthenticatePart::try_from(syntax::trim_whitespace(auth_part))?;
-                auth_parts.insert(part);
-            }
+            decode_parts(v[1], &mut auth_parts)?;
             // return Ok(Authorization::Digest(auth_parts))
             return Ok(Authorization{ method:AuthenticateMethod::Digest, parts:auth_parts})
         },
 
         "BASIC" =>{
 
-            let authenticates = v[1].split(',');
-            for auth_part in authenticates {
-                let part = AuthenticatePart::try_from(syntax::trim_whitespace(auth_part))?;
-                auth_parts.insert(part);
-            }
+            decode_parts(v[1], &mut auth_parts)?;
             // return Ok(Authorization::Basic(auth_parts))
             return Ok(Authorization{ method:AuthenticateMethod::Basic, parts:auth_parts})
         },
@@ -262,6 +517,23 @@ pub fn decode_autherization(value:&str) -> Result<Authorization, WWWAuthenticate
 }
 
 
+/// Decodes the comma separated parameters of a challenge or credentials. Commas inside of quoted
+/// values, e.g. `qop="auth,auth-int"`, do not separate parameters.
+fn decode_parts(
+    value: &str,
+    auth_parts: &mut LinkedHashSet<AuthenticatePart>,
+) -> Result<(), WWWAuthenticateError> {
+    for auth_part in syntax::split_unquoted(value.as_bytes(), b',') {
+        let auth_part = syntax::trim_bytes_whitespace(auth_part);
+
+        if !auth_part.is_empty() {
+            auth_parts.insert(AuthenticatePart::try_from(auth_part)?);
+        }
+    }
+
+    Ok(())
+}
+
 /// A possible error value when converting to a [`CSeq`] from [`HeaderName`]s.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 #[non_exhaustive]
@@ -353,6 +625,16 @@ pub enum AuthenticatePart {
 
     BasicResponse(String),
 
+    Algorithm(DigestAlgorithm),
+
+    Opaque(String),
+
+    Qop(String),
+
+    Cnonce(String),
+
+    NonceCount(u32),
+
     Extension(String, String)
 
 
@@ -385,6 +667,14 @@ impl AuthenticatePart {
             Uri(value) => format!("uri=\"{}\"", value),
             Response(value) => format!("response=\"{}\"", value),
             BasicResponse(value) => format!("{}", value),
+            Algorithm(value) => format!("algorithm={}", value),
+            Opaque(value) => format!("opaque=\"{}\"", value),
+            // A single quality of protection is a token and sent unquoted, a list of them has to
+            // be quoted.
+            Qop(value) if syntax::is_token(value.as_bytes()) => format!("qop={}", value),
+            Qop(value) => format!("qop=\"{}\"", value),
+            Cnonce(value) => format!("cnonce=\"{}\"", value),
+            NonceCount(value) => format!("nc={:08x}", value),
             Extension(name, value)=> format!("{}=\"{}\"", name, value),
 
         }
@@ -471,17 +761,36 @@ impl<'auth_part> TryFrom<&'auth_part [u8]> for AuthenticatePart {
         use self::AuthenticatePart::*;
 
         let s = unsafe { str::from_utf8_unchecked(value) };
-        let r = Regex::new("(\\w+)=\"(.+)\"").unwrap();
+        // Parameter values are usually quoted, but `stale` and `algorithm` are tokens.
+        let r = Regex::new("(\\w+)=(?:\"(.*)\"|(\\S+))").unwrap();
         if let Some(caps) = r.captures(s){
 
             let item = &caps[1];
-            let val = &caps[2];
+            let val = caps.get(2).or_else(|| caps.get(3)).unwrap().as_str();
 
             // info!("AuthenticatePart-{} {}", item, val);
 
             match item.to_lowercase().as_str() {
                 "realm" => return Ok(AuthenticatePart::Realm(val.to_string())),
                 "nonce" => return Ok(AuthenticatePart::Nonce(val.to_string())),
+                "username" => return Ok(AuthenticatePart::Username(val.to_string())),
+                "uri" => return Ok(AuthenticatePart::Uri(val.to_string())),
+                "response" => return Ok(AuthenticatePart::Response(val.to_string())),
+                "opaque" => return Ok(AuthenticatePart::Opaque(val.to_string())),
+                "qop" => return Ok(AuthenticatePart::Qop(val.to_string())),
+                "cnonce" => return Ok(AuthenticatePart::Cnonce(val.to_string())),
+                "nc" => {
+                    return u32::from_str_radix(val, 16)
+                        .map(AuthenticatePart::NonceCount)
+                        .map_err(|_| WWWAuthenticateError::InvalidAuthenticateItem)
+                },
+                "algorithm" => {
+                    // Unknown algorithms are kept, so that the challenge can be recognized as
+                    // unsupported instead of failing to decode the whole header.
+                    return Ok(DigestAlgorithm::try_from(val)
+                        .map(AuthenticatePart::Algorithm)
+                        .unwrap_or_else(|_| AuthenticatePart::Extension(item.to_string(), val.to_string())))
+                },
                 "stale" => {
                     let stale = bool::from_str(val.to_lowercase().as_str()) ;
                     match stale{
@@ -536,6 +845,151 @@ mod test {
 
     }
 
+    // The examples of RFC7616, section 3.9.1.
+    #[test]
+    fn test_digest_authorization_rfc7616() {
+        let username = "Mufasa";
+        let password = "Circle of Life";
+        let uri = "/dir/index.html";
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+        let challenge = decoce_www_authenticate(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", algorithm=SHA-256, \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+        )
+        .unwrap();
+        assert_eq!(challenge.algorithm(), Some(DigestAlgorithm::SHA256));
+        assert_eq!(challenge.qop_options(), vec!["auth", "auth-int"]);
+
+        let authorization = Authorization::gen_digest_authorization(
+            &challenge, username, password, "GET", uri, b"", 1, cnonce,
+        )
+        .unwrap();
+        assert!(authorization.parts.contains(&AuthenticatePart::Response(
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1".to_string()
+        )));
+
+        let value = authorization.parts.iter().map(AuthenticatePart::to_string).join(", ");
+        assert_eq!(
+            value,
+            "username=\"Mufasa\", realm=\"http-auth@example.org\", \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", uri=\"/dir/index.html\", \
+             algorithm=SHA-256, \
+             response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\", qop=auth, nc=00000001, \
+             cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\""
+        );
+
+        let challenge = decoce_www_authenticate(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", algorithm=MD5, \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+        )
+        .unwrap();
+        let authorization = Authorization::gen_digest_authorization(
+            &challenge, username, password, "GET", uri, b"", 1, cnonce,
+        )
+        .unwrap();
+        assert!(authorization.parts.contains(&AuthenticatePart::Response(
+            "8ca523f5e9506fed4657c9700eebdbec".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_digest_authorization_auth_int() {
+        let challenge = decoce_www_authenticate(
+            "Digest realm=\"camera\", qop=\"auth-int\", nonce=\"abc\"",
+        )
+        .unwrap();
+
+        // The entity body is hashed as is, even if it is not UTF-8.
+        let body = [0x00, 0xff, 0xfe, 0x80];
+        let authorization = Authorization::gen_digest_authorization(
+            &challenge, "admin", "secret", "SET_PARAMETER", "rtsp://camera/", &body, 1, "xyz",
+        )
+        .unwrap();
+
+        let ha1 = format!("{:x}", md5::compute("admin:camera:secret"));
+        let body_hash = format!("{:x}", md5::compute(&body[..]));
+        let ha2 = format!("{:x}", md5::compute(format!("SET_PARAMETER:rtsp://camera/:{}", body_hash)));
+        let response = format!(
+            "{:x}",
+            md5::compute(format!("{}:abc:00000001:xyz:auth-int:{}", ha1, ha2))
+        );
+        assert!(authorization.parts.contains(&AuthenticatePart::Response(response)));
+    }
+
+    #[test]
+    fn test_digest_authorization_rfc2069() {
+        let challenge = decoce_www_authenticate(
+            "Digest realm=\"IP Camera(C6496)\", nonce=\"06f85e0128e71d0b8c48373762bb62ba\"",
+        )
+        .unwrap();
+        let authorization = Authorization::gen_digest_authorization(
+            &challenge,
+            "admin",
+            "dm666666",
+            "SETUP",
+            "rtsp://192.168.30.224:554/h264/ch1/main/av_stream",
+            b"",
+            1,
+            "0a4f113b",
+        )
+        .unwrap();
+
+        assert!(authorization.parts.contains(&AuthenticatePart::Response(
+            "dc3170611844e63d0705eb9cf9d42e7f".to_string()
+        )));
+        assert!(!authorization
+            .parts
+            .iter()
+            .any(|part| matches!(part, AuthenticatePart::NonceCount(_))));
+    }
+
+    #[test]
+    fn test_unsupported_digest_algorithm() {
+        let unsupported = decoce_www_authenticate(
+            "Digest realm=\"camera\", nonce=\"abc\", algorithm=SHA-1024",
+        )
+        .unwrap();
+        let sha256 = decoce_www_authenticate(
+            "Digest realm=\"camera\", nonce=\"abc\", algorithm=SHA-256",
+        )
+        .unwrap();
+        let md5 = decoce_www_authenticate("Digest realm=\"camera\", nonce=\"abc\"").unwrap();
+
+        assert_eq!(unsupported.algorithm(), None);
+
+        let authenticate =
+            WWWAuthenticate::from_iter(vec![unsupported.clone(), md5.clone(), sha256.clone()]);
+        assert_eq!(authenticate.preferred(), Some(&sha256));
+
+        let authenticate = WWWAuthenticate::from_iter(vec![unsupported]);
+        assert_eq!(authenticate.preferred(), None);
+    }
+
+    #[test]
+    fn test_preferred_challenge() {
+        let basic = decoce_www_authenticate("Basic realm=\"IP Camera(C6496)\"").unwrap();
+        let digest = decoce_www_authenticate(
+            "Digest realm=\"IP Camera(C6496)\", nonce=\"06f85e0128e71d0b8c48373762bb62ba\", stale=\"TRUE\"",
+        )
+        .unwrap();
+
+        let authenticate = WWWAuthenticate::from_iter(vec![basic.clone(), digest.clone()]);
+        assert_eq!(
+            AuthenticatePart::try_from("stale=FALSE"),
+            Ok(AuthenticatePart::Stale(false))
+        );
+        assert_eq!(authenticate.preferred(), Some(&digest));
+        assert!(authenticate.preferred().unwrap().stale());
+
+        let authenticate = WWWAuthenticate::from_iter(vec![basic.clone()]);
+        assert_eq!(authenticate.preferred(), Some(&basic));
+        assert!(!basic.stale());
+    }
+
     #[test]
     fn test_digest_response_fail() {
         let username = "admin";
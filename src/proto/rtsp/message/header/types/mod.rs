@@ -6,6 +6,7 @@ pub use self::date::Date;
 pub use self::expires::Expires;
 pub use self::public::Public;
 pub use self::session::Session;
+pub use self::transport::Transport;
 
 pub mod accept;
 pub mod accept_ranges;
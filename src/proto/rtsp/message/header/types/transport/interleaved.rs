@@ -20,6 +20,39 @@ use crate::proto::rtsp::message::syntax;
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Interleaved(RangeInclusive<u8>);
 
+impl Interleaved {
+    /// Constructs a new interleaved parameter using the channels from `channel_start` to
+    /// `channel_end` (inclusive).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtsp::header::types::transport::Interleaved;
+    ///
+    /// assert_eq!(Interleaved::new(0, 1).to_string(), "0-1");
+    /// assert_eq!(Interleaved::new(2, 2).to_string(), "2");
+    /// ```
+    pub fn new(channel_start: u8, channel_end: u8) -> Self {
+        Interleaved(RangeInclusive::new(channel_start, channel_end))
+    }
+
+    /// Returns the channel used for RTP when the range is an RTP/RTCP pair.
+    pub fn rtp_channel(&self) -> u8 {
+        *self.0.start()
+    }
+
+    /// Returns the channel used for RTCP when the range is an RTP/RTCP pair.
+    ///
+    /// If only a single channel was given, `None` is returned.
+    pub fn rtcp_channel(&self) -> Option<u8> {
+        if self.0.start() == self.0.end() {
+            None
+        } else {
+            Some(*self.0.end())
+        }
+    }
+}
+
 impl Deref for Interleaved {
     type Target = RangeInclusive<u8>;
 
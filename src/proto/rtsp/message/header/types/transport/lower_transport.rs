@@ -0,0 +1,162 @@
+use std::convert::{Infallible, TryFrom};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The lower-level transport protocol of a transport specification.
+///
+/// The lower transport is the optional third component of the transport identifier, e.g. the
+/// `"TCP"` in `"RTP/AVP/TCP"`. If it is not specified, the default for the given profile is
+/// assumed, which is `"UDP"` for all of the RTP profiles.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LowerTransport {
+    /// The media stream is carried over TCP, either interleaved on the RTSP connection or on a
+    /// separate connection.
+    TCP,
+
+    /// The media stream is carried over UDP.
+    UDP,
+}
+
+impl LowerTransport {
+    /// Returns a `&str` representation of the lower transport.
+    ///
+    /// The returned string is always uppercase.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtsp::header::types::transport::LowerTransport;
+    ///
+    /// assert_eq!(LowerTransport::TCP.as_str(), "TCP");
+    /// assert_eq!(LowerTransport::UDP.as_str(), "UDP");
+    /// ```
+    pub fn as_str(&self) -> &str {
+        use self::LowerTransport::*;
+
+        match self {
+            TCP => "TCP",
+            UDP => "UDP",
+        }
+    }
+}
+
+impl AsRef<[u8]> for LowerTransport {
+    fn as_ref(&self) -> &[u8] {
+        self.as_str().as_bytes()
+    }
+}
+
+impl AsRef<str> for LowerTransport {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Default for LowerTransport {
+    fn default() -> Self {
+        LowerTransport::UDP
+    }
+}
+
+impl Display for LowerTransport {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.as_str())
+    }
+}
+
+impl From<LowerTransport> for String {
+    fn from(value: LowerTransport) -> Self {
+        value.to_string()
+    }
+}
+
+impl PartialEq<[u8]> for LowerTransport {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_str().as_bytes().eq_ignore_ascii_case(other)
+    }
+}
+
+impl PartialEq<LowerTransport> for [u8] {
+    fn eq(&self, other: &LowerTransport) -> bool {
+        self.eq_ignore_ascii_case(other.as_str().as_bytes())
+    }
+}
+
+impl<'lower_transport> PartialEq<&'lower_transport [u8]> for LowerTransport {
+    fn eq(&self, other: &&'lower_transport [u8]) -> bool {
+        self.as_str().as_bytes().eq_ignore_ascii_case(other)
+    }
+}
+
+impl<'lower_transport> PartialEq<LowerTransport> for &'lower_transport [u8] {
+    fn eq(&self, other: &LowerTransport) -> bool {
+        self.eq_ignore_ascii_case(other.as_str().as_bytes())
+    }
+}
+
+impl PartialEq<str> for LowerTransport {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str().eq_ignore_ascii_case(other)
+    }
+}
+
+impl PartialEq<LowerTransport> for str {
+    fn eq(&self, other: &LowerTransport) -> bool {
+        self.eq_ignore_ascii_case(other.as_str())
+    }
+}
+
+impl<'lower_transport> PartialEq<&'lower_transport str> for LowerTransport {
+    fn eq(&self, other: &&'lower_transport str) -> bool {
+        self.as_str().eq_ignore_ascii_case(other)
+    }
+}
+
+impl<'lower_transport> PartialEq<LowerTransport> for &'lower_transport str {
+    fn eq(&self, other: &LowerTransport) -> bool {
+        self.eq_ignore_ascii_case(other.as_str())
+    }
+}
+
+impl<'lower_transport> TryFrom<&'lower_transport [u8]> for LowerTransport {
+    type Error = LowerTransportError;
+
+    fn try_from(value: &'lower_transport [u8]) -> Result<Self, Self::Error> {
+        use self::LowerTransport::*;
+
+        if value.eq_ignore_ascii_case(b"TCP") {
+            Ok(TCP)
+        } else if value.eq_ignore_ascii_case(b"UDP") {
+            Ok(UDP)
+        } else {
+            Err(LowerTransportError)
+        }
+    }
+}
+
+impl<'lower_transport> TryFrom<&'lower_transport str> for LowerTransport {
+    type Error = LowerTransportError;
+
+    fn try_from(value: &'lower_transport str) -> Result<Self, Self::Error> {
+        LowerTransport::try_from(value.as_bytes())
+    }
+}
+
+/// A possible error value when converting to a [`LowerTransport`] from a `&[u8]` or `&str`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct LowerTransportError;
+
+impl Display for LowerTransportError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "unknown lower transport")
+    }
+}
+
+impl Error for LowerTransportError {}
+
+impl From<Infallible> for LowerTransportError {
+    fn from(_: Infallible) -> Self {
+        LowerTransportError
+    }
+}
@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
 use std::ops::Deref;
 
 use base64::{self, DecodeError as Base64DecodeError};
@@ -24,6 +25,18 @@ impl Deref for MIKEY {
     }
 }
 
+impl Display for MIKEY {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", base64::encode(&self.0))
+    }
+}
+
+impl From<Vec<u8>> for MIKEY {
+    fn from(value: Vec<u8>) -> Self {
+        MIKEY(value)
+    }
+}
+
 impl<'mikey> TryFrom<&'mikey [u8]> for MIKEY {
     type Error = MIKEYError;
 
@@ -32,4 +45,12 @@ impl<'mikey> TryFrom<&'mikey [u8]> for MIKEY {
     }
 }
 
+impl<'mikey> TryFrom<&'mikey str> for MIKEY {
+    type Error = MIKEYError;
+
+    fn try_from(value: &'mikey str) -> Result<Self, Self::Error> {
+        MIKEY::try_from(value.as_bytes())
+    }
+}
+
 pub type MIKEYError = Base64DecodeError;
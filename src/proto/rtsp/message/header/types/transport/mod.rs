@@ -1,18 +1,220 @@
+use std::convert::TryFrom;
+use std::iter::{once, FromIterator};
+use std::ops::{Deref, DerefMut};
+
+use itertools::Itertools;
+
+use crate::proto::rtsp::message::header::map::TypedHeader;
+use crate::proto::rtsp::message::header::name::HeaderName;
+use crate::proto::rtsp::message::header::value::HeaderValue;
+use crate::proto::rtsp::message::syntax;
+
 pub use self::address::{Address, AddressError, ExtensionAddress, HostPort};
 pub use self::connection::{Connection, ConnectionError};
 pub use self::delivery_type::{DeliveryType, DeliveryTypeError};
 pub use self::interleaved::{Interleaved, InterleavedError};
 pub use self::layers::{Layers, LayersError};
+pub use self::lower_transport::{LowerTransport, LowerTransportError};
 pub use self::mikey::{MIKEY, MIKEYError};
 pub use self::mode::{Mode, ModeError};
+pub use self::port::{PortRange, PortRangeError};
 pub use self::setup::{Setup, SetupError};
+pub use self::spec::{TransportError, TransportSpec};
 
 mod address;
 mod connection;
 mod delivery_type;
 mod interleaved;
 mod layers;
+mod lower_transport;
 mod mikey;
 mod mode;
+mod port;
 mod setup;
+mod spec;
+
+/// The `"Transport"` typed header as described by
+/// [RFC2326](https://tools.ietf.org/html/rfc2326#section-12.39) and
+/// [RFC7826](https://tools.ietf.org/html/rfc7826#section-18.54).
+///
+/// The transport specifications are kept in order of preference.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Transport(Vec<TransportSpec>);
+
+impl Transport {
+    /// Constructs a new header with no transport specifications by default.
+    pub fn new() -> Self {
+        Transport::default()
+    }
+}
+
+impl Deref for Transport {
+    type Target = Vec<TransportSpec>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Transport {
+    fn deref_mut(&mut self) -> &mut Vec<TransportSpec> {
+        &mut self.0
+    }
+}
+
+impl From<TransportSpec> for Transport {
+    fn from(value: TransportSpec) -> Self {
+        Transport(vec![value])
+    }
+}
+
+impl FromIterator<TransportSpec> for Transport {
+    fn from_iter<TIterator>(iterator: TIterator) -> Self
+    where
+        TIterator: IntoIterator<Item = TransportSpec>,
+    {
+        Transport(Vec::from_iter(iterator))
+    }
+}
+
+impl TypedHeader for Transport {
+    type DecodeError = TransportError;
+
+    /// Converts the raw header values to the [`Transport`] header type. Based on the syntax
+    /// provided by [RFC7826](https://tools.ietf.org/html/rfc7826#section-20), this header has the
+    /// following syntax:
+    ///
+    /// ```text
+    /// Transport = "Transport" HCOLON transport-spec *(COMMA transport-spec)
+    /// transport-spec = transport-id *parameter
+    /// transport-id = trans-id-rtp / other-trans
+    /// trans-id-rtp = "RTP/" profile ["/" lower-transport]
+    /// profile = "AVP" / "SAVP" / "AVPF" / "SAVPF" / token
+    /// lower-transport = "TCP" / "UDP" / token
+    /// other-trans = token *("/" token)
+    /// parameter = SEMI trn-par-name [EQUAL trn-par-value]
+    /// ```
+    ///
+    /// Commas inside of quoted parameter values (e.g. `mode="PLAY, RECORD"`) do not separate
+    /// transport specifications.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::Transport;
+    /// use rtsp::header::types::transport::{Interleaved, PortRange, TransportSpec};
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let raw_header: Vec<HeaderValue> = vec![];
+    /// assert_eq!(Transport::decode(&mut raw_header.iter()).unwrap(), None);
+    ///
+    /// let typed_header = vec![
+    ///     TransportSpec::udp_unicast(PortRange::pair(63994).unwrap()),
+    ///     TransportSpec::tcp_interleaved(Interleaved::new(0, 1)),
+    /// ].into_iter().collect::<Transport>();
+    /// let raw_header = vec![HeaderValue::try_from(
+    ///     "RTP/AVP;unicast;client_port=63994-63995, RTP/AVP/TCP;unicast;interleaved=0-1"
+    /// ).unwrap()];
+    /// assert_eq!(
+    ///     Transport::decode(&mut raw_header.iter()).unwrap(),
+    ///     Some(typed_header)
+    /// );
+    /// ```
+    fn decode<'header, Iter>(values: &mut Iter) -> Result<Option<Self>, Self::DecodeError>
+    where
+        Iter: Iterator<Item = &'header HeaderValue>,
+    {
+        let mut specs = Vec::new();
+        let mut present = false;
+
+        for value in values {
+            for part in syntax::split_unquoted(value.as_str().as_bytes(), b',') {
+                specs.push(TransportSpec::try_from(syntax::trim_bytes_whitespace(part))?);
+            }
+
+            present = true;
+        }
+
+        if present {
+            Ok(Some(Transport(specs)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Converts the [`Transport`] type to raw header values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::Transport;
+    /// use rtsp::header::types::transport::{PortRange, TransportSpec};
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let typed_header = Transport::from(TransportSpec::udp_unicast(PortRange::pair(63994).unwrap()));
+    /// let expected_raw_header = vec![
+    ///     HeaderValue::try_from("RTP/AVP;unicast;client_port=63994-63995").unwrap()
+    /// ];
+    /// let mut raw_header = vec![];
+    /// typed_header.encode(&mut raw_header);
+    /// assert_eq!(raw_header, expected_raw_header);
+    /// ```
+    fn encode<Target>(&self, values: &mut Target)
+    where
+        Target: Extend<HeaderValue>,
+    {
+        // Unsafe Justification
+        //
+        // Header values must be valid UTF-8 without unprintable ASCII-US characters. All of the
+        // transport parameters are either validated tokens, quoted strings validated as `qdtext`,
+        // numbers or Base64, none of which can contain newlines.
+        let value = self.iter().map(TransportSpec::to_string).join(", ");
+        values.extend(once(unsafe { HeaderValue::from_string_unchecked(value) }));
+    }
+
+    /// Returns the statically assigned [`HeaderName`] for this header.
+    fn header_name() -> &'static HeaderName {
+        &HeaderName::Transport
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::proto::rtsp::message::header::map::{HeaderMap, HeaderMapExtension};
+    use crate::proto::rtsp::message::header::name::HeaderName;
+    use crate::proto::rtsp::message::header::types::transport::{PortRange, Transport, TransportSpec};
+    use crate::proto::rtsp::message::header::value::HeaderValue;
+
+    #[test]
+    fn test_transport_typed_get_insert() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::Transport,
+            HeaderValue::try_from(
+                "RTP/AVP;unicast;client_port=63994-63995;server_port=8218-8219;ssrc=5F3A21C0",
+            )
+            .unwrap(),
+        );
+
+        let transport = headers.typed_get::<Transport>().unwrap();
+        assert_eq!(transport.len(), 1);
+        assert_eq!(transport[0].server_port(), Some(&PortRange::new(8218, 8219).unwrap()));
 
+        let mut headers = HeaderMap::new();
+        headers.typed_insert(Transport::from(TransportSpec::udp_unicast(
+            PortRange::pair(5000).unwrap(),
+        )));
+        assert_eq!(
+            headers.get(&HeaderName::Transport).unwrap().as_str(),
+            "RTP/AVP;unicast;client_port=5000-5001"
+        );
+    }
+}
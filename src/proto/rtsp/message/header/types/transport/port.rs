@@ -0,0 +1,190 @@
+use std::convert::{Infallible, TryFrom};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write};
+use std::ops::{Deref, RangeInclusive};
+
+use crate::proto::rtsp::message::syntax;
+
+/// A port or a range of ports as used by the `"port"`, `"client_port"` and `"server_port"`
+/// transport header parameters.
+///
+/// For RTP based transports the range usually consists of two ports, the first being used for RTP
+/// and the second one for RTCP, e.g. `"client_port=63994-63995"`. A single port is also allowed in
+/// which case the start and the end of the range are equal.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PortRange(RangeInclusive<u16>);
+
+impl PortRange {
+    /// Constructs a new port range from `start` to `end` (inclusive).
+    ///
+    /// Returns an error if `end` is smaller than `start`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtsp::header::types::transport::PortRange;
+    ///
+    /// let range = PortRange::new(63994, 63995).unwrap();
+    /// assert_eq!(range.to_string(), "63994-63995");
+    /// assert!(PortRange::new(63995, 63994).is_err());
+    /// ```
+    pub fn new(start: u16, end: u16) -> Result<Self, PortRangeError> {
+        if end < start {
+            Err(PortRangeError::InvalidRange)
+        } else {
+            Ok(PortRange(RangeInclusive::new(start, end)))
+        }
+    }
+
+    /// Constructs a new port range consisting of a single port.
+    pub fn single(port: u16) -> Self {
+        PortRange(RangeInclusive::new(port, port))
+    }
+
+    /// Constructs the usual RTP/RTCP port pair, `port` and `port + 1`.
+    ///
+    /// Returns an error if `port` is the largest possible port.
+    pub fn pair(port: u16) -> Result<Self, PortRangeError> {
+        let end = port.checked_add(1).ok_or(PortRangeError::Overflow)?;
+        Ok(PortRange(RangeInclusive::new(port, end)))
+    }
+
+    /// Returns the first port of the range, used for RTP when the range is an RTP/RTCP pair.
+    pub fn rtp_port(&self) -> u16 {
+        *self.0.start()
+    }
+
+    /// Returns the second port of the range, used for RTCP when the range is an RTP/RTCP pair.
+    ///
+    /// If the range is a single port, `None` is returned.
+    pub fn rtcp_port(&self) -> Option<u16> {
+        if self.0.start() == self.0.end() {
+            None
+        } else {
+            Some(*self.0.end())
+        }
+    }
+}
+
+impl Deref for PortRange {
+    type Target = RangeInclusive<u16>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Display for PortRange {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        self.0.start().fmt(formatter)?;
+
+        if self.0.start() != self.0.end() {
+            formatter.write_char('-')?;
+            self.0.end().fmt(formatter)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<PortRange> for String {
+    fn from(value: PortRange) -> Self {
+        value.to_string()
+    }
+}
+
+impl<'port> TryFrom<&'port [u8]> for PortRange {
+    type Error = PortRangeError;
+
+    fn try_from(value: &'port [u8]) -> Result<Self, Self::Error> {
+        let (port_start, value) = parse_port(value)?;
+
+        if value.is_empty() {
+            return Ok(PortRange::single(port_start));
+        }
+
+        let value = syntax::trim_bytes_whitespace_left(value);
+
+        if !value.starts_with(b"-") {
+            return Err(PortRangeError::InvalidCharacter);
+        }
+
+        let value = syntax::trim_bytes_whitespace_left(&value[1..]);
+        let (port_end, value) = parse_port(value)?;
+
+        if !value.is_empty() {
+            return Err(PortRangeError::InvalidCharacter);
+        }
+
+        PortRange::new(port_start, port_end)
+    }
+}
+
+impl<'port> TryFrom<&'port str> for PortRange {
+    type Error = PortRangeError;
+
+    fn try_from(value: &'port str) -> Result<Self, Self::Error> {
+        PortRange::try_from(value.as_bytes())
+    }
+}
+
+/// A possible error value when converting to [`PortRange`] from a `&[u8]` or `&str`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum PortRangeError {
+    /// The parameter was empty.
+    Empty,
+
+    /// An invalid character was used in the parameter. Only decimal digits are allowed.
+    InvalidCharacter,
+
+    /// The end of the range was smaller than its start.
+    InvalidRange,
+
+    /// One of the ports was a valid number, but it was too large to fit in a `u16`.
+    Overflow,
+}
+
+impl Display for PortRangeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::PortRangeError::*;
+
+        match self {
+            Empty => write!(formatter, "empty port parameter"),
+            InvalidCharacter => write!(formatter, "invalid port parameter character"),
+            InvalidRange => write!(formatter, "invalid port range"),
+            Overflow => write!(formatter, "port overflow"),
+        }
+    }
+}
+
+impl Error for PortRangeError {}
+
+impl From<Infallible> for PortRangeError {
+    fn from(_: Infallible) -> Self {
+        PortRangeError::Empty
+    }
+}
+
+fn parse_port(value: &[u8]) -> Result<(u16, &[u8]), PortRangeError> {
+    let mut port = 0u16;
+    let mut digits_found = 0;
+
+    for byte in value {
+        if !byte.is_ascii_digit() {
+            break;
+        }
+
+        port = port.checked_mul(10).ok_or(PortRangeError::Overflow)?;
+        port = port
+            .checked_add((byte - b'0').into())
+            .ok_or(PortRangeError::Overflow)?;
+        digits_found += 1;
+    }
+
+    if digits_found == 0 {
+        Err(PortRangeError::Empty)
+    } else {
+        Ok((port, &value[digits_found..]))
+    }
+}
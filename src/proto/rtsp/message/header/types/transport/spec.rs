@@ -0,0 +1,941 @@
+use std::convert::{Infallible, TryFrom};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str;
+
+use itertools::Itertools;
+use linked_hash_set::LinkedHashSet;
+
+use crate::proto::rtsp::message::header::types::transport::{
+    mode, Address, AddressError, Connection, ConnectionError, DeliveryType, DeliveryTypeError,
+    Interleaved, InterleavedError, Layers, LayersError, LowerTransport, LowerTransportError,
+    MIKEYError, Mode, ModeError, PortRange, PortRangeError, Setup, SetupError, MIKEY,
+};
+use crate::proto::rtsp::message::syntax;
+
+/// A single transport specification of a `"Transport"` header.
+///
+/// A transport specification consists of a transport identifier (e.g. `"RTP/AVP/TCP"`) followed by
+/// a list of parameters, as described by
+/// [[RFC2326, Section 12.39](https://tools.ietf.org/html/rfc2326#section-12.39)] and
+/// [[RFC7826, Section 18.54](https://tools.ietf.org/html/rfc7826#section-18.54)]. Both the
+/// RTSP/1.0 (`"destination"`, `"source"`) and RTSP/2.0 (`"dest_addr"`, `"src_addr"`) address
+/// parameters are understood when decoding. Parameters that are not known are preserved as
+/// extensions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransportSpec {
+    protocol: String,
+    profile: String,
+    lower_transport: Option<LowerTransport>,
+    delivery_type: Option<DeliveryType>,
+    destination: Option<Vec<Address>>,
+    source: Vec<Address>,
+    interleaved: Option<Interleaved>,
+    append: bool,
+    ttl: Option<u8>,
+    layers: Option<Layers>,
+    port: Option<PortRange>,
+    client_port: Option<PortRange>,
+    server_port: Option<PortRange>,
+    ssrc: Vec<u32>,
+    modes: LinkedHashSet<Mode>,
+    setup: Option<Setup>,
+    connection: Option<Connection>,
+    mikey: Option<MIKEY>,
+    extensions: Vec<(String, Option<String>)>,
+}
+
+impl TransportSpec {
+    /// Constructs a new transport specification with the given protocol and profile and no
+    /// parameters.
+    ///
+    /// Both the protocol and the profile must be tokens, they are converted to uppercase.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtsp::header::types::transport::TransportSpec;
+    ///
+    /// let spec = TransportSpec::new("rtp", "avp").unwrap();
+    /// assert_eq!(spec.to_string(), "RTP/AVP");
+    /// ```
+    pub fn new(protocol: &str, profile: &str) -> Result<Self, TransportError> {
+        if !syntax::is_token(protocol.as_bytes()) || !syntax::is_token(profile.as_bytes()) {
+            return Err(TransportError::InvalidTransportID);
+        }
+
+        Ok(TransportSpec {
+            protocol: protocol.to_ascii_uppercase(),
+            profile: profile.to_ascii_uppercase(),
+            lower_transport: None,
+            delivery_type: None,
+            destination: None,
+            source: Vec::new(),
+            interleaved: None,
+            append: false,
+            ttl: None,
+            layers: None,
+            port: None,
+            client_port: None,
+            server_port: None,
+            ssrc: Vec::new(),
+            modes: LinkedHashSet::new(),
+            setup: None,
+            connection: None,
+            mikey: None,
+            extensions: Vec::new(),
+        })
+    }
+
+    /// Constructs an `"RTP/AVP"` transport specification with no parameters.
+    pub fn rtp_avp() -> Self {
+        TransportSpec::new("RTP", "AVP").unwrap()
+    }
+
+    /// Constructs the transport specification a client sends to receive RTP over UDP on the
+    /// given port range, e.g. `"RTP/AVP;unicast;client_port=63994-63995"`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtsp::header::types::transport::{PortRange, TransportSpec};
+    ///
+    /// let spec = TransportSpec::udp_unicast(PortRange::pair(63994).unwrap());
+    /// assert_eq!(spec.to_string(), "RTP/AVP;unicast;client_port=63994-63995");
+    /// ```
+    pub fn udp_unicast(client_port: PortRange) -> Self {
+        let mut spec = TransportSpec::rtp_avp();
+        spec.delivery_type = Some(DeliveryType::Unicast);
+        spec.client_port = Some(client_port);
+        spec
+    }
+
+    /// Constructs the transport specification a client sends to receive RTP interleaved on the
+    /// RTSP connection, e.g. `"RTP/AVP/TCP;unicast;interleaved=0-1"`.
+    pub fn tcp_interleaved(interleaved: Interleaved) -> Self {
+        let mut spec = TransportSpec::rtp_avp();
+        spec.lower_transport = Some(LowerTransport::TCP);
+        spec.delivery_type = Some(DeliveryType::Unicast);
+        spec.interleaved = Some(interleaved);
+        spec
+    }
+
+    /// The transport protocol, e.g. `"RTP"`. The returned string is always uppercase.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// The profile of the transport protocol, e.g. `"AVP"`. The returned string is always
+    /// uppercase.
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// The lower transport if it was explicitly given.
+    pub fn lower_transport(&self) -> Option<LowerTransport> {
+        self.lower_transport
+    }
+
+    pub fn lower_transport_mut(&mut self) -> &mut Option<LowerTransport> {
+        &mut self.lower_transport
+    }
+
+    /// Returns whether the media is carried over TCP, which is the case when the lower transport
+    /// is `"TCP"` or the stream is interleaved.
+    pub fn is_tcp(&self) -> bool {
+        self.lower_transport == Some(LowerTransport::TCP) || self.interleaved.is_some()
+    }
+
+    pub fn delivery_type(&self) -> Option<DeliveryType> {
+        self.delivery_type
+    }
+
+    pub fn delivery_type_mut(&mut self) -> &mut Option<DeliveryType> {
+        &mut self.delivery_type
+    }
+
+    /// The destination addresses (`"destination"` or `"dest_addr"` parameter).
+    ///
+    /// A `"destination"` parameter without a value is present with no addresses, meaning the
+    /// address of the client itself.
+    pub fn destination(&self) -> Option<&[Address]> {
+        self.destination.as_deref()
+    }
+
+    pub fn destination_mut(&mut self) -> &mut Option<Vec<Address>> {
+        &mut self.destination
+    }
+
+    /// The source addresses (`"source"` or `"src_addr"` parameter).
+    pub fn source(&self) -> &[Address] {
+        &self.source
+    }
+
+    pub fn source_mut(&mut self) -> &mut Vec<Address> {
+        &mut self.source
+    }
+
+    pub fn interleaved(&self) -> Option<&Interleaved> {
+        self.interleaved.as_ref()
+    }
+
+    pub fn interleaved_mut(&mut self) -> &mut Option<Interleaved> {
+        &mut self.interleaved
+    }
+
+    pub fn append(&self) -> bool {
+        self.append
+    }
+
+    pub fn append_mut(&mut self) -> &mut bool {
+        &mut self.append
+    }
+
+    /// The multicast time-to-live.
+    pub fn ttl(&self) -> Option<u8> {
+        self.ttl
+    }
+
+    pub fn ttl_mut(&mut self) -> &mut Option<u8> {
+        &mut self.ttl
+    }
+
+    pub fn layers(&self) -> Option<Layers> {
+        self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut Option<Layers> {
+        &mut self.layers
+    }
+
+    /// The multicast port range (`"port"` parameter).
+    pub fn port(&self) -> Option<&PortRange> {
+        self.port.as_ref()
+    }
+
+    pub fn port_mut(&mut self) -> &mut Option<PortRange> {
+        &mut self.port
+    }
+
+    pub fn client_port(&self) -> Option<&PortRange> {
+        self.client_port.as_ref()
+    }
+
+    pub fn client_port_mut(&mut self) -> &mut Option<PortRange> {
+        &mut self.client_port
+    }
+
+    pub fn server_port(&self) -> Option<&PortRange> {
+        self.server_port.as_ref()
+    }
+
+    pub fn server_port_mut(&mut self) -> &mut Option<PortRange> {
+        &mut self.server_port
+    }
+
+    /// The synchronization sources the server will use. RTSP/1.0 only allows a single SSRC,
+    /// whereas RTSP/2.0 allows a `"/"` separated list.
+    pub fn ssrc(&self) -> &[u32] {
+        &self.ssrc
+    }
+
+    pub fn ssrc_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.ssrc
+    }
+
+    /// The modes to be supported for this session. If empty, `"PLAY"` is assumed.
+    pub fn modes(&self) -> &LinkedHashSet<Mode> {
+        &self.modes
+    }
+
+    pub fn modes_mut(&mut self) -> &mut LinkedHashSet<Mode> {
+        &mut self.modes
+    }
+
+    pub fn setup(&self) -> Option<Setup> {
+        self.setup
+    }
+
+    pub fn setup_mut(&mut self) -> &mut Option<Setup> {
+        &mut self.setup
+    }
+
+    pub fn connection(&self) -> Option<Connection> {
+        self.connection
+    }
+
+    pub fn connection_mut(&mut self) -> &mut Option<Connection> {
+        &mut self.connection
+    }
+
+    pub fn mikey(&self) -> Option<&MIKEY> {
+        self.mikey.as_ref()
+    }
+
+    pub fn mikey_mut(&mut self) -> &mut Option<MIKEY> {
+        &mut self.mikey
+    }
+
+    /// Parameters that are not known to this implementation, in the order they were given.
+    pub fn extensions(&self) -> &[(String, Option<String>)] {
+        &self.extensions
+    }
+
+    /// Adds an extension parameter.
+    ///
+    /// The name must be a token and the value, if given, must either be a token or a quoted
+    /// string.
+    pub fn add_extension(
+        &mut self,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<(), TransportError> {
+        if !syntax::is_token(name.as_bytes()) {
+            return Err(TransportError::InvalidParameter);
+        }
+
+        if let Some(value) = value {
+            if !is_parameter_value(value.as_bytes()) {
+                return Err(TransportError::InvalidParameter);
+            }
+        }
+
+        self.extensions
+            .push((name.to_string(), value.map(str::to_string)));
+        Ok(())
+    }
+
+    /// Removes all extension parameters with the given name (case-insensitive).
+    pub fn remove_extension(&mut self, name: &str) {
+        self.extensions
+            .retain(|(extension, _)| !extension.eq_ignore_ascii_case(name));
+    }
+
+    fn set_parameter(&mut self, name: &[u8], value: Option<&[u8]>) -> Result<(), TransportError> {
+        // Unsafe: the whole header value is valid UTF-8 and the parameter was split on ASCII-US
+        // characters.
+        let name = unsafe { str::from_utf8_unchecked(name) }.to_ascii_lowercase();
+
+        match (name.as_str(), value) {
+            ("unicast", None) => {
+                set_once(&mut self.delivery_type, DeliveryType::Unicast)?;
+            }
+            ("multicast", None) => {
+                set_once(&mut self.delivery_type, DeliveryType::Multicast)?;
+            }
+            ("append", None) => {
+                self.append = true;
+            }
+            ("destination", None) => {
+                set_once(&mut self.destination, Vec::new())?;
+            }
+            ("destination", Some(value)) | ("dest_addr", Some(value)) => {
+                set_once(&mut self.destination, parse_addresses(value)?)?;
+            }
+            ("source", Some(value)) | ("src_addr", Some(value)) => {
+                if !self.source.is_empty() {
+                    return Err(TransportError::DuplicateParameter);
+                }
+
+                self.source = parse_addresses(value)?;
+            }
+            ("interleaved", Some(value)) => {
+                set_once(&mut self.interleaved, Interleaved::try_from(value)?)?;
+            }
+            ("ttl", Some(value)) => {
+                let ttl = parse_decimal(value).ok_or(TransportError::InvalidTTL)?;
+                set_once(&mut self.ttl, ttl)?;
+            }
+            ("layers", Some(value)) => {
+                let layers = parse_decimal(value).ok_or(LayersError)?;
+                set_once(&mut self.layers, Layers::try_from(layers)?)?;
+            }
+            ("port", Some(value)) => {
+                set_once(&mut self.port, PortRange::try_from(value)?)?;
+            }
+            ("client_port", Some(value)) => {
+                set_once(&mut self.client_port, PortRange::try_from(value)?)?;
+            }
+            ("server_port", Some(value)) => {
+                set_once(&mut self.server_port, PortRange::try_from(value)?)?;
+            }
+            ("ssrc", Some(value)) => {
+                if !self.ssrc.is_empty() {
+                    return Err(TransportError::DuplicateParameter);
+                }
+
+                for ssrc in value.split(|&byte| byte == b'/') {
+                    self.ssrc.push(parse_ssrc(syntax::trim_bytes_whitespace(ssrc))?);
+                }
+            }
+            ("mode", Some(value)) => {
+                if !self.modes.is_empty() {
+                    return Err(TransportError::DuplicateParameter);
+                }
+
+                if value.starts_with(b"\"") {
+                    self.modes = mode::parse_modes(value)?;
+                } else {
+                    self.modes.insert(Mode::try_from(value)?);
+                }
+            }
+            ("setup", Some(value)) => {
+                set_once(&mut self.setup, Setup::try_from(value)?)?;
+            }
+            ("connection", Some(value)) => {
+                set_once(&mut self.connection, Connection::try_from(value)?)?;
+            }
+            ("mikey", Some(value)) => {
+                set_once(&mut self.mikey, MIKEY::try_from(value)?)?;
+            }
+            ("unicast", Some(_))
+            | ("multicast", Some(_))
+            | ("append", Some(_))
+            | ("dest_addr", None)
+            | ("source", None)
+            | ("src_addr", None)
+            | ("interleaved", None)
+            | ("ttl", None)
+            | ("layers", None)
+            | ("port", None)
+            | ("client_port", None)
+            | ("server_port", None)
+            | ("ssrc", None)
+            | ("mode", None)
+            | ("setup", None)
+            | ("connection", None)
+            | ("mikey", None) => return Err(TransportError::InvalidParameter),
+            (_, value) => {
+                if !syntax::is_token(name.as_bytes()) {
+                    return Err(TransportError::InvalidParameter);
+                }
+
+                let value = match value {
+                    Some(value) if is_parameter_value(value) => {
+                        // Unsafe: see above.
+                        Some(unsafe { str::from_utf8_unchecked(value) }.to_string())
+                    }
+                    Some(_) => return Err(TransportError::InvalidParameter),
+                    None => None,
+                };
+
+                self.extensions.push((name, value));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for TransportSpec {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}/{}", self.protocol, self.profile)?;
+
+        if let Some(lower_transport) = self.lower_transport {
+            write!(formatter, "/{}", lower_transport)?;
+        }
+
+        if let Some(delivery_type) = self.delivery_type {
+            write!(formatter, ";{}", delivery_type)?;
+        }
+
+        match self.destination.as_deref() {
+            Some([]) => write!(formatter, ";destination")?,
+            Some(destination) => {
+                write_addresses(formatter, "destination", "dest_addr", destination)?
+            }
+            None => (),
+        }
+        write_addresses(formatter, "source", "src_addr", &self.source)?;
+
+        if let Some(interleaved) = self.interleaved.as_ref() {
+            write!(formatter, ";interleaved={}", interleaved)?;
+        }
+
+        if self.append {
+            write!(formatter, ";append")?;
+        }
+
+        if let Some(ttl) = self.ttl {
+            write!(formatter, ";ttl={}", ttl)?;
+        }
+
+        if let Some(layers) = self.layers {
+            write!(formatter, ";layers={}", *layers)?;
+        }
+
+        if let Some(port) = self.port.as_ref() {
+            write!(formatter, ";port={}", port)?;
+        }
+
+        if let Some(client_port) = self.client_port.as_ref() {
+            write!(formatter, ";client_port={}", client_port)?;
+        }
+
+        if let Some(server_port) = self.server_port.as_ref() {
+            write!(formatter, ";server_port={}", server_port)?;
+        }
+
+        if !self.ssrc.is_empty() {
+            let ssrc = self
+                .ssrc
+                .iter()
+                .map(|ssrc| format!("{:08X}", ssrc))
+                .join("/");
+            write!(formatter, ";ssrc={}", ssrc)?;
+        }
+
+        if !self.modes.is_empty() {
+            let modes = self.modes.iter().map(Mode::as_str).join(", ");
+            write!(formatter, ";mode=\"{}\"", modes)?;
+        }
+
+        if let Some(setup) = self.setup {
+            write!(formatter, ";setup={}", setup)?;
+        }
+
+        if let Some(connection) = self.connection {
+            write!(formatter, ";connection={}", connection)?;
+        }
+
+        if let Some(mikey) = self.mikey.as_ref() {
+            write!(formatter, ";MIKEY={}", mikey)?;
+        }
+
+        for (name, value) in &self.extensions {
+            match value {
+                Some(value) => write!(formatter, ";{}={}", name, value)?,
+                None => write!(formatter, ";{}", name)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<TransportSpec> for String {
+    fn from(value: TransportSpec) -> Self {
+        value.to_string()
+    }
+}
+
+impl<'spec> TryFrom<&'spec [u8]> for TransportSpec {
+    type Error = TransportError;
+
+    /// Parses a single transport specification following the syntax:
+    ///
+    /// ```text
+    /// transport-spec = transport-id *parameter
+    /// transport-id = trans-id-rtp / other-trans
+    /// trans-id-rtp = "RTP/" profile ["/" lower-transport]
+    /// other-trans = token *("/" token)
+    /// parameter = SEMI trn-par-name [EQUAL trn-par-value]
+    /// ```
+    fn try_from(value: &'spec [u8]) -> Result<Self, Self::Error> {
+        let mut parts = syntax::split_unquoted(value, b';').into_iter();
+        let transport_id = syntax::trim_bytes_whitespace(parts.next().unwrap());
+
+        if transport_id.is_empty() {
+            return Err(TransportError::Empty);
+        }
+
+        let mut ids = transport_id.split(|&byte| byte == b'/');
+        let protocol = ids.next().unwrap();
+        let profile = ids.next().ok_or(TransportError::InvalidTransportID)?;
+        let lower_transport = ids.next();
+
+        if ids.next().is_some() || !syntax::is_token(protocol) || !syntax::is_token(profile) {
+            return Err(TransportError::InvalidTransportID);
+        }
+
+        // Unsafe: [`syntax::is_token`] ensures that the values are valid ASCII-US.
+        let mut spec = TransportSpec::new(
+            unsafe { str::from_utf8_unchecked(protocol) },
+            unsafe { str::from_utf8_unchecked(profile) },
+        )?;
+
+        if let Some(lower_transport) = lower_transport {
+            spec.lower_transport = Some(LowerTransport::try_from(lower_transport)?);
+        }
+
+        for part in parts {
+            let part = syntax::trim_bytes_whitespace(part);
+
+            // Some servers end the transport specification with a trailing semicolon.
+            if part.is_empty() {
+                continue;
+            }
+
+            match part.iter().position(|&byte| byte == b'=') {
+                Some(index) => spec.set_parameter(
+                    syntax::trim_bytes_whitespace(&part[..index]),
+                    Some(syntax::trim_bytes_whitespace(&part[index + 1..])),
+                )?,
+                None => spec.set_parameter(part, None)?,
+            }
+        }
+
+        Ok(spec)
+    }
+}
+
+impl<'spec> TryFrom<&'spec str> for TransportSpec {
+    type Error = TransportError;
+
+    fn try_from(value: &'spec str) -> Result<Self, Self::Error> {
+        TransportSpec::try_from(value.as_bytes())
+    }
+}
+
+/// A possible error value when converting to a [`TransportSpec`] or a `"Transport"` header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum TransportError {
+    /// The `"dest_addr"` or `"src_addr"` parameter was invalid.
+    Address(AddressError),
+
+    /// The `"connection"` parameter was invalid.
+    Connection(ConnectionError),
+
+    /// The delivery type was invalid.
+    DeliveryType(DeliveryTypeError),
+
+    /// A parameter was given more than once.
+    DuplicateParameter,
+
+    /// The transport specification was empty.
+    Empty,
+
+    /// The `"interleaved"` parameter was invalid.
+    Interleaved(InterleavedError),
+
+    /// A parameter had invalid syntax, e.g. a missing value.
+    InvalidParameter,
+
+    /// The `"ssrc"` parameter was not a 32-bit hexadecimal number.
+    InvalidSSRC,
+
+    /// The transport identifier was not of the form `"protocol/profile[/lower-transport]"`.
+    InvalidTransportID,
+
+    /// The `"ttl"` parameter was not a number fitting in a `u8`.
+    InvalidTTL,
+
+    /// The `"layers"` parameter was invalid.
+    Layers(LayersError),
+
+    /// The lower transport was unknown.
+    LowerTransport(LowerTransportError),
+
+    /// The `"MIKEY"` parameter was not valid Base64.
+    MIKEY(MIKEYError),
+
+    /// The `"mode"` parameter was invalid.
+    Mode(ModeError),
+
+    /// One of the port parameters was invalid.
+    PortRange(PortRangeError),
+
+    /// The `"setup"` parameter was invalid.
+    Setup(SetupError),
+}
+
+impl Display for TransportError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::TransportError::*;
+
+        match self {
+            Address(error) => error.fmt(formatter),
+            Connection(error) => error.fmt(formatter),
+            DeliveryType(error) => error.fmt(formatter),
+            DuplicateParameter => write!(formatter, "duplicate transport parameter"),
+            Empty => write!(formatter, "empty transport specification"),
+            Interleaved(error) => error.fmt(formatter),
+            InvalidParameter => write!(formatter, "invalid transport parameter"),
+            InvalidSSRC => write!(formatter, "invalid ssrc parameter"),
+            InvalidTransportID => write!(formatter, "invalid transport identifier"),
+            InvalidTTL => write!(formatter, "invalid ttl parameter"),
+            Layers(error) => error.fmt(formatter),
+            LowerTransport(error) => error.fmt(formatter),
+            MIKEY(error) => error.fmt(formatter),
+            Mode(error) => error.fmt(formatter),
+            PortRange(error) => error.fmt(formatter),
+            Setup(error) => error.fmt(formatter),
+        }
+    }
+}
+
+impl Error for TransportError {}
+
+impl From<Infallible> for TransportError {
+    fn from(_: Infallible) -> Self {
+        TransportError::Empty
+    }
+}
+
+impl From<AddressError> for TransportError {
+    fn from(value: AddressError) -> Self {
+        TransportError::Address(value)
+    }
+}
+
+impl From<ConnectionError> for TransportError {
+    fn from(value: ConnectionError) -> Self {
+        TransportError::Connection(value)
+    }
+}
+
+impl From<DeliveryTypeError> for TransportError {
+    fn from(value: DeliveryTypeError) -> Self {
+        TransportError::DeliveryType(value)
+    }
+}
+
+impl From<InterleavedError> for TransportError {
+    fn from(value: InterleavedError) -> Self {
+        TransportError::Interleaved(value)
+    }
+}
+
+impl From<LayersError> for TransportError {
+    fn from(value: LayersError) -> Self {
+        TransportError::Layers(value)
+    }
+}
+
+impl From<LowerTransportError> for TransportError {
+    fn from(value: LowerTransportError) -> Self {
+        TransportError::LowerTransport(value)
+    }
+}
+
+impl From<MIKEYError> for TransportError {
+    fn from(value: MIKEYError) -> Self {
+        TransportError::MIKEY(value)
+    }
+}
+
+impl From<ModeError> for TransportError {
+    fn from(value: ModeError) -> Self {
+        TransportError::Mode(value)
+    }
+}
+
+impl From<PortRangeError> for TransportError {
+    fn from(value: PortRangeError) -> Self {
+        TransportError::PortRange(value)
+    }
+}
+
+impl From<SetupError> for TransportError {
+    fn from(value: SetupError) -> Self {
+        TransportError::Setup(value)
+    }
+}
+
+fn set_once<T>(field: &mut Option<T>, value: T) -> Result<(), TransportError> {
+    if field.is_some() {
+        Err(TransportError::DuplicateParameter)
+    } else {
+        *field = Some(value);
+        Ok(())
+    }
+}
+
+fn parse_decimal(value: &[u8]) -> Option<u8> {
+    if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    // Unsafe: only ASCII-US digits are present.
+    unsafe { str::from_utf8_unchecked(value) }.parse().ok()
+}
+
+fn parse_ssrc(value: &[u8]) -> Result<u32, TransportError> {
+    if value.is_empty() || value.len() > 8 || !value.iter().all(u8::is_ascii_hexdigit) {
+        return Err(TransportError::InvalidSSRC);
+    }
+
+    // Unsafe: only ASCII-US hexadecimal digits are present.
+    u32::from_str_radix(unsafe { str::from_utf8_unchecked(value) }, 16)
+        .map_err(|_| TransportError::InvalidSSRC)
+}
+
+/// Parses a single address as used by `"destination"` or a `"/"` separated list of quoted addresses
+/// as used by `"dest_addr"`.
+fn parse_addresses(value: &[u8]) -> Result<Vec<Address>, TransportError> {
+    let mut addresses = Vec::new();
+
+    for address in syntax::split_unquoted(value, b'/') {
+        let address = syntax::trim_bytes_whitespace(address);
+        let address = if address.len() >= 2 && address.starts_with(b"\"") && address.ends_with(b"\"")
+        {
+            &address[1..address.len() - 1]
+        } else {
+            address
+        };
+
+        if address.is_empty() {
+            return Err(TransportError::Address(AddressError));
+        }
+
+        addresses.push(Address::try_from(address)?);
+    }
+
+    Ok(addresses)
+}
+
+/// Writes a single address using the RTSP/1.0 parameter name, or multiple addresses using the
+/// quoted RTSP/2.0 list syntax.
+fn write_addresses(
+    formatter: &mut Formatter,
+    single_name: &str,
+    list_name: &str,
+    addresses: &[Address],
+) -> fmt::Result {
+    match addresses.len() {
+        0 => Ok(()),
+        1 => write!(formatter, ";{}={}", single_name, addresses[0]),
+        _ => {
+            let addresses = addresses
+                .iter()
+                .map(|address| format!("\"{}\"", address))
+                .join("/");
+            write!(formatter, ";{}={}", list_name, addresses)
+        }
+    }
+}
+
+fn is_parameter_value(value: &[u8]) -> bool {
+    if value.len() >= 2 && value.starts_with(b"\"") && value.ends_with(b"\"") {
+        syntax::is_qdtext(&value[1..value.len() - 1])
+    } else {
+        syntax::is_token(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::proto::rtsp::message::header::types::transport::{
+        Address, DeliveryType, Interleaved, LowerTransport, Mode, PortRange, TransportError,
+        TransportSpec,
+    };
+
+    #[test]
+    fn test_transport_spec_udp() {
+        let spec = TransportSpec::try_from(
+            "RTP/AVP;unicast;client_port=63994-63995;server_port=8218-8219;ssrc=1F6A3C2B;mode=\"play\"",
+        )
+        .unwrap();
+
+        assert_eq!(spec.protocol(), "RTP");
+        assert_eq!(spec.profile(), "AVP");
+        assert_eq!(spec.lower_transport(), None);
+        assert!(!spec.is_tcp());
+        assert_eq!(spec.delivery_type(), Some(DeliveryType::Unicast));
+        assert_eq!(spec.client_port(), Some(&PortRange::new(63994, 63995).unwrap()));
+        assert_eq!(spec.server_port().unwrap().rtp_port(), 8218);
+        assert_eq!(spec.server_port().unwrap().rtcp_port(), Some(8219));
+        assert_eq!(spec.ssrc(), &[0x1F6A_3C2B]);
+        assert!(spec.modes().contains(&Mode::Play));
+        assert_eq!(
+            spec.to_string(),
+            "RTP/AVP;unicast;client_port=63994-63995;server_port=8218-8219;ssrc=1F6A3C2B;mode=\"PLAY\""
+        );
+    }
+
+    #[test]
+    fn test_transport_spec_interleaved() {
+        let spec = TransportSpec::try_from("RTP/AVP/TCP;unicast;interleaved=0-1;").unwrap();
+
+        assert_eq!(spec.lower_transport(), Some(LowerTransport::TCP));
+        assert!(spec.is_tcp());
+        assert_eq!(spec.interleaved(), Some(&Interleaved::new(0, 1)));
+        assert_eq!(spec, TransportSpec::tcp_interleaved(Interleaved::new(0, 1)));
+        assert_eq!(spec.to_string(), "RTP/AVP/TCP;unicast;interleaved=0-1");
+    }
+
+    #[test]
+    fn test_transport_spec_multicast() {
+        let spec = TransportSpec::try_from(
+            "RTP/AVP;multicast;destination=224.2.0.1;port=3456-3457;ttl=16;source=192.168.1.125",
+        )
+        .unwrap();
+
+        assert_eq!(spec.delivery_type(), Some(DeliveryType::Multicast));
+        assert_eq!(
+            spec.destination(),
+            Some(&[Address::try_from("224.2.0.1").unwrap()][..])
+        );
+        assert_eq!(spec.source(), &[Address::try_from("192.168.1.125").unwrap()]);
+        assert_eq!(spec.port(), Some(&PortRange::new(3456, 3457).unwrap()));
+        assert_eq!(spec.ttl(), Some(16));
+
+        let spec =
+            TransportSpec::try_from("RTP/AVP;multicast;dest_addr=\"224.2.0.1:3456\"/\"224.2.0.1:3457\"")
+                .unwrap();
+        assert_eq!(spec.destination().unwrap().len(), 2);
+        assert_eq!(
+            spec.to_string(),
+            "RTP/AVP;multicast;dest_addr=\"224.2.0.1:3456\"/\"224.2.0.1:3457\""
+        );
+    }
+
+    #[test]
+    fn test_transport_spec_destination_client() {
+        let spec = TransportSpec::try_from("RTP/AVP;unicast;destination;client_port=4588-4589")
+            .unwrap();
+
+        assert_eq!(spec.destination(), Some(&[][..]));
+        assert_eq!(
+            spec.to_string(),
+            "RTP/AVP;unicast;destination;client_port=4588-4589"
+        );
+        assert_eq!(TransportSpec::rtp_avp().destination(), None);
+        assert_eq!(
+            TransportSpec::try_from("RTP/AVP;destination;destination=224.2.0.1"),
+            Err(TransportError::DuplicateParameter)
+        );
+        assert_eq!(
+            TransportSpec::try_from("RTP/AVP;dest_addr"),
+            Err(TransportError::InvalidParameter)
+        );
+    }
+
+    #[test]
+    fn test_transport_spec_extension() {
+        let spec = TransportSpec::try_from("RTP/AVP;unicast;x-dynamic-rate=1;x-foo").unwrap();
+
+        assert_eq!(
+            spec.extensions(),
+            &[
+                ("x-dynamic-rate".to_string(), Some("1".to_string())),
+                ("x-foo".to_string(), None)
+            ]
+        );
+        assert_eq!(spec.to_string(), "RTP/AVP;unicast;x-dynamic-rate=1;x-foo");
+    }
+
+    #[test]
+    fn test_transport_spec_invalid() {
+        assert_eq!(TransportSpec::try_from(""), Err(TransportError::Empty));
+        assert_eq!(
+            TransportSpec::try_from("RTP"),
+            Err(TransportError::InvalidTransportID)
+        );
+        assert_eq!(
+            TransportSpec::try_from("RTP/AVP;unicast;multicast"),
+            Err(TransportError::DuplicateParameter)
+        );
+        assert_eq!(
+            TransportSpec::try_from("RTP/AVP;client_port"),
+            Err(TransportError::InvalidParameter)
+        );
+        assert_eq!(
+            TransportSpec::try_from("RTP/AVP;ssrc=123456789"),
+            Err(TransportError::InvalidSSRC)
+        );
+    }
+}
@@ -59,6 +59,34 @@ pub fn is_token(token: &[u8]) -> bool {
     true
 }
 
+/// Splits the given byte slice on every occurrence of `separator` that is not inside of a quoted
+/// string. Quoted strings may contain escaped quotes (`\"`), so the backslash escape is honored
+/// while inside of quotes.
+///
+/// No trimming is done on the resulting parts.
+pub fn split_unquoted(value: &[u8], separator: u8) -> Vec<&[u8]> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (index, &byte) in value.iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && byte == b'\\' {
+            escaped = true;
+        } else if byte == b'"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes && byte == separator {
+            parts.push(&value[start..index]);
+            start = index + 1;
+        }
+    }
+
+    parts.push(&value[start..]);
+    parts
+}
+
 pub fn trim_bytes_whitespace(value: &[u8]) -> &[u8] {
     trim_bytes_whitespace_right(trim_bytes_whitespace_left(value))
 }
@@ -150,6 +178,23 @@ mod test {
         assert_eq!(false, syntax::is_token(b"not a token"));
     }
 
+    #[test]
+    fn test_split_unquoted() {
+        assert_eq!(
+            vec![&b"RTP/AVP"[..], b"unicast", b"client_port=1-2"],
+            syntax::split_unquoted(b"RTP/AVP;unicast;client_port=1-2", b';')
+        );
+        assert_eq!(
+            vec![&b"mode=\"PLAY, RECORD\""[..], b" ttl=1"],
+            syntax::split_unquoted(b"mode=\"PLAY, RECORD\", ttl=1", b',')
+        );
+        assert_eq!(
+            vec![&b"a=\"\\\",\""[..], b"b"],
+            syntax::split_unquoted(b"a=\"\\\",\",b", b',')
+        );
+        assert_eq!(vec![&b""[..]], syntax::split_unquoted(b"", b','));
+    }
+
     #[test]
     fn test_trim_bytes_whitespace() {
         assert_eq!(
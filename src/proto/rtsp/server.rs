@@ -1 +1,454 @@
-use log::info;
\ No newline at end of file
+use std::io;
+use std::iter;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{info, error};
+
+use bytes::BytesMut;
+use futures::{future, Future, FutureExt, StreamExt};
+use futures::channel::{mpsc, oneshot};
+use futures::future::Shared;
+use futures::stream::{FuturesOrdered, FuturesUnordered};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+
+use crate::proto::rtsp::connection::{Config, Connection, ConnectionHandle, IncomingRequest};
+use crate::proto::rtsp::message::header::types::{CSeq, Public};
+use crate::proto::rtsp::message::method::Method;
+use crate::proto::rtsp::message::request::Request;
+use crate::proto::rtsp::message::response::{Response, NOT_IMPLEMENTED_RESPONSE};
+use crate::proto::rtsp::message::status::StatusCode;
+
+/// How long to wait before accepting connections again after accepting one failed, e.g. because
+/// the process ran out of file descriptors.
+const ACCEPT_RETRY_DURATION: Duration = Duration::from_millis(100);
+
+/// The future returned by [`Service::call`], resolving to the response to a request.
+pub type ServiceFuture = Pin<Box<dyn Future<Output = Response<BytesMut>> + Send>>;
+
+/// The application logic of an RTSP server, mapping requests into responses.
+///
+/// A single service is shared by all connections of a [`Server`], so it has to synchronize any
+/// state it keeps itself.
+pub trait Service: Send + Sync + 'static {
+    /// Returns the methods the service implements.
+    ///
+    /// OPTIONS requests are answered by the server from this list, and requests with any other
+    /// method are answered with Not Implemented (501) without calling the service.
+    fn methods(&self) -> Vec<Method>;
+
+    /// Maps the request into its response. The address of the client that sent the request is
+    /// given, e.g. for delivering media over UDP.
+    ///
+    /// The `"CSeq"` of the response is set by the server.
+    fn call(&self, request: Request<BytesMut>, peer_address: SocketAddr) -> ServiceFuture;
+}
+
+/// An RTSP server accepting connections over TCP.
+///
+/// Every connection is run as a separate task and sends its requests to the shared [`Service`].
+/// Requests on a connection may be pipelined, their responses are always sent in the order the
+/// requests were received.
+pub struct Server<TService>
+    where
+        TService: Service,
+{
+    /// The configuration used for every accepted connection.
+    config: Config,
+
+    service: Arc<TService>,
+}
+
+impl<TService> Server<TService>
+    where
+        TService: Service,
+{
+    /// Constructs a new server using the default connection configuration.
+    pub fn new(service: TService) -> Self {
+        Server::with_config(service, Config::default())
+    }
+
+    /// Constructs a new server using the given connection configuration.
+    pub fn with_config(service: TService, config: Config) -> Self {
+        Server {
+            config,
+            service: Arc::new(service),
+        }
+    }
+
+    /// Accepts connections from the listener until `shutdown` resolves.
+    ///
+    /// On shutdown the listener is closed and every connection stops accepting requests. The
+    /// requests that were already received are still answered before the connection is closed,
+    /// unless this takes longer than the graceful shutdown timeout of the configuration.
+    pub async fn run<TShutdown>(self, listener: TcpListener, shutdown: TShutdown) -> io::Result<()>
+        where
+            TShutdown: Future<Output = ()>,
+    {
+        let (tx_shutdown, rx_shutdown) = oneshot::channel();
+        let rx_shutdown = rx_shutdown.shared();
+        let mut connections = FuturesUnordered::new();
+        let shutdown = shutdown.fuse();
+        futures::pin_mut!(shutdown);
+
+        loop {
+            tokio::select! {
+                result = listener.accept() => match result {
+                    Ok((stream, address)) => {
+                        info!("accepted RTSP connection from {}", address);
+                        connections.push(tokio::spawn(serve_connection(
+                            self.service.clone(),
+                            stream,
+                            address,
+                            self.config.clone(),
+                            rx_shutdown.clone(),
+                        )));
+                    }
+                    Err(error) => {
+                        error!("cannot accept RTSP connection: {}", error);
+                        time::sleep(ACCEPT_RETRY_DURATION).await;
+                    }
+                },
+                Some(_) = connections.next(), if !connections.is_empty() => (),
+                _ = &mut shutdown => break,
+            }
+        }
+
+        info!("RTSP server shutting down");
+        drop(listener);
+        let _ = tx_shutdown.send(());
+
+        // Every connection bounds its own graceful shutdown, so this finishes in time.
+        while connections.next().await.is_some() {}
+
+        Ok(())
+    }
+}
+
+/// Runs a single connection of the server until it is closed by the client, or the server shuts
+/// down.
+async fn serve_connection<TService>(
+    service: Arc<TService>,
+    stream: TcpStream,
+    peer_address: SocketAddr,
+    config: Config,
+    shutdown: Shared<oneshot::Receiver<()>>,
+) where
+    TService: Service,
+{
+    let graceful_shutdown_timeout_duration = config.graceful_shutdown_timeout_default_duration();
+    let (mut connection, handle, rx_incoming_request) =
+        Connection::with_request_receiver(stream, config);
+    let mut requests = RequestServer::new(service, handle, peer_address, rx_incoming_request);
+
+    let shutting_down = tokio::select! {
+        _ = &mut connection => return,
+        _ = requests.serve() => false,
+        _ = shutdown => true,
+    };
+
+    if !shutting_down {
+        // No more requests will be received, so the connection is closing already.
+        connection.await;
+        return;
+    }
+
+    requests.close();
+
+    let drained = time::timeout(graceful_shutdown_timeout_duration, async {
+        tokio::select! {
+            _ = &mut connection => return,
+            _ = requests.serve() => (),
+        }
+
+        requests.handle.shutdown();
+        (&mut connection).await;
+    })
+    .await;
+
+    if drained.is_err() {
+        error!("graceful shutdown of RTSP connection timed out");
+    }
+}
+
+/// Passes the incoming requests of a connection to the service and sends back their responses.
+struct RequestServer<TService>
+    where
+        TService: Service,
+{
+    handle: ConnectionHandle,
+
+    /// The address of the client.
+    peer_address: SocketAddr,
+
+    /// Has the stream of incoming requests ended.
+    requests_ended: bool,
+
+    /// The responses of the requests being processed, in the order in which they are sent.
+    responses: FuturesOrdered<Pin<Box<dyn Future<Output = (CSeq, Response<BytesMut>)> + Send>>>,
+
+    rx_incoming_request: mpsc::Receiver<(CSeq, IncomingRequest)>,
+
+    service: Arc<TService>,
+}
+
+impl<TService> RequestServer<TService>
+    where
+        TService: Service,
+{
+    fn new(
+        service: Arc<TService>,
+        handle: ConnectionHandle,
+        peer_address: SocketAddr,
+        rx_incoming_request: mpsc::Receiver<(CSeq, IncomingRequest)>,
+    ) -> Self {
+        RequestServer {
+            handle,
+            peer_address,
+            requests_ended: false,
+            responses: FuturesOrdered::new(),
+            rx_incoming_request,
+            service,
+        }
+    }
+
+    /// Stops accepting requests. The requests that were already received are still served.
+    fn close(&mut self) {
+        self.rx_incoming_request.close();
+    }
+
+    /// Serves requests until the stream of incoming requests ends and all of them are answered.
+    ///
+    /// Cancelling the returned future does not lose any requests or responses.
+    async fn serve(&mut self) {
+        loop {
+            tokio::select! {
+                request = self.rx_incoming_request.next(), if !self.requests_ended => match request {
+                    Some((cseq, IncomingRequest::Request(request))) => {
+                        let response = respond(&*self.service, request, self.peer_address);
+                        self.responses.push(Box::pin(response.map(move |response| (cseq, response))));
+                    }
+                    Some((cseq, IncomingRequest::Rejected(response))) => {
+                        self.responses.push(Box::pin(future::ready((cseq, response))));
+                    }
+                    None => self.requests_ended = true,
+                },
+                Some((cseq, response)) = self.responses.next(), if !self.responses.is_empty() => {
+                    if let Err(error) = self.handle.send_response(cseq, response) {
+                        error!("cannot send response to request {}: {}", cseq, error);
+                    }
+                }
+                else => return,
+            }
+        }
+    }
+}
+
+/// Maps the request into its response, answering OPTIONS requests and requests with
+/// unimplemented methods without calling the service.
+fn respond<TService>(
+    service: &TService,
+    request: Request<BytesMut>,
+    peer_address: SocketAddr,
+) -> ServiceFuture
+    where
+        TService: Service,
+{
+    let methods = service.methods();
+
+    match request.method() {
+        Method::Options => {
+            let public = iter::once(Method::Options).chain(methods).collect::<Public>();
+            let response = Response::<()>::builder()
+                .with_status_code(StatusCode::OK)
+                .with_typed_header(public)
+                .with_body(BytesMut::new())
+                .build()
+                .expect("OPTIONS response should be valid");
+
+            Box::pin(future::ready(response))
+        }
+        method if methods.contains(method) => service.call(request, peer_address),
+        _ => Box::pin(future::ready(NOT_IMPLEMENTED_RESPONSE.clone())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use bytes::BytesMut;
+    use futures::channel::oneshot;
+    use futures::executor::block_on;
+    use futures::{future, FutureExt, SinkExt, StreamExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::time;
+    use tokio_util::codec::Framed;
+
+    use crate::proto::rtsp::codec::{Codec, Message};
+    use crate::proto::rtsp::message::header::map::HeaderMapExtension;
+    use crate::proto::rtsp::message::header::types::{CSeq, Public};
+    use crate::proto::rtsp::message::method::Method;
+    use crate::proto::rtsp::message::request::Request;
+    use crate::proto::rtsp::message::response::Response;
+    use crate::proto::rtsp::message::status::StatusCode;
+    use crate::proto::rtsp::message::uri::request::URI;
+    use crate::proto::rtsp::server::{respond, Server, Service, ServiceFuture};
+
+    struct DescribeService;
+
+    impl Service for DescribeService {
+        fn methods(&self) -> Vec<Method> {
+            vec![Method::Describe]
+        }
+
+        fn call(&self, _: Request<BytesMut>, _: SocketAddr) -> ServiceFuture {
+            let response = Response::<()>::builder()
+                .with_status_code(StatusCode::OK)
+                .with_body(BytesMut::from("v=0\r\n"))
+                .build()
+                .unwrap();
+
+            Box::pin(future::ready(response))
+        }
+    }
+
+    /// Answers with the path of the request URI, after a while for `"/slow"`.
+    struct PathService;
+
+    impl Service for PathService {
+        fn methods(&self) -> Vec<Method> {
+            vec![Method::Describe]
+        }
+
+        fn call(&self, request: Request<BytesMut>, _: SocketAddr) -> ServiceFuture {
+            let path = request.uri().path().to_string();
+
+            Box::pin(async move {
+                if path == "/slow" {
+                    time::sleep(Duration::from_millis(100)).await;
+                }
+
+                Response::<()>::builder()
+                    .with_status_code(StatusCode::OK)
+                    .with_body(BytesMut::from(path.as_str()))
+                    .build()
+                    .unwrap()
+            })
+        }
+    }
+
+    fn request(method: Method) -> Request<BytesMut> {
+        Request::<BytesMut>::builder()
+            .with_method(method)
+            .with_uri(URI::try_from("rtsp://127.0.0.1/stream").unwrap())
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap()
+    }
+
+    fn path_request(path: &str, cseq: u32) -> Message {
+        scheme_request("rtsp", path, cseq)
+    }
+
+    fn scheme_request(scheme: &str, path: &str, cseq: u32) -> Message {
+        let uri = format!("{}://127.0.0.1{}", scheme, path);
+        let request = Request::<()>::builder()
+            .with_method(Method::Describe)
+            .with_uri(URI::try_from(uri.as_str()).unwrap())
+            .with_typed_header(CSeq::try_from(cseq).unwrap())
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+
+        Message::Request(request)
+    }
+
+    /// Receives a response, checking that it answers the request with the CSeq.
+    async fn next_response(framed: &mut Framed<TcpStream, Codec>, cseq: u32) -> Response<BytesMut> {
+        match framed.next().await {
+            Some(Ok(Message::Response(response))) => {
+                assert_eq!(response.headers().typed_get::<CSeq>(), CSeq::try_from(cseq).ok());
+                response
+            }
+            message => panic!("expected a response, got {:?}", message),
+        }
+    }
+
+    /// Receives a response, checking that it answers the request with the CSeq and path.
+    async fn expect_response(framed: &mut Framed<TcpStream, Codec>, cseq: u32, path: &str) {
+        let response = next_response(framed, cseq).await;
+        assert_eq!(response.body(), &BytesMut::from(path));
+    }
+
+    fn peer_address() -> SocketAddr {
+        "127.0.0.1:50000".parse().unwrap()
+    }
+
+    #[test]
+    fn test_respond() {
+        let response = block_on(respond(&DescribeService, request(Method::Options), peer_address()));
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response.headers().typed_get::<Public>(),
+            Some(vec![Method::Options, Method::Describe].into_iter().collect())
+        );
+
+        let response = block_on(respond(&DescribeService, request(Method::Describe), peer_address()));
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.body(), &BytesMut::from("v=0\r\n"));
+
+        let response = block_on(respond(&DescribeService, request(Method::Play), peer_address()));
+        assert_eq!(response.status_code(), StatusCode::NotImplemented);
+    }
+
+    #[tokio::test]
+    async fn test_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (tx_shutdown, rx_shutdown) = oneshot::channel::<()>();
+        let server = tokio::spawn(Server::new(PathService).run(listener, rx_shutdown.map(|_| ())));
+
+        let stream = TcpStream::connect(address).await.unwrap();
+        let mut framed = Framed::new(stream, Codec::new());
+
+        // Pipelined requests are answered in order, even if the first one takes the longest.
+        framed.feed(path_request("/slow", 1)).await.unwrap();
+        framed.feed(path_request("/fast", 2)).await.unwrap();
+        framed.feed(path_request("/fast", 3)).await.unwrap();
+        framed.flush().await.unwrap();
+
+        expect_response(&mut framed, 1, "/slow").await;
+        expect_response(&mut framed, 2, "/fast").await;
+        expect_response(&mut framed, 3, "/fast").await;
+
+        // So are the requests the connection rejects itself.
+        framed.feed(path_request("/slow", 4)).await.unwrap();
+        framed.feed(scheme_request("rtspu", "/fast", 5)).await.unwrap();
+        framed.flush().await.unwrap();
+
+        expect_response(&mut framed, 4, "/slow").await;
+        let response = next_response(&mut framed, 5).await;
+        assert_eq!(response.status_code(), StatusCode::NotImplemented);
+
+        // A request received before the shutdown is still answered, then the connection closes.
+        framed.send(path_request("/slow", 6)).await.unwrap();
+        time::sleep(Duration::from_millis(20)).await;
+        tx_shutdown.send(()).unwrap();
+
+        expect_response(&mut framed, 6, "/slow").await;
+        assert!(matches!(framed.next().await, None | Some(Err(_))));
+
+        time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server should shut down")
+            .unwrap()
+            .unwrap();
+        assert!(TcpStream::connect(address).await.is_err());
+    }
+}
@@ -601,6 +601,22 @@ pub struct SdpAttributeFmtpParameters {
     // RTX
     pub rtx: Option<RtxFmtpParameters>,
 
+    // MPEG-4 generic https://tools.ietf.org/html/rfc3640
+    pub streamtype: u32,
+    pub mode: String,
+    pub size_length: u32,
+    pub index_length: u32,
+    pub index_delta_length: u32,
+    pub cts_delta_length: u32,
+    pub dts_delta_length: u32,
+    pub random_access_indication: bool,
+    pub constant_size: u32,
+    pub constant_duration: u32,
+
+    // MPEG-4 generic and MP4A-LATM https://tools.ietf.org/html/rfc6416
+    pub config: Vec<u8>,
+    pub cpresent: bool,
+
     // Unknown
     pub unknown_tokens: Vec<String>,
 }
@@ -635,7 +651,27 @@ impl fmt::Display for SdpAttributeFmtpParameters {
                 maybe_print_bool_param("usedtx", self.usedtx, false),
                 maybe_print_bool_param("stereo", self.stereo, false),
                 maybe_print_bool_param("useinbandfec", self.useinbandfec, false),
-                maybe_print_bool_param("cbr", self.cbr, false)
+                maybe_print_bool_param("cbr", self.cbr, false),
+                maybe_print_param("streamtype=", self.streamtype, 0),
+                maybe_print_param("mode=", self.mode.clone(), "".to_string()),
+                maybe_print_param("sizelength=", self.size_length, 0),
+                maybe_print_param("indexlength=", self.index_length, 0),
+                maybe_print_param("indexdeltalength=", self.index_delta_length, 0),
+                maybe_print_param("ctsdeltalength=", self.cts_delta_length, 0),
+                maybe_print_param("dtsdeltalength=", self.dts_delta_length, 0),
+                maybe_print_bool_param(
+                    "randomaccessindication",
+                    self.random_access_indication,
+                    false
+                ),
+                maybe_print_param("constantsize=", self.constant_size, 0),
+                maybe_print_param("constantduration=", self.constant_duration, 0),
+                maybe_print_param(
+                    "config=",
+                    self.config.iter().map(|byte| format!("{:02x}", byte)).collect::<String>(),
+                    "".to_string()
+                ),
+                maybe_print_bool_param("cpresent", self.cpresent, true)
             ]
             .join(";"),
             red = maybe_vector_to_string!("{}", self.encodings, "/"),
@@ -2031,6 +2067,18 @@ fn parse_fmtp(to_parse: &str) -> Result<SdpAttribute, SdpParserInternalError> {
         encodings: Vec::new(),
         dtmf_tones: "".to_string(),
         rtx: None,
+        streamtype: 0,
+        mode: "".to_string(),
+        size_length: 0,
+        index_length: 0,
+        index_delta_length: 0,
+        cts_delta_length: 0,
+        dts_delta_length: 0,
+        random_access_indication: false,
+        constant_size: 0,
+        constant_duration: 0,
+        config: Vec::new(),
+        cpresent: true,
         unknown_tokens: Vec::new(),
     };
 
@@ -2039,6 +2087,7 @@ fn parse_fmtp(to_parse: &str) -> Result<SdpAttribute, SdpParserInternalError> {
             // Permit Leading/Trailing/Inner ';' by filtering out empty splits
             let parameter_tokens: Vec<&str> = parameter_token
                 .split(';')
+                .map(str::trim)
                 .filter(|token| !token.is_empty())
                 .collect();
             for parameter_token in parameter_tokens.iter() {
@@ -2132,6 +2181,32 @@ fn parse_fmtp(to_parse: &str) -> Result<SdpAttribute, SdpParserInternalError> {
                             ));
                         }
                     }
+                    // MPEG-4 generic https://tools.ietf.org/html/rfc3640
+                    "STREAMTYPE" => parameters.streamtype = parameter_val.parse::<u32>()?,
+                    "MODE" => parameters.mode = parameter_val.to_string(),
+                    "SIZELENGTH" => parameters.size_length = parameter_val.parse::<u32>()?,
+                    "INDEXLENGTH" => parameters.index_length = parameter_val.parse::<u32>()?,
+                    "INDEXDELTALENGTH" => {
+                        parameters.index_delta_length = parameter_val.parse::<u32>()?
+                    }
+                    "CTSDELTALENGTH" => {
+                        parameters.cts_delta_length = parameter_val.parse::<u32>()?
+                    }
+                    "DTSDELTALENGTH" => {
+                        parameters.dts_delta_length = parameter_val.parse::<u32>()?
+                    }
+                    "RANDOMACCESSINDICATION" => {
+                        parameters.random_access_indication =
+                            parse_bool(parameter_val, "randomAccessIndication")?
+                    }
+                    "CONSTANTSIZE" => parameters.constant_size = parameter_val.parse::<u32>()?,
+                    "CONSTANTDURATION" => {
+                        parameters.constant_duration = parameter_val.parse::<u32>()?
+                    }
+
+                    // MPEG-4 generic and MP4A-LATM https://tools.ietf.org/html/rfc6416
+                    "CONFIG" => parameters.config = parse_hex_config(parameter_val)?,
+                    "CPRESENT" => parameters.cpresent = parse_bool(parameter_val, "cpresent")?,
                     _ => parameters
                         .unknown_tokens
                         .push((*parameter_token).to_string()),
@@ -2209,6 +2284,29 @@ fn parse_fmtp(to_parse: &str) -> Result<SdpAttribute, SdpParserInternalError> {
     }))
 }
 
+/// Parses the hexadecimal `config` fmtp parameter of the MPEG-4 payloads.
+fn parse_hex_config(to_parse: &str) -> Result<Vec<u8>, SdpParserInternalError> {
+    if to_parse.len() % 2 != 0 {
+        return Err(SdpParserInternalError::Generic(
+            "The fmtp parameter 'config' must have an even number of hex digits".to_string(),
+        ));
+    }
+
+    (0..to_parse.len())
+        .step_by(2)
+        .map(|index| {
+            to_parse
+                .get(index..index + 2)
+                .ok_or_else(|| {
+                    SdpParserInternalError::Generic(
+                        "The fmtp parameter 'config' must be hexadecimal".to_string(),
+                    )
+                })
+                .and_then(|byte| Ok(u8::from_str_radix(byte, 16)?))
+        })
+        .collect()
+}
+
 ///////////////////////////////////////////////////////////////////////////
 // a=group, RFC5888
 //-------------------------------------------------------------------------
@@ -3689,6 +3787,29 @@ mod tests {
         );
         check_parse_and_serialize("fmtp:97 apt=96");
         check_parse_and_serialize("fmtp:97 apt=96;rtx-time=3000");
+        check_parse_and_serialize(
+            "fmtp:97 mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config=1190",
+        );
+        check_parse_and_serialize("fmtp:96 config=400024203fc0;cpresent=0");
+        assert!(parse_attribute("fmtp:96 config=119").is_err());
+        assert!(parse_attribute("fmtp:96 config=11x0").is_err());
+    }
+
+    #[test]
+    fn test_parse_attribute_fmtp_mpeg4_generic() {
+        let check_parse = make_check_parse!(SdpAttributeFmtp, SdpAttribute::Fmtp);
+        let fmtp = check_parse(
+            "fmtp:96 streamtype=5; profile-level-id=15; mode=AAC-hbr; config=1210; \
+             SizeLength=13; IndexLength=3; IndexDeltaLength=3",
+        );
+
+        assert_eq!(fmtp.parameters.streamtype, 5);
+        assert_eq!(fmtp.parameters.mode, "AAC-hbr");
+        assert_eq!(fmtp.parameters.config, vec![0x12, 0x10]);
+        assert_eq!(fmtp.parameters.size_length, 13);
+        assert_eq!(fmtp.parameters.index_length, 3);
+        assert_eq!(fmtp.parameters.index_delta_length, 3);
+        assert!(fmtp.parameters.cpresent);
     }
 
     #[test]
@@ -549,6 +549,18 @@ mod tests {
                     encodings: Vec::new(),
                     dtmf_tones: "".to_string(),
                     rtx: None,
+                    streamtype: 0,
+                    mode: "".to_string(),
+                    size_length: 0,
+                    index_length: 0,
+                    index_delta_length: 0,
+                    cts_delta_length: 0,
+                    dts_delta_length: 0,
+                    random_access_indication: false,
+                    constant_size: 0,
+                    constant_duration: 0,
+                    config: Vec::new(),
+                    cpresent: true,
                     unknown_tokens: Vec::new()
                 }
             },))
@@ -1,4 +1,5 @@
-mod rtcp_session;
+pub mod rtcp_session;
+pub mod relay;
 pub mod rtp_session;
 mod rtp_transport;
 
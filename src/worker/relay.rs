@@ -0,0 +1,1188 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use log::{info, error};
+
+use bytes::BytesMut;
+use fnv::FnvBuildHasher;
+use futures::{future, FutureExt, StreamExt};
+use futures::channel::oneshot;
+use futures::future::{Either, Shared};
+use futures::stream::FuturesUnordered;
+use tokio::net::UdpSocket;
+use tokio::time::{self, Duration};
+
+use crate::proto::rtp::rtp::{RtpFixedHeader, RtpPacket};
+use crate::proto::rtsp::client::{ClientError, RTSPClient};
+use crate::proto::rtsp::connection::Credentials;
+use crate::proto::rtsp::message::header::map::HeaderMapExtension;
+use crate::proto::rtsp::message::header::name::HeaderName;
+use crate::proto::rtsp::message::header::types::{Session, Transport};
+use crate::proto::rtsp::message::header::types::transport::{PortRange, TransportSpec};
+use crate::proto::rtsp::message::header::value::HeaderValue;
+use crate::proto::rtsp::message::method::Method;
+use crate::proto::rtsp::message::request::Request;
+use crate::proto::rtsp::message::response::Response;
+use crate::proto::rtsp::message::session::{SessionID, DEFAULT_SESSION_TIMEOUT};
+use crate::proto::rtsp::message::status::StatusCode;
+use crate::proto::rtsp::message::uri::request::URI;
+use crate::proto::rtsp::server::{Service, ServiceFuture};
+use crate::proto::sdp::SdpSession;
+use crate::proto::sdp::attribute_type::{SdpAttribute, SdpAttributeType};
+use crate::proto::traits::{ReadFrom, WriteTo};
+
+/// How long to wait before pulling a camera again after the upstream session failed.
+pub const UPSTREAM_RETRY_DURATION: Duration = Duration::from_secs(5);
+
+/// The expired downstream sessions are looked for this many times per session timeout.
+const SESSION_EXPIRY_CHECKS_PER_TIMEOUT: u32 = 2;
+
+/// How many times binding an RTP/RTCP port pair is attempted before giving up.
+const PORT_PAIR_BIND_ATTEMPTS: usize = 10;
+
+/// The largest possible UDP payload.
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// The prefix of the control URL of every track in the republished SDP.
+const TRACK_CONTROL_PREFIX: &str = "trackID=";
+
+/// An RTSP service republishing cameras to any number of downstream clients.
+///
+/// Every camera is pulled at most once through an [`RTSPClient`], no matter how many clients
+/// watch it. The upstream session is started by the first downstream `SETUP` and torn down once
+/// the last downstream session is torn down or has timed out.
+///
+/// Downstream clients get the original SDP with the control URLs rewritten to point to the
+/// relay, and the RTP packets of the camera over UDP. Every downstream session has its own SSRC,
+/// sequence numbers and timestamps, which stay continuous even if the camera has to be pulled
+/// again.
+#[derive(Clone, Default)]
+pub struct Relay {
+    state: Arc<Mutex<RelayState>>,
+}
+
+impl Relay {
+    /// Constructs a new relay without any sources.
+    pub fn new() -> Self {
+        Relay::default()
+    }
+
+    /// Republishes the camera at `url` under `path`, e.g. `"/camera1"` for
+    /// `"rtsp://relay:8554/camera1"`.
+    ///
+    /// The credentials are used to answer authentication challenges of the camera.
+    pub fn add_source<TPath, TURL>(&self, path: TPath, url: TURL, credentials: Option<Credentials>)
+        where
+            TPath: Into<String>,
+            TURL: Into<String>,
+    {
+        let path = path.into().trim_end_matches('/').to_string();
+        let source = Source {
+            url: url.into(),
+            credentials,
+            description: None,
+            tracks: Vec::new(),
+            tx_stop_upstream: None,
+        };
+
+        self.lock().sources.insert(path, source);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RelayState> {
+        self.state
+            .lock()
+            .expect("`Relay.state` should not be poisoned")
+    }
+
+    async fn respond(
+        &self,
+        request: Request<BytesMut>,
+        peer_address: SocketAddr,
+    ) -> Result<Response<BytesMut>, StatusCode> {
+        match request.method() {
+            Method::Describe => self.describe(&request).await,
+            Method::Setup => self.setup(&request, peer_address).await,
+            Method::Play => self.set_playing(&request, true),
+            Method::Pause => self.set_playing(&request, false),
+            Method::Teardown => self.teardown(&request),
+            _ => self.keep_alive(&request),
+        }
+    }
+
+    async fn describe(&self, request: &Request<BytesMut>) -> Result<Response<BytesMut>, StatusCode> {
+        let path = source_path(request.uri());
+        let description = self.description(&path).await?;
+        let content_base = format!("{}/", request.uri().to_string().trim_end_matches('/'));
+
+        Ok(Response::<()>::builder()
+            .with_status_code(StatusCode::OK)
+            .with_header(
+                HeaderName::ContentType,
+                HeaderValue::try_from("application/sdp").unwrap(),
+            )
+            .with_header(
+                HeaderName::ContentBase,
+                HeaderValue::try_from(content_base.as_str())
+                    .map_err(|_| StatusCode::BadRequest)?,
+            )
+            .with_body(BytesMut::from(description.sdp.as_str()))
+            .build()
+            .expect("DESCRIBE response should be valid"))
+    }
+
+    async fn setup(
+        &self,
+        request: &Request<BytesMut>,
+        peer_address: SocketAddr,
+    ) -> Result<Response<BytesMut>, StatusCode> {
+        let (path, track) = track_path(request.uri()).ok_or(StatusCode::NotFound)?;
+        let client_port = request
+            .headers()
+            .typed_try_get::<Transport>()
+            .map_err(|_| StatusCode::BadRequest)?
+            .and_then(|transport| {
+                transport
+                    .iter()
+                    .filter(|spec| !spec.is_tcp())
+                    .find_map(|spec| spec.client_port().cloned())
+            })
+            .ok_or(StatusCode::UnsupportedTransport)?;
+        let session_id = session_id(request)?;
+
+        // Everything that needs to wait is done before the session is touched.
+        let description = self.description(&path).await?;
+
+        if track >= description.track_count {
+            return Err(StatusCode::NotFound);
+        }
+
+        // The camera may have changed since the client described it, e.g. rebooted with another
+        // configuration, so it is described again before it is pulled. The client has to
+        // describe it again too if its tracks changed.
+        if !self.is_relaying(&path) {
+            let current = self.describe_upstream(&path).await?;
+
+            if current.track_count != description.track_count {
+                info!("tracks of {} changed since it was described", path);
+                return Err(StatusCode::ServiceUnavailable);
+            }
+        }
+
+        let tracks = self.tracks(&path, description.track_count).await?;
+
+        let mut state = self.lock();
+        let state = &mut *state;
+
+        // The source may have been stopped and started again in the meantime.
+        let current = state.sources.get(&path).map_or(false, |source| {
+            source.tracks.len() == tracks.len()
+                && source.tracks.iter().zip(tracks.iter()).all(|(a, b)| Arc::ptr_eq(a, b))
+        });
+
+        if !current {
+            return Err(StatusCode::ServiceUnavailable);
+        }
+
+        let session_id = match session_id {
+            Some(session_id) => {
+                let session = state
+                    .sessions
+                    .get_mut(&session_id)
+                    .ok_or(StatusCode::SessionNotFound)?;
+
+                if session.path != path {
+                    return Err(StatusCode::AggregateOperationNotAllowed);
+                }
+
+                session.last_seen = Instant::now();
+                session_id
+            }
+            None => {
+                let session_id = SessionID::random();
+                let session = DownstreamSession {
+                    path: path.clone(),
+                    playing: false,
+                    last_seen: Instant::now(),
+                };
+
+                info!("relay session {} created for {}", session_id, path);
+                state.sessions.insert(session_id.clone(), session);
+                session_id
+            }
+        };
+        let playing = state.sessions[&session_id].playing;
+
+        let source = state.sources.get_mut(&path).ok_or(StatusCode::NotFound)?;
+
+        let fanout = tracks[track].clone();
+        let rewriter = RtpRewriter::random();
+        let ssrc = rewriter.ssrc();
+        let output = Output {
+            destination: SocketAddr::new(peer_address.ip(), client_port.rtp_port()),
+            playing,
+            rewriter,
+        };
+        fanout.lock().insert(session_id.clone(), output);
+
+        if source.tx_stop_upstream.is_none() {
+            let (tx_stop_upstream, rx_stop_upstream) = oneshot::channel();
+            source.tx_stop_upstream = Some(tx_stop_upstream);
+
+            tokio::spawn(self.clone().run_source(
+                path,
+                source.url.clone(),
+                source.credentials.clone(),
+                tracks,
+                rx_stop_upstream,
+            ));
+        }
+
+        let mut transport = TransportSpec::udp_unicast(client_port);
+        *transport.server_port_mut() = Some(fanout.server_port.clone());
+        transport.ssrc_mut().push(ssrc);
+
+        Ok(Response::<()>::builder()
+            .with_status_code(StatusCode::OK)
+            .with_typed_header(session_header(&session_id))
+            .with_typed_header(Transport::from(transport))
+            .with_body(BytesMut::new())
+            .build()
+            .expect("SETUP response should be valid"))
+    }
+
+    fn set_playing(
+        &self,
+        request: &Request<BytesMut>,
+        playing: bool,
+    ) -> Result<Response<BytesMut>, StatusCode> {
+        let session_id = session_id(request)?.ok_or(StatusCode::SessionNotFound)?;
+        let mut state = self.lock();
+        let session = state
+            .sessions
+            .get_mut(&session_id)
+            .ok_or(StatusCode::SessionNotFound)?;
+        session.last_seen = Instant::now();
+        session.playing = playing;
+        let path = session.path.clone();
+
+        if let Some(source) = state.sources.get(&path) {
+            for fanout in source.tracks.iter() {
+                if let Some(output) = fanout.lock().get_mut(&session_id) {
+                    output.playing = playing;
+                }
+            }
+        }
+
+        Ok(session_response(&session_id))
+    }
+
+    fn teardown(&self, request: &Request<BytesMut>) -> Result<Response<BytesMut>, StatusCode> {
+        let session_id = session_id(request)?.ok_or(StatusCode::SessionNotFound)?;
+
+        if !self.lock().remove_session(&session_id) {
+            return Err(StatusCode::SessionNotFound);
+        }
+
+        Ok(Response::<()>::builder()
+            .with_status_code(StatusCode::OK)
+            .with_body(BytesMut::new())
+            .build()
+            .expect("TEARDOWN response should be valid"))
+    }
+
+    /// Answers requests only sent to keep the session alive, e.g. an empty `GET_PARAMETER`.
+    fn keep_alive(&self, request: &Request<BytesMut>) -> Result<Response<BytesMut>, StatusCode> {
+        match session_id(request)? {
+            Some(session_id) => {
+                self.lock()
+                    .sessions
+                    .get_mut(&session_id)
+                    .ok_or(StatusCode::SessionNotFound)?
+                    .last_seen = Instant::now();
+
+                Ok(session_response(&session_id))
+            }
+            None => Ok(Response::<()>::builder()
+                .with_status_code(StatusCode::OK)
+                .with_body(BytesMut::new())
+                .build()
+                .expect("keep-alive response should be valid")),
+        }
+    }
+
+    /// Returns the republished description of the source, asking the camera for it if needed.
+    async fn description(&self, path: &str) -> Result<Description, StatusCode> {
+        {
+            let state = self.lock();
+            let source = state.sources.get(path).ok_or(StatusCode::NotFound)?;
+
+            if let Some(description) = source.description.as_ref() {
+                return Ok(description.clone());
+            }
+        }
+
+        self.describe_upstream(path).await
+    }
+
+    /// Asks the camera for its description, replacing the one republished so far.
+    async fn describe_upstream(&self, path: &str) -> Result<Description, StatusCode> {
+        let (url, credentials) = {
+            let state = self.lock();
+            let source = state.sources.get(path).ok_or(StatusCode::NotFound)?;
+            (source.url.clone(), source.credentials.clone())
+        };
+
+        let description = fetch_description(url.as_str(), credentials)
+            .await
+            .map_err(|error| {
+                error!("cannot describe upstream {}: {}", url, error);
+                StatusCode::BadGateway
+            })?;
+
+        if let Some(source) = self.lock().sources.get_mut(path) {
+            source.description = Some(description.clone());
+        }
+
+        Ok(description)
+    }
+
+    /// Returns whether the camera of the source is being pulled.
+    fn is_relaying(&self, path: &str) -> bool {
+        self.lock()
+            .sources
+            .get(path)
+            .map_or(false, |source| source.tx_stop_upstream.is_some())
+    }
+
+    /// Returns the downstream side of the tracks of the source, binding their sockets if needed.
+    async fn tracks(&self, path: &str, count: usize) -> Result<Vec<Arc<TrackFanout>>, StatusCode> {
+        {
+            let state = self.lock();
+            let source = state.sources.get(path).ok_or(StatusCode::NotFound)?;
+
+            if !source.tracks.is_empty() {
+                return Ok(source.tracks.clone());
+            }
+        }
+
+        let mut tracks = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let (socket, rtcp_socket) = bind_port_pair().await.map_err(|error| {
+                error!("cannot bind relay ports: {}", error);
+                StatusCode::InternalServerError
+            })?;
+            tracks.push(Arc::new(TrackFanout::new(socket, rtcp_socket)?));
+        }
+
+        let mut state = self.lock();
+        let source = state.sources.get_mut(path).ok_or(StatusCode::NotFound)?;
+
+        if source.tracks.is_empty() {
+            source.tracks = tracks;
+        }
+
+        Ok(source.tracks.clone())
+    }
+
+    /// Pulls the camera and forwards its packets until the source is stopped, meanwhile expiring
+    /// the downstream sessions of the source that are no longer kept alive.
+    async fn run_source(
+        self,
+        path: String,
+        url: String,
+        credentials: Option<Credentials>,
+        tracks: Vec<Arc<TrackFanout>>,
+        rx_stop_upstream: oneshot::Receiver<()>,
+    ) {
+        let rx_stop_upstream = rx_stop_upstream.shared();
+
+        let pull = async {
+            loop {
+                match pull_upstream(url.as_str(), credentials.clone(), &tracks, rx_stop_upstream.clone()).await {
+                    Ok(None) => return,
+                    Ok(Some(description)) => {
+                        error!("tracks of upstream {} changed, dropping its sessions", url);
+                        self.lock().replace_description(path.as_str(), description);
+                        return;
+                    }
+                    Err(error) => error!("relaying upstream {} failed: {}", url, error),
+                }
+
+                let retry = Box::pin(time::sleep(UPSTREAM_RETRY_DURATION));
+
+                if let Either::Left(_) = future::select(rx_stop_upstream.clone(), retry).await {
+                    return;
+                }
+            }
+        };
+        let expire = self.expire_sessions(path.as_str());
+
+        futures::pin_mut!(pull, expire);
+        future::select(pull, expire).await;
+
+        info!("stopped relaying {}", path);
+    }
+
+    /// Removes the sessions of the source that have not been used within the session timeout.
+    async fn expire_sessions(&self, path: &str) {
+        loop {
+            time::sleep(DEFAULT_SESSION_TIMEOUT / SESSION_EXPIRY_CHECKS_PER_TIMEOUT).await;
+
+            let mut state = self.lock();
+            let expired = state
+                .sessions
+                .iter()
+                .filter(|(_, session)| {
+                    session.path == path && session.last_seen.elapsed() > DEFAULT_SESSION_TIMEOUT
+                })
+                .map(|(session_id, _)| session_id.clone())
+                .collect::<Vec<_>>();
+
+            for session_id in expired {
+                info!("relay session {} timed out", session_id);
+                state.remove_session(&session_id);
+            }
+        }
+    }
+}
+
+impl Service for Relay {
+    fn methods(&self) -> Vec<Method> {
+        vec![
+            Method::Describe,
+            Method::GetParameter,
+            Method::Pause,
+            Method::Play,
+            Method::Setup,
+            Method::Teardown,
+        ]
+    }
+
+    fn call(&self, request: Request<BytesMut>, peer_address: SocketAddr) -> ServiceFuture {
+        let relay = self.clone();
+
+        Box::pin(async move {
+            relay
+                .respond(request, peer_address)
+                .await
+                .unwrap_or_else(|status_code| {
+                    Response::<()>::builder()
+                        .with_status_code(status_code)
+                        .with_body(BytesMut::new())
+                        .build()
+                        .expect("error response should be valid")
+                })
+        })
+    }
+}
+
+#[derive(Default)]
+struct RelayState {
+    /// The downstream sessions by their identifier.
+    sessions: HashMap<SessionID, DownstreamSession, FnvBuildHasher>,
+
+    /// The cameras by the path they are republished under.
+    sources: HashMap<String, Source>,
+}
+
+impl RelayState {
+    /// Removes the session, stopping its source if it was the last session watching it.
+    ///
+    /// Returns whether the session existed.
+    fn remove_session(&mut self, session_id: &SessionID) -> bool {
+        let session = match self.sessions.remove(session_id) {
+            Some(session) => session,
+            None => return false,
+        };
+
+        info!("relay session {} removed from {}", session_id, session.path);
+
+        let watched = self
+            .sessions
+            .values()
+            .any(|other| other.path == session.path);
+
+        if let Some(source) = self.sources.get_mut(&session.path) {
+            for fanout in source.tracks.iter() {
+                fanout.lock().remove(session_id);
+            }
+
+            if !watched {
+                source.stop();
+            }
+        }
+
+        true
+    }
+
+    /// Removes the sessions of the source, whose tracks no longer match the camera, and
+    /// republishes the new description of the camera.
+    fn replace_description(&mut self, path: &str, description: Description) {
+        let session_ids = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.path == path)
+            .map(|(session_id, _)| session_id.clone())
+            .collect::<Vec<_>>();
+
+        for session_id in session_ids {
+            self.remove_session(&session_id);
+        }
+
+        if let Some(source) = self.sources.get_mut(path) {
+            source.stop();
+            source.description = Some(description);
+        }
+    }
+}
+
+/// A camera republished by the relay.
+struct Source {
+    url: String,
+
+    credentials: Option<Credentials>,
+
+    /// The republished SDP, fetched from the camera by the first `DESCRIBE` or `SETUP`.
+    description: Option<Description>,
+
+    /// The downstream side of every track, created by the first `SETUP`.
+    tracks: Vec<Arc<TrackFanout>>,
+
+    /// Stops the upstream session when sent to or dropped.
+    tx_stop_upstream: Option<oneshot::Sender<()>>,
+}
+
+impl Source {
+    /// Stops the upstream session and forgets everything learned from the camera.
+    fn stop(&mut self) {
+        if let Some(tx_stop_upstream) = self.tx_stop_upstream.take() {
+            let _ = tx_stop_upstream.send(());
+        }
+
+        self.description = None;
+        self.tracks.clear();
+    }
+}
+
+/// The SDP of a camera as it is republished by the relay.
+#[derive(Clone, Debug)]
+struct Description {
+    sdp: String,
+    track_count: usize,
+}
+
+impl From<SdpSession> for Description {
+    /// Rewrites the control URLs, so that the presentation is controlled through the relay and
+    /// every track is set up by its index.
+    fn from(mut sdp: SdpSession) -> Self {
+        sdp.attribute
+            .retain(|attribute| SdpAttributeType::from(attribute) != SdpAttributeType::Control);
+        sdp.attribute.push(SdpAttribute::Control("*".to_string()));
+
+        for (index, media) in sdp.media.iter_mut().enumerate() {
+            let control = format!("{}{}", TRACK_CONTROL_PREFIX, index);
+            media
+                .set_attribute(SdpAttribute::Control(control))
+                .expect("control should be allowed at media level");
+        }
+
+        Description {
+            sdp: sdp.to_string(),
+            track_count: sdp.media.len(),
+        }
+    }
+}
+
+/// A downstream session set up with the relay.
+struct DownstreamSession {
+    /// The path of the source the session watches.
+    path: String,
+
+    playing: bool,
+
+    /// When the last request was received within the session.
+    last_seen: Instant,
+}
+
+/// The downstream side of a track, shared with the task forwarding its packets.
+struct TrackFanout {
+    /// The socket the packets are sent from.
+    socket: UdpSocket,
+
+    /// Bound only to reserve the RTCP port of the pair, incoming reports are ignored.
+    _rtcp_socket: UdpSocket,
+
+    server_port: PortRange,
+
+    outputs: Mutex<HashMap<SessionID, Output, FnvBuildHasher>>,
+}
+
+impl TrackFanout {
+    fn new(socket: UdpSocket, rtcp_socket: UdpSocket) -> Result<Self, StatusCode> {
+        let port = socket
+            .local_addr()
+            .map_err(|_| StatusCode::InternalServerError)?
+            .port();
+
+        Ok(TrackFanout {
+            socket,
+            _rtcp_socket: rtcp_socket,
+            server_port: PortRange::pair(port).map_err(|_| StatusCode::InternalServerError)?,
+            outputs: Mutex::new(HashMap::default()),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<SessionID, Output, FnvBuildHasher>> {
+        self.outputs
+            .lock()
+            .expect("`TrackFanout.outputs` should not be poisoned")
+    }
+
+    /// Sends the packet to every playing session, rewritten for each of them.
+    async fn forward(&self, packet: RtpPacket) {
+        for (destination, datagram) in self.datagrams(packet) {
+            if let Err(error) = self.socket.send_to(&datagram, destination).await {
+                error!("cannot relay RTP packet to {}: {}", destination, error);
+            }
+        }
+    }
+
+    fn datagrams(&self, packet: RtpPacket) -> Vec<(SocketAddr, Vec<u8>)> {
+        self.lock()
+            .values_mut()
+            .filter(|output| output.playing)
+            .filter_map(|output| {
+                let mut packet = packet.clone();
+                output.rewriter.rewrite(&mut packet.header);
+                packet
+                    .to_bytes()
+                    .ok()
+                    .map(|datagram| (output.destination, datagram))
+            })
+            .collect()
+    }
+}
+
+/// A downstream session receiving a track.
+struct Output {
+    /// The address the RTP packets are sent to.
+    destination: SocketAddr,
+
+    playing: bool,
+
+    rewriter: RtpRewriter,
+}
+
+/// Rewrites the RTP packets of an upstream track for a single downstream session.
+///
+/// The session gets its own SSRC and its sequence numbers and timestamps start at their own
+/// (usually random) values. When the upstream SSRC changes, e.g. because the camera was pulled
+/// again, the sequence numbers continue where they left off and the timestamps one frame after
+/// the latest one, so the session sees a single uninterrupted stream.
+#[derive(Clone, Debug)]
+pub struct RtpRewriter {
+    ssrc: u32,
+
+    /// The upstream SSRC the offsets were computed for.
+    upstream_ssrc: Option<u32>,
+
+    /// The sequence number following the one of the latest packet.
+    next_sequence_number: u16,
+
+    sequence_number_offset: u16,
+
+    /// The timestamp of the latest packet.
+    last_timestamp: u32,
+
+    /// The timestamp difference between the latest two adjacent frames.
+    frame_duration: u32,
+
+    timestamp_offset: u32,
+}
+
+impl RtpRewriter {
+    /// Constructs a new rewriter with the given SSRC, first sequence number and first timestamp.
+    pub fn new(ssrc: u32, sequence_number: u16, timestamp: u32) -> Self {
+        RtpRewriter {
+            ssrc,
+            upstream_ssrc: None,
+            next_sequence_number: sequence_number,
+            sequence_number_offset: 0,
+            last_timestamp: timestamp,
+            frame_duration: 0,
+            timestamp_offset: 0,
+        }
+    }
+
+    /// Constructs a new rewriter with a random SSRC, first sequence number and first timestamp.
+    pub fn random() -> Self {
+        RtpRewriter::new(rand::random(), rand::random(), rand::random())
+    }
+
+    /// Returns the SSRC of the rewritten packets.
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Rewrites the SSRC, sequence number and timestamp of the packet.
+    pub fn rewrite(&mut self, header: &mut RtpFixedHeader) {
+        if self.upstream_ssrc != Some(header.ssrc) {
+            // The first frame of a new pull follows the latest one, instead of sharing its
+            // timestamp.
+            let next_timestamp = match self.upstream_ssrc {
+                Some(_) => self.last_timestamp.wrapping_add(self.frame_duration),
+                None => self.last_timestamp,
+            };
+
+            self.upstream_ssrc = Some(header.ssrc);
+            self.sequence_number_offset = self.next_sequence_number.wrapping_sub(header.seq_num);
+            self.timestamp_offset = next_timestamp.wrapping_sub(header.timestamp);
+        }
+
+        header.ssrc = self.ssrc;
+        header.seq_num = header.seq_num.wrapping_add(self.sequence_number_offset);
+        header.timestamp = header.timestamp.wrapping_add(self.timestamp_offset);
+
+        // Reordered packets must not move the sequence number back.
+        if header.seq_num.wrapping_sub(self.next_sequence_number) < 0x8000 {
+            let duration = header.timestamp.wrapping_sub(self.last_timestamp);
+
+            if header.seq_num == self.next_sequence_number && duration != 0 && duration < 0x8000_0000 {
+                self.frame_duration = duration;
+            }
+
+            self.next_sequence_number = header.seq_num.wrapping_add(1);
+            self.last_timestamp = header.timestamp;
+        }
+    }
+}
+
+/// Pulls the camera into the tracks until stopped.
+///
+/// If the tracks of the camera no longer match the relayed ones, e.g. because it was reconfigured
+/// while being pulled again, nothing is pulled and its new description is returned instead.
+/// Errors are returned if the upstream session cannot be set up or receiving fails.
+async fn pull_upstream(
+    url: &str,
+    credentials: Option<Credentials>,
+    tracks: &[Arc<TrackFanout>],
+    rx_stop_upstream: Shared<oneshot::Receiver<()>>,
+) -> Result<Option<Description>, ClientError> {
+    let mut client = upstream_client(url, credentials);
+    client.connect().await.map_err(ClientError::IO)?;
+    let sdp = client.describe().await?;
+
+    if client.tracks().len() != tracks.len() {
+        return Ok(Some(Description::from(sdp)));
+    }
+
+    let mut forwarders = FuturesUnordered::new();
+
+    for (index, fanout) in tracks.iter().enumerate() {
+        let (socket, rtcp_socket) = bind_port_pair().await.map_err(ClientError::IO)?;
+        let port = socket.local_addr().map_err(ClientError::IO)?.port();
+        let client_port = PortRange::pair(port).expect("RTP port should be even");
+
+        client.setup(index, TransportSpec::udp_unicast(client_port)).await?;
+        forwarders.push(forward(socket, rtcp_socket, fanout.clone()));
+    }
+
+    client.play(None).await?;
+    let _keep_alive_failures = client.start_keep_alive()?;
+
+    info!("relaying upstream {}", url);
+
+    let result = if forwarders.is_empty() {
+        let _ = rx_stop_upstream.await;
+        Ok(())
+    } else {
+        match future::select(rx_stop_upstream, forwarders.next()).await {
+            Either::Left(_) => Ok(()),
+            Either::Right((result, _)) => result.unwrap_or(Ok(())).map_err(ClientError::IO),
+        }
+    };
+
+    if let Err(error) = client.teardown().await {
+        error!("cannot tear down upstream {}: {}", url, error);
+    }
+
+    result.map(|()| None)
+}
+
+/// Receives the RTP packets of an upstream track and forwards them to the downstream sessions.
+async fn forward(
+    socket: UdpSocket,
+    _rtcp_socket: UdpSocket,
+    fanout: Arc<TrackFanout>,
+) -> io::Result<()> {
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+
+    loop {
+        let (length, _) = socket.recv_from(&mut buffer).await?;
+
+        match RtpPacket::read_from(&mut &buffer[..length]) {
+            Ok(packet) => fanout.forward(packet).await,
+            Err(error) => error!("dropped invalid RTP packet: {}", error),
+        }
+    }
+}
+
+/// Asks the camera for its SDP.
+async fn fetch_description(
+    url: &str,
+    credentials: Option<Credentials>,
+) -> Result<Description, ClientError> {
+    let mut client = upstream_client(url, credentials);
+    client.connect().await.map_err(ClientError::IO)?;
+
+    Ok(Description::from(client.describe().await?))
+}
+
+fn upstream_client(url: &str, credentials: Option<Credentials>) -> RTSPClient {
+    let mut client = RTSPClient::new(url.to_string());
+
+    if let Some(credentials) = credentials {
+        client.set_credentials(credentials.username, credentials.password);
+    }
+
+    client
+}
+
+/// Binds two UDP sockets on consecutive ports, the first one being even, as used for RTP and RTCP.
+async fn bind_port_pair() -> io::Result<(UdpSocket, UdpSocket)> {
+    let mut last_error = None;
+
+    for _ in 0..PORT_PAIR_BIND_ATTEMPTS {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        let port = socket.local_addr()?.port();
+
+        if port % 2 != 0 {
+            continue;
+        }
+
+        match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port + 1)).await {
+            Ok(rtcp_socket) => return Ok((socket, rtcp_socket)),
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::AddrInUse, "no free RTP/RTCP port pair")
+    }))
+}
+
+/// Returns the path of the source a request URI refers to, ignoring a trailing slash.
+fn source_path(uri: &URI) -> String {
+    uri.path().to_string().trim_end_matches('/').to_string()
+}
+
+/// Splits a track control URI into the path of its source and the index of the track.
+fn track_path(uri: &URI) -> Option<(String, usize)> {
+    let path = source_path(uri);
+    let separator = path.rfind('/')?;
+    let track = path[separator + 1..]
+        .strip_prefix(TRACK_CONTROL_PREFIX)?
+        .parse()
+        .ok()?;
+
+    Some((path[..separator].to_string(), track))
+}
+
+/// Returns the identifier of the session the request was sent within, if any.
+fn session_id(request: &Request<BytesMut>) -> Result<Option<SessionID>, StatusCode> {
+    request
+        .headers()
+        .typed_try_get::<Session>()
+        .map(|session| session.map(|session| session.id().clone()))
+        .map_err(|_| StatusCode::BadRequest)
+}
+
+fn session_header(session_id: &SessionID) -> Session {
+    Session::with_timeout(session_id.as_str(), DEFAULT_SESSION_TIMEOUT)
+        .expect("generated session identifier should be valid")
+}
+
+fn session_response(session_id: &SessionID) -> Response<BytesMut> {
+    Response::<()>::builder()
+        .with_status_code(StatusCode::OK)
+        .with_typed_header(session_header(session_id))
+        .with_body(BytesMut::new())
+        .build()
+        .expect("session response should be valid")
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use bytes::BytesMut;
+    use futures::channel::oneshot;
+    use futures::{future, FutureExt};
+    use tokio::net::TcpListener;
+    use tokio::time::{self, Duration};
+
+    use crate::proto::rtp::rtp::RtpFixedHeader;
+    use crate::proto::rtsp::message::header::map::HeaderMapExtension;
+    use crate::proto::rtsp::message::header::name::HeaderName;
+    use crate::proto::rtsp::message::header::types::transport::{PortRange, TransportSpec};
+    use crate::proto::rtsp::message::header::types::{Session, Transport};
+    use crate::proto::rtsp::message::header::value::HeaderValue;
+    use crate::proto::rtsp::message::method::Method;
+    use crate::proto::rtsp::message::request::Request;
+    use crate::proto::rtsp::message::response::Response;
+    use crate::proto::rtsp::message::session::DEFAULT_SESSION_TIMEOUT;
+    use crate::proto::rtsp::message::status::StatusCode;
+    use crate::proto::rtsp::message::uri::request::URI;
+    use crate::proto::rtsp::server::{Server, Service, ServiceFuture};
+    use crate::proto::sdp::parse_sdp;
+    use crate::worker::relay::{track_path, Description, Relay, RtpRewriter};
+
+    /// A camera with the given number of video tracks, recording the requests it receives. It
+    /// never sends any media.
+    #[derive(Clone, Default)]
+    struct Camera {
+        track_count: Arc<AtomicUsize>,
+        methods: Arc<Mutex<Vec<Method>>>,
+    }
+
+    impl Camera {
+        fn sdp(&self) -> String {
+            let mut sdp = "v=0\r\n\
+                           o=- 1 1 IN IP4 127.0.0.1\r\n\
+                           s=Camera\r\n\
+                           t=0 0\r\n"
+                .to_string();
+
+            for index in 0..self.track_count.load(Ordering::SeqCst) {
+                sdp.push_str("m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\n");
+                sdp.push_str(&format!("a=control:trackID={}\r\n", index + 1));
+            }
+
+            sdp
+        }
+
+        fn received(&self, method: Method) -> bool {
+            self.methods.lock().unwrap().contains(&method)
+        }
+
+        /// Waits until the camera received a request with the method.
+        async fn wait_for(&self, method: Method) {
+            time::timeout(Duration::from_secs(5), async {
+                while !self.received(method.clone()) {
+                    time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("camera should have received {}", method));
+        }
+    }
+
+    impl Service for Camera {
+        fn methods(&self) -> Vec<Method> {
+            vec![
+                Method::Describe,
+                Method::GetParameter,
+                Method::Play,
+                Method::Setup,
+                Method::Teardown,
+            ]
+        }
+
+        fn call(&self, request: Request<BytesMut>, _: SocketAddr) -> ServiceFuture {
+            self.methods.lock().unwrap().push(request.method().clone());
+
+            let builder = Response::<()>::builder().with_status_code(StatusCode::OK);
+            let session = Session::with_timeout("12345678", DEFAULT_SESSION_TIMEOUT).unwrap();
+            let response = match request.method() {
+                Method::Describe => builder
+                    .with_header(
+                        HeaderName::ContentType,
+                        HeaderValue::try_from("application/sdp").unwrap(),
+                    )
+                    .with_body(BytesMut::from(self.sdp().as_str())),
+                Method::Setup => builder
+                    .with_typed_header(session)
+                    .with_typed_header(request.headers().typed_get::<Transport>().unwrap())
+                    .with_body(BytesMut::new()),
+                _ => builder.with_typed_header(session).with_body(BytesMut::new()),
+            };
+
+            Box::pin(future::ready(response.build().unwrap()))
+        }
+    }
+
+    /// Serves the camera until the returned sender is dropped.
+    async fn serve(camera: &Camera) -> (SocketAddr, oneshot::Sender<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (tx_shutdown, rx_shutdown) = oneshot::channel::<()>();
+
+        tokio::spawn(Server::new(camera.clone()).run(listener, rx_shutdown.map(|_| ())));
+        (address, tx_shutdown)
+    }
+
+    fn request(method: Method, uri: &str, session: Option<&Session>) -> Request<BytesMut> {
+        let mut builder = Request::<()>::builder()
+            .with_method(method)
+            .with_uri(URI::try_from(uri).unwrap());
+
+        if let Some(session) = session {
+            let session = Session::without_timeout(session.id().as_str()).unwrap();
+            builder = builder.with_typed_header(session);
+        }
+
+        builder.with_body(BytesMut::new()).build().unwrap()
+    }
+
+    fn setup_request(uri: &str) -> Request<BytesMut> {
+        let transport = TransportSpec::udp_unicast(PortRange::pair(50_000).unwrap());
+        let mut request = request(Method::Setup, uri, None);
+        request.headers_mut().typed_insert(Transport::from(transport));
+        request
+    }
+
+    fn peer_address() -> SocketAddr {
+        "127.0.0.1:50000".parse().unwrap()
+    }
+
+    fn header(ssrc: u32, seq_num: u16, timestamp: u32) -> RtpFixedHeader {
+        RtpFixedHeader {
+            padding: false,
+            marker: false,
+            payload_type: 96,
+            seq_num,
+            timestamp,
+            ssrc,
+            csrc_list: Vec::new(),
+            extension: None,
+        }
+    }
+
+    fn rewrite(rewriter: &mut RtpRewriter, ssrc: u32, seq_num: u16, timestamp: u32) -> (u32, u16, u32) {
+        let mut header = header(ssrc, seq_num, timestamp);
+        rewriter.rewrite(&mut header);
+        (header.ssrc, header.seq_num, header.timestamp)
+    }
+
+    #[test]
+    fn test_rtp_rewriter() {
+        let mut rewriter = RtpRewriter::new(0xAAAA_AAAA, 65535, 1000);
+
+        assert_eq!(rewrite(&mut rewriter, 1, 100, 90_000), (0xAAAA_AAAA, 65535, 1000));
+        assert_eq!(rewrite(&mut rewriter, 1, 101, 93_000), (0xAAAA_AAAA, 0, 4000));
+        assert_eq!(rewrite(&mut rewriter, 1, 103, 99_000), (0xAAAA_AAAA, 2, 10_000));
+
+        // A late packet keeps its place without moving the sequence number back.
+        assert_eq!(rewrite(&mut rewriter, 1, 102, 96_000), (0xAAAA_AAAA, 1, 7000));
+
+        // The camera was pulled again, the session continues one frame after where it left off.
+        assert_eq!(rewrite(&mut rewriter, 2, 5000, 123), (0xAAAA_AAAA, 3, 13_000));
+        assert_eq!(rewrite(&mut rewriter, 2, 5001, 3123), (0xAAAA_AAAA, 4, 16_000));
+    }
+
+    #[test]
+    fn test_description() {
+        let sdp = parse_sdp(
+            "v=0\r\n\
+             o=- 1 1 IN IP4 192.168.1.125\r\n\
+             s=Media Presentation\r\n\
+             t=0 0\r\n\
+             a=control:rtsp://192.168.1.125:554/h264/ch1/main/av_stream/\r\n\
+             m=video 0 RTP/AVP 96\r\n\
+             a=rtpmap:96 H264/90000\r\n\
+             a=control:rtsp://192.168.1.125:554/h264/ch1/main/av_stream/trackID=1\r\n\
+             m=audio 0 RTP/AVP 8\r\n\
+             a=control:trackID=2\r\n",
+            false,
+        )
+        .unwrap();
+        let description = Description::from(sdp);
+
+        assert_eq!(description.track_count, 2);
+        assert!(description.sdp.contains("a=control:*\r\n"));
+        assert!(description.sdp.contains("a=control:trackID=0\r\n"));
+        assert!(description.sdp.contains("a=control:trackID=1\r\n"));
+        assert!(!description.sdp.contains("192.168.1.125:554"));
+    }
+
+    #[test]
+    fn test_track_path() {
+        let uri = URI::try_from("rtsp://127.0.0.1:8554/camera1/trackID=1").unwrap();
+        assert_eq!(track_path(&uri), Some(("/camera1".to_string(), 1)));
+
+        let uri = URI::try_from("rtsp://127.0.0.1:8554/camera1/").unwrap();
+        assert_eq!(track_path(&uri), None);
+    }
+
+    #[tokio::test]
+    async fn test_relay_session() {
+        let camera = Camera::default();
+        camera.track_count.store(1, Ordering::SeqCst);
+        let (address, _tx_shutdown) = serve(&camera).await;
+
+        let relay = Relay::new();
+        relay.add_source("/camera", format!("rtsp://{}/stream", address), None);
+
+        let uri = "rtsp://127.0.0.1:8554/camera";
+        let response = relay.call(request(Method::Describe, uri, None), peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(std::str::from_utf8(response.body()).unwrap().contains("a=control:trackID=0\r\n"));
+
+        let track_uri = "rtsp://127.0.0.1:8554/camera/trackID=0";
+        let response = relay.call(setup_request(track_uri), peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let session = response.headers().typed_get::<Session>().unwrap();
+
+        let response = relay.call(request(Method::Play, uri, Some(&session)), peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        camera.wait_for(Method::Play).await;
+
+        // Tearing down the last session stops pulling the camera.
+        let teardown = request(Method::Teardown, uri, Some(&session));
+        let response = relay.call(teardown, peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        camera.wait_for(Method::Teardown).await;
+        assert!(!relay.is_relaying("/camera"));
+
+        let response = relay.call(request(Method::Play, uri, Some(&session)), peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::SessionNotFound);
+    }
+
+    #[tokio::test]
+    async fn test_relay_changed_tracks() {
+        let camera = Camera::default();
+        camera.track_count.store(1, Ordering::SeqCst);
+        let (address, _tx_shutdown) = serve(&camera).await;
+
+        let relay = Relay::new();
+        relay.add_source("/camera", format!("rtsp://{}/stream", address), None);
+
+        let uri = "rtsp://127.0.0.1:8554/camera";
+        let response = relay.call(request(Method::Describe, uri, None), peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+
+        // The camera rebooted with another track, which the client has to describe again.
+        camera.track_count.store(2, Ordering::SeqCst);
+
+        let track_uri = "rtsp://127.0.0.1:8554/camera/trackID=0";
+        let response = relay.call(setup_request(track_uri), peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::ServiceUnavailable);
+        assert!(!relay.is_relaying("/camera"));
+
+        let response = relay.call(request(Method::Describe, uri, None), peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(std::str::from_utf8(response.body()).unwrap().contains("a=control:trackID=1\r\n"));
+
+        let response = relay.call(setup_request(track_uri), peer_address()).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        camera.wait_for(Method::Setup).await;
+    }
+}
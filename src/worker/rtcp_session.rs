@@ -0,0 +1,909 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime};
+
+use fnv::FnvBuildHasher;
+use log::{error, warn};
+
+use crate::proto::rtcp::bye_packet::GoodbyePacket;
+use crate::proto::rtcp::interval::{
+    self, IntervalParameters, RTCP_BANDWIDTH_FRACTION, UDP_IPV4_OVERHEAD,
+};
+use crate::proto::rtcp::reception::ReceptionStatistics;
+use crate::proto::rtcp::report_packet::{
+    ReceiverReportPacket, ReceptionReport, SenderReportPacket,
+};
+use crate::proto::rtcp::rtcp_packet::{RtcpCompoundPacket, RtcpPacket};
+use crate::proto::rtcp::source_description_packet::{SdesChunk, SdesItem, SourceDescriptionPacket};
+use crate::proto::rtp::rtp::RtpPacket;
+use crate::proto::rtp::sender::RtpSender;
+use crate::proto::traits::WriteTo;
+
+/// The session bandwidth assumed unless configured otherwise, in bits per second.
+pub const DEFAULT_SESSION_BANDWIDTH: u32 = 1_000_000;
+
+/// The number of reception reports a report packet holds at most.
+const MAX_REPORTS_PER_PACKET: usize = 31;
+
+/// The number of deterministic intervals without packets after which a participant times out.
+const MEMBER_TIMEOUT_INTERVALS: u32 = 5;
+
+/// The number of deterministic intervals without RTP packets after which a sender is no longer
+/// one.
+const SENDER_TIMEOUT_INTERVALS: u32 = 2;
+
+/// The number of deterministic intervals for which the address of a collision is remembered.
+const CONFLICT_TIMEOUT_INTERVALS: u32 = 10;
+
+/// The number of members up to which a BYE is sent right away rather than on the interval.
+const MAX_IMMEDIATE_BYE_MEMBERS: usize = 50;
+
+/// A remote participant of an RTP session, known from its RTP or RTCP packets.
+#[derive(Debug)]
+pub struct Participant {
+    ssrc: u32,
+    cname: Option<String>,
+
+    /// The source addresses of the RTP and the RTCP packets, which are fixed to detect SSRC
+    /// collisions and loops.
+    rtp_addr: Option<SocketAddr>,
+    rtcp_addr: Option<SocketAddr>,
+
+    statistics: Option<ReceptionStatistics>,
+    rtcp_received: bool,
+    last_rtp: Option<Instant>,
+    last_activity: Instant,
+}
+
+impl Participant {
+    fn new(ssrc: u32, now: Instant) -> Self {
+        Participant {
+            ssrc,
+            cname: None,
+            rtp_addr: None,
+            rtcp_addr: None,
+            statistics: None,
+            rtcp_received: false,
+            last_rtp: None,
+            last_activity: now,
+        }
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Returns the CNAME of the participant, once an SDES packet of it arrived.
+    pub fn cname(&self) -> Option<&str> {
+        self.cname.as_deref()
+    }
+
+    /// Returns the reception statistics of the RTP packets of the participant, if any arrived.
+    pub fn statistics(&self) -> Option<&ReceptionStatistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Returns whether the participant is counted as a member of the session, i.e. an RTCP
+    /// packet of it arrived or its RTP packets are valid.
+    pub fn is_member(&self) -> bool {
+        self.rtcp_received || self.is_valid_source()
+    }
+
+    /// Returns whether the participant sent valid RTP packets recently.
+    pub fn is_sender(&self) -> bool {
+        self.last_rtp.is_some() && self.is_valid_source()
+    }
+
+    fn is_valid_source(&self) -> bool {
+        self.statistics
+            .as_ref()
+            .map_or(false, |statistics| statistics.is_valid())
+    }
+
+    /// Returns when the last packet of the participant arrived.
+    pub fn last_activity(&self) -> Instant {
+        self.last_activity
+    }
+}
+
+/// The state of a local participant leaving a large session, which sends its BYE on the
+/// interval computed from the BYEs of the others (section 6.3.7 of RFC 3550).
+#[derive(Debug)]
+struct Leaving {
+    reason: Option<String>,
+    members: usize,
+}
+
+/// The RTCP state of the local participant of an RTP session, following section 6.3 of
+/// RFC 3550.
+///
+/// The participants of the session are tracked from their RTP and RTCP packets, and time out
+/// after 5 intervals without packets or leave with a BYE. Reports are sent on the randomized
+/// interval, which is reconsidered as participants join and leave. Packets with the SSRC of the
+/// local participant make it change the SSRC, or are dropped as a loop if they come from an
+/// address which did so before.
+pub struct RtcpSession {
+    ssrc: u32,
+    cname: String,
+    clock_rate: u32,
+
+    /// The bandwidth of the session in octets per second.
+    session_bandwidth: f64,
+
+    participants: HashMap<u32, Participant, FnvBuildHasher>,
+
+    /// The addresses which sent packets with the SSRC of the local participant, and when the
+    /// last of them arrived.
+    conflicting_addrs: Vec<(SocketAddr, Instant)>,
+    collisions: u64,
+    loops: u64,
+    third_party_conflicts: u64,
+
+    /// The packets to send right away, such as the BYE of an SSRC given up after a collision.
+    pending: VecDeque<RtcpCompoundPacket>,
+
+    /// The RTP sender of the local participant as of its last packet, and when that was sent.
+    sender: Option<(RtpSender, Instant)>,
+
+    average_packet_size: f64,
+    initial: bool,
+    we_sent: bool,
+
+    /// The number of members when the last report was sent, and when it was.
+    pmembers: usize,
+    last_report: Instant,
+    next_report: Instant,
+
+    leaving: Option<Leaving>,
+    left: bool,
+}
+
+impl RtcpSession {
+    /// Constructs the RTCP state of a participant with the SSRC and the CNAME, which receives
+    /// RTP packets with timestamps of the clock rate. The first report is scheduled from now.
+    pub fn new(ssrc: u32, cname: String, clock_rate: u32, now: Instant) -> Self {
+        let mut session = RtcpSession {
+            ssrc,
+            cname,
+            clock_rate,
+            session_bandwidth: f64::from(DEFAULT_SESSION_BANDWIDTH) / 8.0,
+            participants: HashMap::default(),
+            conflicting_addrs: Vec::new(),
+            collisions: 0,
+            loops: 0,
+            third_party_conflicts: 0,
+            pending: VecDeque::new(),
+            sender: None,
+            average_packet_size: 0.0,
+            initial: true,
+            we_sent: false,
+            pmembers: 1,
+            last_report: now,
+            next_report: now,
+            leaving: None,
+            left: false,
+        };
+
+        // The average starts with the size of the first report.
+        session.average_packet_size =
+            session.compound_size(&session.compound_packet(ssrc, Vec::new(), None)) as f64;
+        session.next_report = now + session.interval();
+        session
+    }
+
+    /// Sets the bandwidth of the session in bits per second, 5% of which is used for RTCP.
+    pub fn set_session_bandwidth(&mut self, bits_per_second: u32) {
+        self.session_bandwidth = f64::from(bits_per_second) / 8.0;
+    }
+
+    /// Returns the SSRC of the local participant, which changes on collisions.
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    pub fn cname(&self) -> &str {
+        &self.cname
+    }
+
+    /// Returns a remote participant, if any packets of it arrived.
+    pub fn participant(&self, ssrc: u32) -> Option<&Participant> {
+        self.participants.get(&ssrc)
+    }
+
+    pub fn participants(&self) -> impl Iterator<Item = &Participant> {
+        self.participants.values()
+    }
+
+    /// Returns the reception statistics of a remote source, if any RTP packets of it arrived.
+    pub fn source(&self, ssrc: u32) -> Option<&ReceptionStatistics> {
+        self.participants.get(&ssrc)?.statistics()
+    }
+
+    /// Returns the number of members of the session, including the local participant.
+    pub fn members(&self) -> usize {
+        match self.leaving {
+            Some(ref leaving) => leaving.members,
+            None => {
+                let members = self.participants.values().filter(|p| p.is_member());
+                members.count() + 1
+            }
+        }
+    }
+
+    /// Returns the number of senders of the session, including the local participant.
+    pub fn senders(&self) -> usize {
+        if self.leaving.is_some() {
+            return 0;
+        }
+
+        let senders = self.participants.values().filter(|p| p.is_sender());
+        senders.count() + self.we_sent as usize
+    }
+
+    /// Returns the number of collisions of the SSRC of the local participant.
+    pub fn collisions(&self) -> u64 {
+        self.collisions
+    }
+
+    /// Returns the number of packets with the SSRC of the local participant dropped as loops.
+    pub fn loops(&self) -> u64 {
+        self.loops
+    }
+
+    /// Returns the number of packets dropped for coming from another address than the one of
+    /// their participant, i.e. collisions or loops between remote participants.
+    pub fn third_party_conflicts(&self) -> u64 {
+        self.third_party_conflicts
+    }
+
+    /// Returns whether the BYE of the local participant was sent.
+    pub fn has_left(&self) -> bool {
+        self.left && self.pending.is_empty()
+    }
+
+    /// Handles an RTP packet which arrived at the given time from the source address. Returns
+    /// whether the packet is to be processed, which it is not on SSRC collisions and loops.
+    pub fn receive_rtp(
+        &mut self,
+        packet: &RtpPacket,
+        source: SocketAddr,
+        arrival: Instant,
+    ) -> bool {
+        let clock_rate = self.clock_rate;
+        let header = &packet.header;
+
+        let participant = match self.admit(header.ssrc, source, false, arrival) {
+            Some(participant) => participant,
+            None => return false,
+        };
+
+        participant.last_rtp = Some(arrival);
+        participant
+            .statistics
+            .get_or_insert_with(|| {
+                ReceptionStatistics::new(header.ssrc, clock_rate, header.seq_num)
+            })
+            .update(packet, arrival);
+        true
+    }
+
+    /// Records an RTP packet sent at the given time by the sender of the local participant, which
+    /// then sends sender reports. Senders of a previous SSRC are ignored.
+    pub fn on_rtp_sent(&mut self, sender: &RtpSender, now: Instant) {
+        if sender.ssrc() != self.ssrc || self.left || self.leaving.is_some() {
+            return;
+        }
+
+        self.sender = Some((sender.clone(), now));
+        self.we_sent = true;
+    }
+
+    /// Handles a compound RTCP packet which arrived at the given time from the source address.
+    pub fn receive_rtcp(
+        &mut self,
+        packet: &RtcpCompoundPacket,
+        source: SocketAddr,
+        arrival: Instant,
+    ) {
+        let size = self.compound_size(packet);
+        self.average_packet_size =
+            interval::update_average_packet_size(self.average_packet_size, size);
+
+        for packet in packet.packets.iter() {
+            match packet {
+                RtcpPacket::Sr(sender_report) => {
+                    if let Some(participant) = self.admit(sender_report.ssrc, source, true, arrival)
+                    {
+                        if let Some(statistics) = participant.statistics.as_mut() {
+                            statistics.on_sender_report(sender_report, arrival);
+                        }
+                    }
+                }
+                RtcpPacket::Rr(receiver_report) => {
+                    self.admit(receiver_report.ssrc, source, true, arrival);
+                }
+                RtcpPacket::Sdes(description) => {
+                    for chunk in description.chunks.iter() {
+                        if let Some(participant) =
+                            self.admit(chunk.ssrc_or_csrc, source, true, arrival)
+                        {
+                            for item in chunk.items.iter() {
+                                if let SdesItem::Cname(cname) = item {
+                                    participant.cname = Some(cname.clone());
+                                }
+                            }
+                        }
+                    }
+                }
+                RtcpPacket::Bye(goodbye) => {
+                    for &ssrc in goodbye.ssrc_csrc_list.iter() {
+                        self.remove(ssrc, arrival);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the participant of a packet with the SSRC from the source address, unless the
+    /// packet is a collision or a loop.
+    fn admit(
+        &mut self,
+        ssrc: u32,
+        source: SocketAddr,
+        rtcp: bool,
+        now: Instant,
+    ) -> Option<&mut Participant> {
+        if ssrc == self.ssrc {
+            if let Some(conflict) = self
+                .conflicting_addrs
+                .iter_mut()
+                .find(|(addr, _)| *addr == source)
+            {
+                conflict.1 = now;
+                self.loops += 1;
+                return None;
+            }
+
+            // The SSRC is given up to the remote participant.
+            self.conflicting_addrs.push((source, now));
+            self.collisions += 1;
+            self.change_ssrc();
+        }
+
+        let participant = self
+            .participants
+            .entry(ssrc)
+            .or_insert_with(|| Participant::new(ssrc, now));
+        let addr = if rtcp {
+            &mut participant.rtcp_addr
+        } else {
+            &mut participant.rtp_addr
+        };
+
+        match addr {
+            Some(addr) if *addr != source => {
+                self.third_party_conflicts += 1;
+                return None;
+            }
+            _ => *addr = Some(source),
+        }
+
+        participant.rtcp_received |= rtcp;
+        participant.last_activity = now;
+        Some(participant)
+    }
+
+    /// Sends a BYE for the SSRC of the local participant and changes it to a random unused one.
+    fn change_ssrc(&mut self) {
+        let old_ssrc = self.ssrc;
+        let bye = self.bye_packet(old_ssrc, Some("SSRC collision".to_owned()));
+        self.pending.push_back(bye);
+
+        while self.ssrc == old_ssrc || self.participants.contains_key(&self.ssrc) {
+            self.ssrc = rand::random();
+        }
+
+        // The new SSRC has not sent anything yet.
+        self.sender = None;
+        self.we_sent = false;
+
+        warn!(
+            "SSRC collision, changed the SSRC {:08x} to {:08x}",
+            old_ssrc, self.ssrc
+        );
+    }
+
+    /// Removes a participant which left the session.
+    fn remove(&mut self, ssrc: u32, now: Instant) {
+        if let Some(ref mut leaving) = self.leaving {
+            leaving.members += 1;
+            return;
+        }
+
+        if self.participants.remove(&ssrc).is_some() {
+            self.reconsider_reverse(now);
+        }
+    }
+
+    /// Moves the next report closer as the number of members decreased, so that the interval
+    /// does not stay longer than needed (section 6.3.4 of RFC 3550).
+    fn reconsider_reverse(&mut self, now: Instant) {
+        let members = self.members();
+        if members >= self.pmembers {
+            return;
+        }
+
+        let ratio = members as f64 / self.pmembers as f64;
+        let until_next = self.next_report.saturating_duration_since(now);
+        let since_last = now.saturating_duration_since(self.last_report);
+
+        self.next_report = now + until_next.mul_f64(ratio);
+        self.last_report = now
+            .checked_sub(since_last.mul_f64(ratio))
+            .unwrap_or(self.last_report);
+        self.pmembers = members;
+    }
+
+    /// Removes the participants without packets for 5 intervals, and drops the sender status of
+    /// those without RTP packets for 2, including the local participant.
+    fn expire(&mut self, now: Instant) {
+        let interval = self.deterministic_interval();
+        let member_timeout = interval * MEMBER_TIMEOUT_INTERVALS;
+        let sender_timeout = interval * SENDER_TIMEOUT_INTERVALS;
+
+        if let Some((_, last_sent)) = self.sender {
+            if now.saturating_duration_since(last_sent) > sender_timeout {
+                self.we_sent = false;
+            }
+        }
+
+        for participant in self.participants.values_mut() {
+            if let Some(last_rtp) = participant.last_rtp {
+                if now.saturating_duration_since(last_rtp) > sender_timeout {
+                    participant.last_rtp = None;
+                }
+            }
+        }
+
+        let participants = self.participants.len();
+        self.participants.retain(|_, participant| {
+            now.saturating_duration_since(participant.last_activity) <= member_timeout
+        });
+
+        let conflict_timeout = interval * CONFLICT_TIMEOUT_INTERVALS;
+        self.conflicting_addrs
+            .retain(|(_, last)| now.saturating_duration_since(*last) <= conflict_timeout);
+
+        if self.participants.len() < participants {
+            self.reconsider_reverse(now);
+        }
+    }
+
+    /// Leaves the session, sending a BYE with the reason. In sessions of more than 50 members
+    /// the BYE waits for an interval computed from the BYEs of the others, so that they do not
+    /// flood the session.
+    pub fn leave(&mut self, reason: Option<String>, now: Instant) {
+        if self.left || self.leaving.is_some() {
+            return;
+        }
+
+        // A participant which sent no packets leaves silently.
+        if self.initial && !self.we_sent {
+            self.left = true;
+            return;
+        }
+
+        let bye = self.bye_packet(self.ssrc, reason.clone());
+
+        if self.members() <= MAX_IMMEDIATE_BYE_MEMBERS {
+            self.pending.push_back(bye);
+            self.left = true;
+            return;
+        }
+
+        self.average_packet_size = self.compound_size(&bye) as f64;
+        self.leaving = Some(Leaving { reason, members: 1 });
+        self.initial = true;
+        self.we_sent = false;
+        self.pmembers = 1;
+        self.last_report = now;
+        self.next_report = now + self.interval();
+    }
+
+    /// Returns when the next report is due.
+    pub fn next_report(&self) -> Instant {
+        self.next_report
+    }
+
+    /// Returns the next packet to send at the given time, if any, and schedules the next report
+    /// once one is sent.
+    ///
+    /// When the report is due, the interval is reconsidered with the current number of
+    /// members, and the report is postponed if that interval has not yet passed since the last
+    /// one (section 6.3.6 of RFC 3550).
+    pub fn poll_report(&mut self, now: Instant) -> Option<RtcpCompoundPacket> {
+        if let Some(packet) = self.pending.pop_front() {
+            return Some(packet);
+        }
+
+        if self.left || now < self.next_report {
+            return None;
+        }
+
+        if self.leaving.is_none() {
+            self.expire(now);
+        }
+
+        let interval = self.interval();
+        if self.last_report + interval > now {
+            self.next_report = self.last_report + interval;
+            return None;
+        }
+
+        let packet = match self.leaving.take() {
+            Some(leaving) => {
+                self.left = true;
+                self.bye_packet(self.ssrc, leaving.reason)
+            }
+            None => self.report(now),
+        };
+
+        let size = self.compound_size(&packet);
+        self.average_packet_size =
+            interval::update_average_packet_size(self.average_packet_size, size);
+        self.initial = false;
+        self.last_report = now;
+        self.pmembers = self.members();
+        self.next_report = now + self.interval();
+
+        Some(packet)
+    }
+
+    /// Returns a compound packet of a sender report if the local participant sent RTP packets
+    /// recently, or else a receiver report, with the reception reports of the senders and the
+    /// CNAME.
+    pub fn report(&mut self, now: Instant) -> RtcpCompoundPacket {
+        let mut reports: Vec<_> = self
+            .participants
+            .values_mut()
+            .filter(|participant| participant.is_sender())
+            .filter_map(|participant| participant.statistics.as_mut())
+            .map(|statistics| statistics.report(now))
+            .collect();
+        reports.sort_by_key(|report| report.ssrc);
+
+        let sender_report = match self.sender {
+            Some((ref sender, _)) if self.we_sent => {
+                Some(sender.sender_report(now, SystemTime::now()))
+            }
+            _ => None,
+        };
+
+        self.compound_packet(self.ssrc, reports, sender_report)
+    }
+
+    fn compound_packet(
+        &self,
+        ssrc: u32,
+        reports: Vec<ReceptionReport>,
+        sender_report: Option<SenderReportPacket>,
+    ) -> RtcpCompoundPacket {
+        let mut packets = Vec::new();
+        let mut chunks = reports.chunks(MAX_REPORTS_PER_PACKET);
+        let first_chunk = chunks.next().map_or_else(Vec::new, |chunk| chunk.to_vec());
+
+        // A compound packet starts with a report packet, even without reception reports.
+        match sender_report {
+            Some(mut sender_report) => {
+                sender_report.reception_reports = first_chunk;
+                packets.push(RtcpPacket::Sr(sender_report));
+            }
+            None => {
+                let mut receiver_report = ReceiverReportPacket::new(ssrc);
+                receiver_report.reception_reports = first_chunk;
+                packets.push(RtcpPacket::Rr(receiver_report));
+            }
+        }
+
+        for chunk in chunks {
+            let mut receiver_report = ReceiverReportPacket::new(ssrc);
+            receiver_report.reception_reports = chunk.to_vec();
+            packets.push(RtcpPacket::Rr(receiver_report));
+        }
+
+        let mut description = SourceDescriptionPacket::new();
+        description.chunks.push(SdesChunk {
+            ssrc_or_csrc: ssrc,
+            items: vec![SdesItem::Cname(self.cname.clone())],
+        });
+        packets.push(RtcpPacket::Sdes(description));
+
+        RtcpCompoundPacket::new(packets)
+    }
+
+    /// Returns a compound packet ending with a BYE for the SSRC.
+    fn bye_packet(&self, ssrc: u32, reason: Option<String>) -> RtcpCompoundPacket {
+        let mut packet = self.compound_packet(ssrc, Vec::new(), None);
+
+        let mut goodbye = GoodbyePacket::new();
+        goodbye.ssrc_csrc_list.push(ssrc);
+        goodbye.reason = reason;
+        packet.packets.push(RtcpPacket::Bye(goodbye));
+
+        packet
+    }
+
+    /// Returns the size of a compound packet including the UDP and IP headers.
+    fn compound_size(&self, packet: &RtcpCompoundPacket) -> usize {
+        match packet.to_bytes() {
+            Ok(bytes) => bytes.len() + UDP_IPV4_OVERHEAD,
+            Err(e) => {
+                error!("cannot serialize RTCP packet: {}", e);
+                UDP_IPV4_OVERHEAD
+            }
+        }
+    }
+
+    fn interval_parameters(&self) -> IntervalParameters {
+        IntervalParameters {
+            members: self.members(),
+            senders: self.senders(),
+            rtcp_bandwidth: self.session_bandwidth * RTCP_BANDWIDTH_FRACTION,
+            we_sent: self.we_sent,
+            average_packet_size: self.average_packet_size,
+            initial: self.initial,
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval_parameters().interval(&mut rand::thread_rng())
+    }
+
+    /// Returns the interval without randomization that the timeouts are multiples of.
+    fn deterministic_interval(&self) -> Duration {
+        let parameters = IntervalParameters {
+            initial: false,
+            ..self.interval_parameters()
+        };
+
+        parameters.deterministic_interval()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant, SystemTime};
+
+    use crate::proto::rtcp::bye_packet::GoodbyePacket;
+    use crate::proto::rtcp::report_packet::{ReceiverReportPacket, SenderReportPacket};
+    use crate::proto::rtcp::rtcp_packet::{RtcpCompoundPacket, RtcpPacket};
+    use crate::proto::rtcp::source_description_packet::SdesItem;
+    use crate::proto::rtp::rtp::{test_support, RtpPacket};
+    use crate::proto::rtp::sender::RtpSender;
+
+    use super::RtcpSession;
+
+    fn packet(ssrc: u32, seq_num: u16) -> RtpPacket {
+        let mut packet = test_support::packet(seq_num, 0, false, &[0]);
+        packet.header.ssrc = ssrc;
+        packet
+    }
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 168, 0, 1], port))
+    }
+
+    fn receiver_report(ssrc: u32) -> RtcpCompoundPacket {
+        RtcpCompoundPacket::new(vec![ReceiverReportPacket::new(ssrc).into()])
+    }
+
+    fn goodbye(ssrc: u32) -> RtcpCompoundPacket {
+        let mut goodbye = GoodbyePacket::new();
+        goodbye.ssrc_csrc_list.push(ssrc);
+        RtcpCompoundPacket::new(vec![ReceiverReportPacket::new(ssrc).into(), goodbye.into()])
+    }
+
+    /// Polls the session when the report is due until it is sent, as the reconsideration may
+    /// postpone it.
+    fn send_report(session: &mut RtcpSession) -> (Instant, RtcpCompoundPacket) {
+        loop {
+            let now = session.next_report();
+            if let Some(report) = session.poll_report(now) {
+                return (now, report);
+            }
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let start = Instant::now();
+        let mut session = RtcpSession::new(1, "user@example.com".to_owned(), 90_000, start);
+
+        // The initial interval is randomized around half the minimum one.
+        assert!(session.poll_report(start).is_none());
+        assert!(session.next_report() <= start + Duration::from_millis(3100));
+        assert!(session.next_report() >= start + Duration::from_millis(1020));
+
+        for seq_num in &[1, 2, 3, 5] {
+            assert!(session.receive_rtp(&packet(2, *seq_num), addr(5000), start));
+        }
+        session.receive_rtp(&packet(3, 1), addr(6000), start);
+        assert_eq!(session.members(), 2);
+        assert_eq!(session.senders(), 1);
+
+        let mut sender_report = SenderReportPacket::new(2);
+        sender_report.ntp_sec = 0x0001_0002;
+        let sender_report = RtcpCompoundPacket::new(vec![sender_report.into()]);
+        session.receive_rtcp(&sender_report, addr(5001), start);
+
+        let (now, report) = send_report(&mut session);
+        assert!(session.next_report() >= now + Duration::from_millis(2050));
+
+        match &report.packets[..] {
+            [RtcpPacket::Rr(receiver_report), RtcpPacket::Sdes(description)] => {
+                assert_eq!(receiver_report.ssrc, 1);
+
+                // The source still in probation is not reported.
+                let reports = &receiver_report.reception_reports;
+                assert_eq!(reports.len(), 1);
+                assert_eq!(reports[0].ssrc, 2);
+                assert_eq!(reports[0].packets_lost, 1);
+                assert_eq!(reports[0].seq_num_ext, 5);
+                assert_eq!(reports[0].last_sr_timestamp, 0x0002_0000);
+
+                assert_eq!(description.chunks[0].ssrc_or_csrc, 1);
+                assert_eq!(
+                    description.chunks[0].items,
+                    vec![SdesItem::Cname("user@example.com".to_owned())]
+                );
+            }
+            packets => panic!("unexpected packets: {:?}", packets),
+        }
+    }
+
+    #[test]
+    fn test_reconsideration() {
+        let start = Instant::now();
+        let mut session = RtcpSession::new(1, "user@example.com".to_owned(), 90_000, start);
+        session.set_session_bandwidth(16_000);
+
+        // Members joining before the report is due postpone it.
+        for ssrc in 2..202 {
+            session.receive_rtcp(&receiver_report(ssrc), addr(5000 + ssrc as u16), start);
+        }
+        assert_eq!(session.members(), 201);
+
+        let scheduled = session.next_report();
+        assert!(session.poll_report(scheduled).is_none());
+        assert!(session.next_report() > start + Duration::from_secs(20));
+
+        // Members leaving after the report bring the next one closer.
+        let (now, _) = send_report(&mut session);
+        let next_report = session.next_report();
+        for ssrc in 2..102 {
+            session.receive_rtcp(&goodbye(ssrc), addr(5000 + ssrc as u16), now);
+        }
+        assert_eq!(session.members(), 101);
+        assert!(session.next_report() < next_report);
+        assert!(session.participant(2).is_none());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let start = Instant::now();
+        let mut session = RtcpSession::new(1, "user@example.com".to_owned(), 90_000, start);
+
+        session.receive_rtcp(&receiver_report(2), addr(5001), start);
+        for seq_num in 1..4 {
+            session.receive_rtp(&packet(3, seq_num), addr(6000), start);
+        }
+        assert_eq!(session.members(), 3);
+        assert_eq!(session.senders(), 1);
+
+        // Senders become receivers after 2 intervals, members time out after 5.
+        let now = start + Duration::from_secs(11);
+        session.receive_rtcp(&receiver_report(3), addr(6001), now);
+        assert!(session.poll_report(now).is_some());
+        assert_eq!(session.senders(), 0);
+        assert_eq!(session.members(), 3);
+
+        let now = start + Duration::from_secs(26);
+        session.poll_report(now);
+        assert!(session.participant(2).is_none());
+        assert_eq!(session.members(), 2);
+        assert_eq!(
+            session.participant(3).unwrap().last_activity(),
+            start + Duration::from_secs(11)
+        );
+    }
+
+    #[test]
+    fn test_collision() {
+        let start = Instant::now();
+        let mut session = RtcpSession::new(1, "user@example.com".to_owned(), 90_000, start);
+
+        // A remote participant using the local SSRC makes it change, with a BYE for the old one.
+        assert!(session.receive_rtp(&packet(1, 1), addr(5000), start));
+        assert_eq!(session.collisions(), 1);
+        assert_ne!(session.ssrc(), 1);
+        assert!(session.participant(1).is_some());
+
+        match &session.poll_report(start).unwrap().packets[..] {
+            [RtcpPacket::Rr(receiver_report), RtcpPacket::Sdes(_), RtcpPacket::Bye(goodbye)] => {
+                assert_eq!(receiver_report.ssrc, 1);
+                assert_eq!(goodbye.ssrc_csrc_list, vec![1]);
+            }
+            packets => panic!("unexpected packets: {:?}", packets),
+        }
+
+        // Packets with the new SSRC from the same address are a loop.
+        let ssrc = session.ssrc();
+        assert!(!session.receive_rtp(&packet(ssrc, 1), addr(5000), start));
+        assert_eq!(session.loops(), 1);
+        assert_eq!(session.ssrc(), ssrc);
+
+        // Packets of a remote participant from another address are dropped.
+        assert!(!session.receive_rtp(&packet(1, 2), addr(7000), start));
+        assert_eq!(session.third_party_conflicts(), 1);
+    }
+
+    #[test]
+    fn test_sender_report() {
+        let start = Instant::now();
+        let mut session = RtcpSession::new(1, "user@example.com".to_owned(), 90_000, start);
+        let mut sender = RtpSender::new(session.ssrc(), 96, 90_000);
+
+        for seq_num in 1..4 {
+            session.receive_rtp(&packet(2, seq_num), addr(5000), start);
+        }
+        let sent = sender.packetize(vec![0; 100], 0, true, start);
+        session.on_rtp_sent(&sender, start);
+        assert_eq!(session.senders(), 2);
+
+        let (now, report) = send_report(&mut session);
+        match &report.packets[..] {
+            [RtcpPacket::Sr(sender_report), RtcpPacket::Sdes(_)] => {
+                let elapsed = (now - start).as_secs_f64() * 90_000.0;
+                let timestamp = sent.header.timestamp.wrapping_add(elapsed as u32);
+
+                assert_eq!(sender_report.ssrc, 1);
+                assert_eq!(sender_report.rtp_timestamp, timestamp);
+                assert_eq!(sender_report.sent_packets, 1);
+                assert_eq!(sender_report.sent_octets, 100);
+                assert_eq!(sender_report.reception_reports[0].ssrc, 2);
+            }
+            packets => panic!("unexpected packets: {:?}", packets),
+        }
+
+        // The local participant sends receiver reports again after 2 intervals without packets.
+        let report = session
+            .poll_report(start + Duration::from_secs(60))
+            .unwrap();
+        assert!(matches!(report.packets[0], RtcpPacket::Rr(_)));
+        assert_eq!(session.senders(), 0);
+    }
+
+    #[test]
+    fn test_leave() {
+        let start = Instant::now();
+        let mut session = RtcpSession::new(1, "user@example.com".to_owned(), 90_000, start);
+        session.receive_rtcp(&receiver_report(2), addr(5001), start);
+
+        let (now, _) = send_report(&mut session);
+        session.leave(Some("done".to_owned()), now);
+        assert!(!session.has_left());
+
+        match &session.poll_report(now).unwrap().packets[..] {
+            [RtcpPacket::Rr(_), RtcpPacket::Sdes(_), RtcpPacket::Bye(goodbye)] => {
+                assert_eq!(goodbye.ssrc_csrc_list, vec![1]);
+                assert_eq!(goodbye.reason.as_deref(), Some("done"));
+            }
+            packets => panic!("unexpected packets: {:?}", packets),
+        }
+
+        assert!(session.has_left());
+        assert!(session.poll_report(now + Duration::from_secs(60)).is_none());
+    }
+}
@@ -1,4 +1,7 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Instant;
+use fnv::FnvBuildHasher;
 use futures::stream::SplitStream;
 use futures::stream::SplitSink;
 use tokio_util::codec::{Decoder, Encoder, Framed};
@@ -7,13 +10,19 @@ use futures::{Future, StreamExt, Stream, SinkExt};
 use futures::task::{Context, Poll};
 use tokio::macros::support::Pin;
 use tokio::net::{UdpSocket, TcpStream};
+use tokio::time;
 use std::io;
 // use tokio_util::udp::UdpFramed;
 use crate::proto::common::UdpFramed;
 use crate::proto::common::TransportProtocol;
 use crate::proto::rtp::codec::Codec;
+use crate::proto::rtp::jitter::{JitterBuffer, Latency};
 use crate::proto::rtp::mutex::MuxedPacket;
+use crate::proto::rtp::payload::{Depacketizer, Frame};
+use crate::proto::rtp::sender::RtpSender;
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use crate::proto::rtcp::rtcp_packet::{RtcpCompoundPacket, RtcpPacket};
+use crate::worker::rtcp_session::RtcpSession;
 use crate::proto::rtsp::codec::ProtocolError;
 
 use log::{info, error};
@@ -34,6 +43,30 @@ pub struct RTPSession
     stream:Option<SplitStream<UdpFramed<Codec>>>,
     sink: Option<SplitSink<UdpFramed<Codec>, (MuxedPacket<RtpPacket, RtcpCompoundPacket>, SocketAddr)>>,
 
+    /// Reassembles the received RTP packets into frames, which are sent through the channel.
+    depacketizer: Option<(Box<dyn Depacketizer + Send>, UnboundedSender<Frame>)>,
+
+    /// The clock rate and latency of the jitter buffers, if the received RTP packets are
+    /// reordered before they are depacketized.
+    jitter_buffer: Option<(u32, Latency)>,
+
+    /// The jitter buffers by SSRC.
+    jitter_buffers: HashMap<u32, JitterBuffer, FnvBuildHasher>,
+
+    /// Fires at the earliest deadline of the jitter buffers, which it is armed for, so that held
+    /// packets are released even if no further packets arrive.
+    jitter_timer: Option<(Instant, Pin<Box<dyn Future<Output = ()> + Send>>)>,
+
+    /// Reports the reception of the RTP packets, over the RTP socket with rtcp-mux or over the
+    /// RTCP socket on the port following the RTP one.
+    rtcp: Option<RtcpSession>,
+    rtcp_mux: bool,
+    rtcp_stream: Option<SplitStream<UdpFramed<Codec>>>,
+    rtcp_sink: Option<SplitSink<UdpFramed<Codec>, (MuxedPacket<RtpPacket, RtcpCompoundPacket>, SocketAddr)>>,
+    rtcp_timer: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+
+    /// Assigns the sequence numbers and timestamps of the sent RTP packets.
+    sender: Option<RtpSender>,
 
 }
 
@@ -59,8 +92,152 @@ impl RTPSession
                 // udp_socket:None,
                 // tcp_stream:None,
                 stream:None,
-                sink:None
+                sink:None,
+                depacketizer: None,
+                jitter_buffer: None,
+                jitter_buffers: HashMap::default(),
+                jitter_timer: None,
+                rtcp: None,
+                rtcp_mux: false,
+                rtcp_stream: None,
+                rtcp_sink: None,
+                rtcp_timer: None,
+                sender: None,
+            }
+    }
+
+    /// Reassembles the received RTP packets into frames with the given depacketizer, e.g. an
+    /// [`H264Depacketizer`](crate::proto::rtp::payload::h264::H264Depacketizer), and returns the
+    /// receiver of the frames.
+    pub fn frames(&mut self, depacketizer: Box<dyn Depacketizer + Send>) -> UnboundedReceiver<Frame> {
+        let (tx_frame, rx_frame) = mpsc::unbounded();
+        self.depacketizer = Some((depacketizer, tx_frame));
+        rx_frame
+    }
+
+    /// Reorders the received RTP packets of every SSRC with a jitter buffer before they are
+    /// depacketized, so that packets arriving out of order do not corrupt the frames.
+    ///
+    /// Packets held back after a gap are released once the missing ones arrive or the latency
+    /// has passed, in which case the missing ones are declared lost.
+    pub fn set_jitter_buffer(&mut self, clock_rate: u32, latency: Latency) {
+        self.jitter_buffer = Some((clock_rate, latency));
+        self.jitter_buffers.clear();
+        self.jitter_timer = None;
+    }
+
+    /// Sends RTCP receiver reports of the received RTP packets with timestamps of the clock
+    /// rate, from the SSRC with the CNAME, once connected.
+    ///
+    /// With rtcp-mux the reports share the socket of the RTP packets, otherwise the port
+    /// following the RTP one is used on both ends.
+    pub fn set_rtcp(&mut self, ssrc: u32, cname: String, clock_rate: u32, rtcp_mux: bool) {
+        self.rtcp = Some(RtcpSession::new(ssrc, cname, clock_rate, Instant::now()));
+        self.rtcp_mux = rtcp_mux;
+        self.rtcp_timer = None;
+
+        if let Some(sender) = self.sender.as_mut() {
+            sender.set_ssrc(ssrc);
+        }
+    }
+
+    /// Sends RTP packets of the payload type with media timestamps of the clock rate through
+    /// `send`, from the SSRC of the RTCP reports if they are sent, or else from a random one.
+    pub fn set_sender(&mut self, payload_type: u8, clock_rate: u32) {
+        let ssrc = self.rtcp.as_ref().map_or_else(rand::random, |rtcp| rtcp.ssrc());
+        self.sender = Some(RtpSender::new(ssrc, payload_type, clock_rate));
+    }
+
+    /// Returns the sender of the RTP packets, if any.
+    pub fn sender(&self) -> Option<&RtpSender> {
+        self.sender.as_ref()
+    }
+
+    /// Sends a payload in an RTP packet with the media timestamp and the marker bit. The SSRC,
+    /// the sequence number and the offset of the RTP timestamp are assigned by the sender, which
+    /// is reported in RTCP sender reports.
+    pub async fn send(&mut self, payload: Vec<u8>, timestamp: u32, marker: bool) -> io::Result<()> {
+        let (sender, sink, remote_addr) = match (self.sender.as_mut(), self.sink.as_mut(), self.remote_addr) {
+            (Some(sender), Some(sink), Some(remote_addr)) => (sender, sink, remote_addr),
+            _ => return Err(io::Error::new(io::ErrorKind::NotConnected, "no RTP sender or not connected")),
+        };
+
+        // The SSRC follows the RTCP session, which changes it on collisions.
+        if let Some(rtcp) = self.rtcp.as_ref() {
+            if sender.ssrc() != rtcp.ssrc() {
+                sender.set_ssrc(rtcp.ssrc());
             }
+        }
+
+        let now = Instant::now();
+        let packet = sender.packetize(payload, timestamp, marker, now);
+
+        if let Some(rtcp) = self.rtcp.as_mut() {
+            rtcp.on_rtp_sent(sender, now);
+        }
+
+        sink.send((MuxedPacket::Rtp(packet), remote_addr))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
+    /// Returns the RTCP state of the session, if reports are sent.
+    pub fn rtcp(&self) -> Option<&RtcpSession> {
+        self.rtcp.as_ref()
+    }
+
+    /// Leaves the session, sending an RTCP BYE with the reason on the next poll if reports are
+    /// sent.
+    pub fn leave(&mut self, reason: Option<String>) {
+        if let Some(rtcp) = self.rtcp.as_mut() {
+            rtcp.leave(reason, Instant::now());
+            self.rtcp_timer = None;
+        }
+    }
+
+    /// Passes a received RTP packet through its jitter buffer, if any, to the depacketizer.
+    /// Packets of SSRC collisions and loops are dropped.
+    fn receive(&mut self, packet: RtpPacket, source: SocketAddr) {
+        let now = Instant::now();
+
+        if let Some(rtcp) = self.rtcp.as_mut() {
+            if !rtcp.receive_rtp(&packet, source, now) {
+                return;
+            }
+        }
+
+        let (clock_rate, latency) = match self.jitter_buffer {
+            Some(jitter_buffer) => jitter_buffer,
+            None => return self.depacketize(&packet),
+        };
+
+        let jitter_buffer = self
+            .jitter_buffers
+            .entry(packet.header.ssrc)
+            .or_insert_with(|| JitterBuffer::new(clock_rate, latency));
+        jitter_buffer.push(packet, now);
+
+        let packets: Vec<_> = std::iter::from_fn(|| jitter_buffer.pop(now)).collect();
+
+        for packet in packets {
+            self.depacketize(&packet);
+        }
+    }
+
+    /// Passes a received RTP packet to the depacketizer, sending any completed frames.
+    fn depacketize(&mut self, packet: &RtpPacket) {
+        if let Some((depacketizer, tx_frame)) = self.depacketizer.as_mut() {
+            if let Err(error) = depacketizer.push(packet) {
+                error!("cannot depacketize RTP packet {}: {}", packet.header.seq_num, error);
+            }
+
+            while let Some(frame) = depacketizer.pull() {
+                if tx_frame.unbounded_send(frame).is_err() {
+                    self.depacketizer = None;
+                    break;
+                }
+            }
+        }
     }
 
 
@@ -78,12 +255,29 @@ impl RTPSession
             Err(e)=> Err(e),
             Ok(udp) => {
                 udp.connect(self.remote_addr.unwrap()).await;
+
+                // The RTCP port follows the one actually bound, which the configured one may
+                // leave to the system.
+                let rtcp_addrs = if self.rtcp.is_some() && !self.rtcp_mux {
+                    Some((rtcp_addr(udp.local_addr()?)?, rtcp_addr(self.remote_addr.unwrap())?))
+                } else {
+                    None
+                };
+
                 // self.udp_socket = Some(udp);
                 let (sink, stream ) = UdpFramed::new(udp, Codec::new()).split();
                 self.stream = Some(stream);
                 self.sink = Some(sink);
                 // let Some(socket) = self.udp_socket.as_mut();
                 // socket.connect(self.remote_addr.unwrap()).await
+
+                if let Some((local_addr, remote_addr)) = rtcp_addrs {
+                    let rtcp_udp = UdpSocket::bind(local_addr).await?;
+                    rtcp_udp.connect(remote_addr).await?;
+                    let (sink, stream) = UdpFramed::new(rtcp_udp, Codec::new()).split();
+                    self.rtcp_stream = Some(stream);
+                    self.rtcp_sink = Some(sink);
+                }
                 return Ok(());
             }
         }
@@ -93,71 +287,171 @@ impl RTPSession
     }
 
 
+    /// Receives the packets of the sockets until they have none left, and sends the RTCP
+    /// packets that are due.
+    ///
+    /// Returns `Poll::Ready` once the RTP socket is closed.
     pub fn poll_stream(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        while let Some(stream) = self.stream.as_mut() {
+            match stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok((MuxedPacket::Rtp(packet), source)))) => self.receive(packet, source),
+                Poll::Ready(Some(Ok((MuxedPacket::Rtcp(packet), source)))) => {
+                    self.receive_rtcp(&packet, source)
+                }
+                Poll::Ready(Some(Err(e))) => error!("poll_stream error:{}", e),
+                Poll::Ready(None) => {
+                    info!("RTP socket closed");
+                    return Poll::Ready(());
+                }
+                Poll::Pending => break,
+            }
+        }
 
-        let stream_result = self.stream.as_mut().unwrap().poll_next_unpin(cx);
-        match stream_result {
-            Poll::Ready(Some(result)) => {
-                match result{
-                    Ok(message) => {
+        self.poll_rtcp_stream(cx);
+        self.poll_jitter_buffers(cx);
+        self.poll_rtcp(cx);
 
-                        // info!("RTP Message recieved:{:?} from {}", message.0, message.1);
-                    },
+        Poll::Pending
+    }
 
-                    Err(p) =>{
-                        error!("poll_stream error:{}", p);
-                        // return Poll::Ready(Err(ProtocolError::IO(Arc::new(Error("")))))
-                    }
+    /// Releases the packets of the jitter buffers that are due, and arms the timer of the
+    /// earliest deadline of the packets still held.
+    fn poll_jitter_buffers(&mut self, cx: &mut Context<'_>) {
+        loop {
+            let now = Instant::now();
+            let packets: Vec<_> = self
+                .jitter_buffers
+                .values_mut()
+                .flat_map(|jitter_buffer| std::iter::from_fn(move || jitter_buffer.pop(now)))
+                .collect();
+
+            for packet in packets {
+                self.depacketize(&packet);
+            }
 
+            let deadline = match self.jitter_buffers.values().filter_map(JitterBuffer::deadline).min() {
+                Some(deadline) => deadline,
+                None => {
+                    self.jitter_timer = None;
+                    return;
                 }
+            };
+
+            if self.jitter_timer.as_ref().map_or(true, |(armed, _)| *armed != deadline) {
+                let timer = Box::pin(time::sleep_until(time::Instant::from_std(deadline)));
+                self.jitter_timer = Some((deadline, timer));
             }
-            Poll::Pending => {
-                // self.stream = Some(stream);
-                error!("poll_stream pending");
+
+            // Polls the timer so that it wakes the task up at the deadline, which has passed if
+            // it is ready.
+            if let Some((_, timer)) = self.jitter_timer.as_mut() {
+                if timer.as_mut().poll(cx).is_pending() {
+                    return;
+                }
             }
-            Poll::Ready(None) => {
-                error!("poll_stream ready none");
+
+            self.jitter_timer = None;
+        }
+    }
+
+    /// Passes a received compound RTCP packet to the RTCP state, if reports are sent.
+    fn receive_rtcp(&mut self, packet: &RtcpCompoundPacket, source: SocketAddr) {
+        if let Some(rtcp) = self.rtcp.as_mut() {
+            rtcp.receive_rtcp(packet, source, Instant::now());
+        }
+    }
+
+    /// Receives the packets of the RTCP socket, if any.
+    fn poll_rtcp_stream(&mut self, cx: &mut Context<'_>) {
+        while let Some(stream) = self.rtcp_stream.as_mut() {
+            match stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok((MuxedPacket::Rtcp(packet), source)))) => {
+                    self.receive_rtcp(&packet, source)
+                }
+                Poll::Ready(Some(Ok(_))) => {}
+                Poll::Ready(Some(Err(e))) => error!("poll_rtcp_stream error:{}", e),
+                Poll::Ready(None) => self.rtcp_stream = None,
+                Poll::Pending => break,
             }
         }
-        // match stream_result {
-        //     Poll::Ready(Some(result)) => {
-        //         match result{
-        //             Ok(message) => {
-        //
-        //                 info!("Message recieved");
-        //
-        //                 if let Some(message_handler) = self.message_handler.as_mut() {
-        //                     message_handler.handle_message(message);
-        //                 }
-        //             },
-        //
-        //             Err(p) =>{
-        //                 error!("poll_stream error:{}", p);
-        //                 return Poll::Ready(Err(p))
-        //             }
-        //
-        //         }
-        //     }
-        //     Poll::Pending => {
-        //         // self.stream = Some(stream);
-        //         return Poll::Pending;
-        //     }
-        //     Poll::Ready(None) => return Poll::Ready(Ok(())),
-        //     // Poll::Ready(Err(error)) => {
-        //     //     self.handle_protocol_error(&error);
-        //     //     return Poll::Ready(Err(error));
-        //     // }
-        // }
+    }
 
+    /// Sends the RTCP packets once they are due, and arms the timer of the next report.
+    fn poll_rtcp(&mut self, cx: &mut Context<'_>) {
+        if let Some(timer) = self.rtcp_timer.as_mut() {
+            if timer.as_mut().poll(cx).is_pending() {
+                return;
+            }
+        }
 
+        let now = Instant::now();
+        while let Some(packet) = self.rtcp.as_mut().and_then(|rtcp| rtcp.poll_report(now)) {
+            self.send_rtcp(packet, cx);
+        }
 
-        Poll::Pending
+        if let Some(rtcp) = self.rtcp.as_ref().filter(|rtcp| !rtcp.has_left()) {
+            let deadline = time::Instant::from_std(rtcp.next_report());
+            let mut timer: Pin<Box<dyn Future<Output = ()> + Send>> = Box::pin(time::sleep_until(deadline));
+
+            // Polls the new timer once so that it wakes the task up at the deadline.
+            if timer.as_mut().poll(cx).is_pending() {
+                self.rtcp_timer = Some(timer);
+            } else {
+                self.rtcp_timer = None;
+            }
+        }
+    }
+
+    /// Sends a compound RTCP packet over the RTP socket with rtcp-mux, or else over the RTCP
+    /// socket. The packet is dropped if the socket is busy.
+    fn send_rtcp(&mut self, packet: RtcpCompoundPacket, cx: &mut Context<'_>) {
+        let (sink, remote_addr) = if self.rtcp_mux {
+            (self.sink.as_mut(), self.remote_addr)
+        } else {
+            (self.rtcp_sink.as_mut(), self.remote_addr.and_then(|addr| rtcp_addr(addr).ok()))
+        };
+        let (sink, remote_addr) = match (sink, remote_addr) {
+            (Some(sink), Some(remote_addr)) => (sink, remote_addr),
+            _ => return,
+        };
+
+        match sink.poll_ready_unpin(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => {
+                error!("cannot send RTCP packet: {}", e);
+                return;
+            }
+            Poll::Pending => {
+                error!("RTCP packet dropped, the socket is busy");
+                return;
+            }
+        }
 
+        if let Err(e) = sink.start_send_unpin((MuxedPacket::Rtcp(packet), remote_addr)) {
+            error!("cannot send RTCP packet: {}", e);
+            return;
+        }
 
+        if let Poll::Ready(Err(e)) = sink.poll_flush_unpin(cx) {
+            error!("cannot send RTCP packet: {}", e);
+        }
     }
 
 }
 
+/// Returns the address of the RTCP port following the RTP one.
+///
+/// Errors are returned for the ports no RTCP port can follow, i.e. zero and the last one.
+fn rtcp_addr(addr: SocketAddr) -> io::Result<SocketAddr> {
+    match addr.port() {
+        0 | u16::MAX => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no RTCP port follows the RTP port of {}", addr),
+        )),
+        port => Ok(SocketAddr::new(addr.ip(), port + 1)),
+    }
+}
+
 impl Future for RTPSession
 {
     type Output = Result<(), ProtocolError>;
@@ -165,88 +459,99 @@ impl Future for RTPSession
 
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.as_mut().poll_stream(cx).map(Ok)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use futures::future;
+    use futures::task::Poll;
+    use futures::{Future, FutureExt, StreamExt};
+    use tokio::macros::support::Pin;
+    use tokio::time;
+
+    use crate::proto::rtp::jitter::Latency;
+    use crate::proto::rtp::payload::{DepacketizeError, Depacketizer, Frame};
+    use crate::proto::rtp::rtp::{test_support::packet, RtpPacket};
+
+    use super::{rtcp_addr, RTPSession};
+
+    /// Makes a frame of every packet.
+    #[derive(Default)]
+    struct PacketFrames(Vec<Frame>);
+
+    impl Depacketizer for PacketFrames {
+        fn push(&mut self, packet: &RtpPacket) -> Result<(), DepacketizeError> {
+            self.0.push(Frame {
+                timestamp: packet.header.timestamp,
+                data: packet.payload.clone(),
+                keyframe: true,
+                corrupted: false,
+            });
+            Ok(())
+        }
 
-        info!("RTPSession poll");
-        // self.stream.as_mut().unwrap().poll_next_unpin(cx);
-        // self.stream.
-        loop{
-            match self.as_mut().poll_stream(cx) {
-                // Poll::Ready(Ok(message))=> {
-                //     // match message{
-                //     //     Message::Request(request) =>{
-                //     //         info!("request received");
-                //     //     },
-                //     //     Message::Response(request) =>{
-                //     //         info!("response received");
-                //     //     },
-                //     //     _ => {}
-                //     // }
-                //     info!("message received ok");
-                // },
-
-                Poll::Ready(())=> {
-                    info!("message received error");
-                },
-
-                // Ok(Async::Ready(_)) | Err(_) => {
-                //     self.shutdown_receiving();
-                // }
-                _ => {
-                    // info!("message received");
-                    // ()
-                },
-            }
-        }
-
-
-        return Poll::Pending;
-        //
-        // // self.sink.as_mut().unwrap().poll_ready_unpin(cx);
-        // let stream_result = self.stream.as_mut().unwrap().poll_next_unpin(cx);
-        // match stream_result {
-        //     Poll::Ready(Some(result)) => {
-        //         match result{
-        //             Ok(message) => {
-        //
-        //                 info!("RTP Message recieved:{:?} from {}", message.0, message.1);
-        //
-        //                 // if let Some(message_handler) = self.message_handler.as_mut() {
-        //                 //     message_handler.handle_message(message);
-        //                 // }
-        //
-        //                 // if let Message::Request(request) = message.clone(){
-        //                 //     info!("Message recieved:{}", "request");
-        //                 // }
-        //                 // if let Message::Response(response) = message.clone(){
-        //                 //     info!("Message recieved:{}", "response");
-        //                 // }
-        //
-        //
-        //
-        //                 // if let Err(error) = self.as_mut().handle_message(message) {
-        //                 //     // self.as_mut().handle_request_receiver_error(error);
-        //                 // }
-        //                 // return Poll::Ready(Ok(message.0))
-        //                 return Poll::Pending
-        //             },
-        //
-        //             Err(p) =>{
-        //                 error!("poll_stream error:{}", p);
-        //                 // return Poll::Ready(Err(ProtocolError::IO(Arc::new(Error("")))))
-        //                 return Poll::Pending;
-        //             }
-        //
-        //         }
-        //     }
-        //     Poll::Pending => {
-        //         // self.stream = Some(stream);
-        //         error!("poll_stream pending");
-        //         return Poll::Pending;
-        //     }
-        //     Poll::Ready(None) => {
-        //         error!("poll_stream ready none");
-        //         return Poll::Pending
-        //     }
-        // }
+        fn pull(&mut self) -> Option<Frame> {
+            if self.0.is_empty() {
+                None
+            } else {
+                Some(self.0.remove(0))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_pending() {
+        let remote_addr = "127.0.0.1:9".parse().unwrap();
+        let mut session = RTPSession::newSession("127.0.0.1:0".parse().unwrap(), Some(remote_addr));
+        session.connect().await.unwrap();
+
+        // Without packets to receive the session yields instead of spinning.
+        let poll = future::poll_fn(|cx| Poll::Ready(Pin::new(&mut session).poll(cx))).await;
+        assert!(poll.is_pending());
+    }
+
+    #[tokio::test]
+    async fn test_connect_rtcp() {
+        let remote_addr = "127.0.0.1:5004".parse().unwrap();
+        let mut session = RTPSession::newSession("127.0.0.1:0".parse().unwrap(), Some(remote_addr));
+        session.set_rtcp(0x1234_5678, "test".to_string(), 90_000, false);
+        session.connect().await.unwrap();
+        assert!(session.rtcp_sink.is_some());
+
+        assert_eq!(rtcp_addr(remote_addr).unwrap(), "127.0.0.1:5005".parse().unwrap());
+        assert!(rtcp_addr("127.0.0.1:0".parse().unwrap()).is_err());
+        assert!(rtcp_addr("127.0.0.1:65535".parse().unwrap()).is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_jitter_buffer_timeout() {
+        let source: SocketAddr = "127.0.0.1:5004".parse().unwrap();
+        let mut session = RTPSession::newSession("127.0.0.1:0".parse().unwrap(), None);
+        let mut frames = session.frames(Box::new(PacketFrames::default()));
+        session.set_jitter_buffer(90_000, Latency::Fixed(Duration::from_millis(20)));
+
+        // The second packet is lost.
+        session.receive(packet(1, 0, true, &[1]), source);
+        session.receive(packet(3, 6000, true, &[3]), source);
+        assert_eq!(frames.next().now_or_never().unwrap().unwrap().data, vec![1]);
+        assert_eq!(frames.next().now_or_never(), None);
+
+        // Without further packets, the timer releases the packet after the gap once the latency
+        // has passed.
+        let released = future::poll_fn(|cx| {
+            session.poll_jitter_buffers(cx);
+
+            if session.jitter_timer.is_none() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        });
+        time::timeout(Duration::from_secs(1), released).await.unwrap();
+        assert_eq!(frames.next().now_or_never().unwrap().unwrap().data, vec![3]);
+    }
+}